@@ -1,28 +1,45 @@
 use std::{
     fmt::{Debug, Display},
     fs,
-    io::Write,
+    io::{Read, Write},
+    path::Path,
     process,
     rc::Rc,
     str::FromStr,
 };
 
-use clap::Parser;
+use clap::{builder::PossibleValuesParser, Parser, Subcommand};
 use colored::Colorize;
 use mipsy_interactive::prompt;
-use mipsy_lib::error::runtime::{Error, ErrorContext, InvalidSyscallReason, RuntimeError};
+use mipsy_lib::error::{
+    runtime::{Error, ErrorContext, InvalidSyscallReason, RuntimeError},
+    util::tip_header,
+};
 use mipsy_lib::runtime::{SYS13_OPEN, SYS14_READ, SYS15_WRITE, SYS16_CLOSE};
 use mipsy_lib::{
     compile::{get_kernel, CompilerOptions},
-    Binary, InstSet, MipsyError, MipsyResult, MpProgram, Runtime, Safe,
+    decompile,
+    diagnostic::{self, Diagnostic},
+    disasm,
+    elf::{self, ElfError},
+    error::codes,
+    image::{self, ImageFormat, ImageSegment},
+    Binary, InstSet, MemoryLayout, MipsyError, MipsyResult, MpProgram, Runtime, Safe,
 };
 use mipsy_parser::TaggedFile;
 use mipsy_utils::{config_path, read_config, MipsyConfig, MipsyConfigError};
 use text_io::try_read;
 
 #[derive(Parser, Debug)]
-#[command(version = VERSION, author = "Zac K. <zac.kologlu@gmail.com>")]
+#[command(
+    version = VERSION,
+    author = "Zac K. <zac.kologlu@gmail.com>",
+    args_conflicts_with_subcommands = true
+)]
 struct Opts {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Just output compilation errors, if any
     #[arg(long)]
     check: bool,
@@ -31,6 +48,19 @@ struct Opts {
     #[arg(long)]
     check_no_main: bool,
 
+    /// Show compiler warnings before running (they are always shown with --check)
+    #[arg(short = 'W', long)]
+    warnings: bool,
+
+    /// How to report errors and warnings: `json` and `sarif` are written to stderr
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser = PossibleValuesParser::new(["human", "json", "sarif"]),
+        default_value = "human"
+    )]
+    diagnostics: String,
+
     /// Just compile program instead of executing
     #[arg(long)]
     compile: bool,
@@ -43,14 +73,69 @@ struct Opts {
     #[arg(long)]
     hex_pad_zero: bool,
 
+    /// Just compile program and write a listing of it to FILE (or `-` for stdout)
+    #[arg(long, value_name = "FILE")]
+    listing: Option<String>,
+
+    /// Just compile program and write it out in the given format
+    #[arg(long, value_name = "FORMAT", value_parser = PossibleValuesParser::new(EMIT_FORMATS))]
+    emit: Option<String>,
+
+    /// Where --emit writes to, or `-` for stdout (defaults to the first file's name)
+    #[arg(short = 'o', long, value_name = "FILE", requires = "emit")]
+    output: Option<String>,
+
+    /// Which segment a memory image (i.e. any --emit format other than elf) holds
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(["text", "data", "ktext", "kdata"]),
+        default_value = "text",
+        requires = "emit"
+    )]
+    segment: String,
+
+    /// The address that a memory image starts at (defaults to the start of the segment)
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, requires = "emit")]
+    base_address: Option<u32>,
+
+    /// The byte that uninitialised memory is filled with in a memory image
+    #[arg(
+        long,
+        value_name = "BYTE",
+        value_parser = parse_byte,
+        default_value = "0",
+        requires = "emit"
+    )]
+    pad: u8,
+
     /// Enable some SPIM compatibility options
     #[arg(long)]
     spim: bool,
 
+    /// Report loads and stores that overrun the labelled data they point into
+    #[arg(long)]
+    bounds_check: bool,
+
+    /// Where to put each segment in memory
+    #[arg(long, value_parser = PossibleValuesParser::new(MemoryLayout::PRESETS))]
+    layout: Option<String>,
+
+    /// Limit the stack to this many bytes
+    #[arg(long)]
+    stack_size: Option<u32>,
+
     /// Move a label to point to a different label
     #[arg(long)]
     move_label: Vec<String>,
 
+    /// Explain an error code (such as M0105) in detail, with examples
+    #[arg(long, value_name = "CODE")]
+    explain: Option<String>,
+
+    /// Define a constant for `.if` and `.ifdef` (VALUE defaults to 1)
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    define: Vec<String>,
+
     /// File(s) to be loaded and executed
     #[arg()]
     files: Vec<String>,
@@ -60,6 +145,47 @@ struct Opts {
     args: Vec<String>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Format MIPS assembly files in place (configured by `format` in the mipsy config)
+    Fmt {
+        /// Don't write anything, just fail if any file isn't formatted
+        #[arg(long)]
+        check: bool,
+
+        /// File(s) to be formatted, or `-` to format stdin to stdout
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Disassemble machine code: hex words (as printed by --hex), a raw memory image, or an ELF executable
+    Disasm {
+        /// How to read the input (guessed from its contents by default)
+        #[arg(long, value_parser = PossibleValuesParser::new(["hex", "bin", "bin-be", "elf"]))]
+        format: Option<String>,
+
+        /// The address of the first word (defaults to the start of the text segment)
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+        base_address: Option<u32>,
+
+        /// Label the targets of branches and jumps
+        #[arg(long)]
+        labels: bool,
+
+        /// Write out a source file that mipsy can assemble again, rather than a listing
+        #[arg(long)]
+        source: bool,
+
+        /// Disassemble the arguments themselves as hex words, rather than reading files
+        #[arg(short = 'w', long, conflicts_with = "format")]
+        words: bool,
+
+        /// File(s) to be disassembled (or `-` for stdin), or with --words, the words themselves
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+}
+
 fn get_input<T>(name: &str, line: bool) -> T
 where
     T: FromStr + Display,
@@ -151,6 +277,42 @@ fn get_input_int(name: &str) -> Option<i32> {
     }
 }
 
+/// Parses the value of a `-D NAME=VALUE`, in decimal or hexadecimal.
+fn parse_define_value(value: &str) -> Option<i64> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+
+    let value = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => value.parse().ok()?,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// Parses an address (or byte) for a memory image, in decimal or hex.
+fn parse_address(value: &str) -> Result<u32, String> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|err| err.to_string())
+}
+
+fn parse_byte(value: &str) -> Result<u8, String> {
+    let value = parse_address(value)?;
+
+    u8::try_from(value).map_err(|err| err.to_string())
+}
+
 fn main() {
     let opts: Opts = Opts::parse();
 
@@ -174,6 +336,26 @@ fn main() {
             .collect::<Vec<_>>()
     };
 
+    let defines = {
+        opts.define
+            .into_iter()
+            .map(|s| {
+                let (name, value) = s.split_once('=').unwrap_or((&s, "1"));
+
+                let value = match parse_define_value(value) {
+                    Some(value) if !name.is_empty() => value,
+                    _ => {
+                        eprintln!("Invalid define: {s}");
+                        eprintln!("Must be in format: -D NAME or -D NAME=VALUE ...");
+                        std::process::exit(1);
+                    }
+                };
+
+                (name.to_string(), value)
+            })
+            .collect::<Vec<_>>()
+    };
+
     let mut config = match read_config() {
         Ok(config) => config,
         Err(MipsyConfigError::InvalidConfig(to_path, config)) => {
@@ -198,11 +380,58 @@ fn main() {
         config.spim = true;
     }
 
+    if opts.bounds_check {
+        config.bounds_check = true;
+    }
+
+    if let Some(layout) = opts.layout {
+        config.memory_layout = layout;
+    }
+
+    if opts.stack_size.is_some() {
+        config.stack_size = opts.stack_size;
+    }
+
+    if let Some(code) = opts.explain {
+        match codes::explain(&code) {
+            Some(explanation) => print!("{}", explanation),
+            None => {
+                prompt::error(format!("`{}` is not a mipsy error code", code));
+                process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    match opts.command {
+        Some(Command::Fmt { check, files }) => format_files(&config, check, &files),
+        Some(Command::Disasm {
+            format,
+            base_address,
+            labels,
+            source,
+            words,
+            inputs,
+        }) => disassemble(
+            &config,
+            format,
+            base_address,
+            labels,
+            source,
+            words,
+            &inputs,
+        ),
+        None => {}
+    }
+
     if opts.files.is_empty() {
         // launch() returns !
         mipsy_interactive::launch(config);
     }
 
+    let first_file = opts.files[0].clone();
+
     let files = opts
         .files
         .into_iter()
@@ -212,7 +441,7 @@ fn main() {
                 name = String::from("/dev/stdin");
             }
 
-            let file_contents = match fs::read_to_string(&name) {
+            let file_contents = match fs::read(&name) {
                 Ok(contents) => contents,
                 Err(err) => {
                     prompt::error_nl(format!(
@@ -229,66 +458,138 @@ fn main() {
         })
         .collect::<Vec<_>>();
 
+    // a single ELF file is loaded as it is, rather than compiled
+    let elf = match files.as_slice() {
+        [(name, contents)] if elf::is_elf(contents) => Some((name.clone(), contents.clone())),
+        _ => None,
+    };
+
+    let files = if elf.is_some() {
+        vec![]
+    } else {
+        files
+            .into_iter()
+            .map(|(name, contents)| match String::from_utf8(contents) {
+                Ok(contents) => (name, contents),
+                Err(err) => {
+                    prompt::error_nl(format!(
+                        "failed to read file `{}`: {}",
+                        name.bold(),
+                        err.to_string().bright_red()
+                    ));
+
+                    process::exit(1);
+                }
+            })
+            .collect()
+    };
+
     let args = opts.args.iter().map(|arg| &**arg).collect::<Vec<_>>();
 
-    let compiler_options = CompilerOptions::new(moves);
+    let compiler_options = CompilerOptions::new(moves, defines);
 
-    let compiled = if opts.check_no_main {
-        compile_with_kernel(
-            &compiler_options,
-            &config,
-            &files,
-            &args,
-            &mut MpProgram::new(vec![], vec![]),
-        )
+    let compiled = if let Some((name, contents)) = &elf {
+        match load_elf(&config, contents, &args) {
+            Ok(loaded) => Ok(loaded),
+            Err(err) => {
+                prompt::error_nl(format!(
+                    "failed to load `{}`: {}",
+                    name.bold(),
+                    err.to_string().bright_red()
+                ));
+
+                process::exit(1);
+            }
+        }
+    } else if opts.check || opts.check_no_main {
+        // every syntax error is reported, rather than just the first
+        let mut kernel = if opts.check_no_main {
+            MpProgram::new(vec![], vec![])
+        } else {
+            get_kernel()
+        };
+
+        match check_with_kernel(&compiler_options, &config, &files, &args, &mut kernel) {
+            Ok(compiled) => Ok(compiled),
+            Err(errors) if opts.diagnostics != "human" => {
+                let diagnostics: Vec<_> = errors
+                    .iter()
+                    .map(|error| compile_diagnostic(&config, &files, error))
+                    .collect();
+
+                report(&opts.diagnostics, &diagnostics);
+                process::exit(1);
+            }
+            Err(errors) => {
+                let codes: Vec<_> = errors.iter().map(MipsyError::code).collect();
+
+                for (i, error) in errors.into_iter().enumerate() {
+                    if i != 0 {
+                        eprintln!();
+                    }
+
+                    show_compile_error(&config, &files, error);
+                }
+
+                explain_hint(&codes);
+                process::exit(1);
+            }
+        }
     } else {
         compile(&compiler_options, &config, &files, &args)
     };
 
     let (iset, binary, mut runtime) = match compiled {
         Ok((iset, binary, runtime)) => (iset, binary, runtime),
-
-        Err(MipsyError::Parser(error)) => {
-            prompt::error(format!("failed to parse `{}`", error.file_tag()));
-
-            let file_tag = error.file_tag();
-
-            let file = files
-                .iter()
-                .find(|(tag, _)| &**tag == &*file_tag)
-                .map(|(_, str)| Rc::from(&**str))
-                .expect("for file to throw a parser error, it should probably exist");
-
-            error.show_error(&config, file);
+        Err(error) if opts.diagnostics != "human" => {
+            report(
+                &opts.diagnostics,
+                &[compile_diagnostic(&config, &files, &error)],
+            );
 
             process::exit(1);
         }
+        Err(error) => {
+            let code = error.code();
+            show_compile_error(&config, &files, error);
 
-        Err(MipsyError::Compiler(error)) => {
-            let compile_tag = if error.file_tag().is_empty() {
-                String::new()
-            } else {
-                format!(" `{}`", error.file_tag())
-            };
+            explain_hint(&[code]);
+            process::exit(1);
+        }
+    };
 
-            prompt::error(format!("failed to compile{}", compile_tag));
+    // so that runtime errors can show lines from included files too
+    let files: Vec<_> = files
+        .into_iter()
+        .chain(
+            binary
+                .included_files
+                .iter()
+                .map(|(tag, contents)| (tag.to_string(), contents.to_string())),
+        )
+        .collect();
 
-            let file_tag = error.file_tag();
+    // only shown when asked for, so that they aren't mixed into the program's output
+    if (opts.warnings || opts.check || opts.check_no_main) && opts.diagnostics != "human" {
+        let diagnostics: Vec<_> = binary
+            .warnings
+            .iter()
+            .map(|warning| {
+                let file = source_of(&files, &warning.file_tag()).unwrap_or_else(|| Rc::from(""));
 
-            let file = files
-                .iter()
-                .find(|(tag, _)| &**tag == &*file_tag)
-                .map(|(_, str)| Rc::from(&**str))
-                .unwrap_or_else(|| Rc::from(""));
+                Diagnostic::from_warning(warning, &config, &file)
+            })
+            .collect();
 
-            error.show_error(&config, file);
+        report(&opts.diagnostics, &diagnostics);
+    } else if opts.warnings || opts.check || opts.check_no_main {
+        for warning in &binary.warnings {
+            let file = source_of(&files, &warning.file_tag()).unwrap_or_else(|| Rc::from(""));
 
-            process::exit(1);
+            warning.show_warning(&config, file);
+            println!();
         }
-
-        // unreachable: a bit tricky to get a runtime error at compile-time
-        Err(MipsyError::Runtime(_)) => unreachable!(),
-    };
+    }
 
     if opts.check || opts.check_no_main {
         return;
@@ -317,6 +618,109 @@ fn main() {
         return;
     }
 
+    if let Some(output) = opts.listing {
+        let sources: Vec<_> = files
+            .iter()
+            .map(|(tag, contents)| (Rc::from(&**tag), Rc::from(&**contents)))
+            .collect();
+
+        let listing = mipsy_lib::listing::listing(&iset, &binary, &sources, &config);
+
+        if output == "-" {
+            print!("{}", listing);
+        } else if let Err(err) = fs::write(&output, listing) {
+            prompt::error_nl(format!(
+                "failed to write file `{}`: {}",
+                output.bold(),
+                err.to_string().bright_red()
+            ));
+
+            process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(format) = opts.emit {
+        let segment = match opts.segment.as_str() {
+            "text" => ImageSegment::Text,
+            "data" => ImageSegment::Data,
+            "ktext" => ImageSegment::KText,
+            "kdata" => ImageSegment::KData,
+            _ => unreachable!("clap only accepts the segments listed"),
+        };
+
+        let image_format = match format.as_str() {
+            "elf" => None,
+            "logisim" => Some(ImageFormat::Logisim),
+            "ihex" => Some(ImageFormat::IntelHex),
+            "bin" => Some(ImageFormat::RawLittleEndian),
+            "bin-be" => Some(ImageFormat::RawBigEndian),
+            "mif" => Some(ImageFormat::Mif),
+            "coe" => Some(ImageFormat::Coe),
+            _ => unreachable!("clap only accepts the formats in EMIT_FORMATS"),
+        };
+
+        let bytes = match image_format {
+            None => elf::write_elf(&binary),
+            Some(image_format) => {
+                match image::write_image(
+                    &binary,
+                    segment,
+                    image_format,
+                    opts.base_address,
+                    opts.pad,
+                ) {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        prompt::error_nl(format!(
+                            "failed to write a memory image: {}",
+                            err.to_string().bright_red()
+                        ));
+
+                        process::exit(1);
+                    }
+                }
+            }
+        };
+
+        let output = opts.output.unwrap_or_else(|| {
+            let stem = Path::new(&first_file).file_stem().unwrap_or_default();
+
+            // so that the text and data images don't overwrite each other
+            let extension = match image_format {
+                None => String::from("elf"),
+                Some(image_format) if segment == ImageSegment::Text => {
+                    image_format.extension().to_string()
+                }
+                Some(image_format) => format!("{}.{}", opts.segment, image_format.extension()),
+            };
+
+            Path::new(stem)
+                .with_extension(extension)
+                .to_string_lossy()
+                .to_string()
+        });
+
+        let written = if output == "-" {
+            std::io::stdout().write_all(&bytes)
+        } else {
+            fs::write(&output, bytes)
+        };
+
+        if let Err(err) = written {
+            prompt::error_nl(format!(
+                "failed to write file `{}`: {}",
+                output.bold(),
+                err.to_string().bright_red()
+            ));
+
+            process::exit(1);
+        }
+
+        return;
+    }
+
     loop {
         match runtime.step() {
             Ok(stepped_runtime) => {
@@ -388,93 +792,73 @@ fn main() {
                                 // TODO: implement file open for mipsy cli frontend
                                 runtime = guard(-1);
                                 runtime.timeline_mut().pop_last_state();
-                                println!();
-                                RuntimeError::new(Error::InvalidSyscall {
+                                let error = RuntimeError::new(Error::InvalidSyscall {
                                     syscall: SYS13_OPEN,
                                     reason: InvalidSyscallReason::Unimplemented,
-                                })
-                                .show_error(
-                                    ErrorContext::Binary,
-                                    files
-                                        .iter()
-                                        .map(|(tag, content)| {
-                                            (Rc::from(&**tag), Rc::from(&**content))
-                                        })
-                                        .collect(),
+                                });
+
+                                show_runtime_error(
+                                    &opts.diagnostics,
+                                    &error,
+                                    &files,
                                     &iset,
                                     &binary,
                                     &runtime,
                                 );
-                                process::exit(1);
                             }
                             Read(_args, guard) => {
                                 // TODO: implement file read for mipsy cli frontend
                                 runtime = guard((-1, Vec::new()));
                                 runtime.timeline_mut().pop_last_state();
-                                println!();
-                                RuntimeError::new(Error::InvalidSyscall {
+                                let error = RuntimeError::new(Error::InvalidSyscall {
                                     syscall: SYS14_READ,
                                     reason: InvalidSyscallReason::Unimplemented,
-                                })
-                                .show_error(
-                                    ErrorContext::Binary,
-                                    files
-                                        .iter()
-                                        .map(|(tag, content)| {
-                                            (Rc::from(&**tag), Rc::from(&**content))
-                                        })
-                                        .collect(),
+                                });
+
+                                show_runtime_error(
+                                    &opts.diagnostics,
+                                    &error,
+                                    &files,
                                     &iset,
                                     &binary,
                                     &runtime,
                                 );
-                                process::exit(1);
                             }
                             Write(_args, guard) => {
                                 // TODO: implement file write for mipsy cli frontend
                                 runtime = guard(-1);
                                 runtime.timeline_mut().pop_last_state();
-                                println!();
-                                RuntimeError::new(Error::InvalidSyscall {
+                                let error = RuntimeError::new(Error::InvalidSyscall {
                                     syscall: SYS15_WRITE,
                                     reason: InvalidSyscallReason::Unimplemented,
-                                })
-                                .show_error(
-                                    ErrorContext::Binary,
-                                    files
-                                        .iter()
-                                        .map(|(tag, content)| {
-                                            (Rc::from(&**tag), Rc::from(&**content))
-                                        })
-                                        .collect(),
+                                });
+
+                                show_runtime_error(
+                                    &opts.diagnostics,
+                                    &error,
+                                    &files,
                                     &iset,
                                     &binary,
                                     &runtime,
                                 );
-                                process::exit(1);
                             }
                             Close(_args, guard) => {
                                 // TODO: implement file close for mipsy cli frontend
                                 runtime = guard(-1);
                                 runtime.timeline_mut().pop_last_state();
-                                println!();
-                                RuntimeError::new(Error::InvalidSyscall {
+                                let error = RuntimeError::new(Error::InvalidSyscall {
                                     syscall: SYS16_CLOSE,
                                     reason: InvalidSyscallReason::Unimplemented,
-                                })
-                                .show_error(
-                                    ErrorContext::Binary,
-                                    files
-                                        .iter()
-                                        .map(|(tag, content)| {
-                                            (Rc::from(&**tag), Rc::from(&**content))
-                                        })
-                                        .collect(),
+                                });
+
+                                show_runtime_error(
+                                    &opts.diagnostics,
+                                    &error,
+                                    &files,
                                     &iset,
                                     &binary,
                                     &runtime,
                                 );
-                                process::exit(1);
                             }
                             ExitStatus(args, _new_runtime) => {
                                 std::process::exit(args.exit_code);
@@ -493,19 +877,7 @@ fn main() {
             Err((old_runtime, MipsyError::Runtime(err))) => {
                 runtime = old_runtime;
 
-                println!();
-                err.show_error(
-                    ErrorContext::Binary,
-                    files
-                        .iter()
-                        .map(|(tag, content)| (Rc::from(&**tag), Rc::from(&**content)))
-                        .collect(),
-                    &iset,
-                    &binary,
-                    &runtime,
-                );
-
-                process::exit(1);
+                show_runtime_error(&opts.diagnostics, &err, &files, &iset, &binary, &runtime);
             }
             Err((_, MipsyError::Parser(_) | MipsyError::Compiler(_))) => {
                 unreachable!("the only possible error at runtime is a MipsyError::Runtime");
@@ -514,6 +886,196 @@ fn main() {
     }
 }
 
+fn format_files(config: &MipsyConfig, check: bool, files: &[String]) -> ! {
+    let mut unformatted = false;
+
+    for name in files {
+        let source = if name == "-" {
+            std::io::read_to_string(std::io::stdin())
+        } else {
+            fs::read_to_string(name)
+        };
+
+        let source = match source {
+            Ok(source) => source,
+            Err(err) => {
+                prompt::error_nl(format!(
+                    "failed to read file `{}`: {}",
+                    name.bold(),
+                    err.to_string().bright_red()
+                ));
+
+                process::exit(1);
+            }
+        };
+
+        let formatted = mipsy_lib::format::format(&source, config);
+
+        if check {
+            if formatted != source {
+                eprintln!("{} is not formatted", name.bold());
+                unformatted = true;
+            }
+
+            continue;
+        }
+
+        let written = if name == "-" {
+            std::io::stdout().write_all(formatted.as_bytes())
+        } else if formatted != source {
+            fs::write(name, formatted)
+        } else {
+            Ok(())
+        };
+
+        if let Err(err) = written {
+            prompt::error_nl(format!(
+                "failed to write file `{}`: {}",
+                name.bold(),
+                err.to_string().bright_red()
+            ));
+
+            process::exit(1);
+        }
+    }
+
+    process::exit(i32::from(unformatted));
+}
+
+fn disassemble(
+    config: &MipsyConfig,
+    format: Option<String>,
+    base_address: Option<u32>,
+    labels: bool,
+    source: bool,
+    words: bool,
+    inputs: &[String],
+) -> ! {
+    let iset = mipsy_instructions::inst_set();
+    let text_bot = base_address.unwrap_or(MemoryLayout::from_config(config).text_bot);
+
+    let fail = |name: &str, err: &dyn Display, tip: Option<&str>| -> ! {
+        prompt::error_nl(format!(
+            "failed to disassemble `{}`: {}{}",
+            name.bold(),
+            err.to_string().bright_red(),
+            tip.map(|tip| format!("\n{} {}", tip_header(), tip))
+                .unwrap_or_default(),
+        ));
+
+        process::exit(1);
+    };
+
+    let mut binaries = vec![];
+
+    if words {
+        let words = inputs
+            .iter()
+            .flat_map(|word| match disasm::parse_hex_words(word) {
+                Ok(words) => words,
+                Err(_) => {
+                    prompt::error_nl(format!("`{}` is not a 32-bit hex word", word.bold()));
+                    process::exit(1);
+                }
+            })
+            .collect::<Vec<_>>();
+
+        binaries.push((None, disasm::binary_from_words(&words, text_bot)));
+    } else {
+        for name in inputs {
+            let contents = if name == "-" {
+                let mut contents = vec![];
+                std::io::stdin()
+                    .read_to_end(&mut contents)
+                    .map(|_| contents)
+            } else {
+                fs::read(name)
+            };
+
+            let contents = contents.unwrap_or_else(|err| {
+                prompt::error_nl(format!(
+                    "failed to read file `{}`: {}",
+                    name.bold(),
+                    err.to_string().bright_red()
+                ));
+
+                process::exit(1);
+            });
+
+            let guessed = format.is_none();
+            let format = format.as_deref().unwrap_or_else(|| guess_format(&contents));
+
+            let binary = match format {
+                "elf" => elf::read_elf(&contents).unwrap_or_else(|err| fail(name, &err, None)),
+                "hex" => {
+                    let text = String::from_utf8_lossy(&contents);
+                    let words = disasm::parse_hex_words(&text).unwrap_or_else(|err| {
+                        // it was only read as hex because it's text
+                        let tip = "if it's a raw memory image, try `--format bin`";
+
+                        fail(name, &err, guessed.then_some(tip))
+                    });
+
+                    disasm::binary_from_words(&words, text_bot)
+                }
+                "bin" | "bin-be" => {
+                    let words = disasm::words_from_bytes(&contents, format == "bin-be")
+                        .unwrap_or_else(|err| fail(name, &err, None));
+
+                    disasm::binary_from_words(&words, text_bot)
+                }
+                _ => unreachable!("clap only accepts the formats listed"),
+            };
+
+            binaries.push((Some(name), binary));
+        }
+    }
+
+    for (i, (name, mut binary)) in binaries.into_iter().enumerate() {
+        if labels {
+            decompile::recover_labels(&mut binary, &iset);
+        }
+
+        if i != 0 {
+            println!();
+        }
+
+        if let (Some(name), true) = (name, inputs.len() > 1) {
+            match source {
+                true => println!("# {}", name),
+                false => println!("{}:", name.bold()),
+            }
+        }
+
+        match source {
+            true => print!("{}", decompile::source::decompile_to_source(&binary, &iset)),
+            false => print!("{}", mipsy_lib::decompile(&iset, &binary)),
+        }
+    }
+
+    process::exit(0);
+}
+
+/// What sort of machine code a file holds, going by what's in it.
+/// Anything that reads as text is taken to be hex, even if it isn't valid hex,
+/// so that it's the bad word that's complained about rather than its length.
+fn guess_format(contents: &[u8]) -> &'static str {
+    let is_text = |text: &str| {
+        !text.is_empty()
+            && text
+                .chars()
+                .all(|c| !c.is_control() || c.is_ascii_whitespace())
+    };
+
+    if elf::is_elf(contents) {
+        "elf"
+    } else if std::str::from_utf8(contents).is_ok_and(is_text) {
+        "hex"
+    } else {
+        "bin"
+    }
+}
+
 fn read_string(_max_len: u32) -> String {
     loop {
         let input: String = get_input("string", true);
@@ -538,6 +1100,16 @@ fn read_string(_max_len: u32) -> String {
     }
 }
 
+/// The contents of a file we compiled, which might not be one of the files
+/// we were given, if the error is in a file pulled in with `.include`.
+fn source_of(files: &[(String, String)], file_tag: &str) -> Option<Rc<str>> {
+    files
+        .iter()
+        .find(|(tag, _)| tag == file_tag)
+        .map(|(_, str)| Rc::from(&**str))
+        .or_else(|| fs::read_to_string(file_tag).ok().map(Rc::from))
+}
+
 fn compile(
     options: &CompilerOptions,
     config: &MipsyConfig,
@@ -561,13 +1133,182 @@ fn compile_with_kernel(
 
     let iset = mipsy_instructions::inst_set();
     let binary = mipsy_lib::compile_with_kernel(&iset, files, kernel, options, config)?;
+    let mut runtime = mipsy_lib::runtime(&binary, args);
+
+    if config.bounds_check {
+        runtime.enable_bounds_check(&binary);
+    }
+
+    Ok((iset, binary, runtime))
+}
+
+fn check_with_kernel(
+    options: &CompilerOptions,
+    config: &MipsyConfig,
+    files: &[(String, String)],
+    args: &[&str],
+    kernel: &mut MpProgram,
+) -> Result<(InstSet, Binary, Runtime), Vec<MipsyError>> {
+    let files = files
+        .iter()
+        .map(|(k, v)| TaggedFile::new(Some(k), v))
+        .collect::<Vec<_>>();
+
+    let iset = mipsy_instructions::inst_set();
+    let binary = mipsy_lib::check_with_kernel(&iset, files, kernel, options, config)?;
     let runtime = mipsy_lib::runtime(&binary, args);
 
     Ok((iset, binary, runtime))
 }
 
+fn show_compile_error(config: &MipsyConfig, files: &[(String, String)], error: MipsyError) {
+    match error {
+        MipsyError::Parser(error) => {
+            prompt::error_code(
+                error.error().code(),
+                format!("failed to parse `{}`", error.file_tag()),
+            );
+
+            let file_tag = error.file_tag();
+
+            let file = source_of(files, &file_tag)
+                .expect("for file to throw a parser error, it should probably exist");
+
+            error.show_error(config, file);
+        }
+
+        MipsyError::Compiler(error) => {
+            let compile_tag = if error.file_tag().is_empty() {
+                String::new()
+            } else {
+                format!(" `{}`", error.file_tag())
+            };
+
+            prompt::error_code(
+                error.error().code(),
+                format!("failed to compile{}", compile_tag),
+            );
+
+            let file_tag = error.file_tag();
+
+            let file = source_of(files, &file_tag).unwrap_or_else(|| Rc::from(""));
+
+            error.show_error(config, file);
+        }
+
+        // unreachable: a bit tricky to get a runtime error at compile-time
+        MipsyError::Runtime(_) => unreachable!(),
+    }
+}
+
+/// The structured equivalent of [`show_compile_error`].
+fn compile_diagnostic(
+    config: &MipsyConfig,
+    files: &[(String, String)],
+    error: &MipsyError,
+) -> Diagnostic {
+    match error {
+        MipsyError::Parser(error) => {
+            let file = source_of(files, &error.file_tag()).unwrap_or_else(|| Rc::from(""));
+
+            Diagnostic::from_parser_error(error, config, &file)
+        }
+
+        MipsyError::Compiler(error) => {
+            let file = source_of(files, &error.file_tag()).unwrap_or_else(|| Rc::from(""));
+
+            Diagnostic::from_compiler_error(error, config, &file)
+        }
+
+        // unreachable: a bit tricky to get a runtime error at compile-time
+        MipsyError::Runtime(_) => unreachable!(),
+    }
+}
+
+/// Shows a runtime error in the format asked for with `--diagnostics`, and exits.
+fn show_runtime_error(
+    format: &str,
+    error: &RuntimeError,
+    files: &[(String, String)],
+    iset: &InstSet,
+    binary: &Binary,
+    runtime: &Runtime,
+) -> ! {
+    let source_code: Vec<_> = files
+        .iter()
+        .map(|(tag, content)| (Rc::from(&**tag), Rc::from(&**content)))
+        .collect();
+
+    if format == "human" {
+        println!();
+        error.show_error(ErrorContext::Binary, source_code, iset, binary, runtime);
+        explain_hint(&[error.error().code()]);
+    } else {
+        let diagnostic = Diagnostic::from_runtime_error(error, &source_code, iset, binary, runtime);
+
+        report(format, &[diagnostic]);
+    }
+
+    process::exit(1);
+}
+
+/// Points to `--explain` for each of the errors that were just shown.
+fn explain_hint(codes: &[&str]) {
+    let mut codes = codes.to_vec();
+    codes.sort_unstable();
+    codes.dedup();
+
+    match codes.as_slice() {
+        [] => {}
+        [code] => println!(
+            "\nfor more information about this error, try `{}`",
+            format!("mipsy --explain {}", code).bold()
+        ),
+        codes => println!(
+            "\nsome errors have detailed explanations: {}\nfor more information about an error, try `{}`",
+            codes.join(", "),
+            format!("mipsy --explain {}", codes[0]).bold()
+        ),
+    }
+}
+
+/// Writes out diagnostics for `--diagnostics json` (one per line) or `--diagnostics sarif`.
+fn report(format: &str, diagnostics: &[Diagnostic]) {
+    match format {
+        "json" => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostic.to_json());
+            }
+        }
+        "sarif" => eprintln!("{}", diagnostic::sarif(diagnostics)),
+        _ => unreachable!("clap only accepts human, json and sarif"),
+    }
+}
+
+fn load_elf(
+    config: &MipsyConfig,
+    contents: &[u8],
+    args: &[&str],
+) -> Result<(InstSet, Binary, Runtime), ElfError> {
+    let iset = mipsy_instructions::inst_set();
+    let binary = elf::read_elf(contents)?;
+    let mut runtime = mipsy_lib::runtime(&binary, args);
+
+    // a C compiler's system calls will be linux's
+    runtime.enable_linux_syscalls();
+
+    if config.bounds_check {
+        runtime.enable_bounds_check(&binary);
+    }
+
+    Ok((iset, binary, runtime))
+}
+
 pub const VERSION: &str = concat!(
     env!("VERGEN_GIT_COMMIT_DATE"),
     " ",
     env!("VERGEN_GIT_SHA_SHORT")
 );
+
+/// The formats that `--emit` can write a compiled program out as.
+const EMIT_FORMATS: &[&str] = &["elf", "logisim", "ihex", "bin", "bin-be", "mif", "coe"];
@@ -0,0 +1,27 @@
+//! Helpers shared by the integration tests, not all of which use every one.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A directory of its own for a test, which is also where mipsy's config goes.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mipsy-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// The mipsy binary, to be run in `dir`.
+pub fn mipsy(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mipsy"));
+    command
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir);
+
+    command
+}
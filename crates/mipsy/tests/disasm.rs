@@ -0,0 +1,212 @@
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use common::mipsy;
+
+const PROGRAM: &str = "
+main:
+    li   $t0, 3
+loop:
+    addi $t0, $t0, -1
+    bnez $t0, loop
+    jal  f
+    jr   $ra
+f:
+    jr   $ra
+";
+
+const LISTING: &str = "\
+0x00400000 [0x20080003]    addi   $t0, $zero, 3
+0x00400004 [0x2108ffff]    addi   $t0, $t0, -1
+0x00400008 [0x1500ffff]    bne    $t0, $zero, -1
+0x0040000c [0x0c100005]    jal    00400014
+0x00400010 [0x03e00008]    jr     $ra
+0x00400014 [0x03e00008]    jr     $ra
+";
+
+/// A directory of its own for a test, holding `program.s` assembled into each format.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = common::test_dir(&format!("disasm-{}", name));
+    fs::write(dir.join("program.s"), PROGRAM).unwrap();
+
+    for format in ["elf", "bin", "bin-be"] {
+        let output = format!("program.{}", format);
+        let emit = run(&dir, &["program.s", "--emit", format, "-o", &output], None);
+        assert!(
+            emit.status.success(),
+            "{}",
+            String::from_utf8_lossy(&emit.stdout)
+        );
+    }
+
+    let hex = run(&dir, &["program.s", "--hex"], None);
+    fs::write(dir.join("program.hex"), hex.stdout).unwrap();
+
+    dir
+}
+
+/// Runs mipsy in `dir`, which is also where its config goes.
+fn run(dir: &Path, args: &[&str], stdin: Option<&[u8]>) -> Output {
+    let mut child = mipsy(dir)
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.unwrap_or_default())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+/// What `mipsy disasm` prints, without the space after each label.
+fn disasm(dir: &Path, args: &[&str]) -> String {
+    let output = run(dir, &[&["disasm"], args].concat(), None);
+    assert!(output.status.success(), "{:?}", output);
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect()
+}
+
+/// The first `count` lines of `text`.
+fn lines(text: &str, count: usize) -> String {
+    text.lines()
+        .take(count)
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[test]
+fn test_formats() {
+    let dir = test_dir("formats");
+
+    assert_eq!(disasm(&dir, &["--format", "hex", "program.hex"]), LISTING);
+    assert_eq!(disasm(&dir, &["--format", "bin", "program.bin"]), LISTING);
+    assert_eq!(
+        disasm(&dir, &["--format", "bin-be", "program.bin-be"]),
+        LISTING
+    );
+    assert_eq!(
+        disasm(&dir, &["-w", "20080003", "0x2108ffff"]),
+        lines(LISTING, 2)
+    );
+
+    // an ELF file has its own labels, and a delay slot after each branch and jump
+    assert_eq!(
+        disasm(&dir, &["--format", "elf", "program.elf"]),
+        "
+main:
+0x00400000 [0x20080003]    addi   $t0, $zero, 3
+
+loop:
+0x00400004 [0x2108ffff]    addi   $t0, $t0, -1
+0x00400008 [0x1500fffe]    bne    $t0, $zero, loop
+0x0040000c [0x00000000]    nop
+0x00400010 [0x0c100008]    jal    f
+0x00400014 [0x00000000]    nop
+0x00400018 [0x03e00008]    jr     $ra
+0x0040001c [0x00000000]    nop
+
+f:
+0x00400020 [0x03e00008]    jr     $ra
+0x00400024 [0x00000000]    nop
+"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_guess_format() {
+    let dir = test_dir("guess");
+
+    assert_eq!(disasm(&dir, &["program.hex"]), LISTING);
+    assert_eq!(disasm(&dir, &["program.bin"]), LISTING);
+    assert_eq!(
+        disasm(&dir, &["program.elf"]),
+        disasm(&dir, &["--format", "elf", "program.elf"])
+    );
+
+    // the endianness can't be guessed
+    assert_ne!(disasm(&dir, &["program.bin-be"]), LISTING);
+
+    // text that isn't hex is still read as hex, so the bad word is what's complained about
+    let output = run(&dir, &["disasm", "-"], Some(b"zzzz\n"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.contains("`zzzz` (on line 1) is not a 32-bit hex word"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("try `--format bin`"), "{}", stderr);
+
+    // unless it's said to be something else
+    let output = run(&dir, &["disasm", "--format", "bin", "-"], Some(b"zzzz\n"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.contains("5 bytes isn't a whole number of 32-bit words"),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_labels() {
+    let dir = test_dir("labels");
+
+    // every branch and jump target gets a label
+    let listing = "
+loc_00400004:
+0x00400004 [0x2108ffff]    addi   $t0, $t0, -1
+0x00400008 [0x1500ffff]    bne    $t0, $zero, loc_00400004
+0x0040000c [0x0c100005]    jal    loc_00400014
+0x00400010 [0x03e00008]    jr     $ra
+
+loc_00400014:
+0x00400014 [0x03e00008]    jr     $ra
+";
+
+    for input in ["program.hex", "program.bin"] {
+        assert_eq!(
+            disasm(&dir, &["--labels", input]),
+            format!("{}{}", lines(LISTING, 1), listing)
+        );
+    }
+
+    // as they are in source
+    assert_eq!(
+        disasm(&dir, &["--labels", "--source", "program.bin"]),
+        "\t.text
+\tli      $t0, 3
+loc_00400004:
+\taddi    $t0, $t0, -1
+\tbnez    $t0, loc_00400004
+\tjal     loc_00400014
+\tjr      $ra
+loc_00400014:
+\tjr      $ra
+"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use std::fs;
+
+use common::{mipsy, test_dir};
+
+#[test]
+fn test_check() {
+    let dir = test_dir("fmt-check");
+
+    fs::write(dir.join("formatted.s"), "main:\n        jr      $ra\n").unwrap();
+    fs::write(dir.join("unformatted.s"), "main: jr $ra\n").unwrap();
+
+    let formatted = mipsy(&dir)
+        .args(["fmt", "--check", "formatted.s"])
+        .output()
+        .unwrap();
+    assert_eq!(formatted.status.code(), Some(0));
+
+    let unformatted = mipsy(&dir)
+        .args(["fmt", "--check", "formatted.s", "unformatted.s"])
+        .output()
+        .unwrap();
+    assert_eq!(unformatted.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&unformatted.stderr).contains("unformatted.s"));
+
+    // checking doesn't change anything
+    assert_eq!(
+        fs::read_to_string(dir.join("unformatted.s")).unwrap(),
+        "main: jr $ra\n"
+    );
+
+    // but formatting does, after which the check passes
+    let format = mipsy(&dir).args(["fmt", "unformatted.s"]).output().unwrap();
+    assert_eq!(format.status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(dir.join("unformatted.s")).unwrap(),
+        "main:\n        jr      $ra\n"
+    );
+
+    let check = mipsy(&dir)
+        .args(["fmt", "--check", "unformatted.s"])
+        .output()
+        .unwrap();
+    assert_eq!(check.status.code(), Some(0));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
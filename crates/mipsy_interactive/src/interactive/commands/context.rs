@@ -3,7 +3,9 @@ use crate::interactive::{commands::util::expect_u32, error::CommandError};
 use colored::*;
 use mipsy_lib::decompile;
 use mipsy_lib::KTEXT_BOT;
-use mipsy_lib::TEXT_BOT;
+
+/// More instructions than any pseudo-instruction expands into.
+const MAX_EXPANSION: i32 = 16;
 
 #[allow(unreachable_code)]
 pub(crate) fn context_command() -> Command {
@@ -11,7 +13,7 @@ pub(crate) fn context_command() -> Command {
         "context",
         vec!["c", "ctx"],
         vec![],
-        vec!["n"],
+        vec!["n", "native|pseudo"],
         vec![],
         &format!(
             "prints the current and surrounding 3 (or {}) instructions",
@@ -20,11 +22,18 @@ pub(crate) fn context_command() -> Command {
         |_, state, label, args| {
             if label == "__help__" {
                 return Ok(format!(
-                    "prints the current and surrounding 3 (or {}) instructions",
+                    "prints the current and surrounding 3 (or {}) instructions\n\
+                     Pseudo-instructions are shown as they were written, unless\n\
+                     `{}` is set in your mipsy config, or `{}` is given\n\
+                \x20 (and `{}` shows them even if it is set).",
                     "[n]".magenta(),
+                    "native_instructions".bold(),
+                    "native".magenta(),
+                    "pseudo".magenta(),
                 ));
             }
 
+            let (native, args) = util::native_instructions(state.config.native_instructions, args);
             let f: Option<&dyn Fn(i32) -> String> = None;
 
             let n = match args.first() {
@@ -32,6 +41,13 @@ pub(crate) fn context_command() -> Command {
                 None => Ok(3),
             }? as i32;
 
+            if let Some(arg) = args.get(1) {
+                return Err(CommandError::BadArgument {
+                    arg: "[native|pseudo]".magenta().to_string(),
+                    instead: arg.to_string(),
+                });
+            }
+
             if state.exited {
                 return Err(CommandError::ProgramExited);
             }
@@ -41,19 +57,42 @@ pub(crate) fn context_command() -> Command {
             let runtime = &state.runtime;
 
             let base_addr = runtime.timeline().state().pc();
-            for i in (-n)..=n {
-                let addr = {
-                    let addr = base_addr.wrapping_add((i * 4) as u32);
-                    if addr < TEXT_BOT {
-                        continue;
-                    }
+            let addr_at = |i: i32| base_addr.wrapping_add((i * 4) as u32);
 
-                    if addr < KTEXT_BOT && addr >= (TEXT_BOT + binary.text.len() as u32) {
-                        continue;
-                    }
+            let in_text = |addr: u32| {
+                addr >= binary.layout.text_bot
+                    && (addr >= KTEXT_BOT
+                        || addr < (binary.layout.text_bot + binary.text.len() as u32))
+            };
 
-                    addr
-                };
+            // so that a pseudo-instruction cut off by the edge of the context
+            // can still be folded, take in the rest of its line
+            let (mut first, mut last) = (-n, n);
+
+            if !native {
+                while first > -n - MAX_EXPANSION
+                    && !binary.line_numbers.contains_key(&addr_at(first))
+                    && in_text(addr_at(first - 1))
+                {
+                    first -= 1;
+                }
+
+                while last < n + MAX_EXPANSION
+                    && !binary.line_numbers.contains_key(&addr_at(last + 1))
+                    && in_text(addr_at(last + 1))
+                {
+                    last += 1;
+                }
+            }
+
+            let mut parts = vec![];
+
+            for i in first..=last {
+                let addr = addr_at(i);
+
+                if !in_text(addr) {
+                    continue;
+                }
 
                 let inst = {
                     if let Ok(inst) = runtime.timeline().state().read_mem_word(addr) {
@@ -63,8 +102,24 @@ pub(crate) fn context_command() -> Command {
                     }
                 };
 
-                let parts = decompile::decompile_inst_into_parts(binary, &state.iset, inst, addr);
-                util::print_inst_parts(binary, &Ok(parts), Some(program), i == 0);
+                parts.push((
+                    i,
+                    decompile::decompile_inst_into_parts(binary, &state.iset, inst, addr),
+                ));
+            }
+
+            if !native {
+                decompile::fold_pseudo_instructions(
+                    binary,
+                    &state.iset,
+                    parts.iter_mut().map(|(_, parts)| parts),
+                );
+            }
+
+            for (i, parts) in parts {
+                if (-n..=n).contains(&i) {
+                    util::print_inst_parts(binary, &Ok(parts), Some(program), i == 0);
+                }
             }
 
             println!();
@@ -3,32 +3,56 @@ use crate::interactive::error::CommandError;
 use super::*;
 use colored::*;
 
-use mipsy_lib::decompile::{decompile_into_parts, Decompiled, Uninit};
+use mipsy_lib::decompile::{decompile_into_parts, fold_pseudo_instructions, Decompiled, Uninit};
 
 pub(crate) fn disassemble_command() -> Command {
     command(
         "disassemble",
         vec!["d", "dis", "disas", "disasm", "dec", "decompile"],
         vec![],
-        vec![],
+        vec!["native|pseudo"],
         vec![],
         "disassembles the currently loaded file",
-        |_, state, label, _args| {
+        |_, state, label, args| {
             if label == "__help__" {
                 return Ok(
                     format!(
-                        "Disassembles the currently loaded file, similar to how `{}` displays instructions.",
+                        "Disassembles the currently loaded file, similar to how `{}` displays instructions.\n\
+                         Pseudo-instructions are shown as they were written, unless\n\
+                         `{}` is set in your mipsy config, or `{}` is given\n\
+                    \x20 (and `{}` shows them even if it is set).",
                         "step".bold(),
+                        "native_instructions".bold(),
+                        "native".magenta(),
+                        "pseudo".magenta(),
                     ),
                 );
             }
 
+            let (native, args) = util::native_instructions(state.config.native_instructions, args);
+            if let Some(arg) = args.first() {
+                return Err(CommandError::BadArgument {
+                    arg: "[native|pseudo]".magenta().to_string(),
+                    instead: arg.to_string(),
+                });
+            }
+
             let binary = state.binary.as_ref().ok_or(CommandError::MustLoadFile)?;
 
             let mut decompiled = decompile_into_parts(binary, &state.iset)
                 .into_iter()
                 .collect::<Vec<(u32, Result<Decompiled, Uninit>)>>();
 
+            if !native {
+                fold_pseudo_instructions(
+                    binary,
+                    &state.iset,
+                    decompiled
+                        .iter_mut()
+                        .filter_map(|(_, inst)| inst.as_mut().ok()),
+                );
+            }
+
             decompiled.sort_by_key(|&(addr, _)| addr);
 
             if let Some((_, inst)) = decompiled.get(0) {
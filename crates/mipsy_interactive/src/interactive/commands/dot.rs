@@ -50,7 +50,7 @@ pub(crate) fn dot_command() -> Command {
             let empty_binary = Binary::default();
             let binary = state.binary.as_ref().unwrap_or(&empty_binary);
 
-            compile::check_post_data_label(&program, binary).map_err(|error| {
+            compile::check_post_data_label(&program, binary, &state.iset).map_err(|error| {
                 CommandError::CannotCompileLine {
                     line: line.to_string(),
                     error,
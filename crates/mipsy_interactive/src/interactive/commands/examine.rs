@@ -1,8 +1,5 @@
 use colored::Colorize;
-use mipsy_lib::{
-    util::{get_segment, Segment},
-    Register, Safe,
-};
+use mipsy_lib::{util::Segment, Register, Safe};
 use mipsy_parser::{MpArgument, MpImmediate, MpNumber};
 use std::{fmt::Display, str::FromStr};
 
@@ -46,6 +43,7 @@ pub(crate) fn examine_command() -> Command {
             }
 
             let binary = state.binary.as_ref().ok_or(CommandError::MustLoadFile)?;
+            let layout = binary.layout;
 
             let mut segment = if let Some(segment) =
                 args.get(0).and_then(|segment| match segment.as_ref() {
@@ -76,7 +74,7 @@ pub(crate) fn examine_command() -> Command {
                 }
                 base
             } else {
-                Ok(segment.get_lower_bound())
+                Ok(layout.segment_start(segment))
             };
 
             let hide_labels = args
@@ -84,14 +82,14 @@ pub(crate) fn examine_command() -> Command {
                 .map_or(false, |a| a == &String::from("-nolabels"));
             if hide_labels && base_addr.is_err() {
                 // if -labels was provided, ensure base_addr is valid
-                base_addr = Ok(segment.get_lower_bound());
+                base_addr = Ok(layout.segment_start(segment));
             }
 
             let base_addr = base_addr? as usize;
 
             // for most cases this is a no-op, but if given an address in the stack we should
             // reverse the order of the scan
-            segment = get_segment(base_addr as u32);
+            segment = layout.segment_of(base_addr as u32);
 
             let default_size = 16;
             let row_size: usize = termsize::get()
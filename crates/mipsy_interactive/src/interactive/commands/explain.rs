@@ -0,0 +1,34 @@
+use crate::interactive::error::CommandError;
+
+use super::*;
+use colored::*;
+use mipsy_lib::error::codes;
+
+pub(crate) fn explain_command() -> Command {
+    command(
+        "explain",
+        vec![],
+        vec!["code"],
+        vec![],
+        vec![],
+        "explain an error code in detail",
+        |_, _state, label, args| {
+            if label == "__help__" {
+                return Ok(format!(
+                    "Prints a detailed explanation of the error with the given {},\n\
+                     \x20 such as {}, with examples of what causes it and how to fix it.",
+                    "<code>".magenta(),
+                    "M0105".bold(),
+                ));
+            }
+
+            let explanation = codes::explain(&args[0]).ok_or(CommandError::UnknownErrorCode {
+                code: args[0].clone(),
+            })?;
+
+            println!("\n{}", explanation);
+
+            Ok("".into())
+        },
+    )
+}
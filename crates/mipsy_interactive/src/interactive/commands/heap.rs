@@ -0,0 +1,152 @@
+use crate::interactive::{error::CommandError, prompt};
+use mipsy_lib::{runtime::HeapAllocation, Register, Safe};
+
+use super::*;
+use colored::*;
+
+pub(crate) fn heap_command() -> Command {
+    command(
+        "heap",
+        vec!["hp", "allocs"],
+        vec![],
+        vec![],
+        vec![],
+        "list the memory allocated with sbrk",
+        |_, state, label, _args| {
+            if label == "__help__" {
+                return Ok(format!(
+                    "Lists every call to {0} made so far, where it was called,\n\
+                     and the memory it returned.\n\
+                     Also shows which of the allocated bytes are still uninitialized,\n\
+                     and which registers currently point into each allocation.",
+                    "sbrk".yellow().bold(),
+                ));
+            }
+
+            let binary = state.binary.as_ref().ok_or(CommandError::MustLoadFile)?;
+            let runtime_state = state.runtime.timeline().state();
+            let allocations = runtime_state.heap_allocations();
+
+            if allocations.is_empty() {
+                prompt::error_nl("sbrk has not been called yet");
+                return Ok("".into());
+            }
+
+            println!(
+                "\n{} {} bytes in use, from {} calls to sbrk\n",
+                "[heap]".green().bold(),
+                runtime_state.heap_size(),
+                allocations.len(),
+            );
+
+            for allocation in allocations {
+                let site = match binary.line_numbers.get(&allocation.site) {
+                    Some((_, line)) => format!("line {}", line),
+                    None => format!("0x{:08x}", allocation.site),
+                };
+
+                let size = match allocation.size {
+                    0 => "sbrk(0)".to_string(),
+                    size if size < 0 => format!("freed {} bytes", size.unsigned_abs()),
+                    size if allocation.freed > 0 => {
+                        format!("{} bytes, {} freed", size, allocation.freed)
+                    }
+                    size => format!("{} bytes", size),
+                };
+
+                println!(
+                    "0x{:08x}  {:>14}  {} {}",
+                    allocation.addr,
+                    size.bold(),
+                    "from".bright_black(),
+                    site.yellow(),
+                );
+
+                if allocation.size_in_use() == 0 {
+                    continue;
+                }
+
+                let pointers = pointers_into(runtime_state.registers(), allocation);
+                if !pointers.is_empty() {
+                    println!(
+                        "{:28}{} {}",
+                        "",
+                        "pointed to by".bright_black(),
+                        pointers.join(", ")
+                    );
+                }
+
+                let uninitialised = uninitialised_ranges(runtime_state, allocation);
+                if uninitialised.is_empty() {
+                    println!("{:28}{}", "", "fully initialized".green());
+                } else {
+                    let ranges = uninitialised
+                        .iter()
+                        .map(|&(start, end)| {
+                            if end - start == 1 {
+                                format!("+{}", start - allocation.addr)
+                            } else {
+                                format!("+{}..+{}", start - allocation.addr, end - allocation.addr)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    println!("{:28}{} {}", "", "uninitialized bytes:".red(), ranges,);
+                }
+            }
+            println!();
+
+            Ok("".into())
+        },
+    )
+}
+
+fn pointers_into(registers: &[Safe<i32>], allocation: &HeapAllocation) -> Vec<String> {
+    registers
+        .iter()
+        .enumerate()
+        .filter_map(|(reg, value)| {
+            let value = value.as_option()?;
+
+            if !allocation.contains(*value as u32) {
+                return None;
+            }
+
+            let name = format!("${}", Register::from_u32(reg as u32).ok()?.to_lower_str()).yellow();
+            let offset = *value as u32 - allocation.addr;
+
+            Some(if offset == 0 {
+                name.to_string()
+            } else {
+                format!("{} (+{})", name, offset)
+            })
+        })
+        .collect()
+}
+
+/// Returns the half-open ranges of uninitialised bytes in an allocation.
+fn uninitialised_ranges(
+    runtime_state: &mipsy_lib::State,
+    allocation: &HeapAllocation,
+) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = vec![];
+
+    for addr in allocation.addr..allocation.addr + allocation.size_in_use() {
+        let initialised = matches!(
+            runtime_state.read_mem_byte_uninit_unchecked(addr),
+            Ok(Safe::Valid(_))
+        );
+
+        if initialised {
+            continue;
+        }
+
+        match ranges.last_mut() {
+            Some((_, end)) if *end == addr => *end += 1,
+            _ => ranges.push((addr, addr + 1)),
+        }
+    }
+
+    ranges
+}
@@ -1,5 +1,6 @@
 use crate::interactive::error::CommandError;
-use mipsy_lib::DATA_BOT;
+use mipsy_lib::util::Segment;
+use mipsy_parser::is_local_label;
 
 use super::*;
 use colored::*;
@@ -25,7 +26,9 @@ pub(crate) fn labels_command() -> Command {
                 .labels
                 .keys()
                 .filter(|&label| {
-                    !(label.starts_with("kernel__") || label == &String::from("_start"))
+                    !(label.starts_with("kernel__")
+                        || label == &String::from("_start")
+                        || is_local_label(label))
                 })
                 .map(|label| label.len())
                 .max()
@@ -35,26 +38,38 @@ pub(crate) fn labels_command() -> Command {
                 .labels
                 .iter()
                 .map(|(key, &val)| (key.to_string(), val))
-                .filter(|(key, _)| !(key.starts_with("kernel__") || key == &String::from("_start")))
+                .filter(|(key, _)| {
+                    !(key.starts_with("kernel__")
+                        || key == &String::from("_start")
+                        || is_local_label(key))
+                })
                 .collect();
 
             entries.sort_by_key(|(_, val)| *val);
 
-            println!("\n{}", "[text]".green().bold());
+            // the data segment isn't always after the text segment
+            let (text, data): (Vec<_>, Vec<_>) = entries.into_iter().partition(|&(_, addr)| {
+                matches!(
+                    binary.layout.segment_of(addr),
+                    Segment::Text | Segment::KText
+                )
+            });
 
-            let mut printed_data_header = false;
-            for (label, addr) in entries {
-                if addr >= DATA_BOT && !printed_data_header {
-                    println!("\n{}", "[data]".green().bold());
-                    printed_data_header = true;
+            for (header, entries) in [("[text]", text), ("[data]", data)] {
+                if entries.is_empty() {
+                    continue;
                 }
 
-                println!(
-                    "{:max_len$} => 0x{:08x}",
-                    label.yellow().bold(),
-                    addr,
-                    max_len = max_len
-                );
+                println!("\n{}", header.green().bold());
+
+                for (label, addr) in entries {
+                    println!(
+                        "{:max_len$} => 0x{:08x}",
+                        label.yellow().bold(),
+                        addr,
+                        max_len = max_len
+                    );
+                }
             }
             println!();
 
@@ -3,8 +3,10 @@ use crate::interactive::{error::CommandError, prompt};
 use super::*;
 use colored::*;
 use mipsy_lib::compile::CompilerOptions;
+use mipsy_lib::elf;
 use mipsy_parser::TaggedFile;
 use mipsy_utils::expand_tilde;
+use std::rc::Rc;
 
 pub(crate) fn load_command() -> Command {
     command_varargs(
@@ -19,6 +21,7 @@ pub(crate) fn load_command() -> Command {
                 return Ok(
                     format!(
                         "Loads a MIPS file to run, overwriting whatever is currently loaded.\n\
+                         A statically linked MIPS ELF executable can be loaded instead.\n\
                          This command must be run prior to many others, such as `{}`, `{}`, `{}`, ...",
                         "run".bold(),
                         "step".bold(),
@@ -50,7 +53,7 @@ pub(crate) fn load_command() -> Command {
                         path = &stdin;
                     }
 
-                    match std::fs::read_to_string(expand_tilde(path)) {
+                    match std::fs::read(expand_tilde(path)) {
                         Ok(content) => Ok((path.to_string(), content)),
                         Err(err) => Err(CommandError::CannotReadFile {
                             path: path.clone(),
@@ -60,6 +63,48 @@ pub(crate) fn load_command() -> Command {
                 })
                 .collect::<Result<_, _>>()?;
 
+            // a single ELF file is loaded as it is, rather than compiled
+            if let [(path, content)] = program.as_slice() {
+                if elf::is_elf(content) {
+                    let binary = elf::read_elf(content).map_err(|elf_error| {
+                        CommandError::CannotLoadElf {
+                            path: path.clone(),
+                            elf_error,
+                        }
+                    })?;
+
+                    let mut runtime = mipsy_lib::runtime(
+                        &binary,
+                        &arguments.iter().map(|x| &**x).collect::<Vec<_>>(),
+                    );
+                    runtime.enable_linux_syscalls();
+
+                    if state.config.bounds_check {
+                        runtime.enable_bounds_check(&binary);
+                    }
+
+                    state.program = Some(vec![]);
+                    state.binary = Some(binary);
+                    state.runtime = runtime;
+                    state.exited = false;
+
+                    prompt::success_nl("file loaded");
+
+                    return Ok("".into());
+                }
+            }
+
+            let program = program
+                .into_iter()
+                .map(|(path, content)| match String::from_utf8(content) {
+                    Ok(content) => Ok((path, content)),
+                    Err(err) => Err(CommandError::CannotReadFile {
+                        path,
+                        os_error: err.to_string(),
+                    }),
+                })
+                .collect::<Result<_, _>>()?;
+
             state.program = Some(program);
             let program = state.program.as_ref().unwrap();
 
@@ -76,12 +121,12 @@ pub(crate) fn load_command() -> Command {
             )
             .map_err(|err| CommandError::CannotCompile { mipsy_error: err })?;
 
-            let runtime =
+            let mut runtime =
                 mipsy_lib::runtime(&binary, &arguments.iter().map(|x| &**x).collect::<Vec<_>>());
 
-            state.binary = Some(binary);
-            state.runtime = runtime;
-            state.exited = false;
+            if state.config.bounds_check {
+                runtime.enable_bounds_check(&binary);
+            }
 
             let loaded = if program.len() == 1 {
                 "file loaded"
@@ -89,6 +134,30 @@ pub(crate) fn load_command() -> Command {
                 "files loaded"
             };
 
+            // so that lines from included files can be shown too
+            state.program.as_mut().unwrap().extend(
+                binary
+                    .included_files
+                    .iter()
+                    .map(|(tag, contents)| (tag.to_string(), contents.to_string())),
+            );
+
+            let program = state.program.as_ref().unwrap();
+            for warning in &binary.warnings {
+                let file = program
+                    .iter()
+                    .find(|(tag, _)| **tag == *warning.file_tag())
+                    .map(|(_, file)| Rc::from(&**file))
+                    .unwrap_or_else(|| Rc::from(""));
+
+                warning.show_warning(&state.config, file);
+                println!();
+            }
+
+            state.binary = Some(binary);
+            state.runtime = runtime;
+            state.exited = false;
+
             prompt::success_nl(loaded);
 
             Ok("".into())
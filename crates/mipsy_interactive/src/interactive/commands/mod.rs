@@ -6,6 +6,8 @@ mod disassemble;
 mod dot;
 mod examine;
 mod exit;
+mod explain;
+mod heap;
 mod help;
 mod label;
 mod labels;
@@ -23,6 +25,8 @@ pub(crate) use disassemble::disassemble_command;
 pub(crate) use dot::dot_command;
 pub(crate) use examine::examine_command;
 pub(crate) use exit::exit_command;
+pub(crate) use explain::explain_command;
+pub(crate) use heap::heap_command;
 pub(crate) use help::help_command;
 pub(crate) use label::label_command;
 pub(crate) use labels::labels_command;
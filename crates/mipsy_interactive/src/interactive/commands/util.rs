@@ -37,6 +37,34 @@ where
     }
 }
 
+/// Whether to show each native instruction, rather than the pseudo-instructions
+/// they were expanded from: `native_instructions` from the config, unless the
+/// command was given `native` or `pseudo`, which are taken out of its arguments.
+pub(crate) fn native_instructions(
+    native_instructions: bool,
+    args: &[String],
+) -> (bool, Vec<String>) {
+    let mut native = native_instructions;
+
+    let args = args
+        .iter()
+        .filter(|arg| match arg.as_str() {
+            "native" => {
+                native = true;
+                false
+            }
+            "pseudo" => {
+                native = false;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect();
+
+    (native, args)
+}
+
 pub(crate) fn print_inst_parts(
     binary: &Binary,
     parts: &Result<Decompiled, Uninit>,
@@ -88,7 +116,11 @@ pub(crate) fn print_inst_parts(
         return;
     }
 
-    let name = parts.inst_name.as_ref().unwrap();
+    // a folded instruction is shown as part of the pseudo-instruction before it
+    let name = match parts.folded {
+        true => "",
+        false => parts.inst_name.as_ref().unwrap(),
+    };
 
     let last_line_len = get_final_line(binary).to_string().len();
 
@@ -1,4 +1,4 @@
-use mipsy_lib::{MipsyError, Register};
+use mipsy_lib::{elf::ElfError, MipsyError, Register};
 use mipsy_parser::ErrorLocation;
 
 pub type CommandResult<T> = Result<T, CommandError>;
@@ -28,10 +28,17 @@ pub enum CommandError {
     HelpUnknownCommand {
         command: String,
     },
+    UnknownErrorCode {
+        code: String,
+    },
     CannotReadFile {
         path: String,
         os_error: String,
     },
+    CannotLoadElf {
+        path: String,
+        elf_error: ElfError,
+    },
     CannotCompile {
         mipsy_error: MipsyError,
     },
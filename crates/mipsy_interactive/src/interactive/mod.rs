@@ -19,7 +19,6 @@ use mipsy_lib::error::runtime::{Error, ErrorContext, InvalidSyscallReason, Runti
 use mipsy_lib::runtime::{SYS13_OPEN, SYS14_READ, SYS15_WRITE, SYS16_CLOSE};
 use mipsy_lib::{
     compile::breakpoints::{get_affected_registers, TargetAction, TargetWatch},
-    error::parser,
     runtime::{state::TIMELINE_MAX_LEN, SteppedRuntime},
     Binary, InstSet, MipsyError, ParserError, Runtime,
 };
@@ -181,9 +180,15 @@ impl State {
             CommandError::HelpUnknownCommand { command } => {
                 prompt::error(format!("unknown command `{}`", command));
             }
+            CommandError::UnknownErrorCode { code } => {
+                prompt::error(format!("`{}` is not a mipsy error code", code));
+            }
             CommandError::CannotReadFile { path, os_error } => {
                 prompt::error(format!("failed to read file `{}`: {}", path, os_error));
             }
+            CommandError::CannotLoadElf { path, elf_error } => {
+                prompt::error(format!("failed to load `{}`: {}", path, elf_error));
+            }
             CommandError::CannotCompile { mipsy_error } => {
                 let file_tag = match mipsy_error {
                     MipsyError::Parser(ref error) => error.file_tag(),
@@ -200,25 +205,25 @@ impl State {
                     }
                 };
 
-                prompt::error(format!("failed to compile {}", file_prompt));
+                prompt::error_code(
+                    mipsy_error.code(),
+                    format!("failed to compile {}", file_prompt),
+                );
                 self.mipsy_error(mipsy_error, ErrorContext::Interactive, None);
             }
             CommandError::CannotParseLine { line, error } => {
-                prompt::error("failed to parse");
-
-                self.mipsy_error(
-                    MipsyError::Parser(ParserError::new(
-                        parser::Error::ParseFailure,
-                        Rc::from(""),
-                        error.line,
-                        error.col as u32,
-                    )),
-                    ErrorContext::Repl,
-                    Some(line),
-                );
+                let error = MipsyError::Parser(ParserError::new(
+                    error.kind.into(),
+                    Rc::from(""),
+                    error.line,
+                    error.col as u32,
+                ));
+
+                prompt::error_code(error.code(), "failed to parse");
+                self.mipsy_error(error, ErrorContext::Repl, Some(line));
             }
             CommandError::CannotCompileLine { line, error } => {
-                prompt::error("failed to compile instruction");
+                prompt::error_code(error.code(), "failed to compile instruction");
                 self.mipsy_error(error, ErrorContext::Repl, Some(line));
             }
             CommandError::LineDoesNotExist { line_number } => {
@@ -296,6 +301,7 @@ impl State {
         repl_line: Option<String>,
     ) {
         let config = &self.config;
+        let code = error.code();
 
         match error {
             MipsyError::Parser(error) => {
@@ -311,6 +317,8 @@ impl State {
                         .iter()
                         .find(|(tag, _)| tag.as_str() == file_tag.deref())
                         .map(|(_, str)| Rc::from(&**str))
+                        // errors in a file pulled in with `.include`
+                        .or_else(|| std::fs::read_to_string(&*file_tag).ok().map(Rc::from))
                         .expect("for file to throw a parser error, it should probably exist");
 
                     error.show_error(config, file);
@@ -329,6 +337,8 @@ impl State {
                         .iter()
                         .find(|(tag, _)| tag.as_str() == file_tag.deref())
                         .map(|(_, str)| Rc::from(&**str))
+                        // errors in a file pulled in with `.include`
+                        .or_else(|| std::fs::read_to_string(&*file_tag).ok().map(Rc::from))
                         .unwrap_or_else(|| Rc::from(""));
 
                     error.show_error(config, file);
@@ -351,6 +361,11 @@ impl State {
                 &self.runtime,
             ),
         }
+
+        println!(
+            "\nfor more information about this error, try `{}`",
+            format!("explain {}", code).bold()
+        );
     }
 
     pub(crate) fn eval_stepped_runtime(
@@ -672,8 +687,10 @@ fn state(config: MipsyConfig) -> State {
     state.add_command(commands::label_command());
     state.add_command(commands::labels_command());
     state.add_command(commands::examine_command());
+    state.add_command(commands::heap_command());
     state.add_command(commands::print_command());
     state.add_command(commands::dot_command());
+    state.add_command(commands::explain_command());
     state.add_command(commands::help_command());
     state.add_command(commands::exit_command());
 
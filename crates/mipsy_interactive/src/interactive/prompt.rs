@@ -54,6 +54,12 @@ pub fn error<D: Display>(text: D) {
     eprintln!("{}", text);
 }
 
+/// An error with a code that can be looked up with `--explain` (or `explain`).
+pub fn error_code<D: Display>(code: &str, text: D) {
+    ebanner(format!("error[{}]", code).bright_red().bold());
+    eprintln!("{}", text);
+}
+
 pub fn error_nl<D: Display>(text: D) {
     error(text);
     println!();
@@ -0,0 +1,278 @@
+//! # The attributes mipsy understands.
+//!
+//! An attribute is either written just before an item, as `#[name(value)]`,
+//! where it applies to everything on that item's line,
+//! or at the very top of a file, as `#![name(value)]`,
+//! where it applies to the whole file.
+//!
+//! ```text
+//! #![tabsize(4)]
+//! #![deny(unused_label)]
+//!
+//! #[allow(unused)]
+//! _start:
+//!     jal main
+//! ```
+//!
+//! Every supported attribute is listed in [`ATTRIBUTES`], and every lint
+//! that `allow`, `warn` and `deny` can control is listed in [`LINTS`]
+//! (or can be controlled together using one of the [`LINT_GROUPS`]).
+
+use std::{collections::HashMap, rc::Rc};
+
+use mipsy_parser::{Attribute, MpProgram};
+
+use crate::{error::compiler::Error, CompilerError, CompilerWarning, MipsyError, MipsyResult};
+
+/// An attribute that mipsy understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeInfo {
+    pub name: &'static str,
+    /// Whether this can only be used at the top of a file, as `#![...]`.
+    pub file_only: bool,
+    pub description: &'static str,
+}
+
+pub const ATTRIBUTES: &[AttributeInfo] = &[
+    AttributeInfo {
+        name: "allow",
+        file_only: false,
+        description: "don't report the given lints, e.g. `#[allow(unused_label, pseudo_cost)]`",
+    },
+    AttributeInfo {
+        name: "warn",
+        file_only: false,
+        description: "report the given lints as warnings (which is the default)",
+    },
+    AttributeInfo {
+        name: "deny",
+        file_only: false,
+        description: "report the given lints as errors, so that the program doesn't compile",
+    },
+    AttributeInfo {
+        name: "tabsize",
+        file_only: true,
+        description: "how many columns a tab takes up in this file, e.g. `#![tabsize(8)]`",
+    },
+];
+
+/// A kind of warning that can be allowed, warned about, or denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LintInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const LINTS: &[LintInfo] = &[
+    LintInfo {
+        name: "unused_label",
+        description: "a label that is never used (other than `main` and `.globl` labels)",
+    },
+    LintInfo {
+        name: "pseudo_cost",
+        description: "a pseudo-instruction that compiles to several instructions, \
+                      where a native instruction would only take one",
+    },
+    LintInfo {
+        name: "indentation",
+        description: "an instruction that isn't indented, or a label that is",
+    },
+    LintInfo {
+        name: "blank_line_before_label",
+        description: "a label on the line straight after an instruction",
+    },
+];
+
+/// Names that refer to several lints at once.
+pub const LINT_GROUPS: &[(&str, &[&str])] = &[
+    ("unused", &["unused_label"]),
+    ("style", &["indentation", "blank_line_before_label"]),
+    (
+        "warnings",
+        &[
+            "unused_label",
+            "pseudo_cost",
+            "indentation",
+            "blank_line_before_label",
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The lints named by an attribute, along with the level it sets them to.
+type Levels = Vec<(String, LintLevel)>;
+
+/// The lint levels set by the attributes in a program,
+/// in the order they were written.
+pub(super) struct LintLevels {
+    files: HashMap<Rc<str>, Levels>,
+    lines: HashMap<(Rc<str>, u32), Levels>,
+}
+
+impl LintLevels {
+    /// Reads the lint levels out of a program's attributes,
+    /// checking that every attribute is one that we understand.
+    pub(super) fn new(program: &MpProgram) -> MipsyResult<Self> {
+        let mut levels = Self {
+            files: HashMap::new(),
+            lines: HashMap::new(),
+        };
+
+        for (file_tag, attributes) in program.file_attributes() {
+            let file_tag = file_tag.clone().unwrap_or_else(|| Rc::from(""));
+
+            for attribute in attributes {
+                if let Some(lints) = read_attribute(attribute, file_tag.clone(), true)? {
+                    levels
+                        .files
+                        .entry(file_tag.clone())
+                        .or_default()
+                        .extend(lints);
+                }
+            }
+        }
+
+        for attributed_item in program.items() {
+            let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+
+            for attribute in attributed_item.attributes() {
+                let lints = read_attribute(attribute, file_tag.clone(), false)
+                    .map_err(|err| err.in_expansion(attributed_item.expansion()))?;
+
+                if let Some(lints) = lints {
+                    levels
+                        .lines
+                        .entry((file_tag.clone(), attributed_item.line_number()))
+                        .or_default()
+                        .extend(lints);
+                }
+            }
+        }
+
+        Ok(levels)
+    }
+
+    /// Drops any warnings whose lint is allowed,
+    /// failing on the first whose lint is denied.
+    pub(super) fn apply(
+        &self,
+        warnings: Vec<CompilerWarning>,
+    ) -> MipsyResult<Vec<CompilerWarning>> {
+        let mut kept = vec![];
+
+        for warning in warnings {
+            match self.level(&warning) {
+                LintLevel::Allow => {}
+                LintLevel::Warn => kept.push(warning),
+                LintLevel::Deny => return Err(MipsyError::Compiler(warning.into_error())),
+            }
+        }
+
+        Ok(kept)
+    }
+
+    fn level(&self, warning: &CompilerWarning) -> LintLevel {
+        let lint = warning.warning().lint();
+        let file_tag = warning.file_tag();
+
+        let file_levels = self.files.get(&file_tag).into_iter().flatten();
+        let line_levels = self
+            .lines
+            .get(&(file_tag.clone(), warning.line()))
+            .into_iter()
+            .flatten();
+
+        file_levels
+            .chain(line_levels)
+            .rfind(|(name, _)| lint_names_include(name, lint))
+            .map(|&(_, level)| level)
+            .unwrap_or(LintLevel::Warn)
+    }
+}
+
+fn lint_names_include(name: &str, lint: &str) -> bool {
+    name == lint
+        || LINT_GROUPS
+            .iter()
+            .any(|&(group, lints)| group == name && lints.contains(&lint))
+}
+
+/// Checks that an attribute is one we understand, giving back
+/// the lint levels that it sets (if it's an `allow`, `warn` or `deny`).
+fn read_attribute(
+    attribute: &Attribute,
+    file_tag: Rc<str>,
+    on_file: bool,
+) -> MipsyResult<Option<Levels>> {
+    let error = |error: Error| {
+        MipsyError::Compiler(CompilerError::new(
+            error,
+            file_tag.clone(),
+            attribute.line(),
+            attribute.col(),
+            attribute.col_end(),
+        ))
+    };
+
+    let name = attribute.key().to_ascii_lowercase();
+
+    let info = ATTRIBUTES
+        .iter()
+        .find(|info| info.name == name)
+        .ok_or_else(|| {
+            error(Error::UnknownAttribute {
+                attribute: attribute.key().to_string(),
+            })
+        })?;
+
+    if info.file_only && !on_file {
+        return Err(error(Error::FileOnlyAttribute {
+            attribute: info.name.to_string(),
+        }));
+    }
+
+    let level = match info.name {
+        "allow" => LintLevel::Allow,
+        "warn" => LintLevel::Warn,
+        "deny" => LintLevel::Deny,
+        // the tab size is read by the parser
+        _ => return Ok(None),
+    };
+
+    let lints: Vec<&str> = attribute
+        .value()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|lint| !lint.is_empty())
+        .collect();
+
+    if lints.is_empty() {
+        return Err(error(Error::MissingLint {
+            attribute: info.name.to_string(),
+        }));
+    }
+
+    lints
+        .into_iter()
+        .map(|lint| {
+            let known = LINTS.iter().any(|info| info.name == lint)
+                || LINT_GROUPS.iter().any(|&(group, _)| group == lint);
+
+            if known {
+                Ok((lint.to_string(), level))
+            } else {
+                Err(error(Error::UnknownLint {
+                    lint: lint.to_string(),
+                }))
+            }
+        })
+        .collect::<MipsyResult<Vec<_>>>()
+        .map(Some)
+}
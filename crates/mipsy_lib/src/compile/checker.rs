@@ -1,22 +1,28 @@
-use std::rc::Rc;
+use std::{collections::HashSet, rc::Rc};
 
-use mipsy_parser::{MpArgument, MpImmediate, MpItem, MpNumber};
+use mipsy_parser::{
+    is_local_label, is_numeric_label, MpArgument, MpConstValue, MpConstValueLoc, MpDirective,
+    MpImmediate, MpItem, MpMacroExpansion, MpNumber, MpRegister,
+};
 
+use super::{eval_constant, text::find_instruction};
 use crate::{
-    error::{compiler, ToMipsyResult},
-    inst::instruction::ToRegister,
-    Binary, CompilerError, MipsyError, MipsyResult, MpProgram, DATA_BOT, HEAP_BOT,
+    error::{
+        compiler::{self, Warning},
+        ToMipsyResult,
+    },
+    inst::instruction::{SignatureRef, ToRegister},
+    Binary, CompilerError, CompilerWarning, InstSet, MipsyError, MipsyResult, MpProgram,
 };
 
-pub enum Warning {}
-
-pub fn check_pre(program: &MpProgram) -> MipsyResult<Vec<Warning>> {
-    let warnings = vec![];
+pub fn check_pre(program: &MpProgram) -> MipsyResult<Vec<CompilerWarning>> {
+    let mut warnings = vec![];
 
     for attributed_item in program.items() {
         let item = attributed_item.item();
         let line = attributed_item.line_number();
         let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
 
         match item {
             MpItem::Instruction(ref instruction) => {
@@ -24,12 +30,10 @@ pub fn check_pre(program: &MpProgram) -> MipsyResult<Vec<Warning>> {
                     match argument {
                         MpArgument::Register(register) => {
                             let ident = register.get_identifier();
-                            ident.to_register().into_compiler_mipsy_result(
-                                file_tag.clone(),
-                                line,
-                                *col,
-                                *col_end,
-                            )?;
+                            ident
+                                .to_register()
+                                .into_compiler_mipsy_result(file_tag.clone(), line, *col, *col_end)
+                                .map_err(|err| err.in_expansion(expansion.clone()))?;
                         }
                         MpArgument::Number(_) => {} // MpArgument::LabelPlusConst(..) => {}
                     }
@@ -38,38 +42,73 @@ pub fn check_pre(program: &MpProgram) -> MipsyResult<Vec<Warning>> {
             MpItem::Label(_) => {}
             MpItem::Directive(_) => {}
             MpItem::Constant(_) => {}
+            MpItem::Error(_) => {}
         }
     }
 
-    // TODO
+    warnings.extend(check_unused_labels(program));
+    warnings.extend(check_style(program));
 
     Ok(warnings)
 }
 
-pub fn check_post_data_label(program: &MpProgram, binary: &Binary) -> MipsyResult<Vec<Warning>> {
-    let warnings = vec![];
+pub fn check_post_data_label(
+    program: &MpProgram,
+    binary: &Binary,
+    iset: &InstSet,
+) -> MipsyResult<Vec<CompilerWarning>> {
+    let mut warnings = vec![];
 
     for attributed_item in program.items() {
         let item = attributed_item.item();
         let line = attributed_item.line_number();
         let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
 
         match item {
             MpItem::Instruction(ref instruction) => {
                 for (argument, col, col_end) in instruction.arguments() {
                     match argument {
+                        MpArgument::Register(MpRegister::ExpressionOffset(_, value, _)) => {
+                            eval_constant(binary, value, file_tag.clone(), expansion.clone())?;
+                        }
                         MpArgument::Register(_) => {}
                         MpArgument::Number(number) => match number {
-                            MpNumber::Immediate(imm) => {
-                                check_imm(binary, imm, file_tag.clone(), line, *col, *col_end)?
-                            }
+                            MpNumber::Immediate(imm) => check_imm(
+                                binary,
+                                imm,
+                                file_tag.clone(),
+                                expansion.clone(),
+                                line,
+                                *col,
+                                *col_end,
+                            )?,
                             MpNumber::BinaryOpImmediate(i1, _, i2) => {
-                                check_imm(binary, i1, file_tag.clone(), line, *col, *col_end)?;
-                                check_imm(binary, i2, file_tag.clone(), line, *col, *col_end)?;
+                                check_imm(
+                                    binary,
+                                    i1,
+                                    file_tag.clone(),
+                                    expansion.clone(),
+                                    line,
+                                    *col,
+                                    *col_end,
+                                )?;
+                                check_imm(
+                                    binary,
+                                    i2,
+                                    file_tag.clone(),
+                                    expansion.clone(),
+                                    line,
+                                    *col,
+                                    *col_end,
+                                )?;
                             }
                             MpNumber::Float32(_) => {}
                             MpNumber::Float64(_) => {}
                             MpNumber::Char(_) => {}
+                            MpNumber::Relocation(_, value) | MpNumber::Expression(value) => {
+                                eval_constant(binary, value, file_tag.clone(), expansion.clone())?;
+                            }
                         }, // MpArgument::LabelPlusConst(label, _const) => {
                            //     if binary.constants.get(label).is_none() {
                            //         binary.get_label(label)
@@ -82,13 +121,15 @@ pub fn check_post_data_label(program: &MpProgram, binary: &Binary) -> MipsyResul
             MpItem::Label(_) => {}
             MpItem::Directive(_) => {}
             MpItem::Constant(_) => {}
+            MpItem::Error(_) => {}
         }
     }
 
-    if binary.data.len() > (HEAP_BOT - DATA_BOT) as usize {
+    if binary.data.len() > binary.layout.max_data_size() as usize {
         return Err(MipsyError::Compiler(CompilerError::new(
             compiler::Error::TooMuchData {
                 data_size: binary.data.len() as u32,
+                max_size: binary.layout.max_data_size(),
             },
             // this all doesn't end up being used
             Rc::from(""),
@@ -98,15 +139,269 @@ pub fn check_post_data_label(program: &MpProgram, binary: &Binary) -> MipsyResul
         )));
     }
 
-    // TODO
+    warnings.extend(check_pseudo_cost(program, iset));
 
     Ok(warnings)
 }
 
+/// Labels that are defined but never referenced.
+///
+/// `main` and any `.globl` labels are used from outside the program,
+/// and labels from macro expansions aren't where they're written,
+/// so none of these are reported.
+fn check_unused_labels(program: &MpProgram) -> Vec<CompilerWarning> {
+    let mut used = HashSet::new();
+    used.insert(String::from("main"));
+
+    for attributed_item in program.items() {
+        match attributed_item.item() {
+            MpItem::Instruction(instruction) => {
+                for (argument, _, _) in instruction.arguments() {
+                    argument_labels(argument, &mut used);
+                }
+            }
+            MpItem::Directive((directive, _)) => match directive {
+                MpDirective::Byte(values)
+                | MpDirective::Half(values)
+                | MpDirective::Word(values) => {
+                    for (value, repeat) in values {
+                        const_labels(value, &mut used);
+                        repeat
+                            .iter()
+                            .for_each(|repeat| const_labels(repeat, &mut used));
+                    }
+                }
+                MpDirective::Float(values) => {
+                    for (_, repeat) in values {
+                        repeat
+                            .iter()
+                            .for_each(|repeat| const_labels(repeat, &mut used));
+                    }
+                }
+                MpDirective::Double(values) => {
+                    for (_, repeat) in values {
+                        repeat
+                            .iter()
+                            .for_each(|repeat| const_labels(repeat, &mut used));
+                    }
+                }
+                MpDirective::Align(value) | MpDirective::Space(value) | MpDirective::If(value) => {
+                    const_labels(value, &mut used)
+                }
+                MpDirective::Globl(label) | MpDirective::IfDef(label) => {
+                    used.insert(label.to_string());
+                }
+                MpDirective::Text
+                | MpDirective::Data
+                | MpDirective::KText
+                | MpDirective::KData
+                | MpDirective::Ascii(_)
+                | MpDirective::Asciiz(_)
+                | MpDirective::Else
+                | MpDirective::EndIf => {}
+            },
+            MpItem::Constant(constant) => const_labels(constant.value(), &mut used),
+            MpItem::Label(_) | MpItem::Error(_) => {}
+        }
+    }
+
+    program
+        .items()
+        .iter()
+        .filter(|attributed_item| attributed_item.expansion().is_none())
+        .filter_map(|attributed_item| match attributed_item.item() {
+            // numeric labels are meant to be reused, so which `1:` is used isn't clear
+            MpItem::Label(label)
+                if !is_numeric_label(&label.label()) && !used.contains(&label.label()) =>
+            {
+                Some(CompilerWarning::new(
+                    Warning::UnusedLabel {
+                        label: label.label(),
+                    },
+                    attributed_item.file_tag().unwrap_or_else(|| Rc::from("")),
+                    attributed_item.line_number(),
+                    label.col(),
+                    label.col_end(),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn argument_labels(argument: &MpArgument, used: &mut HashSet<String>) {
+    let mut imm_label = |imm: &MpImmediate| {
+        if let MpImmediate::LabelReference(label) = imm {
+            used.insert(label.to_string());
+        }
+    };
+
+    match argument {
+        MpArgument::Register(MpRegister::Normal(_)) => {}
+        MpArgument::Register(MpRegister::Offset(imm, _)) => imm_label(imm),
+        MpArgument::Register(MpRegister::BinaryOpOffset(i1, _, i2, _)) => {
+            imm_label(i1);
+            imm_label(i2);
+        }
+        MpArgument::Number(MpNumber::Immediate(imm)) => imm_label(imm),
+        MpArgument::Number(MpNumber::BinaryOpImmediate(i1, _, i2)) => {
+            imm_label(i1);
+            imm_label(i2);
+        }
+        MpArgument::Number(MpNumber::Float32(_) | MpNumber::Float64(_) | MpNumber::Char(_)) => {}
+        MpArgument::Register(MpRegister::ExpressionOffset(_, value, _))
+        | MpArgument::Number(MpNumber::Relocation(_, value) | MpNumber::Expression(value)) => {
+            const_labels(value, used)
+        }
+    }
+}
+
+fn const_labels(value: &MpConstValueLoc, used: &mut HashSet<String>) {
+    match &value.0 {
+        MpConstValue::Value(_) => {}
+        MpConstValue::Const(label) => {
+            used.insert(label.to_string());
+        }
+        MpConstValue::Minus(value) | MpConstValue::Neg(value) => const_labels(value, used),
+        MpConstValue::Mult(v1, v2)
+        | MpConstValue::Sum(v1, v2)
+        | MpConstValue::Sub(v1, v2)
+        | MpConstValue::Div(v1, v2)
+        | MpConstValue::Mod(v1, v2)
+        | MpConstValue::And(v1, v2)
+        | MpConstValue::Or(v1, v2)
+        | MpConstValue::Xor(v1, v2)
+        | MpConstValue::Shl(v1, v2)
+        | MpConstValue::Shr(v1, v2) => {
+            const_labels(v1, used);
+            const_labels(v2, used);
+        }
+    }
+}
+
+/// Instructions should be indented and labels shouldn't be,
+/// and a label shouldn't come on the line straight after an instruction.
+///
+/// Code expanded from macros isn't checked, as it isn't where it's written.
+fn check_style(program: &MpProgram) -> Vec<CompilerWarning> {
+    let mut warnings = vec![];
+    let mut last_instruction: Option<(Rc<str>, u32)> = None;
+
+    for attributed_item in program.items() {
+        if attributed_item.expansion().is_some() {
+            last_instruction = None;
+            continue;
+        }
+
+        let line = attributed_item.line_number();
+        let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+
+        match attributed_item.item() {
+            MpItem::Instruction(instruction) => {
+                if instruction.col() == 1 {
+                    warnings.push(CompilerWarning::new(
+                        Warning::UnindentedInstruction,
+                        file_tag.clone(),
+                        line,
+                        instruction.col(),
+                        instruction.col_end(),
+                    ));
+                }
+
+                last_instruction = Some((file_tag, line));
+                continue;
+            }
+            MpItem::Label(label) => {
+                if label.col() != 1 {
+                    warnings.push(CompilerWarning::new(
+                        Warning::IndentedLabel {
+                            label: label.label(),
+                        },
+                        file_tag.clone(),
+                        line,
+                        label.col(),
+                        label.col_end(),
+                    ));
+                }
+
+                // local labels mark places inside a function, rather than starting a new one
+                if last_instruction == Some((file_tag.clone(), line - 1))
+                    && !is_local_label(&label.label())
+                {
+                    warnings.push(CompilerWarning::new(
+                        Warning::NoBlankLineBeforeLabel {
+                            label: label.label(),
+                        },
+                        file_tag,
+                        line,
+                        label.col(),
+                        label.col_end(),
+                    ));
+                }
+            }
+            MpItem::Directive(_) | MpItem::Constant(_) | MpItem::Error(_) => {}
+        }
+
+        last_instruction = None;
+    }
+
+    warnings
+}
+
+/// Pseudo-instructions that compile to several instructions, where the immediate
+/// form of the same instruction (e.g. `addi` for `add`) would only take one.
+fn check_pseudo_cost(program: &MpProgram, iset: &InstSet) -> Vec<CompilerWarning> {
+    let mut warnings = vec![];
+
+    for attributed_item in program.items() {
+        let instruction = match attributed_item.item() {
+            MpItem::Instruction(instruction) => instruction,
+            _ => continue,
+        };
+
+        let native_count = match find_instruction(iset, instruction) {
+            Ok(SignatureRef::Pseudo(pseudo)) if pseudo.expansion().len() > 1 => {
+                pseudo.expansion().len()
+            }
+            _ => continue,
+        };
+
+        let name = instruction.name().to_ascii_lowercase();
+        // `add` becomes `addi`, and the unsigned `addu` becomes `addiu`
+        let alternative = std::iter::once(format!("{}i", name))
+            .chain(name.strip_suffix('u').map(|stem| format!("{}iu", stem)))
+            .find(|alternative| {
+                iset.native_set().iter().any(|native| {
+                    native.name() == alternative && native.compile_signature().matches(instruction)
+                })
+            });
+
+        if let Some(alternative) = alternative {
+            warnings.push(
+                CompilerWarning::new(
+                    Warning::ExpensivePseudoInstruction {
+                        inst_name: instruction.name().to_string(),
+                        native_count,
+                        alternative,
+                    },
+                    attributed_item.file_tag().unwrap_or_else(|| Rc::from("")),
+                    attributed_item.line_number(),
+                    instruction.col(),
+                    instruction.col_end(),
+                )
+                .with_expansion(attributed_item.expansion()),
+            );
+        }
+    }
+
+    warnings
+}
+
 fn check_imm(
     binary: &Binary,
     imm: &MpImmediate,
     file_tag: Rc<str>,
+    expansion: Option<Rc<MpMacroExpansion>>,
     line: u32,
     col: u32,
     col_end: u32,
@@ -116,7 +411,8 @@ fn check_imm(
             if binary.constants.get(label).is_none() {
                 binary
                     .get_label(label)
-                    .into_compiler_mipsy_result(file_tag, line, col, col_end)?;
+                    .into_compiler_mipsy_result(file_tag, line, col, col_end)
+                    .map_err(|err| err.in_expansion(expansion))?;
             }
         }
         MpImmediate::I16(_) | MpImmediate::U16(_) | MpImmediate::I32(_) | MpImmediate::U32(_) => {}
@@ -0,0 +1,171 @@
+use std::rc::Rc;
+
+use mipsy_parser::{MpDirective, MpItem, MpProgram};
+
+use super::{data::eval_constant, Binary, CompilerOptions};
+use crate::{error::compiler::Error, CompilerError, MipsyError, MipsyResult};
+
+/// An `.if` or `.ifdef` that hasn't been closed yet.
+struct Conditional {
+    directive: &'static str,
+    file_tag: Rc<str>,
+    line: u32,
+    col: u32,
+    col_end: u32,
+    /// Whether the `.if` (or `.else`) we're currently in is included.
+    active: bool,
+    /// Whether everything around the `.if` is included.
+    enclosing_active: bool,
+    taken: bool,
+    seen_else: bool,
+}
+
+/// Removes everything inside the branches of an `.if` or `.ifdef` that aren't taken,
+/// along with the conditional directives themselves.
+///
+/// Conditions can use any constant defined before them (outside of a branch
+/// that wasn't taken), as well as any constant defined with `-D`.
+/// Constants defined with `-D` override any definition in the program.
+///
+/// The parser has already worked out the same conditions (other than those in
+/// a macro), to decide whether to expand the `.include`s, `.eqv`s and macros in
+/// each branch, and left out the branches it knew weren't taken.
+pub fn resolve_conditionals(program: &mut MpProgram, options: &CompilerOptions) -> MipsyResult<()> {
+    let mut scope = Binary {
+        constants: options.defines().cloned().collect(),
+        ..Default::default()
+    };
+    let mut conditionals: Vec<Conditional> = vec![];
+
+    let items = std::mem::take(program.items_mut());
+    let mut kept = Vec::with_capacity(items.len());
+
+    for attributed_item in items {
+        let active = conditionals.last().is_none_or(|cond| cond.active);
+        let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
+
+        let error = |error: Error, line: u32, col: u32, col_end: u32| {
+            MipsyError::Compiler(CompilerError::new(
+                error,
+                file_tag.clone(),
+                line,
+                col,
+                col_end,
+            ))
+            .in_expansion(expansion.clone())
+        };
+
+        let (directive, position) = match attributed_item.item() {
+            MpItem::Directive((
+                directive @ (MpDirective::If(_)
+                | MpDirective::IfDef(_)
+                | MpDirective::Else
+                | MpDirective::EndIf),
+                position,
+            )) => (directive, position),
+            MpItem::Constant(constant) if active => {
+                if options.defines().any(|(name, _)| name == constant.label()) {
+                    continue;
+                }
+
+                // an invalid constant will be reported when it's compiled
+                if let Ok(value) = eval_constant(&scope, constant.value(), file_tag.clone(), None) {
+                    scope.constants.insert(constant.label().to_string(), value);
+                }
+
+                kept.push(attributed_item);
+                continue;
+            }
+            _ => {
+                if active {
+                    kept.push(attributed_item);
+                }
+
+                continue;
+            }
+        };
+
+        let line = attributed_item.line_number();
+        let (col, col_end) = (position.col(), position.col_end());
+
+        match directive {
+            MpDirective::If(condition) => {
+                let taken = active
+                    && eval_constant(&scope, condition, file_tag.clone(), expansion.clone())? != 0;
+
+                conditionals.push(Conditional {
+                    directive: "if",
+                    file_tag: file_tag.clone(),
+                    line,
+                    col,
+                    col_end,
+                    active: taken,
+                    enclosing_active: active,
+                    taken,
+                    seen_else: false,
+                });
+            }
+            MpDirective::IfDef(name) => {
+                let taken = active && scope.constants.contains_key(name);
+
+                conditionals.push(Conditional {
+                    directive: "ifdef",
+                    file_tag: file_tag.clone(),
+                    line,
+                    col,
+                    col_end,
+                    active: taken,
+                    enclosing_active: active,
+                    taken,
+                    seen_else: false,
+                });
+            }
+            MpDirective::Else => match conditionals.last_mut() {
+                Some(cond) if !cond.seen_else => {
+                    cond.active = cond.enclosing_active && !cond.taken;
+                    cond.seen_else = true;
+                }
+                _ => {
+                    return Err(error(
+                        Error::UnmatchedConditional {
+                            directive: "else".to_string(),
+                        },
+                        line,
+                        col,
+                        col_end,
+                    ));
+                }
+            },
+            MpDirective::EndIf => {
+                if conditionals.pop().is_none() {
+                    return Err(error(
+                        Error::UnmatchedConditional {
+                            directive: "endif".to_string(),
+                        },
+                        line,
+                        col,
+                        col_end,
+                    ));
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if let Some(cond) = conditionals.pop() {
+        return Err(MipsyError::Compiler(CompilerError::new(
+            Error::UnterminatedConditional {
+                directive: cond.directive.to_string(),
+            },
+            cond.file_tag,
+            cond.line,
+            cond.col,
+            cond.col_end,
+        )));
+    }
+
+    *program.items_mut() = kept;
+
+    Ok(())
+}
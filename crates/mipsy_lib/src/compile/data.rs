@@ -1,6 +1,6 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
-use super::{bytes::ToBytes, text::instruction_length, Binary, DATA_BOT, TEXT_BOT};
+use super::{bytes::ToBytes, text::instruction_length, Binary};
 use crate::{
     error::{
         compiler::{DirectiveType, Error},
@@ -11,7 +11,9 @@ use crate::{
     CompilerError, MipsyError, MipsyResult, MpProgram, KDATA_BOT, KTEXT_BOT,
 };
 use mipsy_parser::{
-    MpArgument, MpConstValue, MpConstValueLoc, MpDirective, MpImmediate, MpItem, MpNumber,
+    is_local_label, is_numeric_label, is_scoped_label, MpArgument, MpConstValue, MpConstValueLoc,
+    MpDirective, MpImmediate, MpItem, MpMacroExpansion, MpNumber, MpRegister,
+    LOCAL_LABEL_SEPARATOR,
 };
 use mipsy_utils::MipsyConfig;
 
@@ -25,9 +27,9 @@ pub(crate) enum Segment {
 
 fn align<T: Clone>(binary: &mut Binary, segment: &Segment, align_to: usize) -> Vec<Safe<T>> {
     let (bot, curr_size) = match segment {
-        Segment::Data => (DATA_BOT, binary.data.len()),
+        Segment::Data => (binary.layout.data_bot, binary.data.len()),
         Segment::KData => (KDATA_BOT, binary.kdata.len()),
-        Segment::Text => (TEXT_BOT, binary.text.len()),
+        Segment::Text => (binary.layout.text_bot, binary.text.len()),
         Segment::KText => (KTEXT_BOT, binary.ktext.len()),
     };
 
@@ -56,6 +58,7 @@ pub(super) fn eval_directive(
     binary: &mut Binary,
     config: &MipsyConfig,
     file_tag: Rc<str>,
+    expansion: Option<Rc<MpMacroExpansion>>,
     segment: &mut Segment,
     first_pass: bool,
 ) -> MipsyResult<Vec<Safe<u8>>> {
@@ -105,6 +108,7 @@ pub(super) fn eval_directive(
                         u8::MAX as _,
                         binary,
                         file_tag.clone(),
+                        expansion.clone(),
                     )? as u8,
                     if let Some(n) = n {
                         eval_constant_in_range(
@@ -113,6 +117,7 @@ pub(super) fn eval_directive(
                             u32::MAX as _,
                             binary,
                             file_tag.clone(),
+                            expansion.clone(),
                         )? as u32
                     } else {
                         1
@@ -136,6 +141,7 @@ pub(super) fn eval_directive(
                             u16::MAX as _,
                             binary,
                             file_tag.clone(),
+                            expansion.clone(),
                         )? as u16,
                         if let Some(n) = n {
                             eval_constant_in_range(
@@ -144,6 +150,7 @@ pub(super) fn eval_directive(
                                 u32::MAX as _,
                                 binary,
                                 file_tag.clone(),
+                                expansion.clone(),
                             )? as u32
                         } else {
                             1
@@ -171,6 +178,7 @@ pub(super) fn eval_directive(
                             u32::MAX as _,
                             binary,
                             file_tag.clone(),
+                            expansion.clone(),
                         )? as u32,
                         if let Some(n) = n {
                             eval_constant_in_range(
@@ -179,6 +187,7 @@ pub(super) fn eval_directive(
                                 u32::MAX as _,
                                 binary,
                                 file_tag.clone(),
+                                expansion.clone(),
                             )? as u32
                         } else {
                             1
@@ -208,6 +217,7 @@ pub(super) fn eval_directive(
                                 u32::MAX as _,
                                 binary,
                                 file_tag.clone(),
+                                expansion.clone(),
                             )? as u32
                         } else {
                             1
@@ -237,6 +247,7 @@ pub(super) fn eval_directive(
                                 u32::MAX as _,
                                 binary,
                                 file_tag.clone(),
+                                expansion.clone(),
                             )? as u32
                         } else {
                             1
@@ -252,15 +263,22 @@ pub(super) fn eval_directive(
             alignment.into_iter().chain(doubles).collect()
         }
         MpDirective::Align(num) => {
-            let num = eval_constant_in_range(num, u32::MIN as _, 31, binary, file_tag)? as u32;
+            let num =
+                eval_constant_in_range(num, u32::MIN as _, 31, binary, file_tag, expansion)? as u32;
 
             let multiple = 2usize.pow(num);
 
             align(binary, segment, multiple)
         }
         MpDirective::Space(num) => {
-            let num =
-                eval_constant_in_range(num, u32::MIN as _, u32::MAX as _, binary, file_tag)? as u32;
+            let num = eval_constant_in_range(
+                num,
+                u32::MIN as _,
+                u32::MAX as _,
+                binary,
+                file_tag,
+                expansion,
+            )? as u32;
 
             let space_byte = if config.spim {
                 Safe::Valid(0)
@@ -277,6 +295,10 @@ pub(super) fn eval_directive(
 
             vec![]
         }
+        // these have already been resolved by `resolve_conditionals`
+        MpDirective::If(_) | MpDirective::IfDef(_) | MpDirective::Else | MpDirective::EndIf => {
+            vec![]
+        }
     };
 
     Ok(bytes)
@@ -291,10 +313,15 @@ pub fn populate_labels_and_data(
     let mut text_len = 0;
     let mut ktext_len = 0;
     let mut segment = Segment::Text;
+    // data labels that the current run of data directives belongs to
+    let mut open_labels: Vec<String> = vec![];
+
+    resolve_local_labels(program)?;
 
     for attributed_item in program.items_mut() {
         let line = attributed_item.line_number();
         let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
         let item = attributed_item.item_mut();
 
         match item {
@@ -319,10 +346,44 @@ pub fn populate_labels_and_data(
                 //     }
                 // }
 
-                let bytes =
-                    eval_directive(&directive.0, binary, config, file_tag, &mut segment, true)?;
+                let before = match segment {
+                    Segment::Data => binary.layout.data_bot + binary.data.len() as u32,
+                    Segment::KData => KDATA_BOT + binary.kdata.len() as u32,
+                    Segment::Text | Segment::KText => 0,
+                };
+
+                let bytes = eval_directive(
+                    &directive.0,
+                    binary,
+                    config,
+                    file_tag.clone(),
+                    expansion.clone(),
+                    &mut segment,
+                    true,
+                )?;
                 insert_safe_data(&segment, binary, &bytes);
 
+                // the line's data starts after any padding to align it,
+                // and `.align` is nothing but padding
+                let start = before.next_multiple_of(alignment(&directive.0));
+                let is_data = matches!(segment, Segment::Data | Segment::KData);
+
+                if is_data
+                    && !file_tag.is_empty()
+                    && !matches!(directive.0, MpDirective::Align(_))
+                    && start < before + bytes.len() as u32
+                {
+                    binary.data_line_numbers.insert(start, (file_tag, line));
+                }
+
+                match directive.0 {
+                    MpDirective::Text
+                    | MpDirective::Data
+                    | MpDirective::KText
+                    | MpDirective::KData => open_labels.clear(),
+                    _ => update_label_sizes(binary, &segment, &open_labels),
+                }
+
                 match segment {
                     Segment::Text => {
                         text_len += bytes.len();
@@ -365,25 +426,30 @@ pub fn populate_labels_and_data(
 
                 // We can't compile instructions yet - so just keep track of
                 // how many bytes-worth we've seen so far
-                let inst_length =
-                    instruction_length(iset, instruction).into_compiler_mipsy_result(
+                let inst_length = instruction_length(iset, instruction)
+                    .into_compiler_mipsy_result(
                         file_tag.clone(),
                         line,
                         instruction.col(),
                         instruction.col_end(),
-                    )? * 4;
+                    )
+                    .map_err(|err| err.in_expansion(expansion.clone()))?
+                    * 4;
 
                 let (bot, length) = match segment {
-                    Segment::Text => (TEXT_BOT, &mut text_len),
+                    Segment::Text => (binary.layout.text_bot, &mut text_len),
                     Segment::KText => (KTEXT_BOT, &mut ktext_len),
                     _ => {
-                        return Err(MipsyError::Compiler(CompilerError::new(
-                            Error::InstructionInDataSegment,
-                            file_tag,
-                            instruction.line(),
-                            instruction.col(),
-                            instruction.col_end(),
-                        )));
+                        return Err(MipsyError::Compiler(
+                            CompilerError::new(
+                                Error::InstructionInDataSegment,
+                                file_tag,
+                                instruction.line(),
+                                instruction.col(),
+                                instruction.col_end(),
+                            )
+                            .with_expansion(expansion),
+                        ));
                     }
                 };
 
@@ -413,101 +479,332 @@ pub fn populate_labels_and_data(
                 let col_end = mplabel.col_end();
 
                 if binary.labels.contains_key(&*label) {
-                    return Err(MipsyError::Compiler(CompilerError::new(
-                        Error::RedefinedLabel { label },
-                        file_tag,
-                        line,
-                        col,
-                        col_end,
-                    )));
+                    return Err(MipsyError::Compiler(
+                        CompilerError::new(
+                            Error::RedefinedLabel { label },
+                            file_tag,
+                            line,
+                            col,
+                            col_end,
+                        )
+                        .with_expansion(expansion),
+                    ));
                 }
 
-                binary.labels.insert(
-                    label.to_string(),
-                    match segment {
-                        Segment::Text => TEXT_BOT + text_len as u32,
-                        Segment::Data => DATA_BOT + binary.data.len() as u32,
-                        Segment::KText => KTEXT_BOT + ktext_len as u32,
-                        Segment::KData => KDATA_BOT + binary.kdata.len() as u32,
-                    },
-                );
+                let addr = match segment {
+                    Segment::Text => binary.layout.text_bot + text_len as u32,
+                    Segment::Data => binary.layout.data_bot + binary.data.len() as u32,
+                    Segment::KText => KTEXT_BOT + ktext_len as u32,
+                    Segment::KData => KDATA_BOT + binary.kdata.len() as u32,
+                };
+
+                binary.labels.insert(label.to_string(), addr);
+
+                match segment {
+                    Segment::Data | Segment::KData => {
+                        // labels defined back-to-back refer to the same object
+                        if open_labels
+                            .iter()
+                            .any(|open| binary.labels.get(open) != Some(&addr))
+                        {
+                            open_labels.clear();
+                        }
+
+                        open_labels.push(label.to_string());
+                        binary.label_sizes.insert(label.to_string(), 0);
+                    }
+                    Segment::Text | Segment::KText => open_labels.clear(),
+                }
             }
             MpItem::Constant(constant) => {
                 let label = constant.label();
 
                 if binary.constants.contains_key(label) {
-                    return Err(MipsyError::Compiler(CompilerError::new(
-                        Error::RedefinedConstant {
-                            label: label.to_string(),
-                        },
-                        file_tag,
-                        line,
-                        constant.col(),
-                        constant.col_end(),
-                    )));
+                    return Err(MipsyError::Compiler(
+                        CompilerError::new(
+                            Error::RedefinedConstant {
+                                label: label.to_string(),
+                            },
+                            file_tag,
+                            line,
+                            constant.col(),
+                            constant.col_end(),
+                        )
+                        .with_expansion(expansion),
+                    ));
                 }
 
-                let value = eval_constant(binary, constant.value(), file_tag)?;
+                let value = eval_constant(binary, constant.value(), file_tag, expansion)?;
                 binary.constants.insert(label.to_string(), value);
             }
+            MpItem::Error(_) => {}
         }
     }
 
     Ok(())
 }
 
-fn eval_constant(binary: &Binary, constant: &MpConstValueLoc, file: Rc<str>) -> MipsyResult<i64> {
-    Ok(match &constant.0 {
-        &MpConstValue::Value(value) => value as _,
-        MpConstValue::Const(label) => binary
-            .constants
-            .get(label)
-            .copied()
-            .or_else(|| binary.get_label(label).map(|x| x as i64).ok())
-            .ok_or_else(|| {
-                MipsyError::Compiler(CompilerError::new(
-                    Error::UnresolvedConstant {
-                        label: label.to_string(),
-                    },
-                    file.clone(),
-                    constant.1.line(),
-                    constant.1.col(),
-                    constant.1.col_end(),
-                ))
-            })?,
-        MpConstValue::Minus(value) => -eval_constant(binary, value, file)?,
-        MpConstValue::Sum(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? + eval_constant(binary, v2, file)?
-        }
-        MpConstValue::Sub(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? - eval_constant(binary, v2, file)?
-        }
-        MpConstValue::Div(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? / eval_constant(binary, v2, file)?
-        }
-        MpConstValue::Mult(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? * eval_constant(binary, v2, file)?
-        }
-        MpConstValue::Mod(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? % eval_constant(binary, v2, file)?
+/// Where the local labels in a program are defined.
+#[derive(Default)]
+struct LocalLabels {
+    /// the indices of the items defining each numeric label
+    numeric: HashMap<String, Vec<usize>>,
+    /// the scoped labels belonging to each ordinary label
+    scoped: HashMap<String, Vec<String>>,
+}
+
+impl LocalLabels {
+    /// Finds the unique name for a reference to a local label,
+    /// made from the item at `index` inside the ordinary label `scope`.
+    ///
+    /// Gives back `None` if the reference isn't to a local label.
+    fn resolve(&self, label: &str, index: usize, scope: &str) -> Result<Option<String>, Error> {
+        if is_scoped_label(label) {
+            let labels = self.scoped.get(scope).map(Vec::as_slice).unwrap_or(&[]);
+
+            if labels.iter().any(|scoped| scoped == label) {
+                return Ok(Some(scoped_label_name(scope, label)));
+            }
+
+            let similar = labels
+                .iter()
+                .filter(|scoped| strsim::jaro_winkler(scoped, label) >= 0.9)
+                .cloned()
+                .collect();
+
+            return Err(Error::UnresolvedLabel {
+                label: label.to_string(),
+                similar,
+            });
         }
-        MpConstValue::And(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? & eval_constant(binary, v2, file)?
+
+        let (number, forward) = match label.split_at(label.len().saturating_sub(1)) {
+            (number, "f") if is_numeric_label(number) => (number, true),
+            (number, "b") if is_numeric_label(number) => (number, false),
+            _ => return Ok(None),
+        };
+
+        let definitions = self.numeric.get(number).map(Vec::as_slice).unwrap_or(&[]);
+
+        let nth = if forward {
+            definitions.iter().position(|&def| def > index)
+        } else {
+            definitions.iter().rposition(|&def| def < index)
+        };
+
+        nth.map(|nth| Some(numeric_label_name(number, nth)))
+            .ok_or_else(|| Error::UnresolvedNumericLabel {
+                label: label.to_string(),
+            })
+    }
+}
+
+fn numeric_label_name(number: &str, nth: usize) -> String {
+    format!("{}{}{}", number, LOCAL_LABEL_SEPARATOR, nth)
+}
+
+fn scoped_label_name(scope: &str, label: &str) -> String {
+    format!("{}{}{}", scope, LOCAL_LABEL_SEPARATOR, label)
+}
+
+/// Gives every local label in a program a unique name,
+/// and points each reference to one at that name.
+///
+/// Numeric labels (`1:`) can be defined any number of times:
+/// `1f` refers to the next `1:`, and `1b` to the previous one.
+/// Scoped labels (`.Lloop:`) belong to the ordinary label before them,
+/// and can only be referred to until the next ordinary label.
+fn resolve_local_labels(program: &mut MpProgram) -> MipsyResult<()> {
+    let mut locals = LocalLabels::default();
+    let mut scopes = Vec::with_capacity(program.items().len());
+    let mut scope = String::new();
+
+    for (index, attributed_item) in program.items_mut().iter_mut().enumerate() {
+        let line = attributed_item.line_number();
+        let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
+
+        if let MpItem::Label(mplabel) = attributed_item.item_mut() {
+            let label = mplabel.label();
+
+            if is_numeric_label(&label) {
+                let definitions = locals.numeric.entry(label.clone()).or_default();
+                mplabel.set_label(numeric_label_name(&label, definitions.len()));
+                definitions.push(index);
+            } else if is_scoped_label(&label) {
+                let labels = locals.scoped.entry(scope.clone()).or_default();
+
+                if labels.contains(&label) {
+                    return Err(MipsyError::Compiler(
+                        CompilerError::new(
+                            Error::RedefinedLabel { label },
+                            file_tag,
+                            line,
+                            mplabel.col(),
+                            mplabel.col_end(),
+                        )
+                        .with_expansion(expansion),
+                    ));
+                }
+
+                mplabel.set_label(scoped_label_name(&scope, &label));
+                labels.push(label);
+            } else if !is_local_label(&label) {
+                // the labels in a macro don't start a new scope
+                scope = label;
+            }
         }
-        MpConstValue::Or(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? | eval_constant(binary, v2, file)?
+
+        scopes.push(scope.clone());
+    }
+
+    for (index, attributed_item) in program.items_mut().iter_mut().enumerate() {
+        let line = attributed_item.line_number();
+        let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
+        let scope = &scopes[index];
+
+        let error = |error: Error, line: u32, col: u32, col_end: u32| {
+            MipsyError::Compiler(
+                CompilerError::new(error, file_tag.clone(), line, col, col_end)
+                    .with_expansion(expansion.clone()),
+            )
+        };
+
+        match attributed_item.item_mut() {
+            MpItem::Instruction(instruction) => {
+                for (argument, col, col_end) in instruction.arguments_mut() {
+                    if let MpArgument::Register(MpRegister::ExpressionOffset(_, value, _))
+                    | MpArgument::Number(
+                        MpNumber::Relocation(_, value) | MpNumber::Expression(value),
+                    ) = argument
+                    {
+                        resolve_local_constant(value, &locals, index, scope).map_err(
+                            |(err, position)| {
+                                error(err, position.line(), position.col(), position.col_end())
+                            },
+                        )?;
+                        continue;
+                    }
+
+                    let immediates = match argument {
+                        MpArgument::Number(MpNumber::Immediate(imm))
+                        | MpArgument::Register(MpRegister::Offset(imm, _)) => vec![imm],
+                        MpArgument::Number(MpNumber::BinaryOpImmediate(imm1, _, imm2))
+                        | MpArgument::Register(MpRegister::BinaryOpOffset(imm1, _, imm2, _)) => {
+                            vec![imm1, imm2]
+                        }
+                        _ => vec![],
+                    };
+
+                    for imm in immediates {
+                        if let MpImmediate::LabelReference(label) = imm {
+                            if let Some(name) = locals
+                                .resolve(label, index, scope)
+                                .map_err(|err| error(err, line, *col, *col_end))?
+                            {
+                                *label = name;
+                            }
+                        }
+                    }
+                }
+            }
+            MpItem::Directive((directive, _)) => {
+                let values: Vec<&mut MpConstValueLoc> = match directive {
+                    MpDirective::Byte(values)
+                    | MpDirective::Half(values)
+                    | MpDirective::Word(values) => values
+                        .iter_mut()
+                        .flat_map(|(value, repeat)| std::iter::once(value).chain(repeat))
+                        .collect(),
+                    MpDirective::Float(values) => {
+                        values.iter_mut().flat_map(|(_, repeat)| repeat).collect()
+                    }
+                    MpDirective::Double(values) => {
+                        values.iter_mut().flat_map(|(_, repeat)| repeat).collect()
+                    }
+                    MpDirective::Align(value) | MpDirective::Space(value) => vec![value],
+                    _ => vec![],
+                };
+
+                for value in values {
+                    resolve_local_constant(value, &locals, index, scope).map_err(
+                        |(err, position)| {
+                            error(err, position.line(), position.col(), position.col_end())
+                        },
+                    )?;
+                }
+            }
+            MpItem::Label(_) | MpItem::Constant(_) | MpItem::Error(_) => {}
         }
-        MpConstValue::Xor(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? ^ eval_constant(binary, v2, file)?
+    }
+
+    Ok(())
+}
+
+fn resolve_local_constant(
+    constant: &mut MpConstValueLoc,
+    locals: &LocalLabels,
+    index: usize,
+    scope: &str,
+) -> Result<(), (Error, mipsy_parser::parser::Position)> {
+    match &mut constant.0 {
+        MpConstValue::Value(_) => {}
+        MpConstValue::Const(label) => {
+            if let Some(name) = locals
+                .resolve(label, index, scope)
+                .map_err(|err| (err, constant.1.clone()))?
+            {
+                *label = name;
+            }
         }
-        MpConstValue::Neg(value) => !eval_constant(binary, value, file)?,
-        MpConstValue::Shl(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? << eval_constant(binary, v2, file)?
+        MpConstValue::Minus(value) | MpConstValue::Neg(value) => {
+            resolve_local_constant(value, locals, index, scope)?
         }
-        MpConstValue::Shr(v1, v2) => {
-            eval_constant(binary, v1, file.clone())? >> eval_constant(binary, v2, file)?
+        MpConstValue::Mult(v1, v2)
+        | MpConstValue::Sum(v1, v2)
+        | MpConstValue::Sub(v1, v2)
+        | MpConstValue::Div(v1, v2)
+        | MpConstValue::Mod(v1, v2)
+        | MpConstValue::And(v1, v2)
+        | MpConstValue::Or(v1, v2)
+        | MpConstValue::Xor(v1, v2)
+        | MpConstValue::Shl(v1, v2)
+        | MpConstValue::Shr(v1, v2) => {
+            resolve_local_constant(v1, locals, index, scope)?;
+            resolve_local_constant(v2, locals, index, scope)?;
         }
-    })
+    }
+
+    Ok(())
+}
+
+pub(crate) fn eval_constant(
+    binary: &Binary,
+    constant: &MpConstValueLoc,
+    file: Rc<str>,
+    expansion: Option<Rc<MpMacroExpansion>>,
+) -> MipsyResult<i64> {
+    constant
+        .eval(&|label| {
+            binary
+                .constants
+                .get(label)
+                .copied()
+                .or_else(|| binary.get_label(label).map(|x| x as i64).ok())
+        })
+        .map_err(|(label, position)| {
+            MipsyError::Compiler(
+                CompilerError::new(
+                    Error::UnresolvedConstant { label },
+                    file,
+                    position.line(),
+                    position.col(),
+                    position.col_end(),
+                )
+                .with_expansion(expansion),
+            )
+        })
 }
 
 fn eval_constant_in_range(
@@ -516,27 +813,56 @@ fn eval_constant_in_range(
     range_high: i64,
     binary: &Binary,
     file: Rc<str>,
+    expansion: Option<Rc<MpMacroExpansion>>,
 ) -> MipsyResult<i64> {
-    let value = eval_constant(binary, constant, file.clone())?;
+    let value = eval_constant(binary, constant, file.clone(), expansion.clone())?;
 
     if value < range_low || value > range_high {
-        return Err(MipsyError::Compiler(CompilerError::new(
-            Error::ConstantValueDoesNotFit {
-                directive_type: DirectiveType::Byte,
-                value,
-                range_low,
-                range_high,
-            },
-            file,
-            constant.1.line(),
-            constant.1.col(),
-            constant.1.col_end(),
-        )));
+        return Err(MipsyError::Compiler(
+            CompilerError::new(
+                Error::ConstantValueDoesNotFit {
+                    directive_type: DirectiveType::Byte,
+                    value,
+                    range_low,
+                    range_high,
+                },
+                file,
+                constant.1.line(),
+                constant.1.col(),
+                constant.1.col_end(),
+            )
+            .with_expansion(expansion),
+        ));
     }
 
     Ok(value)
 }
 
+fn update_label_sizes(binary: &mut Binary, segment: &Segment, open_labels: &[String]) {
+    let end = match segment {
+        Segment::Data => binary.layout.data_bot + binary.data.len() as u32,
+        Segment::KData => KDATA_BOT + binary.kdata.len() as u32,
+        Segment::Text | Segment::KText => return,
+    };
+
+    for label in open_labels {
+        // alignment may have moved the label since it was defined
+        if let Some(&addr) = binary.labels.get(label) {
+            binary.label_sizes.insert(label.to_string(), end - addr);
+        }
+    }
+}
+
+/// What the data from a directive is aligned to.
+fn alignment(directive: &MpDirective) -> u32 {
+    match directive {
+        MpDirective::Half(_) => 2,
+        MpDirective::Word(_) | MpDirective::Float(_) => 4,
+        MpDirective::Double(_) => 8,
+        _ => 1,
+    }
+}
+
 fn insert_safe_data(segment: &Segment, binary: &mut Binary, values: &[Safe<u8>]) {
     match segment {
         Segment::Data => &mut binary.data,
@@ -0,0 +1,187 @@
+use mipsy_utils::MipsyConfig;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    DATA_BOT, GLOBAL_BOT, GLOBAL_PTR, HEAP_BOT, KDATA_BOT, KTEXT_BOT, STACK_BOT, STACK_PTR,
+    STACK_TOP, TEXT_BOT, TEXT_TOP,
+};
+use crate::util::Segment;
+
+/// # Where each user segment lives in memory.
+///
+/// The kernel segments (`.ktext` and `.kdata`) are always at
+/// [`KTEXT_BOT`] and [`KDATA_BOT`], as that's where the
+/// mipsy kernel is assembled to.
+///
+/// The data segment runs from `global_bot` up to the heap,
+/// the heap grows up from `heap_bot` to the bottom of the stack,
+/// and the stack grows down from `stack_ptr` to `stack_bot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MemoryLayout {
+    pub text_bot: u32,
+    pub text_top: u32,
+    pub global_bot: u32,
+    pub global_ptr: u32,
+    pub data_bot: u32,
+    pub heap_bot: u32,
+    pub stack_bot: u32,
+    pub stack_ptr: u32,
+    pub stack_top: u32,
+}
+
+impl MemoryLayout {
+    /// The names accepted by [`MemoryLayout::preset`].
+    pub const PRESETS: &'static [&'static str] = &[
+        "spim",
+        "mars-compact-data-at-zero",
+        "mars-compact-text-at-zero",
+        "tiny",
+    ];
+
+    /// The same layout as SPIM (and MARS's default layout).
+    pub const fn spim() -> Self {
+        Self {
+            text_bot: TEXT_BOT,
+            text_top: TEXT_TOP,
+            global_bot: GLOBAL_BOT,
+            global_ptr: GLOBAL_PTR,
+            data_bot: DATA_BOT,
+            heap_bot: HEAP_BOT,
+            stack_bot: STACK_BOT,
+            stack_ptr: STACK_PTR,
+            stack_top: STACK_TOP,
+        }
+    }
+
+    /// MARS's "compact, data at address 0" layout.
+    ///
+    /// MARS lets the stack grow down over `.text`,
+    /// so here the stack sits just below `.text` instead.
+    pub const fn mars_compact_data_at_zero() -> Self {
+        Self {
+            text_bot: 0x00003000,
+            text_top: 0x00003FFF,
+            global_bot: 0x00000000,
+            global_ptr: 0x00001800,
+            data_bot: 0x00000000,
+            heap_bot: 0x00002000,
+            stack_bot: 0x00002800,
+            stack_ptr: 0x00002FFC,
+            stack_top: 0x00002FFF,
+        }
+    }
+
+    /// MARS's "compact, text at address 0" layout.
+    ///
+    /// MARS lets the heap and stack share the same 4KiB,
+    /// so here they get half each.
+    pub const fn mars_compact_text_at_zero() -> Self {
+        Self {
+            text_bot: 0x00000000,
+            text_top: 0x00000FFF,
+            global_bot: 0x00001000,
+            global_ptr: 0x00001800,
+            data_bot: 0x00002000,
+            heap_bot: 0x00003000,
+            stack_bot: 0x00003800,
+            stack_ptr: 0x00003FFC,
+            stack_top: 0x00003FFF,
+        }
+    }
+
+    /// A small layout with short, readable addresses.
+    ///
+    /// The first 1KiB is left unmapped, so that
+    /// dereferencing a null pointer still fails.
+    pub const fn tiny() -> Self {
+        Self {
+            text_bot: 0x00000400,
+            text_top: 0x00000FFF,
+            global_bot: 0x00001000,
+            global_ptr: 0x00001000,
+            data_bot: 0x00001000,
+            heap_bot: 0x00002000,
+            stack_bot: 0x00003000,
+            stack_ptr: 0x00003FFC,
+            stack_top: 0x00003FFF,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "spim" => Some(Self::spim()),
+            "mars-compact-data-at-zero" => Some(Self::mars_compact_data_at_zero()),
+            "mars-compact-text-at-zero" => Some(Self::mars_compact_text_at_zero()),
+            "tiny" => Some(Self::tiny()),
+            _ => None,
+        }
+    }
+
+    /// The layout chosen by the user's config, falling back to SPIM's
+    /// if they've asked for a preset that doesn't exist.
+    pub fn from_config(config: &MipsyConfig) -> Self {
+        let layout = Self::preset(&config.memory_layout).unwrap_or_default();
+
+        match config.stack_size {
+            Some(size) => layout.with_stack_size(size),
+            None => layout,
+        }
+    }
+
+    /// Limits the stack to `size` bytes,
+    /// giving the rest of the space back to the heap.
+    pub fn with_stack_size(mut self, size: u32) -> Self {
+        self.stack_bot = self
+            .stack_top
+            .saturating_sub(size.saturating_sub(1))
+            .clamp(self.heap_bot, self.stack_top);
+
+        self
+    }
+
+    /// The number of bytes the stack may grow to.
+    pub fn stack_size(&self) -> u32 {
+        self.stack_top.wrapping_add(1).wrapping_sub(self.stack_bot)
+    }
+
+    /// The most bytes of `.data` that fit before the heap.
+    pub fn max_data_size(&self) -> u32 {
+        self.heap_bot - self.data_bot
+    }
+
+    /// The most bytes of `.text` that fit in the text segment.
+    pub fn max_text_size(&self) -> u32 {
+        self.text_top.wrapping_add(1).wrapping_sub(self.text_bot)
+    }
+
+    pub fn segment_of(&self, address: u32) -> Segment {
+        match address {
+            _ if (self.text_bot..=self.text_top).contains(&address) => Segment::Text,
+            _ if (self.global_bot..self.stack_bot).contains(&address) => Segment::Data,
+            _ if (self.stack_bot..=self.stack_top).contains(&address) => Segment::Stack,
+            _ if (KTEXT_BOT..KDATA_BOT).contains(&address) => Segment::KText,
+            _ if address >= KDATA_BOT => Segment::KData,
+            _ => Segment::None,
+        }
+    }
+
+    /// Where to start looking at a segment from.
+    ///
+    /// As the stack grows down, this is the top of the stack.
+    pub fn segment_start(&self, segment: Segment) -> u32 {
+        match segment {
+            Segment::None => 0,
+            Segment::Text => self.text_bot,
+            Segment::Data => self.data_bot,
+            Segment::Stack => self.stack_top,
+            Segment::KText => KTEXT_BOT,
+            Segment::KData => KDATA_BOT,
+        }
+    }
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        Self::spim()
+    }
+}
@@ -2,27 +2,36 @@ use crate::compile::breakpoints::Breakpoint;
 use crate::{
     error::{compiler, InternalError, MipsyInternalResult},
     util::Safe,
-    InstSet, MipsyResult, MpProgram,
+    CompilerError, CompilerWarning, InstSet, MipsyError, MipsyResult, MpProgram,
 };
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, rc::Rc};
 mod bytes;
 
+pub mod attributes;
+use attributes::LintLevels;
 pub mod breakpoints;
 mod checker;
 pub use checker::{check_post_data_label, check_pre};
 
+mod conditional;
+use conditional::resolve_conditionals;
+
 mod data;
+pub(crate) use data::eval_constant;
 use data::populate_labels_and_data;
 
 mod text;
 use linked_hash_map::LinkedHashMap;
-use mipsy_parser::TaggedFile;
+use mipsy_parser::{is_local_label, TaggedFile};
 use mipsy_utils::MipsyConfig;
 use text::populate_text;
 
 mod extra;
 
+mod layout;
+pub use layout::MemoryLayout;
+
 pub use text::compile1;
 
 use self::{
@@ -51,11 +60,27 @@ pub struct Binary {
     pub ktext: Vec<Safe<u8>>,
     pub kdata: Vec<Safe<u8>>,
     pub labels: LinkedHashMap<String, u32>,
+    pub label_sizes: HashMap<String, u32>,
     pub constants: HashMap<String, i64>,
     pub globals: Vec<String>,
     pub line_numbers: HashMap<u32, (Rc<str>, u32)>,
+    /// Where each line of data (in `.data` or `.kdata`) starts,
+    /// as `line_numbers` only has the lines of instructions
+    pub data_line_numbers: HashMap<u32, (Rc<str>, u32)>,
     pub breakpoints: HashMap<u32, Breakpoint>,
     pub watchpoints: HashMap<WatchpointTarget, Watchpoint>,
+    pub layout: MemoryLayout,
+    /// Where to start executing, if not at the kernel's `__start`
+    /// (i.e. the entry point of a loaded ELF file)
+    pub entry: Option<u32>,
+    /// Whether the program was built for real hardware (i.e. loaded from an ELF file),
+    /// so each branch is relative to the instruction after it, which is its delay slot:
+    /// it runs before the branch or jump goes anywhere, and calls return past it.
+    pub delay_slots: bool,
+    /// (tag, contents) of every file pulled in with `.include`
+    pub included_files: Vec<(Rc<str>, Rc<str>)>,
+    /// Anything suspicious found while compiling the program
+    pub warnings: Vec<CompilerWarning>,
 }
 
 impl Binary {
@@ -65,9 +90,11 @@ impl Binary {
         } else {
             let label_lower = label.to_ascii_lowercase();
 
+            // local labels are only suggested where they're visible
             let mut similar = self
                 .labels
                 .keys()
+                .filter(|label| !is_local_label(label))
                 .map(|label| label.to_ascii_lowercase())
                 .map(|label| (strsim::jaro_winkler(&label, &label_lower), label))
                 .filter(|&(sim, _)| sim >= 0.9)
@@ -91,6 +118,18 @@ impl Binary {
         self.labels.insert(label.to_string(), addr);
     }
 
+    /// Where a branch at `addr` with an offset of `imm` instructions goes.
+    pub fn branch_target(&self, addr: u32, imm: i16) -> u32 {
+        // without delay slots, offsets are from the branch itself
+        let imm = if self.delay_slots {
+            imm as i32 + 1
+        } else {
+            imm as i32
+        };
+
+        addr.wrapping_add((imm * 4) as u32)
+    }
+
     pub fn text_words(&'_ self) -> impl Iterator<Item = Safe<u32>> + '_ {
         self.text
             .chunks_exact(4)
@@ -139,11 +178,12 @@ impl Binary {
 #[derive(Debug, Default)]
 pub struct CompilerOptions {
     moves: Vec<(String, String)>,
+    defines: Vec<(String, i64)>,
 }
 
 impl CompilerOptions {
-    pub fn new(moves: Vec<(String, String)>) -> Self {
-        Self { moves }
+    pub fn new(moves: Vec<(String, String)>, defines: Vec<(String, i64)>) -> Self {
+        Self { moves, defines }
     }
 
     pub fn moves(&self) -> impl Iterator<Item = (&str, &str)> {
@@ -151,6 +191,11 @@ impl CompilerOptions {
             .iter()
             .map(|(old, new)| (old.as_str(), new.as_str()))
     }
+
+    /// Constants defined from outside the program (i.e. `-D NAME=VALUE`).
+    pub fn defines(&self) -> impl Iterator<Item = &(String, i64)> {
+        self.defines.iter()
+    }
 }
 
 pub fn compile(
@@ -169,28 +214,46 @@ pub fn compile_with_kernel(
     config: &MipsyConfig,
     iset: &InstSet,
 ) -> MipsyResult<Binary> {
-    let warnings = check_pre(program)?;
-    if !warnings.is_empty() {
-        // TODO: Deal with warnings here
-    }
+    resolve_conditionals(program, options)?;
+    let lint_levels = LintLevels::new(program)?;
 
-    let mut binary = Binary::default();
+    let mut warnings = check_pre(program)?;
+
+    let mut binary = Binary {
+        layout: MemoryLayout::from_config(config),
+        included_files: program.included_files().to_vec(),
+        constants: options.defines().cloned().collect(),
+        ..Default::default()
+    };
 
     populate_labels_and_data(&mut binary, config, iset, kernel)?;
 
     populate_labels_and_data(&mut binary, config, iset, program)?;
 
-    let warnings = check_post_data_label(program, &binary)?;
-    if !warnings.is_empty() {
-        // TODO: Deal with warnings here
-    }
+    warnings.extend(check_post_data_label(program, &binary, iset)?);
 
     move_labels(&mut binary, options.moves());
 
     populate_text(&mut binary, iset, config, program)?;
 
+    if binary.text.len() > binary.layout.max_text_size() as usize {
+        return Err(MipsyError::Compiler(CompilerError::new(
+            compiler::Error::TooMuchText {
+                text_size: binary.text.len() as u32,
+                max_size: binary.layout.max_text_size(),
+            },
+            Rc::from(""),
+            0,
+            0,
+            0,
+        )));
+    }
+
     populate_text(&mut binary, iset, config, kernel)?;
 
+    warnings.sort_by_key(|warning| (warning.file_tag(), warning.line(), warning.col()));
+    binary.warnings = lint_levels.apply(warnings)?;
+
     Ok(binary)
 }
 
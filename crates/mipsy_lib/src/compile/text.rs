@@ -5,7 +5,7 @@ use crate::inst::instruction::InstSet;
 use crate::inst::instruction::SignatureRef;
 use crate::{
     error::{compiler, InternalError, MipsyInternalResult, ToMipsyResult},
-    Safe, KTEXT_BOT, TEXT_BOT,
+    Safe, KTEXT_BOT,
 };
 use crate::{MipsyResult, MpProgram};
 use mipsy_parser::{MpInstruction, MpItem};
@@ -89,6 +89,7 @@ pub fn populate_text(
     for attributed_item in program.items() {
         let line = attributed_item.line_number();
         let file_tag = attributed_item.file_tag().unwrap_or_else(|| Rc::from(""));
+        let expansion = attributed_item.expansion();
         let item = attributed_item.item();
         let kernel_tag: Rc<str> = Rc::from("kernel");
 
@@ -99,6 +100,7 @@ pub fn populate_text(
                     binary,
                     config,
                     file_tag.clone(),
+                    expansion.clone(),
                     &mut segment,
                     false,
                 )?;
@@ -114,12 +116,14 @@ pub fn populate_text(
                 }
             }
             MpItem::Instruction(ref instruction) => {
-                let compiled = compile1(binary, iset, instruction).into_compiler_mipsy_result(
-                    file_tag.clone(),
-                    line,
-                    instruction.col(),
-                    instruction.col_end(),
-                )?;
+                let compiled = compile1(binary, iset, instruction)
+                    .into_compiler_mipsy_result(
+                        file_tag.clone(),
+                        line,
+                        instruction.col(),
+                        instruction.col_end(),
+                    )
+                    .map_err(|err| err.in_expansion(expansion.clone()))?;
 
                 let text = match segment {
                     Segment::Text => {
@@ -130,7 +134,7 @@ pub fn populate_text(
 
                         if !file_tag.is_empty() {
                             binary.line_numbers.insert(
-                                TEXT_BOT + (binary.text.len() as u32),
+                                binary.layout.text_bot + (binary.text.len() as u32),
                                 (file_tag.clone(), line),
                             );
                         }
@@ -163,6 +167,7 @@ pub fn populate_text(
             }
             MpItem::Label(_) => {}
             MpItem::Constant(_) => {}
+            MpItem::Error(_) => {}
         }
     }
 
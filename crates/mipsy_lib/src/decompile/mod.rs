@@ -1,6 +1,10 @@
-use std::{collections::HashMap, rc::Rc};
+pub mod source;
 
-use crate::inst::instruction::{ArgumentType, CompileSignature, InstSet, RuntimeSignature};
+use std::{collections::HashMap, rc::Rc, str::FromStr};
+
+use crate::inst::instruction::{
+    ArgumentType, CompileSignature, InstSet, InstSignature, PseudoSignature, RuntimeSignature,
+};
 use crate::inst::register::Register;
 use crate::inst::RuntimeMetadata;
 use crate::{Binary, Safe};
@@ -14,6 +18,10 @@ pub struct Decompiled<'a> {
     pub arguments: Vec<String>,
     pub labels: Vec<String>,
     pub location: Option<(Rc<str>, u32)>,
+    /// Whether this instruction has been folded into the pseudo-instruction
+    /// that starts before it (see [`fold_pseudo_instructions`]),
+    /// so has no name or arguments of its own to show.
+    pub folded: bool,
 }
 
 #[derive(Debug)]
@@ -77,7 +85,7 @@ pub fn decompile_into_parts<'a>(
 ) -> HashMap<u32, Result<Decompiled<'a>, Uninit>> {
     let mut decompiled = HashMap::new();
 
-    let mut text_addr = crate::TEXT_BOT;
+    let mut text_addr = program.layout.text_bot;
 
     for word in program.text_words() {
         if let Safe::Valid(word) = word {
@@ -109,6 +117,48 @@ pub fn decompile_into_parts<'a>(
     decompiled
 }
 
+/// Gives a label to every branch and jump target in the text segment that
+/// doesn't already have one, named after its address (e.g. `loc_00400010`),
+/// so that the decompiled program reads like the original.
+pub fn recover_labels(program: &mut Binary, iset: &InstSet) {
+    let text_bot = program.layout.text_bot;
+    let text_top = text_bot.wrapping_add(program.text.len() as u32);
+
+    let mut targets = program
+        .text_words()
+        .zip((text_bot..).step_by(4))
+        .filter_map(|(word, addr)| match word {
+            Safe::Valid(word) => jump_target(program, iset, word, addr),
+            Safe::Uninitialised => None,
+        })
+        .filter(|target| (text_bot..text_top).contains(target))
+        .collect::<Vec<_>>();
+
+    targets.sort_unstable();
+    targets.dedup();
+
+    for target in targets {
+        if !program.labels.values().any(|&addr| addr == target) {
+            program.insert_label(&format!("loc_{:08x}", target), target);
+        }
+    }
+}
+
+/// Where a branch or jump goes to, if the instruction is one.
+fn jump_target(program: &Binary, iset: &InstSet, inst: u32, text_addr: u32) -> Option<u32> {
+    let sig = decompile_inst_into_parts(program, iset, inst, text_addr).inst_sig?;
+
+    match sig.format().last()? {
+        ArgumentType::I16 if sig.relative_label() => {
+            let imm = (inst & 0xFFFF) as i16;
+
+            Some(program.branch_target(text_addr, imm))
+        }
+        ArgumentType::J => Some((text_addr + 4) & 0xF000_0000 | (inst & 0x3FFFFFF) << 2),
+        _ => None,
+    }
+}
+
 pub fn decompile_inst_into_parts<'a>(
     program: &Binary,
     iset: &'a InstSet,
@@ -124,6 +174,7 @@ pub fn decompile_inst_into_parts<'a>(
         arguments: vec![],
         labels: vec![],
         location: program.line_numbers.get(&text_addr).cloned(),
+        folded: false,
     };
 
     for (label, &addr) in program.labels.iter() {
@@ -132,81 +183,18 @@ pub fn decompile_inst_into_parts<'a>(
         }
     }
 
-    let opcode = inst >> 26;
     let rs = (inst >> 21) & 0x1F;
     let rt = (inst >> 16) & 0x1F;
     let rd = (inst >> 11) & 0x1F;
     let shamt = (inst >> 6) & 0x1F;
-    let funct = inst & 0x3F;
     let imm = (inst & 0xFFFF) as i16;
     let addr = inst & 0x3FFFFFF;
 
-    let mut inst = None;
-
-    for native_inst in iset.native_set() {
-        match *native_inst.runtime_signature() {
-            RuntimeSignature::R {
-                opcode: inst_opcode,
-                funct: inst_funct,
-                shamt: inst_shamt,
-                rs: inst_rs,
-                rt: inst_rt,
-                rd: inst_rd,
-            } => {
-                if inst_opcode as u32 != opcode || inst_funct as u32 != funct {
-                    continue;
-                }
-
-                if let Some(inst_shamt) = inst_shamt {
-                    if inst_shamt as u32 != shamt {
-                        continue;
-                    }
-                }
-
-                if let Some(inst_rs) = inst_rs {
-                    if inst_rs as u32 != rs {
-                        continue;
-                    }
-                }
-
-                if let Some(inst_rt) = inst_rt {
-                    if inst_rt as u32 != rt {
-                        continue;
-                    }
-                }
-
-                if let Some(inst_rd) = inst_rd {
-                    if inst_rd as u32 != rd {
-                        continue;
-                    }
-                }
-            }
-
-            RuntimeSignature::I {
-                opcode: inst_opcode,
-                rt: inst_rt,
-            } => {
-                if inst_opcode as u32 != opcode
-                    || inst_rt.is_some() && inst_rt.unwrap() as u32 != rt
-                {
-                    continue;
-                }
-            }
-
-            RuntimeSignature::J {
-                opcode: inst_opcode,
-                ..
-            } => {
-                if inst_opcode as u32 != opcode {
-                    continue;
-                }
-            }
-        }
+    let inst = native_instruction(iset, inst);
 
-        inst = Some(native_inst);
+    if let Some(native_inst) = inst {
         parts.inst_sig = Some(native_inst.compile_signature());
         parts.runtime_meta = Some(native_inst.runtime_metadata());
-        break;
     }
 
     if let Some(inst) = inst {
@@ -248,7 +236,7 @@ pub fn decompile_inst_into_parts<'a>(
 
                             if inst.compile_signature().relative_label() {
                                 for (label, &addr) in program.labels.iter() {
-                                    if addr == text_addr.wrapping_add((imm as i32 * 4) as u32) {
+                                    if addr == program.branch_target(text_addr, imm) {
                                         res = Some(label);
                                         break;
                                     }
@@ -286,3 +274,396 @@ pub fn decompile_inst_into_parts<'a>(
 
     parts
 }
+
+/// The native instruction that a word encodes, if any.
+fn native_instruction(iset: &InstSet, inst: u32) -> Option<&InstSignature> {
+    let opcode = inst >> 26;
+    let rs = (inst >> 21) & 0x1F;
+    let rt = (inst >> 16) & 0x1F;
+    let rd = (inst >> 11) & 0x1F;
+    let shamt = (inst >> 6) & 0x1F;
+    let funct = inst & 0x3F;
+
+    iset.native_set().iter().find(|native_inst| {
+        match *native_inst.runtime_signature() {
+            RuntimeSignature::R {
+                opcode: inst_opcode,
+                funct: inst_funct,
+                shamt: inst_shamt,
+                rs: inst_rs,
+                rt: inst_rt,
+                rd: inst_rd,
+            } => {
+                if inst_opcode as u32 != opcode || inst_funct as u32 != funct {
+                    return false;
+                }
+
+                if let Some(inst_shamt) = inst_shamt {
+                    if inst_shamt as u32 != shamt {
+                        return false;
+                    }
+                }
+
+                if let Some(inst_rs) = inst_rs {
+                    if inst_rs as u32 != rs {
+                        return false;
+                    }
+                }
+
+                if let Some(inst_rt) = inst_rt {
+                    if inst_rt as u32 != rt {
+                        return false;
+                    }
+                }
+
+                if let Some(inst_rd) = inst_rd {
+                    if inst_rd as u32 != rd {
+                        return false;
+                    }
+                }
+            }
+
+            RuntimeSignature::I {
+                opcode: inst_opcode,
+                rt: inst_rt,
+            } => {
+                if inst_opcode as u32 != opcode
+                    || inst_rt.is_some() && inst_rt.unwrap() as u32 != rt
+                {
+                    return false;
+                }
+            }
+
+            RuntimeSignature::J {
+                opcode: inst_opcode,
+                ..
+            } => {
+                if inst_opcode as u32 != opcode {
+                    return false;
+                }
+            }
+        }
+
+        true
+    })
+}
+
+/// Folds the native instructions that a pseudo-instruction was expanded into
+/// back into it, so that `lui $t0, 4097` and `ori $t0, $t0, 4` are shown
+/// together as `la $t0, msg`.
+///
+/// The instructions from a single line of source (i.e. an instruction with
+/// a location, and those without one or a label that follow it) are folded
+/// if together they're exactly the expansion of a pseudo-instruction (the
+/// first in the instruction set, other than that an address with a label is
+/// loaded with `la`). A lone instruction is only folded if it uses a fixed register,
+/// like `addu $t0, $zero, $t1` into `move $t0, $t1`, as otherwise it's
+/// most likely the native instruction that was written; instructions
+/// without a line of source (e.g. from an ELF file) are all treated as lone.
+///
+/// The first instruction takes the pseudo-instruction's name and arguments,
+/// and the rest are marked as [`folded`](Decompiled::folded).
+pub fn fold_pseudo_instructions<'a, 'b: 'a>(
+    program: &Binary,
+    iset: &InstSet,
+    parts: impl IntoIterator<Item = &'a mut Decompiled<'b>>,
+) {
+    let mut parts = parts.into_iter().collect::<Vec<_>>();
+    parts.sort_by_key(|part| part.addr);
+
+    let mut start = 0;
+
+    while start < parts.len() {
+        let mut end = start + 1;
+
+        // only the first instruction of a line is given its location,
+        // and one that's labelled can be jumped to on its own
+        if parts[start].location.is_some() {
+            while end < parts.len()
+                && parts[end].addr == parts[end - 1].addr.wrapping_add(4)
+                && parts[end].location.is_none()
+                && parts[end].labels.is_empty()
+            {
+                end += 1;
+            }
+        }
+
+        let group = &mut parts[start..end];
+
+        if let Some((name, arguments)) = fold_group(program, iset, group) {
+            group[0].inst_name = Some(name);
+            group[0].arguments = arguments;
+
+            for part in &mut group[1..] {
+                part.folded = true;
+                part.arguments = vec![];
+            }
+        }
+
+        start = end;
+    }
+}
+
+/// An argument of a native instruction, to be matched against
+/// the expansion of a pseudo-instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(u32),
+    Immediate(u16),
+    Offset(u16, u32),
+    Address(u32),
+}
+
+/// The name and arguments of the pseudo-instruction that expands into
+/// exactly these instructions, if there is one.
+fn fold_group(
+    program: &Binary,
+    iset: &InstSet,
+    group: &[&mut Decompiled<'_>],
+) -> Option<(String, Vec<String>)> {
+    let operands = group
+        .iter()
+        .map(|part| {
+            let native = native_instruction(iset, part.opcode)?;
+            let operands = operands(native.compile_signature(), part.opcode, part.addr);
+
+            Some((native.name(), operands, part.addr))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut folded = None;
+
+    for pseudo in iset.pseudo_set() {
+        if pseudo.expansion().len() != group.len()
+            || group.len() == 1 && !uses_fixed_register(pseudo)
+        {
+            continue;
+        }
+
+        let Some(bindings) = bind_expansion(pseudo, &operands) else {
+            continue;
+        };
+
+        let Some((arguments, labelled)) = pseudo_arguments(program, pseudo, &bindings) else {
+            continue;
+        };
+
+        if pseudo.name() == "la" && labelled {
+            return Some((pseudo.name().to_string(), arguments));
+        }
+
+        if folded.is_none() {
+            folded = Some((pseudo.name().to_string(), arguments));
+        }
+    }
+
+    folded
+}
+
+/// Whether any of a pseudo-instruction's expansion uses
+/// a particular register, rather than one of its arguments.
+fn uses_fixed_register(pseudo: &PseudoSignature) -> bool {
+    pseudo.expansion().iter().any(|expand| {
+        expand.data().iter().any(|data| {
+            data.trim_start_matches('(')
+                .trim_end_matches(')')
+                .strip_prefix('$')
+                .is_some_and(|name| Register::from_str(name).is_ok())
+        })
+    })
+}
+
+/// A native instruction's arguments, in the order they're written.
+fn operands(sig: &CompileSignature, inst: u32, text_addr: u32) -> Vec<Operand> {
+    let rs = (inst >> 21) & 0x1F;
+    let rt = (inst >> 16) & 0x1F;
+    let rd = (inst >> 11) & 0x1F;
+    let shamt = (inst >> 6) & 0x1F;
+    let imm = (inst & 0xFFFF) as u16;
+    let addr = inst & 0x3FFFFFF;
+
+    sig.format()
+        .iter()
+        .map(|arg| match arg {
+            ArgumentType::Rd => Operand::Register(rd),
+            ArgumentType::Rt => Operand::Register(rt),
+            ArgumentType::Rs => Operand::Register(rs),
+            ArgumentType::Shamt => Operand::Immediate(shamt as u16),
+            ArgumentType::I16 | ArgumentType::U16 => Operand::Immediate(imm),
+            ArgumentType::OffRs => Operand::Offset(imm, rs),
+            ArgumentType::OffRt => Operand::Offset(imm, rt),
+            ArgumentType::J => Operand::Address((text_addr + 4) & 0xF000_0000 | addr << 2),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Matches each native instruction against a step of the pseudo-instruction's
+/// expansion, giving back what each of its variables (e.g. `$Rd`) must be,
+/// along with the address of the instruction it came from.
+fn bind_expansion(
+    pseudo: &PseudoSignature,
+    natives: &[(&str, Vec<Operand>, u32)],
+) -> Option<HashMap<String, (Operand, u32)>> {
+    let mut bindings = HashMap::new();
+
+    for (expand, (name, operands, addr)) in pseudo.expansion().iter().zip(natives) {
+        if !expand.inst().eq_ignore_ascii_case(name) || expand.data().len() != operands.len() {
+            return None;
+        }
+
+        for (data, &operand) in expand.data().iter().zip(operands) {
+            if !bind(data, operand, *addr, &mut bindings) {
+                return None;
+            }
+        }
+    }
+
+    Some(bindings)
+}
+
+fn bind(
+    data: &str,
+    operand: Operand,
+    addr: u32,
+    bindings: &mut HashMap<String, (Operand, u32)>,
+) -> bool {
+    if let Some((offset, register)) = data.strip_suffix(')').and_then(|data| data.split_once('(')) {
+        let Operand::Offset(imm, reg) = operand else {
+            return false;
+        };
+
+        let offset_matches = if offset.is_empty() {
+            imm == 0
+        } else {
+            bind(offset, Operand::Immediate(imm), addr, bindings)
+        };
+
+        return offset_matches && bind(register, Operand::Register(reg), addr, bindings);
+    }
+
+    let Some(name) = data.strip_prefix('$') else {
+        // a literal, like the `-4` in `push`
+        return data
+            .parse::<i32>()
+            .is_ok_and(|value| operand == Operand::Immediate(value as u16));
+    };
+
+    if let Ok(register) = Register::from_str(name) {
+        return operand == Operand::Register(register.to_u32());
+    }
+
+    match bindings.get(&name.to_ascii_lowercase()) {
+        Some(&(bound, _)) => bound == operand,
+        None => {
+            bindings.insert(name.to_ascii_lowercase(), (operand, addr));
+            true
+        }
+    }
+}
+
+/// The arguments to show for a folded pseudo-instruction, and whether
+/// any 32-bit value among them was shown as a label.
+fn pseudo_arguments(
+    program: &Binary,
+    pseudo: &PseudoSignature,
+    bindings: &HashMap<String, (Operand, u32)>,
+) -> Option<(Vec<String>, bool)> {
+    let register = |name: &str| match bindings.get(name)? {
+        &(Operand::Register(reg), _) => Some(reg),
+        _ => None,
+    };
+
+    let immediate = |name: &str| match bindings.get(name)? {
+        &(Operand::Immediate(imm), addr) => Some((imm, addr)),
+        _ => None,
+    };
+
+    let word = |name: &str| {
+        let (upper, _) = immediate(&format!("{name}uhi"))?;
+        let (lower, _) = immediate(&format!("{name}ulo"))?;
+
+        Some((upper as u32) << 16 | lower as u32)
+    };
+
+    let show_register = |reg: u32| format!("${}", Register::u32_to_str(reg).to_ascii_lowercase());
+
+    let mut labelled = false;
+
+    let mut show_word = |value: u32, as_label: bool| match label_at(program, value) {
+        Some(label) if as_label => {
+            labelled = true;
+            label.to_string()
+        }
+        _ if as_label => format!("0x{:08x}", value),
+        _ => (value as i32).to_string(),
+    };
+
+    let mut arguments = vec![];
+
+    for arg in pseudo.compile_signature().format() {
+        arguments.push(match arg {
+            ArgumentType::Rd => show_register(register("rd")?),
+            ArgumentType::Rs => show_register(register("rs")?),
+            ArgumentType::Rt => show_register(register("rt")?),
+            ArgumentType::Shamt => immediate("shamt")?.0.to_string(),
+            ArgumentType::I16 => {
+                let (imm, addr) = immediate("i16")?;
+                let imm = imm as i16;
+
+                let target = program.branch_target(addr, imm);
+
+                match label_at(program, target) {
+                    Some(label) if pseudo.compile_signature().relative_label() => label.to_string(),
+                    _ => imm.to_string(),
+                }
+            }
+            ArgumentType::U16 => immediate("u16")?.0.to_string(),
+            ArgumentType::I32 => show_word(word("i32")?, pseudo.name() == "la"),
+            ArgumentType::U32 => show_word(word("u32")?, true),
+            ArgumentType::OffRs | ArgumentType::OffRt => {
+                let reg = register(if *arg == ArgumentType::OffRs {
+                    "rs"
+                } else {
+                    "rt"
+                })?;
+                let (offset, _) = immediate("off")?;
+
+                match offset {
+                    0 => format!("({})", show_register(reg)),
+                    _ => format!("{}({})", offset as i16, show_register(reg)),
+                }
+            }
+            ArgumentType::Off32Rs | ArgumentType::Off32Rt => {
+                let reg = register(if *arg == ArgumentType::Off32Rs {
+                    "rs"
+                } else {
+                    "rt"
+                })?;
+
+                format!(
+                    "{}({})",
+                    show_word(word("off32")?, true),
+                    show_register(reg)
+                )
+            }
+            ArgumentType::J => match bindings.get("j")? {
+                &(Operand::Address(addr), _) => label_at(program, addr)
+                    .map(str::to_string)
+                    .unwrap_or(format!("{:08x}", addr)),
+                _ => return None,
+            },
+            ArgumentType::F32 | ArgumentType::F64 => return None,
+        });
+    }
+
+    Some((arguments, labelled))
+}
+
+fn label_at(program: &Binary, addr: u32) -> Option<&str> {
+    program
+        .labels
+        .iter()
+        .find(|&(_, &label_addr)| label_addr == addr)
+        .map(|(label, _)| label.as_str())
+}
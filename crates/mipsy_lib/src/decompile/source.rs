@@ -0,0 +1,446 @@
+//! # Decompiling a program back into source.
+//!
+//! Unlike [`decompile`](super::decompile), which is for reading,
+//! [`decompile_to_source`] writes a complete program that mipsy can
+//! assemble again, into the same text and data segments:
+//!
+//! ```text
+//!         .globl main
+//!
+//!         .text
+//! main:
+//!         la      $a0, msg
+//!         li      $v0, 4
+//!         syscall
+//!
+//!         .data
+//! msg:
+//!         .asciiz "hello world\n"
+//! ```
+//!
+//! Branch and jump targets that aren't labelled are given a label named
+//! after their address (`loc_00400010`), as is data that is referred to
+//! by address (`data_10010004`). The data segment has no record of which
+//! directives wrote it, so it's guessed at: NUL-terminated text becomes
+//! `.asciiz`, aligned words become `.word`, and anything else `.byte`.
+//!
+//! The kernel's segments are left out, as mipsy assembles the kernel
+//! into every program anyway.
+
+use std::fmt::Write;
+
+use linked_hash_map::LinkedHashMap;
+use mipsy_parser::is_local_label;
+
+use super::{decompile_into_parts, fold_pseudo_instructions, recover_labels, Decompiled, Uninit};
+use crate::{compile1, Binary, InstSet, Safe};
+
+/// How many values to put on each line of a data directive.
+const VALUES_PER_LINE: usize = 8;
+
+/// How many times a value has to repeat in a row to be written as `value:n`.
+const MIN_REPEAT: usize = 4;
+
+/// Decompiles a program into a source file that assembles back into the
+/// same text and data segments, with pseudo-instructions folded back together.
+pub fn decompile_to_source(program: &Binary, iset: &InstSet) -> String {
+    let mut program = program.clone();
+
+    // local labels have been renamed to something that can't be written,
+    // and any that are needed will be recovered from their address instead
+    program.labels = program
+        .labels
+        .iter()
+        .filter(|(label, _)| is_plain_label(label))
+        .map(|(label, &addr)| (label.clone(), addr))
+        .collect::<LinkedHashMap<_, _>>();
+
+    recover_labels(&mut program, iset);
+    recover_data_labels(&mut program, iset);
+
+    let mut source = String::new();
+
+    for global in program.globals.iter() {
+        if program.labels.contains_key(global) {
+            writeln!(source, "\t.globl {}", global).unwrap();
+        }
+    }
+
+    if !program.text.is_empty() {
+        if !source.is_empty() {
+            source.push('\n');
+        }
+
+        source.push_str("\t.text\n");
+        write_text(&mut source, &program, iset);
+    }
+
+    if !program.data.is_empty() {
+        if !source.is_empty() {
+            source.push('\n');
+        }
+
+        source.push_str("\t.data\n");
+        write_data(&mut source, &program);
+    }
+
+    source
+}
+
+/// Whether a label can be written as it is,
+/// rather than being a local label or a symbol from elsewhere.
+fn is_plain_label(label: &str) -> bool {
+    let mut chars = label.chars();
+
+    !is_local_label(label)
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Decompiles the text segment, in order,
+/// with its pseudo-instructions folded back together if `fold`.
+fn decompile_text<'a>(
+    program: &Binary,
+    iset: &'a InstSet,
+    fold: bool,
+) -> Vec<Result<Decompiled<'a>, Uninit>> {
+    let mut decompiled = decompile_into_parts(program, iset)
+        .into_values()
+        .collect::<Vec<_>>();
+
+    decompiled.sort_unstable_by_key(|parts| match parts {
+        Ok(parts) => parts.addr,
+        Err(uninit) => uninit.addr,
+    });
+
+    if fold {
+        fold_pseudo_instructions(
+            program,
+            iset,
+            decompiled
+                .iter_mut()
+                .filter_map(|parts| parts.as_mut().ok()),
+        );
+    }
+
+    decompiled
+}
+
+/// Gives a label to every address in the data segment that the text
+/// segment refers to (i.e. with `la` or `lw`), but that doesn't have one.
+fn recover_data_labels(program: &mut Binary, iset: &InstSet) {
+    let data_bot = program.layout.data_bot;
+    let data_top = data_bot.wrapping_add(program.data.len() as u32);
+
+    // unlabelled addresses are written out in hex, e.g. `0x10010004($t0)`
+    let mut targets = decompile_text(program, iset, true)
+        .iter()
+        .filter_map(|parts| parts.as_ref().ok())
+        .flat_map(|parts| parts.arguments.iter())
+        .filter_map(|argument| {
+            let digits = argument.strip_prefix("0x")?.get(..8)?;
+
+            u32::from_str_radix(digits, 16).ok()
+        })
+        .filter(|target| (data_bot..data_top).contains(target))
+        .collect::<Vec<_>>();
+
+    targets.sort_unstable();
+    targets.dedup();
+
+    for target in targets {
+        if !program.labels.values().any(|&addr| addr == target) {
+            program.insert_label(&format!("data_{:08x}", target), target);
+        }
+    }
+}
+
+/// Writes out the text segment, checking that each line assembles back
+/// into the same words. A pseudo-instruction that doesn't (e.g. an `li`
+/// that was written out by hand as an `ori`) is written as the native
+/// instructions it was folded from instead, and a native instruction
+/// that doesn't (or a word that isn't one) as a `.word`.
+fn write_text(source: &mut String, program: &Binary, iset: &InstSet) {
+    let native = decompile_text(program, iset, false);
+    let folded = decompile_text(program, iset, true);
+
+    // compiled against to check each line, as if it were next in the text segment
+    let mut scratch = program.clone();
+
+    let mut index = 0;
+    while index < folded.len() {
+        let parts = match &folded[index] {
+            Ok(parts) => parts,
+            Err(uninit) => {
+                write_labels(source, &uninit.labels);
+                source.push_str("\t.space  4\n");
+
+                index += 1;
+                continue;
+            }
+        };
+
+        let group = folded[index + 1..]
+            .iter()
+            .take_while(|parts| matches!(parts, Ok(parts) if parts.folded))
+            .count()
+            + 1;
+
+        let words = folded[index..index + group]
+            .iter()
+            .filter_map(|parts| parts.as_ref().ok())
+            .map(|parts| parts.opcode)
+            .collect::<Vec<_>>();
+
+        // a label in the middle of a pseudo-instruction has nowhere to go
+        let labelled_inside = folded[index + 1..index + group]
+            .iter()
+            .any(|parts| matches!(parts, Ok(parts) if !parts.labels.is_empty()));
+
+        write_labels(source, &parts.labels);
+
+        match instruction_line(parts) {
+            Some(line)
+                if !labelled_inside
+                    && assembles_to(&mut scratch, iset, parts.addr, &line, &words) =>
+            {
+                writeln!(source, "\t{}", line).unwrap();
+            }
+            _ => {
+                for (offset, parts) in native[index..index + group].iter().enumerate() {
+                    let Ok(parts) = parts else {
+                        unreachable!("a pseudo-instruction is only folded from valid words");
+                    };
+
+                    if offset != 0 {
+                        write_labels(source, &parts.labels);
+                    }
+
+                    match instruction_line(parts) {
+                        Some(line)
+                            if assembles_to(
+                                &mut scratch,
+                                iset,
+                                parts.addr,
+                                &line,
+                                &[parts.opcode],
+                            ) =>
+                        {
+                            writeln!(source, "\t{}", line).unwrap();
+                        }
+                        _ => writeln!(source, "\t.word   0x{:08x}", parts.opcode).unwrap(),
+                    }
+                }
+            }
+        }
+
+        index += group;
+    }
+}
+
+fn write_labels(source: &mut String, labels: &[String]) {
+    for label in labels {
+        writeln!(source, "{}:", label).unwrap();
+    }
+}
+
+/// How an instruction is written, if it's one at all.
+fn instruction_line(parts: &Decompiled<'_>) -> Option<String> {
+    let name = parts.inst_name.as_ref()?;
+
+    match parts.arguments.is_empty() {
+        true => Some(name.clone()),
+        false => Some(format!("{:7} {}", name, parts.arguments.join(", "))),
+    }
+}
+
+/// Whether a line assembles into exactly these words at `addr`.
+fn assembles_to(
+    scratch: &mut Binary,
+    iset: &InstSet,
+    addr: u32,
+    line: &str,
+    words: &[u32],
+) -> bool {
+    let offset = addr.wrapping_sub(scratch.layout.text_bot) as usize;
+    scratch.text.resize(offset, Safe::Uninitialised);
+
+    mipsy_parser::parse_instruction(line, 8)
+        .ok()
+        .and_then(|inst| compile1(scratch, iset, &inst).ok())
+        .is_some_and(|compiled| compiled == words)
+}
+
+/// A data directive being built up, one value at a time.
+struct Directive {
+    name: &'static str,
+    values: Vec<(String, usize)>,
+}
+
+fn write_data(source: &mut String, program: &Binary) {
+    let data_bot = program.layout.data_bot;
+    let data = &program.data;
+
+    let mut labels = program
+        .labels
+        .iter()
+        .filter(|&(_, &addr)| addr >= data_bot && ((addr - data_bot) as usize) <= data.len())
+        .map(|(label, &addr)| ((addr - data_bot) as usize, label.as_str()))
+        .collect::<Vec<_>>();
+    labels.sort_by_key(|&(offset, _)| offset);
+
+    let mut directive: Option<Directive> = None;
+    let mut labels = labels.into_iter().peekable();
+    let mut offset = 0;
+
+    while offset < data.len() || labels.peek().is_some() {
+        if labels
+            .peek()
+            .is_some_and(|&(label_offset, _)| label_offset <= offset)
+        {
+            flush_directive(source, directive.take());
+
+            while let Some((_, label)) = labels.next_if(|&(label_offset, _)| label_offset <= offset)
+            {
+                writeln!(source, "{}:", label).unwrap();
+            }
+        }
+
+        if offset >= data.len() {
+            continue;
+        }
+
+        // data can only be guessed at up to the next label
+        let end = labels
+            .peek()
+            .map_or(data.len(), |&(label_offset, _)| label_offset);
+        let addr = data_bot.wrapping_add(offset as u32);
+
+        let (name, value, len) = if data[offset] == Safe::Uninitialised {
+            let len = data[offset..end]
+                .iter()
+                .take_while(|&&byte| byte == Safe::Uninitialised)
+                .count();
+
+            (".space", len.to_string(), len)
+        } else if let Some((text, len)) = text_at(&data[offset..end], addr) {
+            (".asciiz", format!("\"{}\"", text), len + 1)
+        } else if let Some(word) = word_at(&data[offset..end], addr) {
+            (".word", word_value(program, word), 4)
+        } else {
+            let Safe::Valid(byte) = data[offset] else {
+                unreachable!("just checked the uninitialised case");
+            };
+
+            (".byte", byte.to_string(), 1)
+        };
+
+        match &mut directive {
+            // strings and spaces read better one to a line
+            Some(directive) if directive.name == name && name != ".asciiz" && name != ".space" => {
+                match directive.values.last_mut() {
+                    Some((last, repeats)) if *last == value => *repeats += 1,
+                    _ => directive.values.push((value, 1)),
+                }
+            }
+            _ => {
+                flush_directive(source, directive.take());
+
+                directive = Some(Directive {
+                    name,
+                    values: vec![(value, 1)],
+                });
+            }
+        }
+
+        offset += len;
+    }
+
+    flush_directive(source, directive);
+}
+
+fn flush_directive(source: &mut String, directive: Option<Directive>) {
+    let Some(directive) = directive else {
+        return;
+    };
+
+    let values = directive
+        .values
+        .into_iter()
+        .flat_map(|(value, repeats)| match repeats >= MIN_REPEAT {
+            true => vec![format!("{}:{}", value, repeats)],
+            false => vec![value; repeats],
+        })
+        .collect::<Vec<_>>();
+
+    for line in values.chunks(VALUES_PER_LINE) {
+        writeln!(source, "\t{:7} {}", directive.name, line.join(", ")).unwrap();
+    }
+}
+
+/// The NUL-terminated text at the start of `bytes`, escaped, and how long
+/// it is, if it looks like a string. Short text at an aligned address
+/// is more likely a word.
+fn text_at(bytes: &[Safe<u8>], addr: u32) -> Option<(String, usize)> {
+    let len = bytes
+        .iter()
+        .take_while(|byte| matches!(byte, Safe::Valid(b' '..=b'~' | b'\n' | b'\t' | b'\r')))
+        .count();
+
+    if len == 0 || bytes.get(len) != Some(&Safe::Valid(0)) {
+        return None;
+    }
+
+    if len < 4 && addr.is_multiple_of(4) && bytes.len() >= 4 {
+        return None;
+    }
+
+    let text = bytes[..len]
+        .iter()
+        .map(|byte| match byte {
+            Safe::Valid(b'\n') => String::from("\\n"),
+            Safe::Valid(b'\t') => String::from("\\t"),
+            Safe::Valid(b'\r') => String::from("\\r"),
+            Safe::Valid(b'\\') => String::from("\\\\"),
+            Safe::Valid(b'"') => String::from("\\\""),
+            Safe::Valid(byte) => char::from(*byte).to_string(),
+            Safe::Uninitialised => unreachable!("only valid bytes were taken"),
+        })
+        .collect();
+
+    Some((text, len))
+}
+
+/// The word at the start of `bytes`, if it's aligned and fully initialised.
+fn word_at(bytes: &[Safe<u8>], addr: u32) -> Option<u32> {
+    if !addr.is_multiple_of(4) {
+        return None;
+    }
+
+    match bytes.get(..4)? {
+        &[Safe::Valid(b0), Safe::Valid(b1), Safe::Valid(b2), Safe::Valid(b3)] => {
+            Some(u32::from_le_bytes([b0, b1, b2, b3]))
+        }
+        _ => None,
+    }
+}
+
+/// How to write a word: as the label it points to, if there is one,
+/// and otherwise as a number.
+fn word_value(program: &Binary, word: u32) -> String {
+    let text = program.layout.text_bot..program.layout.text_bot + program.text.len() as u32;
+    let data = program.layout.data_bot..program.layout.data_bot + program.data.len() as u32;
+
+    if text.contains(&word) || data.contains(&word) {
+        if let Some((label, _)) = program.labels.iter().find(|&(_, &addr)| addr == word) {
+            return label.clone();
+        }
+    }
+
+    match (word as i32).unsigned_abs() < 0x10000 {
+        true => (word as i32).to_string(),
+        false => format!("0x{:08x}", word),
+    }
+}
@@ -0,0 +1,497 @@
+//! # Machine-readable diagnostics.
+//!
+//! A [`Diagnostic`] holds everything that `show_error` and `show_warning`
+//! print, as plain data rather than coloured text, so that autograders,
+//! editors and code review tools don't have to scrape mipsy's output.
+//!
+//! Diagnostics serialize to JSON as they are, or can be gathered into a
+//! [SARIF](https://sarifweb.azurewebsites.net/) log with [`sarif`].
+//!
+//! Lines and columns count from 1, and columns count characters along the
+//! line as it is in the file (unlike the columns shown to humans, which
+//! count with tabs expanded). `col_end` is exclusive.
+
+use std::rc::Rc;
+
+use mipsy_parser::{file_tab_size, MpMacroExpansion};
+use mipsy_utils::MipsyConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    compile::{KTEXT_BOT, TEXT_BOT},
+    decompile::decompile_inst_into_parts,
+    error::{
+        codes,
+        runtime::{Error as RuntimeErrorKind, Uninitialised},
+    },
+    Binary, CompilerError, CompilerWarning, InstSet, ParserError, Register, Runtime, RuntimeError,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// The error's code (see [`codes`](crate::error::codes)), or the lint that produced a warning.
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    /// Where the diagnostic is, if it's anywhere in particular.
+    pub location: Option<Location>,
+    /// Each macro invocation the code was expanded from, innermost first.
+    pub expansion: Vec<Expansion>,
+    pub tips: Vec<String>,
+    /// The state of the program when a runtime error happened.
+    pub runtime: Option<RuntimeContext>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub col: Option<u32>,
+    pub col_end: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expansion {
+    /// The name of the macro that was invoked.
+    pub name: String,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeContext {
+    pub pc: u32,
+    /// The instruction at `pc`, decompiled.
+    pub instruction: Option<String>,
+    /// Every register that has been initialised, in order (with `hi` and `lo` last).
+    pub registers: Vec<RegisterValue>,
+    /// The memory around the address the error is about, if it's about one.
+    pub memory: Option<MemoryContext>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisterValue {
+    pub name: String,
+    /// Unsigned, as registers are as often addresses as they are numbers.
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryContext {
+    /// The address the error is about.
+    pub addr: u32,
+    /// The address of the first byte in `bytes`.
+    pub start: u32,
+    /// The row of memory `addr` is in, with uninitialised (or unmapped) bytes as `None`.
+    pub bytes: Vec<Option<u8>>,
+}
+
+/// How many bytes of memory are included around a faulting address.
+const MEMORY_CONTEXT_SIZE: u32 = 16;
+
+impl Diagnostic {
+    /// `file` is the contents of the file the error is in, as with [`ParserError::show_error`].
+    pub fn from_parser_error(error: &ParserError, config: &MipsyConfig, file: &str) -> Self {
+        let tab_size = file_tab_size(file, config.tab_size);
+        let file_tag = error.file_tag();
+
+        Self {
+            code: error.error().code().to_string(),
+            severity: Severity::Error,
+            message: plain(&error.error().message()),
+            location: location(
+                &file_tag,
+                error.line(),
+                Some((error.col(), None)),
+                file,
+                tab_size,
+            ),
+            expansion: expansions(error.expansion(), &file_tag, file, tab_size),
+            tips: error.error().tips().iter().map(|tip| plain(tip)).collect(),
+            runtime: None,
+        }
+    }
+
+    /// `file` is the contents of the file the error is in, as with [`CompilerError::show_error`].
+    pub fn from_compiler_error(error: &CompilerError, config: &MipsyConfig, file: &str) -> Self {
+        let tab_size = file_tab_size(file, config.tab_size);
+        let file_tag = error.file_tag();
+
+        // errors that aren't about any line in particular (e.g. a missing `main`)
+        let location = if error.error().should_highlight_line() {
+            let cols = Some((error.col(), Some(error.col_end())));
+
+            location(&file_tag, error.line(), cols, file, tab_size)
+        } else {
+            None
+        };
+
+        Self {
+            code: error.error().code().to_string(),
+            severity: Severity::Error,
+            message: plain(&error.error().message()),
+            location,
+            expansion: expansions(error.expansion(), &file_tag, file, tab_size),
+            tips: error.error().tips().iter().map(|tip| plain(tip)).collect(),
+            runtime: None,
+        }
+    }
+
+    /// `file` is the contents of the file the warning is in, as with [`CompilerWarning::show_warning`].
+    pub fn from_warning(warning: &CompilerWarning, config: &MipsyConfig, file: &str) -> Self {
+        let tab_size = file_tab_size(file, config.tab_size);
+        let file_tag = warning.file_tag();
+        let cols = Some((warning.col(), Some(warning.col_end())));
+
+        Self {
+            code: warning.warning().lint().to_string(),
+            severity: Severity::Warning,
+            message: plain(&warning.warning().message()),
+            location: location(&file_tag, warning.line(), cols, file, tab_size),
+            expansion: expansions(warning.expansion(), &file_tag, file, tab_size),
+            tips: warning
+                .warning()
+                .tips()
+                .iter()
+                .map(|tip| plain(tip))
+                .collect(),
+            runtime: None,
+        }
+    }
+
+    /// Takes the same arguments as [`RuntimeError::show_error`] (other than the context,
+    /// which only changes how the error is explained), where `runtime` is in the state
+    /// it was in when the error happened.
+    pub fn from_runtime_error(
+        error: &RuntimeError,
+        source_code: &[(Rc<str>, Rc<str>)],
+        inst_set: &InstSet,
+        binary: &Binary,
+        runtime: &Runtime,
+    ) -> Self {
+        let state = runtime.timeline().state();
+        let pc = state.pc();
+
+        let location = source_line(binary, pc).map(|(file_tag, line)| Location {
+            file: file_tag.to_string(),
+            line: *line,
+            col: None,
+            col_end: None,
+        });
+
+        let instruction = state.read_mem_word(pc).ok().and_then(|inst| {
+            let parts = decompile_inst_into_parts(binary, inst_set, inst, pc);
+
+            parts.inst_name.map(|name| {
+                format!("{} {}", name, parts.arguments.join(", "))
+                    .trim_end()
+                    .to_string()
+            })
+        });
+
+        let mut registers: Vec<RegisterValue> = state
+            .registers()
+            .iter()
+            .enumerate()
+            .filter_map(|(num, value)| {
+                let name = format!("${}", Register::u32_to_str(num as u32).to_ascii_lowercase());
+
+                value.as_option().map(|&value| RegisterValue {
+                    name,
+                    value: value as u32,
+                })
+            })
+            .collect();
+
+        for (name, value) in [("hi", state.read_hi()), ("lo", state.read_lo())] {
+            if let Ok(value) = value {
+                registers.push(RegisterValue {
+                    name: name.to_string(),
+                    value: value as u32,
+                });
+            }
+        }
+
+        let memory = faulting_addr(error.error()).map(|addr| {
+            let start = addr - addr % MEMORY_CONTEXT_SIZE;
+
+            let bytes = (start..start.saturating_add(MEMORY_CONTEXT_SIZE))
+                .map(|addr| {
+                    state
+                        .read_mem_byte_uninit_unchecked(addr)
+                        .ok()
+                        .and_then(|byte| byte.into_option())
+                })
+                .collect();
+
+            MemoryContext { addr, start, bytes }
+        });
+
+        Self {
+            code: error.error().code().to_string(),
+            severity: Severity::Error,
+            message: error.error().summary(),
+            location,
+            expansion: vec![],
+            tips: error
+                .error()
+                .tips(source_code, inst_set, binary, runtime)
+                .iter()
+                .map(|tip| plain(tip))
+                .collect(),
+            runtime: Some(RuntimeContext {
+                pc,
+                instruction,
+                registers,
+                memory,
+            }),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("diagnostics are always valid json")
+    }
+}
+
+/// A SARIF 2.1.0 log of a run of mipsy that found the given diagnostics.
+pub fn sarif(diagnostics: &[Diagnostic]) -> String {
+    let mut rules: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| &*diagnostic.code)
+        .collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let results: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut text = diagnostic.message.clone();
+            for tip in &diagnostic.tips {
+                text.push_str(&format!("\ntip: {}", tip.trim_end()));
+            }
+
+            let mut result = json!({
+                "ruleId": diagnostic.code,
+                "level": match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                },
+                "message": { "text": text },
+            });
+
+            if let Some(location) = &diagnostic.location {
+                result["locations"] = json!([sarif_location(location)]);
+            }
+
+            if !diagnostic.expansion.is_empty() {
+                let related: Vec<_> = diagnostic
+                    .expansion
+                    .iter()
+                    .map(|expansion| {
+                        let mut related = sarif_location(&expansion.location);
+                        related["message"] = json!({
+                            "text": format!("in this expansion of `{}`", expansion.name),
+                        });
+
+                        related
+                    })
+                    .collect();
+
+                result["relatedLocations"] = json!(related);
+            }
+
+            if let Some(runtime) = &diagnostic.runtime {
+                result["properties"] = json!({ "runtime": runtime });
+            }
+
+            result
+        })
+        .collect();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "mipsy",
+                    "informationUri": "https://github.com/insou22/mipsy",
+                    "rules": rules.iter().map(|id| sarif_rule(id)).collect::<Vec<_>>(),
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+
+    serde_json::to_string_pretty(&log).expect("sarif logs are always valid json")
+}
+
+/// A rule for each error code, with its explanation (lints are just named).
+fn sarif_rule(id: &str) -> serde_json::Value {
+    let mut rule = json!({ "id": id });
+
+    if let Some(explanation) = codes::explain(id) {
+        let summary = explanation.lines().next().unwrap_or_default();
+
+        rule["shortDescription"] = json!({ "text": summary });
+        rule["help"] = json!({ "text": explanation, "markdown": explanation });
+    }
+
+    rule
+}
+
+fn sarif_location(location: &Location) -> serde_json::Value {
+    let mut region = json!({ "startLine": location.line });
+
+    if let Some(col) = location.col {
+        region["startColumn"] = json!(col);
+    }
+
+    if let Some(col_end) = location.col_end {
+        region["endColumn"] = json!(col_end);
+    }
+
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": location.file },
+            "region": region,
+        },
+    })
+}
+
+/// The address a runtime error is about, if it's about one.
+fn faulting_addr(error: &RuntimeErrorKind) -> Option<u32> {
+    match error {
+        RuntimeErrorKind::UnknownInstruction { addr }
+        | RuntimeErrorKind::UnalignedAccess { addr, .. }
+        | RuntimeErrorKind::OutOfBounds { addr, .. }
+        | RuntimeErrorKind::SegmentationFault { addr, .. }
+        | RuntimeErrorKind::StackOverflow { addr, .. } => Some(*addr),
+        RuntimeErrorKind::Uninitialised {
+            value:
+                Uninitialised::Byte { addr }
+                | Uninitialised::Half { addr }
+                | Uninitialised::Word { addr },
+        } => Some(*addr),
+        RuntimeErrorKind::Uninitialised { .. }
+        | RuntimeErrorKind::IntegerOverflow
+        | RuntimeErrorKind::DivisionByZero
+        | RuntimeErrorKind::InvalidSyscall { .. } => None,
+    }
+}
+
+/// The line an instruction came from, which for any but the first instruction
+/// of a pseudo-instruction is the closest line before it in the same segment.
+fn source_line(binary: &Binary, pc: u32) -> Option<&(Rc<str>, u32)> {
+    let (start, len) = if pc >= KTEXT_BOT {
+        (KTEXT_BOT, binary.ktext.len())
+    } else {
+        (TEXT_BOT, binary.text.len())
+    };
+
+    if pc < start || pc - start >= len as u32 {
+        return None;
+    }
+
+    binary
+        .line_numbers
+        .iter()
+        .filter(|&(&addr, _)| (start..=pc).contains(&addr))
+        .max_by_key(|&(&addr, _)| addr)
+        .map(|(_, line)| line)
+}
+
+/// Where something is, where `cols` are counted with tabs expanded, as the
+/// parser and compiler do. Columns in files other than `file` are left as they are.
+fn location(
+    file_tag: &str,
+    line: u32,
+    cols: Option<(u32, Option<u32>)>,
+    file: &str,
+    tab_size: u32,
+) -> Option<Location> {
+    if file_tag.is_empty() {
+        return None;
+    }
+
+    let source = file.lines().nth((line as usize).saturating_sub(1));
+    let col = |col: u32| match source {
+        Some(source) => unexpanded_col(source, col, tab_size),
+        None => col,
+    };
+
+    Some(Location {
+        file: file_tag.to_string(),
+        line,
+        col: cols.map(|(start, _)| col(start)),
+        col_end: cols.and_then(|(_, end)| end).map(col),
+    })
+}
+
+fn expansions(
+    expansion: Option<&MpMacroExpansion>,
+    file_tag: &str,
+    file: &str,
+    tab_size: u32,
+) -> Vec<Expansion> {
+    let mut expansions = vec![];
+    let mut expansion = expansion;
+
+    while let Some(invocation) = expansion {
+        let invocation_tag = invocation.file_tag();
+        let invocation_tag = invocation_tag.as_deref().unwrap_or("");
+
+        let cols = Some((invocation.col(), Some(invocation.col_end())));
+        let location = if invocation_tag == file_tag {
+            location(invocation_tag, invocation.line(), cols, file, tab_size)
+        } else {
+            location(invocation_tag, invocation.line(), cols, "", tab_size)
+        };
+
+        if let Some(location) = location {
+            expansions.push(Expansion {
+                name: invocation.name().to_string(),
+                location,
+            });
+        }
+
+        expansion = invocation.parent();
+    }
+
+    expansions
+}
+
+/// Converts a column counted with tabs expanded into one counted in characters.
+fn unexpanded_col(line: &str, col: u32, tab_size: u32) -> u32 {
+    let target = col.saturating_sub(1);
+    let tab_size = tab_size.max(1);
+    let mut expanded = 0;
+
+    for (chars, c) in (1..).zip(line.chars()) {
+        if expanded >= target {
+            return chars;
+        }
+
+        expanded += match c {
+            '\t' => tab_size - expanded % tab_size,
+            _ => 1,
+        };
+    }
+
+    // past the end of the line
+    line.chars().count() as u32 + 1 + (target - expanded.min(target))
+}
+
+fn plain(text: &str) -> String {
+    let stripped = strip_ansi_escapes::strip(text).expect("stripping colours can't fail");
+
+    String::from_utf8_lossy(&stripped).trim_end().to_string()
+}
@@ -0,0 +1,124 @@
+//! # Reading machine code that mipsy didn't assemble itself.
+//!
+//! Words can be given in hex (one or more to a line, as printed by
+//! `mipsy --hex`, or in a Logisim image), or as the raw bytes of a memory
+//! image. Either way they're loaded into a [`Binary`] holding nothing but
+//! a text segment, so that they can be shown with [`decompile`](crate::decompile)
+//! (ELF files can be loaded with [`read_elf`](crate::elf::read_elf) instead).
+
+use std::fmt;
+
+use crate::{Binary, MemoryLayout, Safe};
+
+/// Why some machine code couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisasmError {
+    /// Something in a hex file that isn't a 32-bit word.
+    BadWord { line: usize, word: String },
+    /// A raw image that doesn't hold a whole number of words.
+    Unaligned { len: usize },
+}
+
+impl fmt::Display for DisasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisasmError::BadWord { line, word } => {
+                write!(f, "`{word}` (on line {line}) is not a 32-bit hex word")
+            }
+            DisasmError::Unaligned { len } => {
+                write!(f, "{len} bytes isn't a whole number of 32-bit words")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DisasmError {}
+
+/// Reads words written in hex, with or without a leading `0x`,
+/// separated by whitespace or commas. Anything after a `#` is a comment,
+/// a Logisim `v2.0 raw` header is skipped, and `uninitialized`
+/// (as printed by `mipsy --hex`) stands for a word that was never written.
+pub fn parse_hex_words(text: &str) -> Result<Vec<Safe<u32>>, DisasmError> {
+    let mut words = vec![];
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        if line_index == 0 && line == "v2.0 raw" {
+            continue;
+        }
+
+        for word in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if word.is_empty() {
+                continue;
+            }
+
+            words.push(parse_hex_word(word).ok_or_else(|| DisasmError::BadWord {
+                line: line_index + 1,
+                word: word.to_string(),
+            })?);
+        }
+    }
+
+    Ok(words)
+}
+
+fn parse_hex_word(word: &str) -> Option<Safe<u32>> {
+    if word.eq_ignore_ascii_case("uninitialized") || word.eq_ignore_ascii_case("uninitialised") {
+        return Some(Safe::Uninitialised);
+    }
+
+    let digits = word
+        .strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .unwrap_or(word);
+
+    if digits.is_empty() || digits.len() > 8 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(digits, 16).ok().map(Safe::Valid)
+}
+
+/// Reads the words of a raw memory image, such as one written by
+/// `mipsy --emit bin` (or, if `big_endian`, `mipsy --emit bin-be`).
+pub fn words_from_bytes(bytes: &[u8], big_endian: bool) -> Result<Vec<Safe<u32>>, DisasmError> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(DisasmError::Unaligned { len: bytes.len() });
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| {
+            let chunk = [chunk[0], chunk[1], chunk[2], chunk[3]];
+
+            Safe::Valid(if big_endian {
+                u32::from_be_bytes(chunk)
+            } else {
+                u32::from_le_bytes(chunk)
+            })
+        })
+        .collect())
+}
+
+/// Lays the words out as a text segment starting at `text_bot`,
+/// in an otherwise empty binary.
+pub fn binary_from_words(words: &[Safe<u32>], text_bot: u32) -> Binary {
+    let text = words
+        .iter()
+        .flat_map(|word| match *word {
+            Safe::Valid(word) => word.to_le_bytes().map(Safe::Valid),
+            Safe::Uninitialised => [Safe::Uninitialised; 4],
+        })
+        .collect::<Vec<_>>();
+
+    Binary {
+        layout: MemoryLayout {
+            text_bot,
+            text_top: text_bot.wrapping_add(text.len() as u32).wrapping_sub(1),
+            ..MemoryLayout::default()
+        },
+        text,
+        ..Binary::default()
+    }
+}
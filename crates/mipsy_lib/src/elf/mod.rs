@@ -0,0 +1,74 @@
+//! # Reading and writing MIPS32 little-endian ELF files.
+//!
+//! The executables written here can be inspected with
+//! `readelf` and `objdump` from binutils, e.g.
+//!
+//! ```text
+//! $ mipsy --emit elf -o hello.elf hello.s
+//! $ mipsel-linux-gnu-objdump -d -l hello.elf
+//! ```
+//!
+//! As real hardware has delay slots and mipsy doesn't, a `nop` is added after
+//! every branch and jump on the way out, and everything moves down to make room
+//! (see [`write_elf`]), so `objdump` and qemu see the same program that mipsy runs.
+//!
+//! Each non-empty segment (`.text`, `.data`, `.ktext` and `.kdata`) becomes
+//! both a loadable program header and a section of the same name,
+//! labels become symbols in `.symtab`, and the line each instruction
+//! came from is recorded in a minimal `.debug_line`.
+//!
+//! Going the other way, a statically linked executable
+//! (e.g. from a C compiler, or from mipsy) can be loaded back into a [`Binary`](crate::Binary),
+//! which is run like real hardware would, with [delay slots](crate::Binary::delay_slots).
+
+mod read;
+pub use read::{is_elf, read_elf, ElfError};
+
+mod write;
+pub use write::write_elf;
+
+const ELF_HEADER_SIZE: u16 = 52;
+const PROGRAM_HEADER_SIZE: u16 = 32;
+const SECTION_HEADER_SIZE: u16 = 40;
+const SYMBOL_SIZE: u32 = 16;
+
+const ELFCLASS32: u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const EV_CURRENT: u8 = 1;
+
+const ET_EXEC: u16 = 2;
+const EM_MIPS: u16 = 8;
+
+const EF_MIPS_NOREORDER: u32 = 0x0000_0001;
+const EF_MIPS_ABI_O32: u32 = 0x0000_1000;
+const EF_MIPS_ARCH_32: u32 = 0x5000_0000;
+
+const PT_LOAD: u32 = 1;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+
+const SHF_WRITE: u32 = 1;
+const SHF_ALLOC: u32 = 2;
+const SHF_EXECINSTR: u32 = 4;
+const SHF_MERGE: u32 = 0x10;
+const SHF_STRINGS: u32 = 0x20;
+
+const SHN_ABS: u16 = 0xfff1;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+
+/// The start of `.comment` in files that mipsy writes.
+const PRODUCER: &str = "mipsy";
+
+/// Segments are aligned to a page, so that they can be `mmap`ed straight out of the file.
+const PAGE_SIZE: u32 = 0x1000;
@@ -0,0 +1,368 @@
+use std::{collections::HashSet, fmt};
+
+use super::*;
+use crate::{Binary, MemoryLayout, Safe, KDATA_BOT, KTEXT_BOT};
+
+const ET_DYN: u16 = 3;
+const SHN_UNDEF: u16 = 0;
+
+const STT_FUNC: u8 = 2;
+
+/// How much memory the segments that end up in one of
+/// the [`Binary`]'s segments can be spread across.
+const MAX_REGION_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Why an ELF file couldn't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElfError {
+    NotElf,
+    /// The file is an ELF, but not one that mipsy can run.
+    Unsupported {
+        reason: &'static str,
+    },
+    /// A header points past the end of the file.
+    Truncated,
+    NoText,
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::NotElf => write!(f, "not an ELF file"),
+            ElfError::Unsupported { reason } => write!(f, "unsupported ELF file: {reason}"),
+            ElfError::Truncated => write!(f, "the ELF file is truncated"),
+            ElfError::NoText => write!(f, "the ELF file has no executable segment"),
+        }
+    }
+}
+
+impl std::error::Error for ElfError {}
+
+/// Whether some bytes look like the start of an ELF file.
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x7fELF")
+}
+
+/// Loads a statically linked MIPS32 little-endian ELF executable,
+/// such as one built with `mipsel-linux-gnu-gcc -static -nostdlib`
+/// (or written by [`write_elf`](super::write_elf)).
+///
+/// Executable segments are mapped into `.text` and the rest into `.data`,
+/// other than those at or above [`KTEXT_BOT`], which go into the kernel segments.
+/// Bytes between segments are left uninitialised, while `.bss` is zeroed.
+/// Execution starts at the entry point in the header,
+/// and every function and object in `.symtab` becomes a label.
+///
+/// Files are taken to be built for real hardware (which files from
+/// [`write_elf`](super::write_elf) are too), so they run with
+/// [delay slots](Binary::delay_slots).
+///
+/// Programs that make Linux system calls will also want
+/// [`Runtime::enable_linux_syscalls`](crate::Runtime::enable_linux_syscalls).
+pub fn read_elf(bytes: &[u8]) -> Result<Binary, ElfError> {
+    let elf = Elf { bytes };
+
+    if !is_elf(bytes) {
+        return Err(ElfError::NotElf);
+    }
+
+    if elf.u8(4)? != ELFCLASS32 {
+        return Err(ElfError::Unsupported {
+            reason: "only 32-bit executables can be run",
+        });
+    }
+
+    if elf.u8(5)? != ELFDATA2LSB {
+        return Err(ElfError::Unsupported {
+            reason: "only little-endian executables can be run",
+        });
+    }
+
+    if elf.u16(18)? != EM_MIPS {
+        return Err(ElfError::Unsupported {
+            reason: "only MIPS executables can be run",
+        });
+    }
+
+    match elf.u16(16)? {
+        ET_EXEC => {}
+        ET_DYN => {
+            return Err(ElfError::Unsupported {
+                reason:
+                    "position-independent executables can't be run, link with `-static -no-pie`",
+            })
+        }
+        _ => {
+            return Err(ElfError::Unsupported {
+                reason: "only executables can be run, not object files",
+            })
+        }
+    }
+
+    let entry = elf.u32(24)?;
+    let program_headers = elf.u32(28)?;
+    let section_headers = elf.u32(32)?;
+    let program_header_size = elf.u16(42)? as u32;
+    let program_header_count = elf.u16(44)? as u32;
+    let section_header_size = elf.u16(46)? as u32;
+    let section_header_count = elf.u16(48)? as u32;
+
+    let mut text = Region::default();
+    let mut data = Region::default();
+    let mut ktext = Region::default();
+    let mut kdata = Region::default();
+
+    for index in 0..program_header_count {
+        let header = elf.entry(program_headers, index, program_header_size)?;
+
+        if header.u32(0)? != PT_LOAD {
+            continue;
+        }
+
+        let offset = header.u32(4)?;
+        let addr = header.u32(8)?;
+        let file_size = header.u32(16)?;
+        let mem_size = header.u32(20)?;
+        let flags = header.u32(24)?;
+
+        if mem_size == 0 {
+            continue;
+        }
+
+        if addr.checked_add(mem_size).is_none() {
+            return Err(ElfError::Unsupported {
+                reason: "a segment runs past the end of memory",
+            });
+        }
+
+        let contents = elf.slice(offset, file_size.min(mem_size))?;
+
+        let region = match addr {
+            _ if addr >= KDATA_BOT => &mut kdata,
+            _ if addr >= KTEXT_BOT => &mut ktext,
+            _ if flags & PF_X != 0 => &mut text,
+            _ => &mut data,
+        };
+
+        region.segments.push((addr, contents, mem_size));
+    }
+
+    let (text_bot, text) = text.flatten(None)?;
+    if text.is_empty() {
+        return Err(ElfError::NoText);
+    }
+    let text_top = text_bot + text.len() as u32 - 1;
+
+    let (data_bot, data) = match data.flatten(None)? {
+        (_, data) if data.is_empty() => (text_top.wrapping_add(1), data),
+        data => data,
+    };
+    let data_top = data_bot + data.len() as u32;
+
+    let (_, ktext) = ktext.flatten(Some(KTEXT_BOT))?;
+    let (_, kdata) = kdata.flatten(Some(KDATA_BOT))?;
+
+    let mut binary = Binary {
+        text,
+        data,
+        ktext,
+        kdata,
+        entry: Some(entry),
+        ..Default::default()
+    };
+
+    for index in 0..section_header_count {
+        let header = elf.entry(section_headers, index, section_header_size)?;
+
+        if header.u32(4)? == SHT_SYMTAB {
+            read_symbols(
+                &elf,
+                &mut binary,
+                section_headers,
+                section_header_size,
+                &header,
+            )?;
+        }
+    }
+
+    binary.delay_slots = true;
+
+    // gcc points `$gp` 32KiB into the small data, and tells us where with `_gp`
+    let global_ptr = binary
+        .labels
+        .get("_gp")
+        .copied()
+        .unwrap_or(data_bot.wrapping_add(0x8000));
+
+    binary.layout = MemoryLayout {
+        text_bot,
+        text_top,
+        global_bot: data_bot,
+        global_ptr,
+        data_bot,
+        heap_bot: data_top.max(text_top.wrapping_add(1)).div_ceil(PAGE_SIZE) * PAGE_SIZE,
+        ..MemoryLayout::spim()
+    };
+
+    Ok(binary)
+}
+
+fn read_symbols(
+    elf: &Elf<'_>,
+    binary: &mut Binary,
+    section_headers: u32,
+    section_header_size: u32,
+    symtab: &Elf<'_>,
+) -> Result<(), ElfError> {
+    let table = Elf {
+        bytes: elf.slice(symtab.u32(16)?, symtab.u32(20)?)?,
+    };
+    let strtab = elf.entry(section_headers, symtab.u32(24)?, section_header_size)?;
+    let strtab = elf.slice(strtab.u32(16)?, strtab.u32(20)?)?;
+
+    let mut symbols = vec![];
+    let mut seen = HashSet::new();
+
+    // the first symbol is always the null symbol
+    for index in 1..table.bytes.len() as u32 / SYMBOL_SIZE {
+        let symbol = table.entry(0, index, SYMBOL_SIZE)?;
+
+        let name = symbol.u32(0)?;
+        let addr = symbol.u32(4)?;
+        let size = symbol.u32(8)?;
+        let info = symbol.u8(12)?;
+        let section = symbol.u16(14)?;
+
+        let (binding, kind) = (info >> 4, info & 0xf);
+
+        if section == SHN_UNDEF || ![STT_NOTYPE, STT_OBJECT, STT_FUNC].contains(&kind) {
+            continue;
+        }
+
+        let name = string(strtab, name).ok_or(ElfError::Truncated)?;
+        let name = String::from_utf8_lossy(name).to_string();
+
+        if name.is_empty() || !seen.insert(name.clone()) {
+            continue;
+        }
+
+        if binding == STB_GLOBAL {
+            binary.globals.push(name.clone());
+        }
+
+        if size != 0 {
+            binary.label_sizes.insert(name.clone(), size);
+        }
+
+        symbols.push((addr, name));
+    }
+
+    symbols.sort_by_key(|&(addr, _)| addr);
+
+    for (addr, name) in symbols {
+        binary.insert_label(&name, addr);
+    }
+
+    Ok(())
+}
+
+/// The null-terminated string at some offset into a string table.
+fn string(table: &[u8], offset: u32) -> Option<&[u8]> {
+    table
+        .get(offset as usize..)
+        .and_then(|string| string.split(|&byte| byte == 0).next())
+}
+
+/// The loadable segments that end up in one of a [`Binary`]'s segments.
+#[derive(Default)]
+struct Region<'a> {
+    /// The address, contents in the file, and size in memory of each segment.
+    segments: Vec<(u32, &'a [u8], u32)>,
+}
+
+impl Region<'_> {
+    /// Lays the segments out in memory, starting from `base`
+    /// (or the lowest segment), giving back where they start.
+    ///
+    /// Without any segments, there's nothing in memory.
+    fn flatten(self, base: Option<u32>) -> Result<(u32, Vec<Safe<u8>>), ElfError> {
+        let bot = base
+            .or_else(|| self.segments.iter().map(|&(addr, _, _)| addr).min())
+            .unwrap_or(0);
+
+        // segments have already been checked not to run past the end of memory
+        let top = self
+            .segments
+            .iter()
+            .map(|&(addr, _, mem_size)| addr + mem_size)
+            .max()
+            .unwrap_or(bot);
+
+        // every byte in between is stored, so a sparse region would take up a lot of memory
+        if top - bot > MAX_REGION_SIZE {
+            return Err(ElfError::Unsupported {
+                reason: "the segments are spread over more than 16MiB of memory",
+            });
+        }
+
+        let mut bytes = vec![Safe::Uninitialised; (top - bot) as usize];
+
+        for (addr, contents, mem_size) in self.segments {
+            let start = (addr - bot) as usize;
+
+            // anything past the end of the file's contents (i.e. `.bss`) is zeroed
+            for (i, byte) in bytes[start..start + mem_size as usize]
+                .iter_mut()
+                .enumerate()
+            {
+                *byte = Safe::Valid(contents.get(i).copied().unwrap_or(0));
+            }
+        }
+
+        Ok((bot, bytes))
+    }
+}
+
+struct Elf<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Elf<'a> {
+    fn slice(&self, offset: u32, len: u32) -> Result<&'a [u8], ElfError> {
+        let end = (offset as usize)
+            .checked_add(len as usize)
+            .ok_or(ElfError::Truncated)?;
+
+        self.bytes
+            .get(offset as usize..end)
+            .ok_or(ElfError::Truncated)
+    }
+
+    /// An entry in a table (e.g. of program headers) of `entry_size` byte entries.
+    fn entry(&self, table: u32, index: u32, entry_size: u32) -> Result<Elf<'a>, ElfError> {
+        let offset = index
+            .checked_mul(entry_size)
+            .and_then(|offset| offset.checked_add(table))
+            .ok_or(ElfError::Truncated)?;
+
+        Ok(Elf {
+            bytes: self.slice(offset, entry_size)?,
+        })
+    }
+
+    fn u8(&self, offset: u32) -> Result<u8, ElfError> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: u32) -> Result<u16, ElfError> {
+        let bytes = self.slice(offset, 2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, offset: u32) -> Result<u32, ElfError> {
+        let bytes = self.slice(offset, 4)?;
+
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
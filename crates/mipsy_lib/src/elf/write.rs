@@ -0,0 +1,631 @@
+use std::rc::Rc;
+
+use mipsy_parser::is_local_label;
+
+use super::*;
+use crate::{Binary, Safe, KDATA_BOT, KTEXT_BOT};
+
+/// A segment of the binary, which becomes both a program header and a section.
+struct Segment<'a> {
+    name: &'static str,
+    addr: u32,
+    bytes: &'a [Safe<u8>],
+    executable: bool,
+}
+
+impl Segment<'_> {
+    fn end(&self) -> u32 {
+        self.addr + self.bytes.len() as u32
+    }
+
+    fn contains(&self, addr: u32) -> bool {
+        // a label can sit just past the end of its segment
+        (self.addr..=self.end()).contains(&addr)
+    }
+}
+
+#[derive(Default)]
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: u32,
+    addr: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+    info: u32,
+    align: u32,
+    entsize: u32,
+}
+
+/// Writes a compiled program out as a MIPS32 little-endian ELF executable.
+///
+/// Uninitialised bytes are written as zero.
+/// The entry point is the kernel's `__start` if the program was compiled
+/// with the kernel, otherwise it's `main` (or the start of `.text`).
+///
+/// As real hardware has [delay slots](Binary::delay_slots) and mipsy doesn't,
+/// the program is first rewritten to run the same way with them (see [`with_delay_slots`]).
+pub fn write_elf(binary: &Binary) -> Vec<u8> {
+    let rewritten;
+    let binary = if binary.delay_slots {
+        binary
+    } else {
+        rewritten = with_delay_slots(binary);
+        &rewritten
+    };
+
+    let segments: Vec<Segment<'_>> = [
+        (".text", binary.layout.text_bot, &binary.text, true),
+        (".data", binary.layout.data_bot, &binary.data, false),
+        (".ktext", KTEXT_BOT, &binary.ktext, true),
+        (".kdata", KDATA_BOT, &binary.kdata, false),
+    ]
+    .into_iter()
+    .filter(|(_, _, bytes, _)| !bytes.is_empty())
+    .map(|(name, addr, bytes, executable)| Segment {
+        name,
+        addr,
+        bytes,
+        executable,
+    })
+    .collect();
+
+    let entry = if !binary.ktext.is_empty() {
+        KTEXT_BOT
+    } else {
+        binary
+            .labels
+            .get("main")
+            .copied()
+            .unwrap_or(binary.layout.text_bot)
+    };
+
+    let mut shstrtab = StringTable::default();
+    let mut sections = vec![Section::default()];
+
+    let headers_size = ELF_HEADER_SIZE as u32 + PROGRAM_HEADER_SIZE as u32 * segments.len() as u32;
+    let mut file = vec![0; headers_size as usize];
+
+    for segment in &segments {
+        // the file offset has to match the address modulo the page size
+        let page = align_up(file.len() as u32, PAGE_SIZE);
+        let offset = page + segment.addr % PAGE_SIZE;
+        file.resize(offset as usize, 0);
+
+        file.extend(
+            segment
+                .bytes
+                .iter()
+                .map(|byte| byte.as_option().copied().unwrap_or(0)),
+        );
+
+        sections.push(Section {
+            name: shstrtab.add(segment.name),
+            kind: SHT_PROGBITS,
+            flags: if segment.executable {
+                SHF_ALLOC | SHF_EXECINSTR
+            } else {
+                SHF_ALLOC | SHF_WRITE
+            },
+            addr: segment.addr,
+            offset,
+            size: segment.bytes.len() as u32,
+            align: 4,
+            ..Default::default()
+        });
+    }
+
+    let (symtab, strtab, first_global) = symbols(binary, &segments);
+    let symtab_index = sections.len() as u32;
+
+    sections.push(Section {
+        name: shstrtab.add(".symtab"),
+        kind: SHT_SYMTAB,
+        offset: append(&mut file, &symtab, 4),
+        size: symtab.len() as u32,
+        link: symtab_index + 1,
+        info: first_global,
+        align: 4,
+        entsize: SYMBOL_SIZE,
+        ..Default::default()
+    });
+
+    sections.push(Section {
+        name: shstrtab.add(".strtab"),
+        kind: SHT_STRTAB,
+        offset: append(&mut file, &strtab.bytes, 1),
+        size: strtab.bytes.len() as u32,
+        align: 1,
+        ..Default::default()
+    });
+
+    let debug_line = debug_line(binary, &segments);
+
+    sections.push(Section {
+        name: shstrtab.add(".debug_line"),
+        kind: SHT_PROGBITS,
+        offset: append(&mut file, &debug_line, 1),
+        size: debug_line.len() as u32,
+        align: 1,
+        ..Default::default()
+    });
+
+    let comment = format!("{PRODUCER} {}\0", env!("CARGO_PKG_VERSION"));
+
+    sections.push(Section {
+        name: shstrtab.add(".comment"),
+        kind: SHT_PROGBITS,
+        flags: SHF_MERGE | SHF_STRINGS,
+        offset: append(&mut file, comment.as_bytes(), 1),
+        size: comment.len() as u32,
+        align: 1,
+        entsize: 1,
+        ..Default::default()
+    });
+
+    let shstrtab_index = sections.len() as u16;
+    let shstrtab_name = shstrtab.add(".shstrtab");
+
+    sections.push(Section {
+        name: shstrtab_name,
+        kind: SHT_STRTAB,
+        offset: append(&mut file, &shstrtab.bytes, 1),
+        size: shstrtab.bytes.len() as u32,
+        align: 1,
+        ..Default::default()
+    });
+
+    let section_headers_offset = align_up(file.len() as u32, 4);
+    file.resize(section_headers_offset as usize, 0);
+
+    for section in &sections {
+        for field in [
+            section.name,
+            section.kind,
+            section.flags,
+            section.addr,
+            section.offset,
+            section.size,
+            section.link,
+            section.info,
+            section.align,
+            section.entsize,
+        ] {
+            file.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    let mut headers = Vec::with_capacity(headers_size as usize);
+
+    headers.extend_from_slice(b"\x7fELF");
+    headers.extend_from_slice(&[ELFCLASS32, ELFDATA2LSB, EV_CURRENT]);
+    headers.resize(16, 0);
+    headers.extend_from_slice(&ET_EXEC.to_le_bytes());
+    headers.extend_from_slice(&EM_MIPS.to_le_bytes());
+    headers.extend_from_slice(&(EV_CURRENT as u32).to_le_bytes());
+    headers.extend_from_slice(&entry.to_le_bytes());
+    headers.extend_from_slice(&(ELF_HEADER_SIZE as u32).to_le_bytes());
+    headers.extend_from_slice(&section_headers_offset.to_le_bytes());
+    headers
+        .extend_from_slice(&(EF_MIPS_NOREORDER | EF_MIPS_ABI_O32 | EF_MIPS_ARCH_32).to_le_bytes());
+    headers.extend_from_slice(&ELF_HEADER_SIZE.to_le_bytes());
+    headers.extend_from_slice(&PROGRAM_HEADER_SIZE.to_le_bytes());
+    headers.extend_from_slice(&(segments.len() as u16).to_le_bytes());
+    headers.extend_from_slice(&SECTION_HEADER_SIZE.to_le_bytes());
+    headers.extend_from_slice(&(sections.len() as u16).to_le_bytes());
+    headers.extend_from_slice(&shstrtab_index.to_le_bytes());
+
+    // the segments' sections come straight after the null section
+    for (segment, section) in segments.iter().zip(&sections[1..]) {
+        let flags = if segment.executable {
+            PF_R | PF_X
+        } else {
+            PF_R | PF_W
+        };
+
+        for field in [
+            PT_LOAD,
+            section.offset,
+            segment.addr,
+            segment.addr,
+            section.size,
+            section.size,
+            flags,
+            PAGE_SIZE,
+        ] {
+            headers.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    file[..headers.len()].copy_from_slice(&headers);
+
+    file
+}
+
+const SPECIAL: u32 = 0x00;
+const REGIMM: u32 = 0x01;
+const COP1: u32 = 0x11;
+
+const NOP: u32 = 0;
+
+/// How an instruction changes where execution goes next.
+enum Jump {
+    /// A branch, whose 16-bit offset counts instructions.
+    Branch,
+    /// A `j` or `jal`, with the low bits of the address it goes to.
+    Absolute,
+    /// A `jr` or `jalr`, to the address in a register.
+    Register,
+}
+
+fn jump(inst: u32) -> Option<Jump> {
+    let opcode = inst >> 26;
+    let rs = (inst >> 21) & 0x1F;
+    let rt = (inst >> 16) & 0x1F;
+    let funct = inst & 0x3F;
+
+    match opcode {
+        // JR, JALR
+        SPECIAL if funct == 0x08 || funct == 0x09 => Some(Jump::Register),
+        // BLTZ, BGEZ, BLTZAL, BGEZAL (and their "likely" forms)
+        REGIMM if matches!(rt, 0x00..=0x03 | 0x10..=0x13) => Some(Jump::Branch),
+        // J, JAL
+        0x02 | 0x03 => Some(Jump::Absolute),
+        // BEQ, BNE, BLEZ, BGTZ (and their "likely" forms)
+        0x04..=0x07 | 0x14..=0x17 => Some(Jump::Branch),
+        // BC1F, BC1T (and their "likely" forms)
+        COP1 if rs == 0x08 => Some(Jump::Branch),
+        _ => None,
+    }
+}
+
+/// # Rewrites a program for real hardware, with delay slots.
+///
+/// A `nop` goes after every branch and jump to fill its delay slot, and
+/// everything after it moves down to make room, so that `objdump` and qemu
+/// see the same program mipsy runs. Branch offsets then count from the
+/// delay slot, and the targets of jumps, labels, line numbers, and the
+/// addresses of labels in code loaded by a `lui` and `ori` (i.e. `la`) move along.
+///
+/// Addresses of code that are stored in `.data` (e.g. a jump table) can't be
+/// told apart from any other number, so they're left where they were.
+fn with_delay_slots(binary: &Binary) -> Binary {
+    let segments = [
+        (binary.layout.text_bot, &binary.text),
+        (KTEXT_BOT, &binary.ktext),
+    ];
+
+    // where each instruction moves to, in each executable segment
+    let moved: Vec<(u32, Vec<u32>)> = segments
+        .iter()
+        .map(|&(addr, bytes)| {
+            let mut slots = 0;
+            let mut moved = Vec::with_capacity(bytes.len() / 4 + 1);
+
+            for word in words(bytes) {
+                moved.push(addr + (moved.len() as u32 + slots) * 4);
+                slots += word.and_then(jump).is_some() as u32;
+            }
+
+            // a label can sit just past the end of its segment
+            moved.push(addr + (moved.len() as u32 + slots) * 4);
+
+            (addr, moved)
+        })
+        .collect();
+
+    // where an address in code moves to, if it's in code
+    let moved_to = |addr: u32| {
+        moved.iter().find_map(|(start, moved)| {
+            let offset = addr.checked_sub(*start)?;
+
+            moved
+                .get(offset as usize / 4)
+                .map(|&moved| moved + offset % 4)
+        })
+    };
+    let relocate = |addr: u32| moved_to(addr).unwrap_or(addr);
+
+    let code_labels: Vec<u32> = binary
+        .labels
+        .values()
+        .copied()
+        .filter(|&addr| moved_to(addr).is_some())
+        .collect();
+
+    let rewrite = |start: u32, bytes: &[Safe<u8>]| {
+        let words: Vec<Option<u32>> = words(bytes).collect();
+        let mut rewritten = Vec::with_capacity(bytes.len());
+
+        for (index, &word) in words.iter().enumerate() {
+            let addr = start + index as u32 * 4;
+            let next = words.get(index + 1).copied().flatten();
+
+            let inst = match (word, word.and_then(jump)) {
+                (None, _) => {
+                    rewritten.extend_from_slice(&bytes[index * 4..index * 4 + 4]);
+                    continue;
+                }
+                (Some(inst), Some(Jump::Branch)) => {
+                    let target = binary.branch_target(addr, inst as i16);
+                    let offset = (relocate(target) as i32 - relocate(addr) as i32 - 4) / 4;
+
+                    (inst & 0xFFFF_0000) | (offset as u32 & 0xFFFF)
+                }
+                (Some(inst), Some(Jump::Absolute)) => {
+                    let target = (addr & 0xF000_0000) | ((inst & 0x03FF_FFFF) << 2);
+
+                    (inst & 0xFC00_0000) | ((relocate(target) >> 2) & 0x03FF_FFFF)
+                }
+                (Some(inst), _) => inst,
+            };
+
+            // `lui` then `ori` into the same register, loading the address of some code
+            let address = |lui: u32, ori: u32| {
+                let loads = lui >> 26 == 0x0F && ori >> 26 == 0x0D;
+                let same_reg = (lui >> 16) & 0x1F == (ori >> 21) & 0x1F;
+                let addr = (lui << 16) | (ori & 0xFFFF);
+
+                (loads && same_reg && code_labels.contains(&addr)).then(|| relocate(addr))
+            };
+
+            let previous = index.checked_sub(1).and_then(|previous| words[previous]);
+
+            let inst = match (next.and_then(|next| address(inst, next)), previous) {
+                (Some(addr), _) => (inst & 0xFFFF_0000) | (addr >> 16),
+                (None, Some(previous)) => match address(previous, inst) {
+                    Some(addr) => (inst & 0xFFFF_0000) | (addr & 0xFFFF),
+                    None => inst,
+                },
+                (None, None) => inst,
+            };
+
+            rewritten.extend(inst.to_le_bytes().map(Safe::Valid));
+
+            if word.and_then(jump).is_some() {
+                rewritten.extend(NOP.to_le_bytes().map(Safe::Valid));
+            }
+        }
+
+        rewritten
+    };
+
+    Binary {
+        text: rewrite(binary.layout.text_bot, &binary.text),
+        ktext: rewrite(KTEXT_BOT, &binary.ktext),
+        labels: binary
+            .labels
+            .iter()
+            .map(|(label, &addr)| (label.clone(), relocate(addr)))
+            .collect(),
+        line_numbers: binary
+            .line_numbers
+            .iter()
+            .map(|(&addr, line)| (relocate(addr), line.clone()))
+            .collect(),
+        entry: binary.entry.map(relocate),
+        delay_slots: true,
+        ..binary.clone()
+    }
+}
+
+/// The words in some bytes, which are only there if all of their bytes are initialised.
+fn words(bytes: &[Safe<u8>]) -> impl Iterator<Item = Option<u32>> + '_ {
+    bytes.chunks_exact(4).map(|chunk| {
+        let bytes: Option<Vec<u8>> = chunk.iter().map(|byte| byte.as_option().copied()).collect();
+
+        bytes.map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    })
+}
+
+#[derive(Default)]
+struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl StringTable {
+    /// Adds a string to the table, giving back its offset.
+    fn add(&mut self, string: &str) -> u32 {
+        if self.bytes.is_empty() {
+            self.bytes.push(0);
+        }
+
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(string.as_bytes());
+        self.bytes.push(0);
+
+        offset
+    }
+}
+
+/// Builds `.symtab` (and its `.strtab`) out of the program's labels,
+/// also giving back the index of the first global symbol.
+///
+/// Local labels (`1:` and `.Lname:`) are left out, like other assemblers do.
+fn symbols(binary: &Binary, segments: &[Segment<'_>]) -> (Vec<u8>, StringTable, u32) {
+    let mut strtab = StringTable::default();
+
+    let (globals, locals): (Vec<_>, Vec<_>) = binary
+        .labels
+        .iter()
+        .filter(|(label, _)| !is_local_label(label))
+        .partition(|(label, _)| binary.globals.contains(label));
+
+    let mut symtab = vec![0; SYMBOL_SIZE as usize];
+
+    for &(label, &addr) in locals.iter().chain(&globals) {
+        let segment = segments.iter().position(|segment| segment.contains(addr));
+
+        let (section_index, kind) = match segment {
+            Some(index) if segments[index].executable => (index as u16 + 1, STT_NOTYPE),
+            Some(index) => (index as u16 + 1, STT_OBJECT),
+            None => (SHN_ABS, STT_NOTYPE),
+        };
+
+        let binding = if binary.globals.contains(label) {
+            STB_GLOBAL
+        } else {
+            STB_LOCAL
+        };
+
+        let size = binary.label_sizes.get(label).copied().unwrap_or(0);
+
+        symtab.extend_from_slice(&strtab.add(label).to_le_bytes());
+        symtab.extend_from_slice(&addr.to_le_bytes());
+        symtab.extend_from_slice(&size.to_le_bytes());
+        symtab.push(binding << 4 | kind);
+        symtab.push(0);
+        symtab.extend_from_slice(&section_index.to_le_bytes());
+    }
+
+    (symtab, strtab, locals.len() as u32 + 1)
+}
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+/// Builds a DWARF 2 line number program out of the program's line numbers,
+/// with one sequence for each executable segment.
+fn debug_line(binary: &Binary, segments: &[Segment<'_>]) -> Vec<u8> {
+    let mut lines: Vec<(u32, &Rc<str>, u32)> = binary
+        .line_numbers
+        .iter()
+        .filter(|(_, (file_tag, _))| !file_tag.is_empty())
+        .map(|(&addr, (file_tag, line))| (addr, file_tag, *line))
+        .collect();
+    lines.sort_unstable_by_key(|&(addr, _, _)| addr);
+
+    let mut files: Vec<&Rc<str>> = vec![];
+    for &(_, file_tag, _) in &lines {
+        if !files.contains(&file_tag) {
+            files.push(file_tag);
+        }
+    }
+
+    let mut header = vec![
+        1,          // minimum_instruction_length
+        1,          // default_is_stmt
+        -5i8 as u8, // line_base
+        14,         // line_range
+        13,         // opcode_base
+    ];
+    // how many arguments each standard opcode takes
+    header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+
+    // no include directories
+    header.push(0);
+
+    for file in &files {
+        header.extend_from_slice(file.as_bytes());
+        header.push(0);
+        // directory, modification time and length
+        header.extend_from_slice(&[0, 0, 0]);
+    }
+    header.push(0);
+
+    let mut program = vec![];
+
+    for segment in segments.iter().filter(|segment| segment.executable) {
+        let mut lines = lines
+            .iter()
+            .filter(|&&(addr, _, _)| segment.contains(addr))
+            .peekable();
+
+        let Some(&&(first_addr, _, _)) = lines.peek() else {
+            continue;
+        };
+
+        program.extend_from_slice(&[0, 5, DW_LNE_SET_ADDRESS]);
+        program.extend_from_slice(&first_addr.to_le_bytes());
+
+        let (mut addr, mut file, mut line) = (first_addr, 1, 1);
+
+        for &(next_addr, file_tag, next_line) in lines {
+            let next_file = files.iter().position(|&file| file == file_tag).unwrap() as u64 + 1;
+
+            if next_file != file {
+                program.push(DW_LNS_SET_FILE);
+                uleb128(&mut program, next_file);
+                file = next_file;
+            }
+
+            if next_line != line {
+                program.push(DW_LNS_ADVANCE_LINE);
+                sleb128(&mut program, next_line as i64 - line as i64);
+                line = next_line;
+            }
+
+            if next_addr != addr {
+                program.push(DW_LNS_ADVANCE_PC);
+                uleb128(&mut program, (next_addr - addr) as u64);
+                addr = next_addr;
+            }
+
+            program.push(DW_LNS_COPY);
+        }
+
+        program.push(DW_LNS_ADVANCE_PC);
+        uleb128(&mut program, (segment.end() - addr) as u64);
+        program.extend_from_slice(&[0, 1, DW_LNE_END_SEQUENCE]);
+    }
+
+    let mut debug_line = vec![];
+
+    // unit_length, version and header_length
+    let unit_length = 2 + 4 + header.len() + program.len();
+    debug_line.extend_from_slice(&(unit_length as u32).to_le_bytes());
+    debug_line.extend_from_slice(&2u16.to_le_bytes());
+    debug_line.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    debug_line.extend_from_slice(&header);
+    debug_line.extend_from_slice(&program);
+
+    debug_line
+}
+
+fn uleb128(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn sleb128(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+
+        if done {
+            bytes.push(byte);
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Appends some bytes at the given alignment, giving back the offset they were written at.
+fn append(file: &mut Vec<u8>, bytes: &[u8], align: u32) -> u32 {
+    let offset = align_up(file.len() as u32, align);
+    file.resize(offset as usize, 0);
+    file.extend_from_slice(bytes);
+
+    offset
+}
+
+fn align_up(value: u32, align: u32) -> u32 {
+    value.div_ceil(align) * align
+}
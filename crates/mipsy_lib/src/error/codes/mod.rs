@@ -0,0 +1,42 @@
+//! # Error codes.
+//!
+//! Every parser, compiler and runtime error has a code, such as `M0105`,
+//! which is shown alongside it, and which never changes, so that it can be
+//! linked to (e.g. from a course forum). Each code has a long-form
+//! explanation, written in markdown with examples, kept in a file of the
+//! same name next to this one.
+//!
+//! Parser errors are `M00xx`, compiler errors are `M01xx`, and runtime
+//! errors are `M02xx`. A code is never reused for a different error,
+//! even if the error it was given to goes away.
+
+macro_rules! explanations {
+    ($($code:literal),* $(,)?) => {
+        /// Every error code, along with its explanation.
+        pub const EXPLANATIONS: &[(&str, &str)] = &[
+            $(($code, include_str!(concat!($code, ".md"))),)*
+        ];
+    };
+}
+
+#[rustfmt::skip]
+explanations![
+    // parser errors
+    "M0001", "M0002", "M0003", "M0004", "M0005", "M0006", "M0007", "M0008", "M0009", "M0010",
+    "M0011", "M0012", "M0013", "M0014", "M0015", "M0016", "M0017", "M0018", "M0019", "M0020",
+    "M0021", "M0022", "M0023", "M0024", "M0025",
+    // compiler errors
+    "M0101", "M0102", "M0103", "M0104", "M0105", "M0106", "M0107", "M0108", "M0109", "M0110",
+    "M0111", "M0112", "M0113", "M0114", "M0115", "M0116", "M0117", "M0118", "M0119", "M0120",
+    "M0121",
+    // runtime errors
+    "M0201", "M0202", "M0203", "M0204", "M0205", "M0206", "M0207", "M0208", "M0209",
+];
+
+/// The explanation of an error code (ignoring case, so `m0105` works too).
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code.trim()))
+        .map(|&(_, explanation)| explanation)
+}
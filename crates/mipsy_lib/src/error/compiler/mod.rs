@@ -1,12 +1,18 @@
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, path::MAIN_SEPARATOR, rc::Rc};
 
-use crate::{inst::instruction::Signature, DATA_BOT, HEAP_BOT};
+use crate::{
+    compile::attributes::{ATTRIBUTES, LINTS, LINT_GROUPS},
+    inst::instruction::Signature,
+};
 use colored::Colorize;
-use mipsy_parser::{MpDirective, MpInstruction};
+use mipsy_parser::{file_tab_size, MpDirective, MpInstruction, MpMacroExpansion};
 use mipsy_utils::MipsyConfig;
 
-use super::util::{syntax_highlight_argument, tip_header};
+use super::util::{expansion_notes, syntax_highlight_argument, tip_header};
+
+mod warning;
+pub use warning::{CompilerWarning, Warning};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct CompilerError {
@@ -15,6 +21,7 @@ pub struct CompilerError {
     line: u32,
     col: u32,
     col_end: u32,
+    expansion: Option<Rc<MpMacroExpansion>>,
 }
 
 impl CompilerError {
@@ -25,9 +32,16 @@ impl CompilerError {
             line,
             col,
             col_end,
+            expansion: None,
         }
     }
 
+    /// Notes the macro invocation that the erroring code was expanded from.
+    pub fn with_expansion(mut self, expansion: Option<Rc<MpMacroExpansion>>) -> Self {
+        self.expansion = expansion;
+        self
+    }
+
     pub fn error(&self) -> &Error {
         &self.error
     }
@@ -48,21 +62,32 @@ impl CompilerError {
         self.col_end
     }
 
+    pub fn expansion(&self) -> Option<&MpMacroExpansion> {
+        self.expansion.as_deref()
+    }
+
     pub fn show_error(&self, config: &MipsyConfig, file: Rc<str>) {
+        let tab_size = file_tab_size(&file, config.tab_size);
+
         if self.error().should_highlight_line() {
-            self.highlight_line(config, file);
+            self.highlight_line(tab_size, file.clone());
         }
 
         println!("{}", self.error.message());
 
+        if self.error().should_highlight_line() {
+            print!(
+                "{}",
+                expansion_notes(self.expansion(), &self.file_tag, &file, tab_size)
+            );
+        }
+
         for tip in self.error.tips() {
             print!("{} {}", tip_header(), tip);
         }
     }
 
-    // TODO(zkol): Can't just pull tab_size from the config, since
-    // file may have #![tabsize(...)]
-    fn highlight_line(&self, config: &MipsyConfig, file: Rc<str>) {
+    fn highlight_line(&self, tab_size: u32, file: Rc<str>) {
         let line = file
             .lines()
             .nth((self.line - 1) as usize)
@@ -77,8 +102,7 @@ impl CompilerError {
                     continue;
                 }
 
-                let spaces_to_insert =
-                    config.tab_size - (updated_line.len() as u32 % config.tab_size);
+                let spaces_to_insert = tab_size - (updated_line.len() as u32 % tab_size);
                 updated_line.push_str(&" ".repeat(spaces_to_insert as usize));
             }
 
@@ -173,6 +197,9 @@ pub enum Error {
         label: String,
         similar: Vec<String>,
     },
+    UnresolvedNumericLabel {
+        label: String,
+    },
 
     RedefinedConstant {
         label: String,
@@ -188,6 +215,12 @@ pub enum Error {
         range_high: i64,
     },
 
+    ImmediateValueDoesNotFit {
+        value: i64,
+        range_low: i64,
+        range_high: i64,
+    },
+
     DataInTextSegment {
         directive_type: MpDirective,
     },
@@ -195,6 +228,35 @@ pub enum Error {
 
     TooMuchData {
         data_size: u32,
+        max_size: u32,
+    },
+
+    TooMuchText {
+        text_size: u32,
+        max_size: u32,
+    },
+
+    UnmatchedConditional {
+        directive: String,
+    },
+    UnterminatedConditional {
+        directive: String,
+    },
+
+    UnknownAttribute {
+        attribute: String,
+    },
+    FileOnlyAttribute {
+        attribute: String,
+    },
+    MissingLint {
+        attribute: String,
+    },
+    UnknownLint {
+        lint: String,
+    },
+    DeniedLint {
+        warning: Warning,
     },
 }
 
@@ -208,6 +270,35 @@ pub enum DirectiveType {
 }
 
 impl Error {
+    /// The code this error can be looked up by, with [`codes::explain`](super::codes::explain).
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NumberedRegisterOutOfRange { .. }
+            | Error::NamedRegisterOutOfRange { .. }
+            | Error::UnknownRegister { .. } => "M0101",
+            Error::UnknownInstruction { .. } | Error::InstructionSimName { .. } => "M0102",
+            Error::InstructionBadFormat { .. } => "M0103",
+            Error::RedefinedLabel { .. } => "M0104",
+            Error::UnresolvedLabel { .. } => "M0105",
+            Error::UnresolvedNumericLabel { .. } => "M0106",
+            Error::RedefinedConstant { .. } => "M0107",
+            Error::UnresolvedConstant { .. } => "M0108",
+            Error::ConstantValueDoesNotFit { .. } => "M0109",
+            Error::ImmediateValueDoesNotFit { .. } => "M0110",
+            Error::DataInTextSegment { .. } => "M0111",
+            Error::InstructionInDataSegment => "M0112",
+            Error::TooMuchData { .. } => "M0113",
+            Error::TooMuchText { .. } => "M0114",
+            Error::UnmatchedConditional { .. } => "M0115",
+            Error::UnterminatedConditional { .. } => "M0116",
+            Error::UnknownAttribute { .. } => "M0117",
+            Error::FileOnlyAttribute { .. } => "M0118",
+            Error::MissingLint { .. } => "M0119",
+            Error::UnknownLint { .. } => "M0120",
+            Error::DeniedLint { .. } => "M0121",
+        }
+    }
+
     pub fn message(&self) -> String {
         match self {
             Error::NumberedRegisterOutOfRange { reg_num } => {
@@ -270,6 +361,26 @@ impl Error {
                 format!("{} `{}` {}", message_1, label, message_2)
             }
 
+            Error::UnresolvedNumericLabel { label } => {
+                let (number, direction) = label.split_at(label.len() - 1);
+                let message_1 = "cannot find a".bright_red().bold();
+                let message_2 = if direction == "f" {
+                    "label after this"
+                } else {
+                    "label before this"
+                }
+                .bright_red()
+                .bold();
+
+                format!(
+                    "{} `{}{}` {}",
+                    message_1,
+                    number.bold(),
+                    ":".bold(),
+                    message_2
+                )
+            }
+
             Error::RedefinedConstant { label } => {
                 let message_1 = "the constant".bright_red().bold();
                 let message_2 = "is defined multiple times".bright_red().bold();
@@ -304,6 +415,23 @@ impl Error {
                 )
             }
 
+            Error::ImmediateValueDoesNotFit {
+                value,
+                range_low,
+                range_high,
+            } => {
+                let message_1 = "immediate value".bright_red().bold();
+                let message_2 = "must be between".bright_red().bold();
+                let message_3 = "and".bright_red().bold();
+                let low = range_low.to_string().bold();
+                let high = range_high.to_string().bold();
+
+                format!(
+                    "{} `{}` {} {} {} {}",
+                    message_1, value, message_2, low, message_3, high
+                )
+            }
+
             Error::DataInTextSegment { directive_type } => {
                 let message_1 = "cannot put".bright_red().bold();
                 let message_2 = directive_type.to_string().bold();
@@ -327,6 +455,69 @@ impl Error {
 
                 format!("{} `{}` {}", message_1, message_2, message_3)
             }
+
+            Error::TooMuchText { .. } => {
+                let message_1 = "too many instructions to fit in the".bright_red().bold();
+                let message_2 = ".text".bold();
+                let message_3 = "segment".bright_red().bold();
+
+                format!("{} `{}` {}", message_1, message_2, message_3)
+            }
+
+            Error::UnmatchedConditional { directive } => {
+                let message_1 = "the directive".bright_red().bold();
+                let message_2 = "has no matching".bright_red().bold();
+
+                format!(
+                    "{} `{}{}` {} `{}`",
+                    message_1,
+                    ".".bold(),
+                    directive.bold(),
+                    message_2,
+                    ".if".bold()
+                )
+            }
+
+            Error::UnterminatedConditional { directive } => {
+                let message_1 = "the directive".bright_red().bold();
+                let message_2 = "is never closed".bright_red().bold();
+
+                format!(
+                    "{} `{}{}` {}",
+                    message_1,
+                    ".".bold(),
+                    directive.bold(),
+                    message_2
+                )
+            }
+
+            Error::UnknownAttribute { attribute } => {
+                let message = "unknown attribute".bright_red().bold();
+
+                format!("{} `{}`", message, attribute.bold())
+            }
+
+            Error::FileOnlyAttribute { attribute } => {
+                let message_1 = "the attribute".bright_red().bold();
+                let message_2 = "can only be used at the top of a file".bright_red().bold();
+
+                format!("{} `{}` {}", message_1, attribute.bold(), message_2)
+            }
+
+            Error::MissingLint { attribute } => {
+                let message_1 = "the attribute".bright_red().bold();
+                let message_2 = "needs at least one lint".bright_red().bold();
+
+                format!("{} `{}` {}", message_1, attribute.bold(), message_2)
+            }
+
+            Error::UnknownLint { lint } => {
+                let message = "unknown lint".bright_red().bold();
+
+                format!("{} `{}`", message, lint.bold())
+            }
+
+            Error::DeniedLint { warning } => warning.message(),
         }
     }
 
@@ -485,6 +676,19 @@ impl Error {
                 }
             }
 
+            Error::UnresolvedNumericLabel { label } => {
+                let number = &label[..label.len() - 1];
+
+                let tip = format!(
+                    "`{}` refers to the next `{}` label, and `{}` to the previous one\n",
+                    format!("{}f", number).bold(),
+                    format!("{}:", number).bold(),
+                    format!("{}b", number).bold(),
+                );
+
+                vec![tip]
+            }
+
             Error::RedefinedConstant { .. } => {
                 // good luck kiddo
                 vec![]
@@ -507,6 +711,12 @@ impl Error {
                 vec![tip]
             }
 
+            Error::ImmediateValueDoesNotFit { .. } => {
+                let tip = "instructions can use at most 32 bits of immediate\n";
+
+                vec![tip.to_string()]
+            }
+
             Error::DataInTextSegment { directive_type } => {
                 let data = ".data".bold();
                 let tip = format!(
@@ -529,20 +739,113 @@ impl Error {
                 vec![tip]
             }
 
-            Error::TooMuchData { data_size } => {
+            Error::TooMuchData {
+                data_size,
+                max_size,
+            } => {
                 let tip1 = format!(
                     "you have {} bytes of data, but the max is {} bytes\n",
                     data_size.to_string().bold(),
-                    (HEAP_BOT - DATA_BOT).to_string().bold(),
+                    max_size.to_string().bold(),
                 );
 
                 let tip2 = format!(
                     "reduce the amount of data by at least {} byte(s)\n",
-                    (data_size - (HEAP_BOT - DATA_BOT)).to_string().bold(),
+                    (data_size - max_size).to_string().bold(),
+                );
+
+                vec![tip1, tip2]
+            }
+
+            Error::TooMuchText {
+                text_size,
+                max_size,
+            } => {
+                let tip1 = format!(
+                    "you have {} bytes of instructions, but the max is {} bytes\n",
+                    text_size.to_string().bold(),
+                    max_size.to_string().bold(),
+                );
+
+                let tip2 = format!(
+                    "try a memory layout with a bigger text segment, such as `{}`\n",
+                    "--layout spim".bold(),
                 );
 
                 vec![tip1, tip2]
             }
+
+            Error::UnmatchedConditional { .. } => {
+                let tip = format!(
+                    "each `{}` or `{}` can have at most one `{}`, and must end with an `{}`\n",
+                    ".if".bold(),
+                    ".ifdef".bold(),
+                    ".else".bold(),
+                    ".endif".bold(),
+                );
+
+                vec![tip]
+            }
+
+            Error::UnterminatedConditional { .. } => {
+                let tip = format!(
+                    "add an `{}` where the conditional section should end\n",
+                    ".endif".bold(),
+                );
+
+                vec![tip]
+            }
+
+            Error::UnknownAttribute { .. } => {
+                let names = ATTRIBUTES
+                    .iter()
+                    .map(|info| format!("`{}`", info.name.bold()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                vec![format!("the supported attributes are {}\n", names)]
+            }
+
+            Error::FileOnlyAttribute { attribute } => {
+                let tip = format!(
+                    "move it to the top of the file, as `{}`\n",
+                    format!("#![{}(...)]", attribute).bold(),
+                );
+
+                vec![tip]
+            }
+
+            Error::MissingLint { attribute } => {
+                let tip = format!(
+                    "list the lints it applies to, e.g. `{}`\n",
+                    format!("#[{}(unused_label)]", attribute).bold(),
+                );
+
+                vec![tip]
+            }
+
+            Error::UnknownLint { .. } => {
+                let names = LINTS
+                    .iter()
+                    .map(|info| info.name)
+                    .chain(LINT_GROUPS.iter().map(|&(group, _)| group))
+                    .map(|name| format!("`{}`", name.bold()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                vec![format!("the supported lints are {}\n", names)]
+            }
+
+            Error::DeniedLint { warning } => {
+                let mut tips = warning.tips();
+
+                tips.push(format!(
+                    "this is an error because the `{}` lint is denied by an attribute\n",
+                    warning.lint().bold(),
+                ));
+
+                tips
+            }
         }
     }
 
@@ -555,7 +858,7 @@ impl Error {
             // limit, but then it would make it harder to get the total bytes that
             // we currently are trying to store in .data, which seems like a more
             // useful diagnostic at this point
-            Self::TooMuchData { .. } => false,
+            Self::TooMuchData { .. } | Self::TooMuchText { .. } => false,
 
             // otherwise highlight the line causing the error
             _ => true,
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::{path::MAIN_SEPARATOR, rc::Rc};
+
+use colored::Colorize;
+use mipsy_parser::{file_tab_size, tabs_to_spaces, MpMacroExpansion};
+use mipsy_utils::MipsyConfig;
+
+use super::{CompilerError, Error};
+use crate::error::util::{expansion_notes, tip_header};
+
+/// # Something suspicious about a program that still compiles.
+///
+/// Warnings are collected on the compiled [`Binary`](crate::Binary)
+/// rather than stopping compilation.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct CompilerWarning {
+    warning: Warning,
+    file_tag: Rc<str>,
+    line: u32,
+    col: u32,
+    col_end: u32,
+    expansion: Option<Rc<MpMacroExpansion>>,
+}
+
+impl CompilerWarning {
+    pub fn new(warning: Warning, file_tag: Rc<str>, line: u32, col: u32, col_end: u32) -> Self {
+        Self {
+            warning,
+            file_tag,
+            line,
+            col,
+            col_end,
+            expansion: None,
+        }
+    }
+
+    /// Notes the macro invocation that the offending code was expanded from.
+    pub fn with_expansion(mut self, expansion: Option<Rc<MpMacroExpansion>>) -> Self {
+        self.expansion = expansion;
+        self
+    }
+
+    pub fn warning(&self) -> &Warning {
+        &self.warning
+    }
+
+    pub fn file_tag(&self) -> Rc<str> {
+        self.file_tag.clone()
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    pub fn col_end(&self) -> u32 {
+        self.col_end
+    }
+
+    pub fn expansion(&self) -> Option<&MpMacroExpansion> {
+        self.expansion.as_deref()
+    }
+
+    /// Turns this warning into an error, for when its lint is denied.
+    pub fn into_error(self) -> CompilerError {
+        CompilerError::new(
+            Error::DeniedLint {
+                warning: self.warning,
+            },
+            self.file_tag,
+            self.line,
+            self.col,
+            self.col_end,
+        )
+        .with_expansion(self.expansion)
+    }
+
+    pub fn show_warning(&self, config: &MipsyConfig, file: Rc<str>) {
+        let tab_size = file_tab_size(&file, config.tab_size);
+
+        println!(
+            "{}{} {}",
+            "warning".yellow().bold(),
+            ":".bold(),
+            self.warning.message()
+        );
+
+        self.highlight_line(tab_size, file.clone());
+
+        print!(
+            "{}",
+            expansion_notes(self.expansion(), &self.file_tag, &file, tab_size)
+        );
+
+        for tip in self.warning.tips() {
+            print!("{} {}", tip_header(), tip);
+        }
+    }
+
+    fn highlight_line(&self, tab_size: u32, file: Rc<str>) {
+        let line = match file.lines().nth((self.line - 1) as usize) {
+            Some(line) => tabs_to_spaces(line, tab_size),
+            None => return,
+        };
+
+        // format of the warning:
+
+        // warning: some useless diagnosis
+        //   --> ./foo.s:1:2
+        //    |
+        // 22 | mips code here
+        //    |      ^^^^
+        //
+
+        let line_num_str = self.line.to_string();
+        let line_num_blank = " ".repeat(line_num_str.len());
+        let arrow = "-->".bright_blue().bold();
+        let bar = "|".bright_blue().bold();
+
+        if !self.file_tag.is_empty() {
+            let dot_slash = if !self.file_tag.contains(MAIN_SEPARATOR) {
+                "./"
+            } else {
+                ""
+            };
+
+            println!(
+                "{}{} {}",
+                line_num_blank,
+                arrow,
+                format!("{}{}:{}:{}", dot_slash, self.file_tag, self.line, self.col).bold()
+            );
+        }
+
+        println!("{} {}", line_num_blank, bar);
+        println!("{} {} {}", line_num_str.bright_blue().bold(), bar, line);
+        println!(
+            "{} {} {}{}",
+            line_num_blank,
+            bar,
+            " ".repeat((self.col - 1) as usize),
+            "^".repeat(self.col_end.saturating_sub(self.col).max(1) as usize)
+                .yellow()
+                .bold()
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Warning {
+    UnusedLabel {
+        label: String,
+    },
+
+    ExpensivePseudoInstruction {
+        inst_name: String,
+        native_count: usize,
+        alternative: String,
+    },
+
+    UnindentedInstruction,
+    IndentedLabel {
+        label: String,
+    },
+
+    NoBlankLineBeforeLabel {
+        label: String,
+    },
+}
+
+impl Warning {
+    /// The name of the lint that produces this warning.
+    pub fn lint(&self) -> &'static str {
+        match self {
+            Warning::UnusedLabel { .. } => "unused_label",
+            Warning::ExpensivePseudoInstruction { .. } => "pseudo_cost",
+            Warning::UnindentedInstruction | Warning::IndentedLabel { .. } => "indentation",
+            Warning::NoBlankLineBeforeLabel { .. } => "blank_line_before_label",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Warning::UnusedLabel { label } => {
+                format!("the label `{}` is never used", label.bold())
+            }
+
+            Warning::ExpensivePseudoInstruction {
+                inst_name,
+                native_count,
+                ..
+            } => {
+                format!(
+                    "this `{}` is a pseudo-instruction that compiles to {} instructions",
+                    inst_name.bold(),
+                    native_count.to_string().bold(),
+                )
+            }
+
+            Warning::UnindentedInstruction => String::from("this instruction is not indented"),
+
+            Warning::IndentedLabel { label } => {
+                format!("the label `{}` is indented", label.bold())
+            }
+
+            Warning::NoBlankLineBeforeLabel { label } => {
+                format!(
+                    "the label `{}` directly follows an instruction",
+                    label.bold()
+                )
+            }
+        }
+    }
+
+    pub fn tips(&self) -> Vec<String> {
+        match self {
+            Warning::UnusedLabel { .. } => {
+                let tip = "if this label is used by another file, mark it with `.globl`\n";
+
+                vec![tip.to_string()]
+            }
+
+            Warning::ExpensivePseudoInstruction { alternative, .. } => {
+                let tip = format!(
+                    "`{}` takes the same arguments, and compiles to a single instruction\n",
+                    alternative.bold(),
+                );
+
+                vec![tip]
+            }
+
+            Warning::UnindentedInstruction => {
+                let tip = "indent instructions, so that labels stand out\n";
+
+                vec![tip.to_string()]
+            }
+
+            Warning::IndentedLabel { .. } => {
+                let tip = "start labels at the beginning of the line, so that they stand out\n";
+
+                vec![tip.to_string()]
+            }
+
+            Warning::NoBlankLineBeforeLabel { .. } => {
+                let tip = "add a blank line before the label, to separate it from the code above\n";
+
+                vec![tip.to_string()]
+            }
+        }
+    }
+}
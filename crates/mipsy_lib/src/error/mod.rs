@@ -1,6 +1,8 @@
+use mipsy_parser::MpMacroExpansion;
 use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 
+pub mod codes;
 pub mod compiler;
 pub mod parser;
 pub mod runtime;
@@ -9,6 +11,7 @@ pub mod util;
 pub type MipsyResult<T> = Result<T, MipsyError>;
 pub type ParserError = parser::ParserError;
 pub type CompilerError = compiler::CompilerError;
+pub type CompilerWarning = compiler::CompilerWarning;
 pub type RuntimeError = runtime::RuntimeError;
 
 pub type MipsyInternalResult<T> = Result<T, InternalError>;
@@ -20,6 +23,31 @@ pub enum MipsyError {
     Runtime(runtime::RuntimeError),
 }
 
+impl MipsyError {
+    /// The code this error can be looked up by, with [`codes::explain`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            MipsyError::Parser(error) => error.error().code(),
+            MipsyError::Compiler(error) => error.error().code(),
+            MipsyError::Runtime(error) => error.error().code(),
+        }
+    }
+
+    /// Notes that this error came from code expanded by a macro invocation,
+    /// unless we already know which invocation it came from.
+    pub fn in_expansion(self, expansion: Option<Rc<MpMacroExpansion>>) -> Self {
+        match self {
+            MipsyError::Parser(error) if error.expansion().is_none() => {
+                MipsyError::Parser(error.with_expansion(expansion))
+            }
+            MipsyError::Compiler(error) if error.expansion().is_none() => {
+                MipsyError::Compiler(error.with_expansion(expansion))
+            }
+            other => other,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum InternalError {
     Parser(parser::Error),
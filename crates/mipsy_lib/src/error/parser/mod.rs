@@ -1,14 +1,18 @@
 use colored::Colorize;
+use mipsy_parser::{file_tab_size, ErrorKind, MpMacroExpansion, SyntaxError};
 use mipsy_utils::MipsyConfig;
 use serde::{Deserialize, Serialize};
 use std::{path::MAIN_SEPARATOR, rc::Rc};
 
+use super::util::{expansion_notes, tip_header};
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ParserError {
     error: Error,
     file_tag: Rc<str>,
     line: u32,
     col: u32,
+    expansion: Option<Rc<MpMacroExpansion>>,
 }
 
 impl ParserError {
@@ -18,9 +22,16 @@ impl ParserError {
             file_tag,
             line,
             col,
+            expansion: None,
         }
     }
 
+    /// Notes the macro invocation that the unparseable code was expanded from.
+    pub fn with_expansion(mut self, expansion: Option<Rc<MpMacroExpansion>>) -> Self {
+        self.expansion = expansion;
+        self
+    }
+
     pub fn error(&self) -> &Error {
         &self.error
     }
@@ -37,10 +48,13 @@ impl ParserError {
         self.col
     }
 
-    // TODO(zkol): Can't just pull tab_size from the config, since
-    // file may have #![tabsize(...)]
+    pub fn expansion(&self) -> Option<&MpMacroExpansion> {
+        self.expansion.as_deref()
+    }
+
     pub fn show_error(&self, config: &MipsyConfig, file: Rc<str>) {
-        let message = "failed to parse".bright_red().bold();
+        let tab_size = file_tab_size(&file, config.tab_size);
+        let message = self.error.message().bright_red().bold();
 
         let line = {
             let target_line = (self.line - 1) as usize;
@@ -71,8 +85,7 @@ impl ParserError {
                     continue;
                 }
 
-                let spaces_to_insert =
-                    config.tab_size - (updated_line.len() as u32 % config.tab_size);
+                let spaces_to_insert = tab_size - (updated_line.len() as u32 % tab_size);
                 updated_line.push_str(&" ".repeat(spaces_to_insert as usize));
             }
 
@@ -134,10 +147,385 @@ impl ParserError {
             "{} {} {}{} {}",
             line_num_blank, bar, pre_highlight_space, highlight, message
         );
+        eprint!(
+            "{}",
+            expansion_notes(self.expansion(), &self.file_tag, &file, tab_size)
+        );
+
+        for tip in self.error.tips() {
+            eprint!("{} {}", tip_header(), tip);
+        }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Error {
-    ParseFailure,
+    Syntax(SyntaxError),
+    CannotInclude { path: String, os_error: String },
+    IncludeCycle { path: String },
+    InvalidTabSize { value: Option<String> },
+}
+
+impl Error {
+    /// The code this error can be looked up by, with [`codes::explain`](super::codes::explain).
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Syntax(error) => syntax_code(error),
+            Error::CannotInclude { .. } => "M0021",
+            Error::IncludeCycle { .. } => "M0022",
+            Error::InvalidTabSize { .. } => "M0023",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::Syntax(error) => syntax_message(error),
+            Error::CannotInclude { path, os_error } => {
+                format!("failed to include `{}`: {}", path, os_error)
+            }
+            Error::IncludeCycle { path } => {
+                format!("`{}` includes itself (maybe indirectly)", path)
+            }
+            Error::InvalidTabSize { value: Some(value) } => {
+                format!("`{}` is not a valid tab size", value.trim())
+            }
+            Error::InvalidTabSize { value: None } => {
+                "the `tabsize` attribute needs a tab size, e.g. `#![tabsize(8)]`".to_string()
+            }
+        }
+    }
+
+    pub fn tips(&self) -> Vec<String> {
+        match self {
+            Error::Syntax(error) => syntax_tips(error),
+            Error::CannotInclude { .. } => vec![],
+            Error::IncludeCycle { .. } => vec![],
+            Error::InvalidTabSize { .. } => vec![],
+        }
+    }
+}
+
+fn syntax_code(error: &SyntaxError) -> &'static str {
+    match error {
+        SyntaxError::MissingComma { .. } => "M0001",
+        SyntaxError::MissingArgument { .. } => "M0002",
+        SyntaxError::InvalidArgument { .. } => "M0003",
+        SyntaxError::InvalidNumber { .. } => "M0004",
+        SyntaxError::InvalidChar { .. } => "M0005",
+        SyntaxError::UnterminatedString => "M0006",
+        SyntaxError::UnterminatedChar => "M0007",
+        SyntaxError::UnclosedParen => "M0008",
+        SyntaxError::UnexpectedArgument { .. } => "M0009",
+        SyntaxError::UnexpectedToken { .. } => "M0010",
+        SyntaxError::ExpectedItem { .. } => "M0011",
+        SyntaxError::ExpectedString { .. } => "M0012",
+        SyntaxError::UnknownDirective { .. } => "M0013",
+        SyntaxError::UnmatchedEndMacro => "M0014",
+        SyntaxError::NestedMacro => "M0015",
+        SyntaxError::UnterminatedMacro { .. } => "M0016",
+        SyntaxError::InvalidMacroHeader => "M0017",
+        SyntaxError::RecursiveMacro { .. } => "M0018",
+        SyntaxError::InvalidEqv => "M0019",
+        SyntaxError::InvalidInclude => "M0020",
+        SyntaxError::InvalidSet => "M0024",
+        SyntaxError::ReservedLabel { .. } => "M0025",
+    }
+}
+
+fn syntax_message(error: &SyntaxError) -> String {
+    match error {
+        SyntaxError::MissingComma { found } => {
+            format!("expected a `,` between arguments, found `{}`", found)
+        }
+        SyntaxError::MissingArgument { after } if after == "," => {
+            "expected another argument after `,`, found the end of the line".to_string()
+        }
+        SyntaxError::MissingArgument { after } if after == "=" => {
+            "expected a value after `=`, found the end of the line".to_string()
+        }
+        SyntaxError::MissingArgument { after } => {
+            format!(
+                "expected an argument to `{}`, found the end of the line",
+                after
+            )
+        }
+        SyntaxError::InvalidArgument { found } => {
+            format!("expected an argument, found `{}`", found)
+        }
+        SyntaxError::InvalidNumber { found } => {
+            format!("expected a number, found `{}`", found)
+        }
+        SyntaxError::InvalidChar { found } => {
+            format!(
+                "expected a single character between `'`s, found `{}`",
+                found
+            )
+        }
+        SyntaxError::UnterminatedString => {
+            "expected a `\"` to end this string, found the end of the line".to_string()
+        }
+        SyntaxError::UnterminatedChar => {
+            "expected a `'` to end this character, found the end of the line".to_string()
+        }
+        SyntaxError::UnclosedParen => {
+            "expected a `)` to close this `(`, found the end of the line".to_string()
+        }
+        SyntaxError::UnexpectedArgument { directive, found } => {
+            format!(
+                "expected the end of the line after `{}`, found `{}`",
+                directive, found
+            )
+        }
+        SyntaxError::UnexpectedToken { found } => {
+            format!("expected a `,` or the end of the line, found `{}`", found)
+        }
+        SyntaxError::ExpectedItem { found } => {
+            format!(
+                "expected an instruction, directive or label, found `{}`",
+                found
+            )
+        }
+        SyntaxError::ExpectedString { directive, found } => {
+            format!("expected a string after `{}`, found `{}`", directive, found)
+        }
+        SyntaxError::UnknownDirective { directive, .. } => {
+            format!("unknown directive `{}`", directive)
+        }
+        SyntaxError::UnmatchedEndMacro => {
+            "found `.end_macro` without a `.macro` before it".to_string()
+        }
+        SyntaxError::NestedMacro => "expected `.end_macro`, found another `.macro`".to_string(),
+        SyntaxError::UnterminatedMacro { name } => {
+            format!(
+                "expected `.end_macro` to end the macro `{}`, found the end of the file",
+                name
+            )
+        }
+        SyntaxError::InvalidMacroHeader => {
+            "expected a name and parameters after `.macro`".to_string()
+        }
+        SyntaxError::RecursiveMacro { name } => {
+            format!("the macro `{}` expands into itself", name)
+        }
+        SyntaxError::InvalidEqv => "expected a name and a value after `.eqv`".to_string(),
+        SyntaxError::InvalidInclude => {
+            "expected a file name in double quotes after `.include`".to_string()
+        }
+        SyntaxError::InvalidSet => {
+            "expected a name and a value, or an option, after `.set`".to_string()
+        }
+        SyntaxError::ReservedLabel { label } => {
+            format!("the label `{}` is named like the labels in a macro", label)
+        }
+    }
+}
+
+fn syntax_tips(error: &SyntaxError) -> Vec<String> {
+    match error {
+        SyntaxError::MissingComma { found } => {
+            vec![format!(
+                "arguments are separated by commas, try adding a `{}` before `{}`\n",
+                ",".bold(),
+                found.bold()
+            )]
+        }
+
+        SyntaxError::MissingArgument { after } if after == "," => {
+            vec![format!(
+                "if there aren't any more arguments, try removing the `{}`\n",
+                ",".bold()
+            )]
+        }
+
+        SyntaxError::MissingArgument { after } => match directive_example(after) {
+            Some(example) => vec![format!("for example, `{}`\n", example.bold())],
+            None => vec![],
+        },
+
+        SyntaxError::InvalidArgument { .. } => {
+            vec![format!(
+                "an argument can be a register (`{}`), a number (`{}`, `{}` or `{}`), a label (`{}`), or an address (`{}`)\n",
+                "$t0".bold(),
+                "42".bold(),
+                "0x2a".bold(),
+                "'*'".bold(),
+                "main".bold(),
+                "4($sp)".bold(),
+            )]
+        }
+
+        SyntaxError::InvalidNumber { found } => {
+            let mut tips = vec![format!(
+                "numbers can be written in decimal (`{}`), hexadecimal (`{}`), octal (`{}`) or binary (`{}`)\n",
+                "42".bold(),
+                "0x2a".bold(),
+                "0o52".bold(),
+                "0b101010".bold(),
+            )];
+
+            if found
+                .chars()
+                .any(|c| c.is_ascii_alphabetic() && !c.is_ascii_hexdigit())
+            {
+                tips.push("labels and constants can't start with a digit\n".to_string());
+            }
+
+            tips
+        }
+
+        SyntaxError::InvalidChar { .. } => {
+            vec![
+                format!(
+                    "a character is written `{}`, or as an escape such as `{}`, `{}` or `{}`\n",
+                    "'a'".bold(),
+                    "'\\n'".bold(),
+                    "'\\t'".bold(),
+                    "'\\0'".bold(),
+                ),
+                format!(
+                    "for more than one character, use a string in a directive such as `{}`\n",
+                    ".asciiz \"...\"".bold()
+                ),
+            ]
+        }
+
+        SyntaxError::UnterminatedString => {
+            vec![format!(
+                "strings can't go over more than one line, use `{}` for a newline, and `{}` for a `{}` in the string\n",
+                "\\n".bold(),
+                "\\\"".bold(),
+                "\"".bold(),
+            )]
+        }
+
+        SyntaxError::UnterminatedChar => {
+            vec![format!(
+                "a character is written between single quotes, such as `{}`\n",
+                "'a'".bold()
+            )]
+        }
+
+        SyntaxError::UnclosedParen => {
+            vec![format!(
+                "an address is written `{}`, or just `{}`\n",
+                "offset($register)".bold(),
+                "($register)".bold()
+            )]
+        }
+
+        SyntaxError::UnexpectedArgument { directive, .. }
+            if directive == ".ascii" || directive == ".asciiz" =>
+        {
+            vec![format!(
+                "`{}` takes a single string, use another `{}` on the next line for more\n",
+                directive.bold(),
+                directive.bold()
+            )]
+        }
+
+        SyntaxError::UnexpectedArgument { directive, .. } => {
+            vec![format!(
+                "`{}` doesn't take any arguments\n",
+                directive.bold()
+            )]
+        }
+
+        SyntaxError::UnexpectedToken { .. } => vec![],
+
+        SyntaxError::ExpectedItem { .. } => {
+            vec![format!(
+                "each line can have a label (`{}`), followed by an instruction (`{}`) or a directive (`{}`)\n",
+                "main:".bold(),
+                "li $v0, 1".bold(),
+                ".word 42".bold(),
+            )]
+        }
+
+        SyntaxError::ExpectedString { directive, .. } => {
+            vec![format!(
+                "strings go in double quotes, e.g. `{}`\n",
+                format!("{} \"hello\"", directive).bold()
+            )]
+        }
+
+        SyntaxError::UnknownDirective { similar, .. } => match similar {
+            Some(similar) => vec![format!("did you mean `{}`?\n", similar.bold())],
+            None => vec![],
+        },
+
+        SyntaxError::UnmatchedEndMacro => vec![],
+
+        SyntaxError::NestedMacro => {
+            vec![format!(
+                "macros can't be defined inside other macros, but they can use them\n"
+            )]
+        }
+
+        SyntaxError::UnterminatedMacro { .. } => vec![],
+
+        SyntaxError::InvalidMacroHeader => {
+            vec![format!(
+                "macros are defined like `{}`, with each parameter starting with a `{}`\n",
+                ".macro print_int(%reg)".bold(),
+                "%".bold()
+            )]
+        }
+
+        SyntaxError::RecursiveMacro { .. } => {
+            vec!["macros can use other macros, but not themselves (even indirectly)\n".to_string()]
+        }
+
+        SyntaxError::InvalidEqv => {
+            vec![format!("for example, `{}`\n", ".eqv PRINT_INT 1".bold())]
+        }
+
+        SyntaxError::InvalidInclude => {
+            vec![format!("for example, `{}`\n", ".include \"lib.s\"".bold())]
+        }
+
+        SyntaxError::InvalidSet => {
+            vec![format!(
+                "for example, `{}`, which is the same as `{}`\n",
+                ".set ANSWER, 42".bold(),
+                "ANSWER = 42".bold()
+            )]
+        }
+
+        SyntaxError::ReservedLabel { .. } => {
+            vec![format!(
+                "`{}` is only used to give the labels in each use of a macro their own name\n",
+                "::".bold()
+            )]
+        }
+    }
+}
+
+/// An example of a directive with its arguments.
+fn directive_example(directive: &str) -> Option<&'static str> {
+    match directive {
+        ".ascii" => Some(".ascii \"hello\""),
+        ".asciiz" => Some(".asciiz \"hello\""),
+        ".byte" => Some(".byte 1, 2, 3"),
+        ".half" => Some(".half 1, 2, 3"),
+        ".word" => Some(".word 1, 2, 3"),
+        ".float" => Some(".float 1.5"),
+        ".double" => Some(".double 1.5"),
+        ".space" => Some(".space 16"),
+        ".align" => Some(".align 2"),
+        ".globl" => Some(".globl main"),
+        ".if" => Some(".if N > 1"),
+        ".ifdef" => Some(".ifdef DEBUG"),
+        _ => None,
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Syntax(error) => Error::Syntax(error),
+            ErrorKind::CannotInclude { path, os_error } => Error::CannotInclude { path, os_error },
+            ErrorKind::IncludeCycle { path } => Error::IncludeCycle { path },
+            ErrorKind::InvalidTabSize { value } => Error::InvalidTabSize { value },
+        }
+    }
 }
@@ -5,7 +5,7 @@ use crate::{
     decompile::{self, decompile_inst_into_parts, Decompiled},
     inst::ReadsRegisterType,
     runtime::state::{WRITE_MARKER_HI, WRITE_MARKER_LO},
-    util::{get_segment, Segment},
+    util::Segment,
     Binary, InstSet, Register, Runtime, Safe, State,
 };
 use colored::Colorize;
@@ -35,7 +35,7 @@ impl RuntimeError {
     ) {
         println!(
             "{}{} {}",
-            "error".bright_red().bold(),
+            format!("error[{}]", self.error.code()).bright_red().bold(),
             ":".bold(),
             self.error
                 .message(context, &source_code, inst_set, binary, runtime)
@@ -66,6 +66,14 @@ pub enum Error {
         addr: u32,
         alignment_requirement: AlignmentRequirement,
     },
+    OutOfBounds {
+        addr: u32,
+        access: SegmentationFaultAccessType,
+        label: String,
+        label_addr: u32,
+        label_size: u32,
+        into: Option<String>,
+    },
 
     IntegerOverflow,
     DivisionByZero,
@@ -74,6 +82,12 @@ pub enum Error {
         addr: u32,
         access: SegmentationFaultAccessType,
     },
+    StackOverflow {
+        addr: u32,
+        access: SegmentationFaultAccessType,
+        depth: u32,
+        stack_size: u32,
+    },
     InvalidSyscall {
         syscall: i32,
         reason: InvalidSyscallReason,
@@ -110,6 +124,105 @@ pub enum SegmentationFaultAccessType {
 }
 
 impl Error {
+    /// The code this error can be looked up by, with [`codes::explain`](super::codes::explain).
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::UnknownInstruction { .. } => "M0201",
+            Error::Uninitialised { .. } => "M0202",
+            Error::UnalignedAccess { .. } => "M0203",
+            Error::OutOfBounds { .. } => "M0204",
+            Error::IntegerOverflow => "M0205",
+            Error::DivisionByZero => "M0206",
+            Error::SegmentationFault { .. } => "M0207",
+            Error::StackOverflow { .. } => "M0208",
+            Error::InvalidSyscall { .. } => "M0209",
+        }
+    }
+
+    /// What went wrong in a single uncoloured line, without the context [`message`](Self::message) explains it with.
+    pub fn summary(&self) -> String {
+        let access = |access: &SegmentationFaultAccessType| match access {
+            SegmentationFaultAccessType::Read => "read from",
+            SegmentationFaultAccessType::Write => "write to",
+            SegmentationFaultAccessType::Execute => "execute",
+        };
+
+        match self {
+            Error::UnknownInstruction { addr } => {
+                format!("could not find instruction at 0x{:08x}", addr)
+            }
+            Error::Uninitialised { value } => match value {
+                Uninitialised::Byte { addr } => format!("byte at 0x{:08x} is uninitialised", addr),
+                Uninitialised::Half { addr } => format!("half at 0x{:08x} is uninitialised", addr),
+                Uninitialised::Word { addr } => format!("word at 0x{:08x} is uninitialised", addr),
+                Uninitialised::Register { reg_num } => format!(
+                    "your program tried to read uninitialised register ${}",
+                    Register::from_u32(*reg_num).unwrap().to_lower_str()
+                ),
+                Uninitialised::Lo => {
+                    "your program tried to read uninitialised register $lo".to_string()
+                }
+                Uninitialised::Hi => {
+                    "your program tried to read uninitialised register $hi".to_string()
+                }
+            },
+            Error::UnalignedAccess {
+                addr,
+                alignment_requirement,
+            } => format!(
+                "unaligned access: 0x{:08x} is not divisible by {}",
+                addr,
+                match alignment_requirement {
+                    AlignmentRequirement::Half => 2,
+                    AlignmentRequirement::Word => 4,
+                }
+            ),
+            Error::OutOfBounds {
+                addr,
+                label,
+                label_addr,
+                label_size,
+                ..
+            } => format!(
+                "out of bounds access: 0x{:08x} {} `{}` ({} bytes)",
+                addr,
+                if addr < label_addr {
+                    "underruns"
+                } else {
+                    "overruns"
+                },
+                label,
+                label_size
+            ),
+            Error::IntegerOverflow => "integer overflow".to_string(),
+            Error::DivisionByZero => "division by zero".to_string(),
+            Error::SegmentationFault { addr, access: kind } => format!(
+                "segmentation fault: tried to {} 0x{:08x}",
+                access(kind),
+                addr
+            ),
+            Error::StackOverflow {
+                addr,
+                access: kind,
+                depth,
+                ..
+            } => format!(
+                "stack overflow at depth {}: tried to {} 0x{:08x}",
+                depth,
+                access(kind),
+                addr
+            ),
+            Error::InvalidSyscall { syscall, reason } => match reason {
+                InvalidSyscallReason::Unimplemented => {
+                    format!("invalid syscall: {} is not implemented", syscall)
+                }
+                InvalidSyscallReason::Unknown => {
+                    format!("invalid syscall: {} is not valid", syscall)
+                }
+            },
+        }
+    }
+
     pub fn message(
         &self,
         context: ErrorContext,
@@ -279,9 +392,9 @@ impl Error {
                         name.bold()
                     ));
 
-                    let last_inst = last_mod.read_mem_word(last_mod.pc() - 4).unwrap();
+                    let last_inst = last_mod.read_mem_word(last_mod.last_pc()).unwrap();
                     let last_inst_parts =
-                        decompile_inst_into_parts(binary, inst_set, last_inst, last_mod.pc() - 4);
+                        decompile_inst_into_parts(binary, inst_set, last_inst, last_mod.last_pc());
 
                     error.push_str(&format!(
                         "{} {}\n",
@@ -365,7 +478,7 @@ impl Error {
                             last_mod,
                             binary,
                             inst_set,
-                            last_mod.pc() - 4,
+                            last_mod.last_pc(),
                         ) {
                             let (file_tag, line_num) = real_inst_parts.location.unwrap();
                             let mut file = None;
@@ -408,7 +521,7 @@ impl Error {
                                     {
                                         let inst = last_mod.read_mem_word(addr).unwrap();
 
-                                        let failed = addr == last_mod.pc() - 4;
+                                        let failed = addr == last_mod.last_pc();
 
                                         error.push_str(&format!(
                                             "  {} {}{}\n",
@@ -609,6 +722,116 @@ impl Error {
                 error
             }
 
+            Error::OutOfBounds {
+                addr,
+                access,
+                label,
+                label_addr,
+                label_size,
+                into,
+            } => {
+                let mut error = String::new();
+
+                error.push_str("out of bounds access\n");
+
+                let state = runtime.timeline().state();
+                let inst = state.read_mem_word(state.pc()).unwrap();
+                let decompiled =
+                    decompile::decompile_inst_into_parts(binary, inst_set, inst, state.pc());
+
+                if let ErrorContext::Binary | ErrorContext::Interactive = context {
+                    error.push_str("\nthe instruction that failed was:\n");
+                    error.push_str(&inst_parts_to_string(
+                        &decompiled,
+                        source_code,
+                        binary,
+                        false,
+                        false,
+                    ));
+                    error.push('\n');
+                }
+
+                error.push('\n');
+
+                let offset = *addr as i64 - *label_addr as i64;
+                let target = if offset < 0 {
+                    format!("{}{}", label, offset)
+                } else {
+                    format!("{}+{}", label, offset)
+                };
+
+                error.push_str(&format!(
+                    "this happened because `{}` {} `{}` {} `{}` ({} bytes){}\n",
+                    decompiled.inst_name.as_deref().unwrap_or("?").bold(),
+                    match access {
+                        SegmentationFaultAccessType::Write => "to",
+                        _ => "from",
+                    },
+                    target.bold(),
+                    if offset < 0 { "underruns" } else { "overruns" },
+                    label.yellow().bold(),
+                    label_size.to_string().bold(),
+                    match into {
+                        Some(into) => format!(" into `{}`", into.yellow().bold()),
+                        None => String::new(),
+                    },
+                ));
+
+                error
+            }
+
+            Error::StackOverflow {
+                addr,
+                access,
+                depth,
+                stack_size,
+            } => {
+                let mut error = String::new();
+
+                error.push_str(&format!("stack overflow at depth {}\n", depth));
+
+                let state = runtime.timeline().state();
+                let inst = state.read_mem_word(state.pc()).unwrap();
+                let decompiled =
+                    decompile::decompile_inst_into_parts(binary, inst_set, inst, state.pc());
+
+                if let ErrorContext::Binary | ErrorContext::Interactive = context {
+                    error.push_str("\nthe instruction that failed was:\n");
+                    error.push_str(&inst_parts_to_string(
+                        &decompiled,
+                        source_code,
+                        binary,
+                        false,
+                        false,
+                    ));
+                    error.push('\n');
+                }
+
+                error.push_str(&format!(
+                    "\nthis happened because you tried to {} `{}`, which is past the bottom of the stack\n",
+                    match access {
+                        SegmentationFaultAccessType::Write => "write to",
+                        _ => "read from",
+                    },
+                    format!("0x{:08x}", addr).bold(),
+                ));
+
+                error.push_str(&format!(
+                    "the stack can only grow to {} bytes, but {} is `{}`\n",
+                    stack_size.to_string().bold(),
+                    "$sp".yellow(),
+                    format!(
+                        "0x{:08x}",
+                        state
+                            .read_register(Register::Sp.to_u32())
+                            .unwrap_or_default()
+                    )
+                    .bold(),
+                ));
+
+                error
+            }
+
             Error::IntegerOverflow => {
                 let mut error = String::new();
                 error.push_str("integer overflow\n");
@@ -856,8 +1079,8 @@ impl Error {
                             }
                         }
                     }
-                } else if get_segment(prev_state.unwrap().pc()) == Segment::Text
-                    || get_segment(prev_state.unwrap().pc()) == Segment::KText
+                } else if binary.layout.segment_of(prev_state.unwrap().pc()) == Segment::Text
+                    || binary.layout.segment_of(prev_state.unwrap().pc()) == Segment::KText
                 {
                     // Current instruction is not in a TEXT segment, so we are probably executing incorrectly
                     // But we had to get here somehow, so the previuos instruction should be valid.
@@ -1105,9 +1328,9 @@ impl Error {
                         syscall.to_string().bold(),
                     ));
 
-                    let last_inst = last_mod.read_mem_word(last_mod.pc() - 4).unwrap();
+                    let last_inst = last_mod.read_mem_word(last_mod.last_pc()).unwrap();
                     let last_inst_parts =
-                        decompile_inst_into_parts(binary, inst_set, last_inst, last_mod.pc() - 4);
+                        decompile_inst_into_parts(binary, inst_set, last_inst, last_mod.last_pc());
 
                     error.push_str(&format!(
                         "{} {}\n",
@@ -1191,7 +1414,7 @@ impl Error {
                             last_mod,
                             binary,
                             inst_set,
-                            last_mod.pc() - 4,
+                            last_mod.last_pc(),
                         ) {
                             let (file_tag, line_num) = real_inst_parts.location.unwrap();
                             let mut file = None;
@@ -1235,7 +1458,7 @@ impl Error {
                                     {
                                         let inst = last_mod.read_mem_word(addr).unwrap();
 
-                                        let failed = addr == last_mod.pc() - 4;
+                                        let failed = addr == last_mod.last_pc();
 
                                         error.push_str(&format!(
                                             "  {} {}{}\n",
@@ -1347,8 +1570,21 @@ impl Error {
                 }
             }
 
-            Error::Uninitialised { .. } => {
-                vec![]
+            Error::Uninitialised { value } => {
+                let origin = trace_uninitialised(value, inst_set, binary, runtime);
+
+                if origin.is_empty() {
+                    vec![]
+                } else {
+                    vec![format!(
+                        "this is how the uninitialised value got here, through memory:\n{}\n",
+                        origin
+                            .into_iter()
+                            .map(|step| format!("  - {step}"))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )]
+                }
             }
 
             Error::UnalignedAccess {
@@ -1391,6 +1627,62 @@ impl Error {
                 )]
             }
 
+            Error::OutOfBounds {
+                addr,
+                label,
+                label_addr,
+                label_size,
+                ..
+            } => {
+                let mut tips = vec![format!(
+                    "`{}` is {} bytes long, so it spans from `{}` to `{}`",
+                    label.yellow().bold(),
+                    label_size,
+                    format!("0x{:08x}", label_addr).bold(),
+                    format!("0x{:08x}", label_addr + label_size - 1).bold(),
+                )];
+
+                if *addr == label_addr + label_size {
+                    tips.push(format!(
+                        "this is the first address just past the end of `{}` - check your loop condition for an off-by-one error (e.g. `{}` instead of `{}`)",
+                        label.yellow().bold(),
+                        "<=".bold(),
+                        "<".bold(),
+                    ));
+                }
+
+                tips
+            }
+
+            Error::StackOverflow { depth, .. } => {
+                let mut tips = vec![];
+
+                if *depth <= 1 {
+                    tips.push(format!(
+                        "{} when this happened, so you are probably pushing onto the stack more than you pop off it\n\
+                         (for example, by jumping back to the start of a function, or returning without restoring {})",
+                        if *depth == 0 {
+                            "no function calls were in progress"
+                        } else {
+                            "only one function call was in progress"
+                        },
+                        "$sp".yellow(),
+                    ));
+                } else {
+                    tips.push(format!(
+                        "{} function calls were in progress when this happened - check that your recursive function has a base case, and that it is reached",
+                        depth.to_string().bold(),
+                    ));
+                }
+
+                tips.push(format!(
+                    "if your program really does need a bigger stack, try `{}`",
+                    "--stack-size".bold(),
+                ));
+
+                tips
+            }
+
             Error::IntegerOverflow => {
                 let mut tip = String::new();
 
@@ -1450,7 +1742,7 @@ impl Error {
             Error::SegmentationFault { addr, access: _ } => {
                 let addr = *addr;
 
-                match get_segment(addr) {
+                match binary.layout.segment_of(addr) {
                     _ if addr == 0 => {
                         vec![format!(
                             "the address `{}{}` is {}\n",
@@ -1529,6 +1821,278 @@ fn get_last_mod(runtime: &Runtime, write_marker: u32) -> Option<(usize, &State)>
     None
 }
 
+fn get_last_mod_before(
+    runtime: &Runtime,
+    write_marker: u32,
+    before: usize,
+) -> Option<(usize, &State)> {
+    (0..before).rev().find_map(|i| {
+        let old_state = runtime.timeline().nth_state(i)?;
+
+        if old_state.write_marker() & (1u64 << write_marker) != 0 {
+            Some((i, old_state))
+        } else {
+            None
+        }
+    })
+}
+
+fn get_last_mem_mod_before(runtime: &Runtime, addr: u32, before: usize) -> Option<(usize, &State)> {
+    // the seed state is filled in by the loader, not by an instruction
+    (1..before).rev().find_map(|i| {
+        let old_state = runtime.timeline().nth_state(i)?;
+
+        match old_state.mem_write_marker() {
+            Some((start, len)) if (start..start.wrapping_add(len)).contains(&addr) => {
+                Some((i, old_state))
+            }
+            _ => None,
+        }
+    })
+}
+
+enum UninitialisedOrigin {
+    Register { reg_num: u32, before: usize },
+    Memory { addr: u32, len: u32, before: usize },
+}
+
+/// Follows an uninitialised value back through the timeline,
+/// explaining each load and store that carried it, until we find
+/// the register or memory that was never written to.
+///
+/// Loads and stores are the only instructions that can carry one:
+/// anything else that reads an uninitialised register (e.g. a `move`)
+/// fails straight away, so that's where the trace starts from.
+fn trace_uninitialised(
+    value: &Uninitialised,
+    inst_set: &InstSet,
+    binary: &Binary,
+    runtime: &Runtime,
+) -> Vec<String> {
+    const MAX_STEPS: usize = 16;
+
+    let timeline_len = runtime.timeline().timeline_len();
+
+    let mut origin = match *value {
+        Uninitialised::Register { reg_num } => UninitialisedOrigin::Register {
+            reg_num,
+            before: timeline_len,
+        },
+        Uninitialised::Byte { addr } => UninitialisedOrigin::Memory {
+            addr,
+            len: 1,
+            before: timeline_len,
+        },
+        Uninitialised::Half { addr } => UninitialisedOrigin::Memory {
+            addr,
+            len: 2,
+            before: timeline_len,
+        },
+        Uninitialised::Word { addr } => UninitialisedOrigin::Memory {
+            addr,
+            len: 4,
+            before: timeline_len,
+        },
+        // mul clobbers hi/lo directly, there's nothing to trace
+        Uninitialised::Hi | Uninitialised::Lo => return vec![],
+    };
+
+    let mut steps = vec![];
+    // only worth showing if the value actually travelled through memory
+    let mut travelled = false;
+
+    while steps.len() < MAX_STEPS {
+        match origin {
+            UninitialisedOrigin::Register { reg_num, before } => {
+                let name = format!(
+                    "{}{}",
+                    "$".yellow(),
+                    Register::from_u32(reg_num).unwrap().to_lower_str().bold()
+                );
+
+                let (index, last_mod) = match get_last_mod_before(runtime, reg_num, before) {
+                    Some(last_mod) => last_mod,
+                    None => {
+                        steps.push(format!("{name} was {} initialised", "never".bold()));
+                        break;
+                    }
+                };
+
+                let inst_addr = last_mod.last_pc();
+                let inst = match last_mod.read_mem_word(inst_addr) {
+                    Ok(inst) => inst,
+                    Err(_) => break,
+                };
+
+                let len = match inst >> 26 {
+                    // LB, LBU
+                    0x20 | 0x24 => 1,
+                    // LH, LHU
+                    0x21 | 0x25 => 2,
+                    // LW
+                    0x23 => 4,
+                    _ => break,
+                };
+
+                let prev_state = match index
+                    .checked_sub(1)
+                    .and_then(|i| runtime.timeline().nth_state(i))
+                {
+                    Some(prev_state) => prev_state,
+                    None => break,
+                };
+
+                let rs = (inst >> 21) & 0x1F;
+                let imm = (inst & 0xFFFF) as i16 as i32;
+                let base = match prev_state.read_register(rs) {
+                    Ok(base) => base,
+                    Err(_) => break,
+                };
+                let addr = base.wrapping_add(imm) as u32;
+
+                steps.push(format!(
+                    "{name} was loaded by {} from {}",
+                    describe_inst(last_mod, binary, inst_set, inst, inst_addr),
+                    format!("0x{:08x}", addr).bold(),
+                ));
+
+                travelled = true;
+                origin = UninitialisedOrigin::Memory {
+                    addr,
+                    len,
+                    before: index,
+                };
+            }
+
+            UninitialisedOrigin::Memory { addr, len, before } => {
+                // the memory as it was when it was read
+                let state = match before
+                    .checked_sub(1)
+                    .and_then(|i| runtime.timeline().nth_state(i))
+                {
+                    Some(state) => state,
+                    None => break,
+                };
+
+                let byte_addr = match (addr..addr.wrapping_add(len)).find(|&byte_addr| {
+                    matches!(
+                        state.read_mem_byte_uninit_unchecked(byte_addr),
+                        Ok(Safe::Uninitialised)
+                    )
+                }) {
+                    Some(byte_addr) => byte_addr,
+                    None => break,
+                };
+
+                let location = format!("0x{:08x}", byte_addr).bold();
+
+                let (index, last_mod) = match get_last_mem_mod_before(runtime, byte_addr, before) {
+                    Some(last_mod) => last_mod,
+                    None => {
+                        steps.push(format!(
+                            "{location} {}",
+                            describe_unwritten_memory(binary, byte_addr)
+                        ));
+                        break;
+                    }
+                };
+
+                let inst_addr = last_mod.last_pc();
+                let inst = match last_mod.read_mem_word(inst_addr) {
+                    Ok(inst) => inst,
+                    Err(_) => break,
+                };
+
+                match inst >> 26 {
+                    // SB, SH, SW
+                    0x28 | 0x29 | 0x2B => {}
+                    _ => break,
+                }
+
+                let rt = (inst >> 16) & 0x1F;
+
+                steps.push(format!(
+                    "{location} was written by {}, while {}{} was uninitialised",
+                    describe_inst(last_mod, binary, inst_set, inst, inst_addr),
+                    "$".yellow(),
+                    Register::from_u32(rt).unwrap().to_lower_str().bold(),
+                ));
+
+                travelled = true;
+                origin = UninitialisedOrigin::Register {
+                    reg_num: rt,
+                    before: index,
+                };
+            }
+        }
+    }
+
+    if travelled {
+        steps
+    } else {
+        vec![]
+    }
+}
+
+fn describe_inst(
+    state: &State,
+    binary: &Binary,
+    inst_set: &InstSet,
+    inst: u32,
+    addr: u32,
+) -> String {
+    let parts = decompile_inst_into_parts(binary, inst_set, inst, addr);
+
+    let inst_str = format!(
+        "`{} {}`",
+        parts.inst_name.as_deref().unwrap_or("unknown").bold(),
+        parts.arguments.join(", "),
+    );
+
+    match get_real_instruction_start(state, binary, inst_set, addr)
+        .and_then(|real_inst_parts| real_inst_parts.location)
+    {
+        Some((_, line_num)) => format!("{inst_str} on line {}", line_num.to_string().bold()),
+        None => inst_str,
+    }
+}
+
+fn describe_unwritten_memory(binary: &Binary, addr: u32) -> String {
+    match binary.layout.segment_of(addr) {
+        Segment::Stack => "is a stack slot that was never written to".to_string(),
+        Segment::Data if addr >= binary.layout.heap_bot => {
+            format!(
+                "is heap memory (from `{}`) that was never written to",
+                "sbrk".bold()
+            )
+        }
+        Segment::Data | Segment::KData => {
+            let label = binary
+                .labels
+                .iter()
+                .filter(|&(_, &label_addr)| {
+                    label_addr <= addr
+                        && binary.layout.segment_of(label_addr) == binary.layout.segment_of(addr)
+                })
+                .max_by_key(|&(_, &label_addr)| label_addr);
+
+            match label {
+                Some((label, &label_addr)) if label_addr == addr => format!(
+                    "is `{}`, which was never given an initial value",
+                    label.yellow().bold()
+                ),
+                Some((label, &label_addr)) => format!(
+                    "is `{}+{}`, which was never given an initial value",
+                    label.yellow().bold(),
+                    addr - label_addr
+                ),
+                None => "was never written to".to_string(),
+            }
+        }
+        _ => "was never written to".to_string(),
+    }
+}
+
 fn get_real_instruction_start<'inst_set>(
     state: &State,
     binary: &Binary,
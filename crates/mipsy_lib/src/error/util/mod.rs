@@ -1,10 +1,11 @@
-use std::rc::Rc;
+use std::{path::MAIN_SEPARATOR, rc::Rc};
 
 use crate::{
     decompile::{decompile_inst_into_parts, Decompiled},
     ArgumentType, Binary, InstSet, KTEXT_BOT,
 };
 use colored::Colorize;
+use mipsy_parser::{tabs_to_spaces, MpMacroExpansion};
 
 // arg.to_string() will simply use the existing Display impl
 // eg ArgumentType::Rd.to_string() == "$Rd"
@@ -52,6 +53,95 @@ pub fn tip_header() -> String {
     format!("{}{}", header, colon)
 }
 
+/// Shows each macro invocation that an error was expanded from,
+/// innermost first, in the same format as the error itself.
+///
+/// `file` is the contents of `file_tag`, the file the error is in,
+/// so invocations in other files only get their location shown.
+pub fn expansion_notes(
+    expansion: Option<&MpMacroExpansion>,
+    file_tag: &str,
+    file: &str,
+    tab_size: u32,
+) -> String {
+    let mut notes = String::new();
+    let mut expansion = expansion;
+
+    while let Some(invocation) = expansion {
+        let line = if invocation.file_tag().as_deref().unwrap_or("") == file_tag {
+            file.lines()
+                .nth(invocation.line() as usize - 1)
+                .map(|line| tabs_to_spaces(line, tab_size))
+        } else {
+            None
+        };
+
+        let line_num_str = invocation.line().to_string();
+        let line_num_blank = " ".repeat(line_num_str.len());
+        let bar = "|".bright_blue().bold();
+
+        notes.push('\n');
+
+        if let Some(file_tag) = invocation.file_tag().filter(|tag| !tag.is_empty()) {
+            let dot_slash = if !file_tag.contains(MAIN_SEPARATOR) {
+                "./"
+            } else {
+                ""
+            };
+
+            notes.push_str(&format!(
+                "{}{} {}\n",
+                line_num_blank,
+                "-->".bright_blue().bold(),
+                format!(
+                    "{}{}:{}:{}",
+                    dot_slash,
+                    file_tag,
+                    invocation.line(),
+                    invocation.col()
+                )
+                .bold(),
+            ));
+        }
+
+        match line {
+            Some(line) => {
+                notes.push_str(&format!("{} {}\n", line_num_blank, bar));
+                notes.push_str(&format!(
+                    "{} {} {}\n",
+                    line_num_str.bright_blue().bold(),
+                    bar,
+                    line
+                ));
+                notes.push_str(&format!(
+                    "{} {} {}{} {} in this expansion of macro `{}`\n",
+                    line_num_blank,
+                    bar,
+                    " ".repeat(invocation.col() as usize - 1),
+                    "^".repeat((invocation.col_end() - invocation.col()) as usize)
+                        .bright_blue()
+                        .bold(),
+                    "note:".bold(),
+                    invocation.name().bold(),
+                ));
+            }
+            None => {
+                notes.push_str(&format!(
+                    "{} {} {} in this expansion of macro `{}`\n",
+                    line_num_blank,
+                    bar,
+                    "note:".bold(),
+                    invocation.name().bold(),
+                ));
+            }
+        }
+
+        expansion = invocation.parent();
+    }
+
+    notes
+}
+
 pub fn inst_to_string(
     inst: u32,
     addr: u32,
@@ -0,0 +1,395 @@
+//! # Formatting programs into a consistent style.
+//!
+//! Labels and constants go on their own lines against the left margin,
+//! while directives and instructions are indented by one tab stop,
+//! with their operands lined up in a column:
+//!
+//! ```text
+//! main:
+//!         li      $v0, 1          # print_int
+//!         move    $a0, $t0
+//!         syscall
+//! ```
+//!
+//! Comments at the end of a line are lined up with those on the lines around them
+//! (up to the next blank line), and runs of blank lines are squashed down to one.
+//! Any line that doesn't parse is left alone, other than trailing whitespace.
+//!
+//! The layout is configured by the `format` section of the [`MipsyConfig`],
+//! and the file's `#![tabsize(...)]`, if it has one, overrides the config's `tab_size`.
+
+use std::{collections::HashSet, str::FromStr};
+
+use mipsy_parser::{
+    cst::{Cst, CstElement, CstNode, CstToken, NodeKind, TokenKind},
+    file_tab_size, parse_mips, tabs_to_spaces, TaggedFile,
+};
+use mipsy_utils::{MipsyConfig, RegisterStyle};
+
+use crate::Register;
+
+/// A line of the formatted program.
+enum Row {
+    Blank,
+    /// A comment on a line of its own, which keeps to the left margin
+    /// if that's where it was.
+    Comment {
+        indented: bool,
+        comment: String,
+    },
+    Code {
+        code: String,
+        comment: Option<String>,
+    },
+    /// A line that couldn't be parsed, left as it was.
+    Verbatim(String),
+}
+
+/// Formats a single file.
+pub fn format(source: &str, config: &MipsyConfig) -> String {
+    let tab_size = file_tab_size(source, config.tab_size).max(1);
+    let formatter = Formatter { config, tab_size };
+
+    let cst = Cst::parse(source);
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    // the syntax tree is more forgiving than the parser,
+    // so it gets the final say on which lines can be formatted
+    let unparseable: HashSet<u32> = match parse_mips(vec![TaggedFile::new(None, source)], tab_size)
+    {
+        Ok(program) => program
+            .syntax_errors()
+            .into_iter()
+            .filter(|error| error.file_name.is_none() && error.expansion.is_none())
+            .map(|error| error.line)
+            .collect(),
+        Err(_) => HashSet::new(),
+    };
+
+    let mut rows = vec![];
+    for (line_no, line) in (1..).zip(lines(cst.root())) {
+        formatter.format_line(&line, unparseable.contains(&line_no), &mut rows);
+    }
+
+    formatter.write_rows(&rows, newline)
+}
+
+/// Splits the top level of a syntax tree into lines, without their newlines.
+fn lines(root: &CstNode) -> Vec<Vec<&CstElement>> {
+    let mut lines = vec![vec![]];
+
+    for child in root.children() {
+        match child {
+            CstElement::Token(token) if token.kind() == TokenKind::Newline => lines.push(vec![]),
+            _ => lines.last_mut().unwrap().push(child),
+        }
+    }
+
+    lines
+}
+
+struct Formatter<'a> {
+    config: &'a MipsyConfig,
+    tab_size: u32,
+}
+
+impl Formatter<'_> {
+    fn format_line(&self, line: &[&CstElement], unparseable: bool, rows: &mut Vec<Row>) {
+        let mut nodes = vec![];
+        let mut comment = None;
+
+        for element in line {
+            match element {
+                CstElement::Node(node) => nodes.push(node),
+                CstElement::Token(token) if token.kind() == TokenKind::Comment => {
+                    comment = Some(token.text().trim_end().to_string())
+                }
+                CstElement::Token(_) => {}
+            }
+        }
+
+        let unparseable = unparseable
+            || nodes.iter().any(|node| {
+                node.kind() == NodeKind::Error
+                    || node
+                        .tokens()
+                        .iter()
+                        .any(|token| token.kind() == TokenKind::Unknown)
+            });
+
+        if unparseable {
+            let text: String = line.iter().map(|element| text_of(element)).collect();
+            rows.push(Row::Verbatim(text.trim_end().to_string()));
+
+            return;
+        }
+
+        if nodes.is_empty() {
+            rows.push(match comment {
+                Some(comment) => Row::Comment {
+                    indented: matches!(
+                        line.first(),
+                        Some(CstElement::Token(token)) if token.kind() == TokenKind::Whitespace
+                    ),
+                    comment,
+                },
+                None => Row::Blank,
+            });
+
+            return;
+        }
+
+        // attributes stay on the same line as whatever they're attached to
+        let mut attributes = String::new();
+        let mut codes = vec![];
+
+        for node in nodes {
+            match node.kind() {
+                NodeKind::Attribute => {
+                    attributes.push_str(&node.text());
+                    attributes.push(' ');
+                    continue;
+                }
+                NodeKind::Label => codes.push(format!("{}{}", attributes, join_tokens(node))),
+                NodeKind::Constant => codes.push(format!(
+                    "{}{}",
+                    attributes,
+                    self.format_tokens(&node.tokens(), false)
+                )),
+                NodeKind::Directive | NodeKind::Instruction => {
+                    codes.push(format!(
+                        "{}{}{}",
+                        self.indent(),
+                        attributes,
+                        self.format_statement(node)
+                    ));
+                }
+                NodeKind::File | NodeKind::Argument | NodeKind::Error => {
+                    unreachable!("only items are at the top level")
+                }
+            }
+
+            attributes.clear();
+        }
+
+        if !attributes.is_empty() {
+            codes.push(attributes.trim_end().to_string());
+        }
+
+        let last = codes.len() - 1;
+        for (i, code) in codes.into_iter().enumerate() {
+            rows.push(Row::Code {
+                code,
+                comment: if i == last { comment.take() } else { None },
+            });
+        }
+    }
+
+    /// Formats a directive or instruction, such as `li      $v0, 1`.
+    fn format_statement(&self, node: &CstNode) -> String {
+        let name = node.first_token().unwrap().text();
+        let arguments: Vec<&CstNode> = node.child_nodes().collect();
+
+        if arguments.is_empty() {
+            return name.to_string();
+        }
+
+        // coprocessor 0 registers are numbered, so `$12` is the status register, not `$t4`
+        let coprocessor = ["mfc0", "mtc0"].contains(&&*name.to_ascii_lowercase());
+
+        let arguments: Vec<String> = arguments
+            .into_iter()
+            .enumerate()
+            .map(|(i, argument)| self.format_tokens(&argument.tokens(), coprocessor && i > 0))
+            .collect();
+
+        // macros can be called like functions, as in `print_int($t0)`
+        let called = matches!(
+            node.children().get(1),
+            Some(CstElement::Node(argument)) if argument.first_token().is_some_and(|token| token.kind() == TokenKind::LParen)
+        );
+
+        if called {
+            return format!("{}{}", name, arguments.join(", "));
+        }
+
+        // measured from the indent, so that tabs land on the same tab stops
+        let indent = self.tab_size as usize;
+        let name_end = indent + name.chars().count();
+        let operands = indent + self.config.format.mnemonic_width as usize;
+
+        let padding = if name_end < operands {
+            self.pad(name_end, operands)
+        } else {
+            String::from(" ")
+        };
+
+        format!("{}{}{}", name, padding, arguments.join(", "))
+    }
+
+    /// Writes out some tokens with any whitespace between them squashed down
+    /// to a single space (or removed, just inside parentheses).
+    fn format_tokens(&self, tokens: &[&CstToken], keep_registers: bool) -> String {
+        let mut text = String::new();
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token.kind() {
+                TokenKind::Whitespace => {
+                    let after_paren = i
+                        .checked_sub(1)
+                        .is_some_and(|prev| tokens[prev].kind() == TokenKind::LParen);
+                    let before_paren = tokens
+                        .get(i + 1)
+                        .is_some_and(|next| next.kind() == TokenKind::RParen);
+
+                    if !after_paren && !before_paren {
+                        text.push(' ');
+                    }
+                }
+                TokenKind::Register if !keep_registers => {
+                    text.push_str(&self.format_register(token.text()))
+                }
+                _ => text.push_str(token.text()),
+            }
+        }
+
+        text
+    }
+
+    fn format_register(&self, register: &str) -> String {
+        let Ok(parsed) = Register::from_str(&register[1..]) else {
+            // such as the floating point registers
+            return register.to_string();
+        };
+
+        match self.config.format.registers {
+            RegisterStyle::Names => format!("${}", parsed.to_lower_str()),
+            RegisterStyle::Numbers => format!("${}", parsed.to_number()),
+            RegisterStyle::Keep => register.to_string(),
+        }
+    }
+
+    fn indent(&self) -> String {
+        self.pad(0, self.tab_size as usize)
+    }
+
+    /// The whitespace that takes a line from column `from` to column `to`.
+    fn pad(&self, from: usize, to: usize) -> String {
+        let tab_size = self.tab_size as usize;
+
+        if !self.config.format.hard_tabs {
+            return " ".repeat(to.saturating_sub(from));
+        }
+
+        let mut padding = String::new();
+        let mut col = from;
+
+        while (col / tab_size + 1) * tab_size <= to {
+            padding.push('\t');
+            col = (col / tab_size + 1) * tab_size;
+        }
+
+        padding.push_str(&" ".repeat(to.saturating_sub(col)));
+
+        padding
+    }
+
+    fn width(&self, code: &str) -> usize {
+        tabs_to_spaces(code, self.tab_size).chars().count()
+    }
+
+    fn write_rows(&self, rows: &[Row], newline: &str) -> String {
+        let tab_size = self.tab_size as usize;
+
+        // blank lines are squashed down to one, and dropped from the start and end
+        let mut rows: Vec<&Row> = rows
+            .iter()
+            .enumerate()
+            .filter(|&(i, row)| {
+                !matches!(row, Row::Blank) || (i != 0 && !matches!(rows[i - 1], Row::Blank))
+            })
+            .map(|(_, row)| row)
+            .collect();
+
+        while rows.last().is_some_and(|row| matches!(row, Row::Blank)) {
+            rows.pop();
+        }
+
+        // trailing comments line up with the rest of their block,
+        // which runs up to the next blank line
+        let is_break = |row: &&Row| matches!(row, Row::Blank);
+
+        let mut comment_cols = vec![0; rows.len()];
+        let mut start = 0;
+
+        while start < rows.len() {
+            let end = rows[start..]
+                .iter()
+                .position(is_break)
+                .map_or(rows.len(), |pos| start + pos);
+
+            let col = rows[start..end]
+                .iter()
+                .filter_map(|row| match row {
+                    Row::Code {
+                        code,
+                        comment: Some(_),
+                    } => Some(self.width(code)),
+                    _ => None,
+                })
+                .max()
+                .map_or(0, |width| (width / tab_size + 1) * tab_size);
+
+            comment_cols[start..end].fill(col);
+            start = end + 1;
+        }
+
+        let mut output = String::new();
+
+        for (row, comment_col) in rows.into_iter().zip(comment_cols) {
+            match row {
+                Row::Blank => {}
+                Row::Comment { indented, comment } => {
+                    if *indented {
+                        output.push_str(&self.indent());
+                    }
+
+                    output.push_str(comment);
+                }
+                Row::Code { code, comment } => {
+                    output.push_str(code);
+
+                    if let Some(comment) = comment {
+                        output.push_str(&self.pad(self.width(code), comment_col));
+                        output.push_str(comment);
+                    }
+                }
+                Row::Verbatim(text) => output.push_str(text),
+            }
+
+            output.push_str(newline);
+        }
+
+        output
+    }
+}
+
+/// Writes out a label without any whitespace before its colon.
+fn join_tokens(node: &CstNode) -> String {
+    node.tokens()
+        .into_iter()
+        .filter(|token| token.kind() != TokenKind::Whitespace)
+        .map(CstToken::text)
+        .collect()
+}
+
+fn text_of(element: &CstElement) -> String {
+    match element {
+        CstElement::Node(node) => node.text(),
+        CstElement::Token(token) => token.text().to_string(),
+    }
+}
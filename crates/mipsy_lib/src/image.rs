@@ -0,0 +1,254 @@
+//! # Memory images, for loading a program into simulated hardware.
+//!
+//! Each image holds a single segment, and starts from a base address
+//! (which defaults to the start of the segment), so that the first
+//! word in the image is at address 0 of whatever memory it's loaded into.
+//! If the base address is below the start of the segment,
+//! the gap is filled with the padding byte, as is anything that was
+//! left uninitialised (other than in Intel HEX, where it's skipped).
+//!
+//! Formats that hold words (rather than bytes) assemble them the same
+//! way the processor loads them, so `0x3c011001` is written as `3c011001`.
+
+use std::fmt::Write;
+
+use crate::{Binary, Safe, KDATA_BOT, KTEXT_BOT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Logisim's "v2.0 raw" format, for loading into a ROM or RAM component.
+    Logisim,
+    IntelHex,
+    /// The bytes as they are in memory.
+    RawLittleEndian,
+    /// Each word, most significant byte first.
+    RawBigEndian,
+    /// Altera (Intel) memory initialization files.
+    Mif,
+    /// Xilinx coefficient files.
+    Coe,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Logisim => "logisim",
+            ImageFormat::IntelHex => "hex",
+            ImageFormat::RawLittleEndian => "bin",
+            ImageFormat::RawBigEndian => "be.bin",
+            ImageFormat::Mif => "mif",
+            ImageFormat::Coe => "coe",
+        }
+    }
+}
+
+/// The segments that an image can be made from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSegment {
+    Text,
+    Data,
+    KText,
+    KData,
+}
+
+impl ImageSegment {
+    /// Where the segment starts in memory, and what's in it.
+    fn of(self, binary: &Binary) -> (u32, &[Safe<u8>]) {
+        match self {
+            ImageSegment::Text => (binary.layout.text_bot, &binary.text),
+            ImageSegment::Data => (binary.layout.data_bot, &binary.data),
+            ImageSegment::KText => (KTEXT_BOT, &binary.ktext),
+            ImageSegment::KData => (KDATA_BOT, &binary.kdata),
+        }
+    }
+}
+
+/// Why an image couldn't be made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    BaseAboveSegment { base: u32, segment_start: u32 },
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::BaseAboveSegment {
+                base,
+                segment_start,
+            } => write!(
+                f,
+                "the base address 0x{:08x} is after the start of the segment (0x{:08x})",
+                base, segment_start
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Writes one segment of a program out as a memory image.
+///
+/// `base` is the address that the start of the image corresponds to
+/// (defaulting to the start of the segment), and `pad` is the byte
+/// used for anything uninitialised.
+pub fn write_image(
+    binary: &Binary,
+    segment: ImageSegment,
+    format: ImageFormat,
+    base: Option<u32>,
+    pad: u8,
+) -> Result<Vec<u8>, ImageError> {
+    let (segment_start, contents) = segment.of(binary);
+    let base = base.unwrap_or(segment_start);
+
+    if base > segment_start {
+        return Err(ImageError::BaseAboveSegment {
+            base,
+            segment_start,
+        });
+    }
+
+    let gap = (segment_start - base) as usize;
+
+    let mut bytes = vec![pad; gap];
+    bytes.extend(
+        contents
+            .iter()
+            .map(|byte| byte.as_option().copied().unwrap_or(pad)),
+    );
+
+    // only whole words are loaded
+    let words_len = bytes.len().div_ceil(4) * 4;
+    bytes.resize(words_len, pad);
+
+    let words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+
+    Ok(match format {
+        ImageFormat::Logisim => logisim(&words).into_bytes(),
+        // records have addresses, so nothing needs to be filled in
+        ImageFormat::IntelHex => intel_hex(gap as u32, contents).into_bytes(),
+        ImageFormat::RawLittleEndian => bytes,
+        ImageFormat::RawBigEndian => words.iter().flat_map(|word| word.to_be_bytes()).collect(),
+        ImageFormat::Mif => mif(&words).into_bytes(),
+        ImageFormat::Coe => coe(&words).into_bytes(),
+    })
+}
+
+fn logisim(words: &[u32]) -> String {
+    // runs of the same word are written as `count*word`, as padding can get long
+    let mut runs: Vec<(usize, u32)> = vec![];
+    for &word in words {
+        match runs.last_mut() {
+            Some((count, last)) if *last == word => *count += 1,
+            _ => runs.push((1, word)),
+        }
+    }
+
+    let values: Vec<String> = runs
+        .into_iter()
+        .flat_map(|(count, word)| {
+            if count > 3 {
+                vec![format!("{}*{:x}", count, word)]
+            } else {
+                vec![format!("{:x}", word); count]
+            }
+        })
+        .collect();
+
+    let mut image = String::from("v2.0 raw\n");
+
+    for line in values.chunks(8) {
+        writeln!(image, "{}", line.join(" ")).unwrap();
+    }
+
+    image
+}
+
+fn intel_hex(start: u32, bytes: &[Safe<u8>]) -> String {
+    let mut image = String::new();
+    let mut upper = 0;
+    let mut written = 0;
+
+    while written < bytes.len() {
+        if bytes[written].as_option().is_none() {
+            written += 1;
+            continue;
+        }
+
+        let addr = start + written as u32;
+
+        // addresses past 64KiB need an extended linear address record
+        if addr >> 16 != upper {
+            upper = addr >> 16;
+            intel_hex_record(&mut image, 0, 0x04, &(upper as u16).to_be_bytes());
+        }
+
+        // records are kept to a 16 byte line, which never crosses into the next 64KiB,
+        // and stop at anything uninitialised
+        let len = (16 - addr as usize % 16).min(bytes.len() - written);
+        let data: Vec<u8> = bytes[written..written + len]
+            .iter()
+            .map_while(|byte| byte.as_option().copied())
+            .collect();
+
+        intel_hex_record(&mut image, addr as u16, 0x00, &data);
+
+        written += data.len();
+    }
+
+    intel_hex_record(&mut image, 0, 0x01, &[]);
+
+    image
+}
+
+fn intel_hex_record(image: &mut String, addr: u16, kind: u8, data: &[u8]) {
+    let [addr_hi, addr_lo] = addr.to_be_bytes();
+    let mut checksum = (data.len() as u8)
+        .wrapping_add(addr_hi)
+        .wrapping_add(addr_lo)
+        .wrapping_add(kind);
+
+    write!(image, ":{:02X}{:04X}{:02X}", data.len(), addr, kind).unwrap();
+
+    for &byte in data {
+        write!(image, "{:02X}", byte).unwrap();
+        checksum = checksum.wrapping_add(byte);
+    }
+
+    writeln!(image, "{:02X}", checksum.wrapping_neg()).unwrap();
+}
+
+fn mif(words: &[u32]) -> String {
+    let mut image = format!(
+        "DEPTH = {};\nWIDTH = 32;\nADDRESS_RADIX = HEX;\nDATA_RADIX = HEX;\nCONTENT\nBEGIN\n",
+        words.len()
+    );
+
+    for (addr, word) in words.iter().enumerate() {
+        writeln!(image, "{:x} : {:08x};", addr, word).unwrap();
+    }
+
+    image.push_str("END;\n");
+
+    image
+}
+
+fn coe(words: &[u32]) -> String {
+    let mut image =
+        String::from("memory_initialization_radix=16;\nmemory_initialization_vector=\n");
+
+    for (i, word) in words.iter().enumerate() {
+        let separator = if i + 1 == words.len() { ';' } else { ',' };
+
+        writeln!(image, "{:08x}{}", word, separator).unwrap();
+    }
+
+    if words.is_empty() {
+        image.push_str(";\n");
+    }
+
+    image
+}
@@ -2,11 +2,16 @@ use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fmt, str::FromStr};
 
 use super::register::Register;
-use crate::{error::MipsyInternalResult, Binary, TEXT_BOT};
+use crate::{
+    compile::eval_constant,
+    error::{compiler, InternalError, MipsyInternalResult},
+    Binary, MipsyError,
+};
 use mipsy_parser::{
-    parse_argument, MpArgument, MpImmediate, MpImmediateBinaryOp, MpInstruction, MpNumber,
-    MpOffsetOperator, MpRegister, MpRegisterIdentifier,
+    parse_argument, MpArgument, MpConstValueLoc, MpImmediate, MpImmediateBinaryOp, MpInstruction,
+    MpNumber, MpOffsetOperator, MpRegister, MpRegisterIdentifier, MpRelocation,
 };
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct InstSet {
@@ -367,7 +372,8 @@ impl InstSignature {
                                     // must be relative
                                     let addr = program.get_label(label)?;
 
-                                    let current_inst_addr = program.text.len() as u32 + TEXT_BOT;
+                                    let current_inst_addr =
+                                        program.text.len() as u32 + program.layout.text_bot;
 
                                     ((addr.wrapping_sub(current_inst_addr)) / 4) & 0xFFFF
                                 }
@@ -375,6 +381,9 @@ impl InstSignature {
                             _ => unreachable!(),
                         },
                         &MpNumber::Char(chr) => chr as u8 as u32,
+                        MpNumber::Relocation(relocation, value) => {
+                            relocation.apply(eval_expression(program, value)?) as u32
+                        }
                         _ => unreachable!(),
                     },
                     _ => unreachable!(),
@@ -391,7 +400,8 @@ impl InstSignature {
                                     // must be relative
                                     let addr = program.get_label(label)?;
 
-                                    let current_inst_addr = program.text.len() as u32 + TEXT_BOT;
+                                    let current_inst_addr =
+                                        program.text.len() as u32 + program.layout.text_bot;
 
                                     ((addr.wrapping_sub(current_inst_addr)) / 4) & 0xFFFF
                                 }
@@ -399,6 +409,9 @@ impl InstSignature {
                             _ => unreachable!(),
                         },
                         &MpNumber::Char(chr) => chr as u8 as u32,
+                        MpNumber::Relocation(relocation, value) => {
+                            relocation.apply(eval_expression(program, value)?) as u32
+                        }
                         _ => unreachable!(),
                     },
                     _ => unreachable!(),
@@ -430,6 +443,12 @@ impl InstSignature {
                             }
                             _ => unreachable!(),
                         },
+                        MpRegister::ExpressionOffset(Some(relocation), value, reg) => {
+                            let register = reg.to_register()?.to_u32();
+                            let imm = relocation.apply(eval_expression(program, value)?) as u32;
+
+                            (register << 16) | imm
+                        }
                         _ => unreachable!(),
                     },
                     x => unreachable!("{x:?}"),
@@ -461,6 +480,29 @@ impl InstSignature {
     }
 }
 
+/// Evaluates a constant expression in an instruction's argument,
+/// which can refer to labels, and must fit in 32 bits.
+fn eval_expression(program: &Binary, value: &MpConstValueLoc) -> MipsyInternalResult<u32> {
+    let value = eval_constant(program, value, Rc::from(""), None).map_err(|err| match err {
+        MipsyError::Compiler(err) => InternalError::Compiler(err.error().clone()),
+        _ => unreachable!(),
+    })?;
+
+    let (range_low, range_high) = (i32::MIN as i64, u32::MAX as i64);
+
+    if value < range_low || value > range_high {
+        return Err(InternalError::Compiler(
+            compiler::Error::ImmediateValueDoesNotFit {
+                value,
+                range_low,
+                range_high,
+            },
+        ));
+    }
+
+    Ok(value as u32)
+}
+
 impl CompileSignature {
     pub fn matches(&self, inst: &MpInstruction) -> bool {
         self.matches_args(inst.arguments().iter().map(|(arg, _, _)| arg).collect())
@@ -523,7 +565,14 @@ impl ArgumentType {
                         matches!(self, Self::Off32Rs | Self::Off32Rt)
                     }
                 },
-                MpRegister::BinaryOpOffset(..) => matches!(self, Self::Off32Rs | Self::Off32Rt),
+                MpRegister::BinaryOpOffset(..) | MpRegister::ExpressionOffset(None, ..) => {
+                    matches!(self, Self::Off32Rs | Self::Off32Rt)
+                }
+                // relocations always give 16 bits
+                MpRegister::ExpressionOffset(Some(_), ..) => matches!(
+                    self,
+                    Self::OffRs | Self::OffRt | Self::Off32Rs | Self::Off32Rt
+                ),
             },
             MpArgument::Number(number) => {
                 match number {
@@ -561,6 +610,12 @@ impl ArgumentType {
                     }
                     MpNumber::Float32(_) => matches!(self, Self::F32 | Self::F64),
                     MpNumber::Float64(_) => matches!(self, Self::F64),
+                    MpNumber::Relocation(..) => {
+                        matches!(self, Self::I16 | Self::U16 | Self::I32 | Self::U32)
+                    }
+                    MpNumber::Expression(_) => {
+                        matches!(self, Self::I32 | Self::U32 | Self::Off32Rs | Self::Off32Rt)
+                    }
                 }
             } // MpArgument::LabelPlusConst(..)
         }
@@ -671,6 +726,14 @@ impl PseudoSignature {
 
                     (value as u16, (value >> 16) as u16)
                 }
+                MpRegister::ExpressionOffset(relocation, value, _) => {
+                    let num = match relocation {
+                        Some(relocation) => MpNumber::Relocation(*relocation, value.clone()),
+                        None => MpNumber::Expression(value.clone()),
+                    };
+
+                    self.lower_upper(program, &MpArgument::Number(num), last)?
+                }
                 _ => unreachable!(),
             },
             MpArgument::Number(num) => match num {
@@ -690,7 +753,7 @@ impl PseudoSignature {
                             if self.compile.relative_label && last {
                                 let current_inst_addr =
                                     (program.text.len() + self.expand.len() - 1) as u32 * 4
-                                        + TEXT_BOT;
+                                        + program.layout.text_bot;
                                 addr = (addr.wrapping_sub(current_inst_addr)) / 4;
                             }
 
@@ -699,6 +762,20 @@ impl PseudoSignature {
                     }
                 },
                 &MpNumber::Char(chr) => (chr as u16, 0_u16),
+                MpNumber::Relocation(relocation, value) => {
+                    let half = relocation.apply(eval_expression(program, value)?);
+
+                    // `%lo` is sign-extended, like any other i16
+                    match relocation {
+                        MpRelocation::Hi => (half, 0),
+                        MpRelocation::Lo => (half, (half as i16 as i32 >> 16) as u16),
+                    }
+                }
+                MpNumber::Expression(value) => {
+                    let value = eval_expression(program, value)?;
+
+                    ((value & 0xFFFF) as u16, (value >> 16) as u16)
+                }
                 MpNumber::BinaryOpImmediate(imm1, op, imm2) => {
                     let (lower1, upper1) = self.lower_upper(
                         program,
@@ -861,7 +938,7 @@ impl PseudoSignature {
 
                             let current_inst_addr =
                                 (program.text.len() + (self.expand.len() - 1) * 4) as u32
-                                    + TEXT_BOT;
+                                    + program.layout.text_bot;
                             let imm = ((addr.wrapping_sub(current_inst_addr)) / 4) as i16;
 
                             MpArgument::Number(MpNumber::Immediate(MpImmediate::I16(imm)))
@@ -898,9 +975,12 @@ impl PseudoSignature {
                         last,
                     )?;
 
-                    let (imm, reg) = match arg {
+                    let (off, reg) = match arg {
                         MpArgument::Register(reg) => match reg {
-                            MpRegister::Offset(imm, reg) => (imm, reg),
+                            MpRegister::Offset(imm, reg) => (MpNumber::Immediate(imm.clone()), reg),
+                            MpRegister::ExpressionOffset(Some(relocation), value, reg) => {
+                                (MpNumber::Relocation(*relocation, value.clone()), reg)
+                            }
                             _ => unreachable!(),
                         },
                         _ => unreachable!(),
@@ -909,7 +989,7 @@ impl PseudoSignature {
                     self.new_variable(
                         program,
                         PseudoVariable::Off,
-                        MpArgument::Number(MpNumber::Immediate(imm.clone())),
+                        MpArgument::Number(off),
                         &mut variables,
                         &mut used,
                         last,
@@ -933,9 +1013,12 @@ impl PseudoSignature {
                         last,
                     )?;
 
-                    let (imm, reg) = match arg {
+                    let (off, reg) = match arg {
                         MpArgument::Register(reg) => match reg {
-                            MpRegister::Offset(imm, reg) => (imm, reg),
+                            MpRegister::Offset(imm, reg) => (MpNumber::Immediate(imm.clone()), reg),
+                            MpRegister::ExpressionOffset(Some(relocation), value, reg) => {
+                                (MpNumber::Relocation(*relocation, value.clone()), reg)
+                            }
                             _ => unreachable!(),
                         },
                         _ => unreachable!(),
@@ -944,7 +1027,7 @@ impl PseudoSignature {
                     self.new_variable(
                         program,
                         PseudoVariable::Off,
-                        MpArgument::Number(MpNumber::Immediate(imm.clone())),
+                        MpArgument::Number(off),
                         &mut variables,
                         &mut used,
                         last,
@@ -984,7 +1067,9 @@ impl PseudoSignature {
                     let reg = match arg {
                         MpArgument::Register(MpRegister::Normal(id))
                         | MpArgument::Register(
-                            MpRegister::Offset(_, id) | MpRegister::BinaryOpOffset(_, _, _, id),
+                            MpRegister::Offset(_, id)
+                            | MpRegister::BinaryOpOffset(_, _, _, id)
+                            | MpRegister::ExpressionOffset(_, _, id),
                         ) => MpArgument::Register(MpRegister::Normal(id.clone())),
                         _ => MpArgument::Register(MpRegister::Normal(
                             MpRegisterIdentifier::Numbered(0),
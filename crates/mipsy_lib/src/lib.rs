@@ -1,7 +1,13 @@
 pub mod compile;
 pub mod decompile;
+pub mod diagnostic;
+pub mod disasm;
+pub mod elf;
 pub mod error;
+pub mod format;
+pub mod image;
 pub mod inst;
+pub mod listing;
 pub mod runtime;
 pub mod util;
 
@@ -10,17 +16,18 @@ use std::rc::Rc;
 use compile::CompilerOptions;
 pub use mipsy_parser::MpProgram;
 
-pub use compile::Binary;
+pub use compile::{Binary, MemoryLayout};
 pub use compile::{
     DATA_BOT, GLOBAL_BOT, GLOBAL_PTR, HEAP_BOT, KDATA_BOT, KTEXT_BOT, STACK_BOT, STACK_PTR,
     STACK_TOP, TEXT_BOT, TEXT_TOP,
 };
 pub use error::{
-    runtime::Uninitialised, CompilerError, MipsyError, MipsyResult, ParserError, RuntimeError,
+    runtime::Uninitialised, CompilerError, CompilerWarning, MipsyError, MipsyResult, ParserError,
+    RuntimeError,
 };
 pub use inst::instruction::{ArgumentType, InstSet};
 pub use inst::register::Register;
-use mipsy_parser::TaggedFile;
+use mipsy_parser::{ErrorLocation, TaggedFile};
 use mipsy_utils::MipsyConfig;
 pub use runtime::{Runtime, State};
 pub use util::Safe;
@@ -41,20 +48,63 @@ pub fn compile_with_kernel(
     options: &CompilerOptions,
     config: &MipsyConfig,
 ) -> MipsyResult<Binary> {
-    let mut parsed = mipsy_parser::parse_mips(files, config.tab_size).map_err(|err| {
-        error::MipsyError::Parser(ParserError::new(
-            error::parser::Error::ParseFailure,
-            err.file_name.unwrap_or_else(|| Rc::from("")),
-            err.line,
-            err.col as u32,
-        ))
-    })?;
+    let defines: Vec<_> = options.defines().cloned().collect();
+    let mut parsed = mipsy_parser::parse_mips_with_defines(files, config.tab_size, &defines)
+        .map_err(parser_error)?;
+
+    if let Some(error) = parsed.syntax_errors().into_iter().next() {
+        return Err(parser_error(error));
+    }
 
     let compiled = compile::compile_with_kernel(&mut parsed, kernel, options, config, iset)?;
 
     Ok(compiled)
 }
 
+/// Like [`compile_with_kernel`], but rather than stopping at the first line
+/// that can't be parsed, gives back every syntax error in the program,
+/// followed by the first compiler error in the lines that could be parsed.
+pub fn check_with_kernel(
+    iset: &InstSet,
+    files: Vec<TaggedFile<'_, '_>>,
+    kernel: &mut MpProgram,
+    options: &CompilerOptions,
+    config: &MipsyConfig,
+) -> Result<Binary, Vec<MipsyError>> {
+    let defines: Vec<_> = options.defines().cloned().collect();
+    let mut parsed = mipsy_parser::parse_mips_with_defines(files, config.tab_size, &defines)
+        .map_err(|error| vec![parser_error(error)])?;
+
+    let mut errors: Vec<MipsyError> = parsed
+        .syntax_errors()
+        .into_iter()
+        .map(parser_error)
+        .collect();
+
+    parsed.remove_syntax_errors();
+
+    match compile::compile_with_kernel(&mut parsed, kernel, options, config, iset) {
+        Ok(compiled) if errors.is_empty() => Ok(compiled),
+        Ok(_) => Err(errors),
+        Err(error) => {
+            errors.push(error);
+            Err(errors)
+        }
+    }
+}
+
+fn parser_error(error: ErrorLocation) -> MipsyError {
+    MipsyError::Parser(
+        ParserError::new(
+            error.kind.into(),
+            error.file_name.unwrap_or_else(|| Rc::from("")),
+            error.line,
+            error.col as u32,
+        )
+        .with_expansion(error.expansion),
+    )
+}
+
 pub use compile::compile1;
 
 pub fn decompile(iset: &InstSet, binary: &Binary) -> String {
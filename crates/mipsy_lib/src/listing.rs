@@ -0,0 +1,285 @@
+//! # Assembler listings.
+//!
+//! A listing shows each line of a program alongside the address it was
+//! compiled to, its machine words, and the native instructions those
+//! words decompile to (so that it's clear what a pseudo-instruction
+//! such as `la` turned into), followed by a table of every label and constant.
+//! Lines of data show their bytes instead, in the order they're in memory.
+//!
+//! ```text
+//! file.s
+//!     4                                                  main:
+//!     5  0x00400000  0x3c041001  lui    $a0, 4097                la      $a0, msg
+//!        0x00400004  0x34840000  ori    $a0, $a0, 0
+//!     6  0x00400008  0x20020004  addi   $v0, $zero, 4            li      $v0, 4
+//!    ...
+//!    12  0x10010000  68 65 6c 6c                         msg: .asciiz "hello"
+//!        0x10010004  6f 00
+//! ```
+
+use std::{collections::HashMap, fmt::Write, rc::Rc};
+
+use mipsy_parser::{file_tab_size, is_local_label, tabs_to_spaces};
+use mipsy_utils::MipsyConfig;
+
+use crate::{
+    decompile::decompile_inst_into_parts, util::Segment, Binary, InstSet, Safe, KDATA_BOT,
+    KTEXT_BOT,
+};
+
+/// Lists the lines of each of the given `(tag, contents)` files
+/// that were compiled into `binary`.
+pub fn listing(
+    iset: &InstSet,
+    binary: &Binary,
+    files: &[(Rc<str>, Rc<str>)],
+    config: &MipsyConfig,
+) -> String {
+    let mut listing = String::new();
+
+    let mut addrs: Vec<u32> = binary.line_numbers.keys().copied().collect();
+    addrs.sort_unstable();
+
+    let mut data_addrs: Vec<u32> = binary.data_line_numbers.keys().copied().collect();
+    data_addrs.sort_unstable();
+
+    // where each line of each file was compiled to in memory,
+    // which can be in several places if it's part of a macro
+    let mut line_addrs: HashMap<(&str, u32), Vec<u32>> = HashMap::new();
+    for (addr, (file_tag, line)) in binary.line_numbers.iter().chain(&binary.data_line_numbers) {
+        line_addrs.entry((file_tag, *line)).or_default().push(*addr);
+    }
+    for starts in line_addrs.values_mut() {
+        starts.sort_unstable();
+    }
+
+    for (file_tag, contents) in files {
+        let tab_size = file_tab_size(contents, config.tab_size);
+
+        writeln!(listing, "{}", file_tag).unwrap();
+
+        for (line, source) in (1..).zip(contents.lines()) {
+            let source = tabs_to_spaces(source, tab_size);
+
+            let Some(starts) = line_addrs.get(&(&**file_tag, line)) else {
+                let row = format!("{:>5}  {:48}{}", line, "", source);
+                writeln!(listing, "{}", row.trim_end()).unwrap();
+                continue;
+            };
+
+            for &start in starts {
+                if binary.data_line_numbers.contains_key(&start) {
+                    write_data(&mut listing, binary, &data_addrs, start, line, &source);
+                } else {
+                    write_words(&mut listing, iset, binary, &addrs, start, line, &source);
+                }
+            }
+        }
+
+        listing.push('\n');
+    }
+
+    write_symbols(&mut listing, binary);
+
+    listing
+}
+
+/// Writes out the words that a line was compiled to,
+/// which run up to wherever the next line starts.
+fn write_words(
+    listing: &mut String,
+    iset: &InstSet,
+    binary: &Binary,
+    addrs: &[u32],
+    start: u32,
+    line: u32,
+    source: &str,
+) {
+    let next = addrs.partition_point(|&addr| addr <= start);
+    let end = addrs
+        .get(next)
+        .copied()
+        .filter(|&next| same_segment(start, next))
+        .unwrap_or_else(|| segment_end(binary, start));
+
+    for (i, addr) in (start..end).step_by(4).enumerate() {
+        let (word, native) = match read_word(binary, addr) {
+            Some(word) => {
+                let parts = decompile_inst_into_parts(binary, iset, word, addr);
+
+                let native = match parts.inst_name {
+                    Some(name) => format!("{:6} {}", name, parts.arguments.join(", ")),
+                    None => String::from("# unknown instruction"),
+                };
+
+                (format!("0x{:08x}", word), native)
+            }
+            None => (String::from("[uninit] "), String::new()),
+        };
+
+        let row = if i == 0 {
+            format!(
+                "{:>5}  0x{:08x}  {}  {:23} {}",
+                line, addr, word, native, source
+            )
+        } else {
+            format!("       0x{:08x}  {}  {}", addr, word, native)
+        };
+
+        writeln!(listing, "{}", row.trim_end()).unwrap();
+    }
+}
+
+/// How many rows of a line of data to show, before leaving the rest out.
+const DATA_ROWS: usize = 4;
+
+/// Writes out the bytes of a line of data, four to a row,
+/// which run up to wherever the next line of data starts.
+fn write_data(
+    listing: &mut String,
+    binary: &Binary,
+    addrs: &[u32],
+    start: u32,
+    line: u32,
+    source: &str,
+) {
+    let next = addrs.partition_point(|&addr| addr <= start);
+    let end = addrs
+        .get(next)
+        .copied()
+        .filter(|&next| same_segment(start, next))
+        .unwrap_or_else(|| data_segment_end(binary, start));
+
+    for (i, addr) in (start..end).step_by(4).enumerate() {
+        if i == DATA_ROWS {
+            writeln!(listing, "       ... ({} more bytes)", end - addr).unwrap();
+            break;
+        }
+
+        let bytes = (addr..end.min(addr + 4))
+            .map(|addr| match read_data_byte(binary, addr) {
+                Some(byte) => format!("{:02x}", byte),
+                None => String::from(".."),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let row = if i == 0 {
+            format!("{:>5}  0x{:08x}  {:35} {}", line, addr, bytes, source)
+        } else {
+            format!("       0x{:08x}  {}", addr, bytes)
+        };
+
+        writeln!(listing, "{}", row.trim_end()).unwrap();
+    }
+}
+
+fn write_symbols(listing: &mut String, binary: &Binary) {
+    let mut labels: Vec<(&String, u32)> = binary
+        .labels
+        .iter()
+        .filter(|&(label, _)| {
+            !(label.starts_with("kernel__") || label == "_start" || is_local_label(label))
+        })
+        .map(|(label, &addr)| (label, addr))
+        .collect();
+    labels.sort_by_key(|&(label, addr)| (addr, label));
+
+    let mut constants: Vec<(&String, i64)> = binary
+        .constants
+        .iter()
+        .map(|(name, &value)| (name, value))
+        .collect();
+    constants.sort();
+
+    let width = labels
+        .iter()
+        .map(|(label, _)| label.len())
+        .chain(constants.iter().map(|(name, _)| name.len()))
+        .max()
+        .unwrap_or(0);
+
+    // the data segment isn't always after the text segment
+    let (text, data): (Vec<_>, Vec<_>) = labels.into_iter().partition(|&(_, addr)| {
+        matches!(
+            binary.layout.segment_of(addr),
+            Segment::Text | Segment::KText
+        )
+    });
+
+    for (header, labels) in [("text labels", text), ("data labels", data)] {
+        if labels.is_empty() {
+            continue;
+        }
+
+        writeln!(listing, "{}", header).unwrap();
+
+        for (label, addr) in labels {
+            let global = if binary.globals.contains(label) {
+                "  (global)"
+            } else {
+                ""
+            };
+
+            writeln!(listing, "    {:width$}  0x{:08x}{}", label, addr, global).unwrap();
+        }
+
+        listing.push('\n');
+    }
+
+    if constants.is_empty() {
+        return;
+    }
+
+    writeln!(listing, "constants").unwrap();
+    for (name, value) in constants {
+        writeln!(listing, "    {:width$}  {}", name, value).unwrap();
+    }
+}
+
+fn same_segment(addr: u32, other: u32) -> bool {
+    (addr >= KTEXT_BOT) == (other >= KTEXT_BOT)
+}
+
+fn segment_end(binary: &Binary, addr: u32) -> u32 {
+    if addr >= KTEXT_BOT {
+        KTEXT_BOT + binary.ktext.len() as u32
+    } else {
+        binary.layout.text_bot + binary.text.len() as u32
+    }
+}
+
+fn data_segment_end(binary: &Binary, addr: u32) -> u32 {
+    if addr >= KTEXT_BOT {
+        KDATA_BOT + binary.kdata.len() as u32
+    } else {
+        binary.layout.data_bot + binary.data.len() as u32
+    }
+}
+
+fn read_data_byte(binary: &Binary, addr: u32) -> Option<u8> {
+    let byte = if addr >= KTEXT_BOT {
+        binary.kdata.get((addr - KDATA_BOT) as usize)
+    } else {
+        binary.data.get((addr - binary.layout.data_bot) as usize)
+    };
+
+    byte?.as_option().copied()
+}
+
+fn read_word(binary: &Binary, addr: u32) -> Option<u32> {
+    let (segment, offset) = if addr >= KTEXT_BOT {
+        (&binary.ktext, addr - KTEXT_BOT)
+    } else {
+        (&binary.text, addr - binary.layout.text_bot)
+    };
+
+    let bytes = segment.get(offset as usize..offset as usize + 4)?;
+
+    match (bytes[0], bytes[1], bytes[2], bytes[3]) {
+        (Safe::Valid(b1), Safe::Valid(b2), Safe::Valid(b3), Safe::Valid(b4)) => {
+            Some(u32::from_le_bytes([b1, b2, b3, b4]))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,176 @@
+use super::{State, SPECIAL};
+use crate::{
+    error::runtime::{Error, SegmentationFaultAccessType},
+    Binary, MipsyError, MipsyResult, RuntimeError,
+};
+
+struct DataObject {
+    label: String,
+    addr: u32,
+    size: u32,
+}
+
+impl DataObject {
+    fn contains(&self, addr: u32) -> bool {
+        addr >= self.addr && addr - self.addr < self.size
+    }
+}
+
+/// # The extents of every labelled object in the data segments.
+///
+/// Used to catch loads and stores through a pointer to one
+/// labelled object (e.g. an array), which land outside of it.
+///
+/// Each register remembers which object it was derived from
+/// (see [`DataBounds::track_pointers`]), so walking a pointer
+/// off the end of an array is caught, even though the pointer
+/// then points into whatever object comes next.
+pub struct DataBounds {
+    objects: Vec<DataObject>,
+}
+
+impl DataBounds {
+    pub fn new(binary: &Binary) -> Self {
+        let mut objects: Vec<DataObject> = vec![];
+
+        for (label, &addr) in binary.labels.iter() {
+            let size = match binary.label_sizes.get(label) {
+                Some(&size) if size > 0 => size,
+                _ => continue,
+            };
+
+            // labels defined back-to-back are the same object,
+            // so just keep the first one we saw
+            if objects.iter().any(|object| object.addr == addr) {
+                continue;
+            }
+
+            objects.push(DataObject {
+                label: label.to_string(),
+                addr,
+                size,
+            });
+        }
+
+        objects.sort_by_key(|object| object.addr);
+
+        Self { objects }
+    }
+
+    fn object_at(&self, addr: u32) -> Option<&DataObject> {
+        self.objects
+            .binary_search_by_key(&addr, |object| object.addr)
+            .ok()
+            .map(|index| &self.objects[index])
+    }
+
+    fn object_containing(&self, addr: u32) -> Option<&DataObject> {
+        let index = self
+            .objects
+            .partition_point(|object| object.addr <= addr)
+            .checked_sub(1)?;
+
+        let object = &self.objects[index];
+
+        if object.contains(addr) {
+            Some(object)
+        } else {
+            None
+        }
+    }
+
+    /// Checks an access of `len` bytes at `addr`,
+    /// through the pointer `base` held in `base_reg`.
+    ///
+    /// A pointer to the end of one object is also a pointer to
+    /// the start of the next, so a negative offset from it is only
+    /// in bounds if the pointer was derived from the object it's
+    /// the end of (e.g. `la $t0, arr` then `addi $t0, $t0, 40`).
+    pub fn check(
+        &self,
+        state: &State,
+        base_reg: u32,
+        base: u32,
+        addr: u32,
+        len: u32,
+        access: SegmentationFaultAccessType,
+    ) -> MipsyResult<()> {
+        let object = match state.pointer_origins[base_reg as usize]
+            .and_then(|origin| self.object_at(origin))
+            .or_else(|| self.object_containing(base))
+        {
+            Some(object) => object,
+            None => return Ok(()),
+        };
+
+        let last_addr = addr.wrapping_add(len - 1);
+
+        if object.contains(addr) && object.contains(last_addr) {
+            return Ok(());
+        }
+
+        let into = if object.contains(addr) {
+            self.object_containing(last_addr)
+        } else {
+            self.object_containing(addr)
+        };
+
+        Err(MipsyError::Runtime(RuntimeError::new(Error::OutOfBounds {
+            addr,
+            access,
+            label: object.label.clone(),
+            label_addr: object.addr,
+            label_size: object.size,
+            into: into.map(|into| into.label.clone()),
+        })))
+    }
+
+    /// Updates which object each register written by `inst` points into.
+    ///
+    /// Adding to (or subtracting from) a pointer keeps the object it
+    /// was derived from, otherwise a register only points into an object
+    /// if its value is exactly that object's label (e.g. after an `la`).
+    pub(super) fn track_pointers(&self, state: &mut State, inst: u32) {
+        let opcode = inst >> 26;
+        let rs = (inst >> 21) & 0x1F;
+        let rt = (inst >> 16) & 0x1F;
+        let rd = (inst >> 11) & 0x1F;
+        let funct = inst & 0x3F;
+
+        let (dest, sources) = match (opcode, funct) {
+            // ADD, ADDU
+            (SPECIAL, 0x20 | 0x21) => (rd, vec![rs, rt]),
+            // SUB, SUBU
+            (SPECIAL, 0x22 | 0x23) => (rd, vec![rs]),
+            // ADDI, ADDIU
+            (0x08 | 0x09, _) => (rt, vec![rs]),
+            _ => (0, vec![]),
+        };
+
+        let derived = {
+            let mut origins = sources
+                .into_iter()
+                .filter_map(|reg| state.pointer_origins[reg as usize]);
+
+            match (origins.next(), origins.next()) {
+                (Some(origin), None) => Some(origin),
+                // adding two pointers together doesn't make much sense
+                _ => None,
+            }
+        };
+
+        for reg in 1..32 {
+            if state.write_marker() & (1u64 << reg) == 0 {
+                continue;
+            }
+
+            state.pointer_origins[reg as usize] = match derived {
+                Some(origin) if reg == dest => Some(origin),
+                _ => state.registers[reg as usize]
+                    .as_option()
+                    .and_then(|&value| self.object_at(value as u32))
+                    .map(|object| object.addr),
+            };
+        }
+    }
+}
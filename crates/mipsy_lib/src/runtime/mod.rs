@@ -1,19 +1,20 @@
+mod bounds;
 pub mod state;
 
-pub use self::state::State;
+pub use self::bounds::DataBounds;
+pub use self::state::{HeapAllocation, State};
 
 use self::state::Timeline;
 use crate::{
-    compile::GLOBAL_PTR,
     error::runtime::{
         AlignmentRequirement, Error, InvalidSyscallReason, SegmentationFaultAccessType,
     },
-    Binary, MipsyError, MipsyResult, Register, RuntimeError, Safe, Uninitialised, DATA_BOT,
-    HEAP_BOT, KDATA_BOT, KTEXT_BOT, STACK_PTR, TEXT_BOT,
+    Binary, MipsyError, MipsyResult, Register, RuntimeError, Safe, Uninitialised, KDATA_BOT,
+    KTEXT_BOT,
 };
 use std::cmp::Ordering;
 
-use crate::util::{get_segment, Segment};
+use crate::util::Segment;
 
 pub const NUL: u8 = 0;
 pub const NULL: u32 = 0;
@@ -37,6 +38,19 @@ pub const SYS15_WRITE: i32 = 15;
 pub const SYS16_CLOSE: i32 = 16;
 pub const SYS17_EXIT_STATUS: i32 = 17;
 
+/// The mipsy syscall that a Linux (o32) syscall number corresponds to, if any.
+fn linux_syscall(syscall: i32) -> Option<i32> {
+    match syscall {
+        // exit and exit_group
+        4001 | 4246 => Some(SYS17_EXIT_STATUS),
+        4003 => Some(SYS14_READ),
+        4004 => Some(SYS15_WRITE),
+        4005 => Some(SYS13_OPEN),
+        4006 => Some(SYS16_CLOSE),
+        _ => None,
+    }
+}
+
 pub const SPECIAL: u32 = 0b000000;
 pub const SPECIAL2: u32 = 0b011100;
 pub const SPECIAL3: u32 = 0b011111;
@@ -56,9 +70,24 @@ macro_rules! try_owned_self {
 #[derive(Default)]
 pub struct Runtime {
     timeline: Timeline,
+    data_bounds: Option<DataBounds>,
+    linux_syscalls: bool,
 }
 
 impl Runtime {
+    /// Checks that loads and stores through a pointer to a labelled
+    /// object in the data segment stay within that object.
+    pub fn enable_bounds_check(&mut self, binary: &Binary) {
+        self.data_bounds = Some(DataBounds::new(binary));
+    }
+
+    /// Also accepts the Linux (o32) numbers for the system calls
+    /// that mipsy supports, such as `write` (4004) and `exit_group` (4246),
+    /// so that programs built by a C compiler can be run.
+    pub fn enable_linux_syscalls(&mut self) {
+        self.linux_syscalls = true;
+    }
+
     pub fn timeline(&self) -> &Timeline {
         &self.timeline
     }
@@ -74,7 +103,7 @@ impl Runtime {
 
     pub fn step(mut self) -> Result<SteppedRuntime, (Runtime, MipsyError)> {
         let state = self.timeline.state();
-        let segment = get_segment(state.pc());
+        let segment = state.layout().segment_of(state.pc());
         match segment {
             Segment::Text | Segment::KText => {}
             _ => {
@@ -100,7 +129,12 @@ impl Runtime {
         };
 
         let state = self.timeline.push_next_state();
-        state.set_pc(state.pc() + 4);
+        state.last_pc = state.pc();
+
+        // a branch taken by the last instruction goes once its delay slot (this one) has run,
+        // so that's where this instruction moves on to
+        let next_pc = state.delayed_jump.take().unwrap_or(state.pc() + 4);
+        state.set_pc(next_pc);
 
         match self.execute_in_current_state(inst) {
             Err((mut new_self, err)) => {
@@ -153,7 +187,7 @@ impl Runtime {
         let imm = (inst & 0xFFFF) as i16;
         let addr = inst & 0x3FFFFFF;
 
-        match opcode {
+        let stepped = match opcode {
             SPECIAL | SPECIAL2 | SPECIAL3 => {
                 // R-Type
                 self.execute_r(opcode, funct, rd, rs, rt, shamt)
@@ -168,17 +202,62 @@ impl Runtime {
                 // I-Type
                 self.execute_i(opcode, rs, rt, imm)
             }
+        };
+
+        match stepped {
+            Ok(Ok(mut runtime)) => {
+                if let Some(bounds) = &runtime.data_bounds {
+                    bounds.track_pointers(runtime.timeline.state_mut(), inst);
+                }
+
+                Ok(Ok(runtime))
+            }
+            stepped => stepped,
         }
     }
 
     // remove when floating point syscalls are finished
     #[allow(unreachable_code)]
     fn syscall(mut self) -> Result<RuntimeSyscallGuard, (Runtime, MipsyError)> {
-        let syscall = try_owned_self!(
+        let mut syscall = try_owned_self!(
             self,
             self.timeline.state().read_register(Register::V0.to_u32())
         );
 
+        if self.linux_syscalls {
+            if let Some(spim_syscall) = linux_syscall(syscall) {
+                // linux reports whether the call failed in $a3,
+                // but mipsy's syscalls don't fail in a way the program can see
+                self.timeline
+                    .state_mut()
+                    .write_register(Register::A3.to_u32(), 0);
+
+                syscall = spim_syscall;
+
+                // none of the frontends can write to files, but they can all print,
+                // so writes to stdout and stderr are printed instead
+                if syscall == SYS15_WRITE {
+                    let state = self.timeline.state();
+                    let fd = try_owned_self!(self, state.read_register(Register::A0.to_u32()));
+
+                    if fd == 1 || fd == 2 {
+                        let buf = try_owned_self!(self, state.read_register(Register::A1.to_u32()));
+                        let len = try_owned_self!(self, state.read_register(Register::A2.to_u32()));
+                        let value = try_owned_self!(self, state.read_mem_bytes(buf as _, len as _));
+
+                        self.timeline
+                            .state_mut()
+                            .write_register(Register::V0.to_u32(), len);
+
+                        return Ok(RuntimeSyscallGuard::PrintString(
+                            PrintStringArgs { value },
+                            self,
+                        ));
+                    }
+                }
+            }
+        }
+
         Ok(match syscall {
             SYS1_PRINT_INT => {
                 let value = try_owned_self!(
@@ -294,10 +373,21 @@ impl Runtime {
                     self.timeline.state().read_register(Register::A0.to_u32())
                 );
                 let heap_size = self.timeline.state().heap_size();
+                let heap_bot = self.timeline.state().layout().heap_bot;
+                let site = self.timeline.state().last_pc();
+
+                self.timeline
+                    .state_mut()
+                    .write_register(Register::V0.to_u32(), (heap_bot + heap_size) as _);
 
                 self.timeline
                     .state_mut()
-                    .write_register(Register::V0.to_u32(), (HEAP_BOT + heap_size) as _);
+                    .record_heap_allocation(HeapAllocation {
+                        site,
+                        size: bytes,
+                        addr: heap_bot + heap_size,
+                        freed: 0,
+                    });
 
                 let new_heap_size = match bytes.cmp(&0) {
                     Ordering::Greater => heap_size.saturating_add(bytes as _),
@@ -306,6 +396,12 @@ impl Runtime {
                 };
                 self.timeline.state_mut().set_heap_size(new_heap_size);
 
+                if new_heap_size < heap_size {
+                    self.timeline
+                        .state_mut()
+                        .free_heap_allocations_above(heap_bot + new_heap_size);
+                }
+
                 RuntimeSyscallGuard::Sbrk(SbrkArgs { bytes }, self)
             }
             SYS10_EXIT => RuntimeSyscallGuard::Exit(self),
@@ -641,13 +737,21 @@ impl Runtime {
 
                     // JR   $Rs
                     0x08 => {
-                        state.set_pc(state.read_register(rs)? as u32);
+                        if rs == Register::Ra.to_u32() && state.pc() < KTEXT_BOT {
+                            state.call_depth = state.call_depth.saturating_sub(1);
+                        }
+
+                        state.jump(state.read_register(rs)? as u32);
                     }
 
                     // JALR $Rs
                     0x09 => {
-                        state.write_register(rd, state.pc() as _);
-                        state.set_pc(state.read_register(rs)? as _);
+                        if state.pc() < KTEXT_BOT {
+                            state.call_depth += 1;
+                        }
+
+                        state.write_register(rd, state.return_addr() as _);
+                        state.jump(state.read_register(rs)? as _);
                     }
 
                     // MOVZ $Rd, $Rs, $Rt
@@ -1097,6 +1201,7 @@ impl Runtime {
         imm: i16,
     ) -> MipsyResult<()> {
         let state = self.timeline.state_mut();
+        let bounds = self.data_bounds.as_ref();
 
         let imm_zero_extend = imm as u16 as u32 as i32;
         let imm_sign_extend = imm as i32;
@@ -1122,7 +1227,7 @@ impl Runtime {
 
                 // BLTZAL $Rs, Im
                 0x10 => {
-                    state.write_register(Register::Ra.to_number() as u32, state.pc() as _);
+                    state.write_register(Register::Ra.to_number() as u32, state.return_addr() as _);
 
                     if state.read_register(rs)? < 0 {
                         state.branch(imm);
@@ -1131,7 +1236,7 @@ impl Runtime {
 
                 // BGEZAL $Rs, Im
                 0x11 => {
-                    state.write_register(Register::Ra.to_number() as u32, state.pc() as _);
+                    state.write_register(Register::Ra.to_number() as u32, state.return_addr() as _);
 
                     if state.read_register(rs)? >= 0 {
                         state.branch(imm);
@@ -1229,19 +1334,26 @@ impl Runtime {
 
             // LB   $Rt, Im($Rs)
             0x20 => {
-                state.write_register_uninit(
-                    rt,
-                    state
-                        .read_mem_byte_uninit(
-                            state.read_register(rs)?.wrapping_add(imm_sign_extend) as _,
-                        )?
-                        .extend_sign(),
-                );
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
+
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    1,
+                    SegmentationFaultAccessType::Read,
+                )?;
+
+                state.write_register_uninit(rt, state.read_mem_byte_uninit(addr)?.extend_sign());
             }
 
             // LH   $Rt, Im($Rs)
             0x21 => {
-                let addr = state.read_register(rs)?.wrapping_add(imm_sign_extend) as _;
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
 
                 if addr % 2 != 0 {
                     return Err(MipsyError::Runtime(RuntimeError::new(
@@ -1252,6 +1364,16 @@ impl Runtime {
                     )));
                 }
 
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    2,
+                    SegmentationFaultAccessType::Read,
+                )?;
+
                 state.write_register_uninit(rt, state.read_mem_half_uninit(addr)?.extend_sign());
             }
 
@@ -1262,7 +1384,8 @@ impl Runtime {
 
             // LW   $Rt, Im($Rs)
             0x23 => {
-                let addr = state.read_register(rs)?.wrapping_add(imm_sign_extend) as _;
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
 
                 if addr % 4 != 0 {
                     return Err(MipsyError::Runtime(RuntimeError::new(
@@ -1273,24 +1396,41 @@ impl Runtime {
                     )));
                 }
 
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    4,
+                    SegmentationFaultAccessType::Read,
+                )?;
+
                 state.write_register_uninit(rt, state.read_mem_word_uninit(addr)?.extend_sign());
             }
 
             // LBU  $Rt, Im($Rs)
             0x24 => {
-                state.write_register_uninit(
-                    rt,
-                    state
-                        .read_mem_byte_uninit(
-                            state.read_register(rs)?.wrapping_add(imm_sign_extend) as _,
-                        )?
-                        .extend_zero(),
-                );
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
+
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    1,
+                    SegmentationFaultAccessType::Read,
+                )?;
+
+                state.write_register_uninit(rt, state.read_mem_byte_uninit(addr)?.extend_zero());
             }
 
             // LHU  $Rt, Im($Rs)
             0x25 => {
-                let addr = state.read_register(rs)?.wrapping_add(imm_sign_extend) as _;
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
 
                 if addr % 2 != 0 {
                     return Err(MipsyError::Runtime(RuntimeError::new(
@@ -1301,6 +1441,16 @@ impl Runtime {
                     )));
                 }
 
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    2,
+                    SegmentationFaultAccessType::Read,
+                )?;
+
                 state.write_register_uninit(rt, state.read_mem_half_uninit(addr)?.extend_zero());
             }
 
@@ -1314,15 +1464,26 @@ impl Runtime {
 
             // SB   $Rt, Im($Rs)
             0x28 => {
-                state.write_mem_byte_uninit(
-                    state.read_register(rs)?.wrapping_add(imm_sign_extend) as _,
-                    state.read_register_uninit(rt).truncate(),
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
+
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    1,
+                    SegmentationFaultAccessType::Write,
                 )?;
+
+                state.write_mem_byte_uninit(addr, state.read_register_uninit(rt).truncate())?;
             }
 
             // SH   $Rt, Im($Rs)
             0x29 => {
-                let addr = state.read_register(rs)?.wrapping_add(imm_sign_extend) as _;
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
 
                 if addr % 2 != 0 {
                     return Err(MipsyError::Runtime(RuntimeError::new(
@@ -1333,6 +1494,16 @@ impl Runtime {
                     )));
                 }
 
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    2,
+                    SegmentationFaultAccessType::Write,
+                )?;
+
                 state.write_mem_half_uninit(addr, state.read_register_uninit(rt).truncate())?;
             }
 
@@ -1341,7 +1512,8 @@ impl Runtime {
 
             // SW   $Rt, Im($Rs)
             0x2B => {
-                let addr = state.read_register(rs)?.wrapping_add(imm_sign_extend) as _;
+                let base = state.read_register(rs)?;
+                let addr = base.wrapping_add(imm_sign_extend) as _;
 
                 if addr % 4 != 0 {
                     return Err(MipsyError::Runtime(RuntimeError::new(
@@ -1352,6 +1524,16 @@ impl Runtime {
                     )));
                 }
 
+                check_bounds(
+                    bounds,
+                    state,
+                    rs,
+                    base as _,
+                    addr,
+                    4,
+                    SegmentationFaultAccessType::Write,
+                )?;
+
                 state.write_mem_word_uninit(addr, state.read_register_uninit(rt).truncate())?;
             }
 
@@ -1431,12 +1613,16 @@ impl Runtime {
 
         match opcode {
             // J    addr
-            0x02 => state.set_pc((state.pc() & 0xF000_0000) | (target << 2)),
+            0x02 => state.jump((state.pc() & 0xF000_0000) | (target << 2)),
 
             // JAL  addr
             0x03 => {
-                state.write_register(Register::Ra.to_number() as u32, state.pc() as _);
-                state.set_pc((state.pc() & 0xF000_0000) | (target << 2));
+                if state.pc() < KTEXT_BOT {
+                    state.call_depth += 1;
+                }
+
+                state.write_register(Register::Ra.to_number() as u32, state.return_addr() as _);
+                state.jump((state.pc() & 0xF000_0000) | (target << 2));
             }
 
             _ => unreachable!(),
@@ -1761,26 +1947,40 @@ impl Runtime {
 
         Self {
             timeline: Timeline::new(initial_state),
+            data_bounds: None,
+            linux_syscalls: false,
         }
     }
 
     pub fn new(program: &Binary, args: &[&str]) -> Self {
-        let mut initial_state = State::default();
+        let layout = program.layout;
+        let mut initial_state = State {
+            layout,
+            ..Default::default()
+        };
 
-        Self::fill_all_state(TEXT_BOT, &program.text, &mut initial_state);
-        Self::fill_valid_state(DATA_BOT, &program.data, &mut initial_state);
+        Self::fill_all_state(layout.text_bot, &program.text, &mut initial_state);
+        Self::fill_valid_state(layout.data_bot, &program.data, &mut initial_state);
         Self::fill_all_state(KTEXT_BOT, &program.ktext, &mut initial_state);
         Self::fill_valid_state(KDATA_BOT, &program.kdata, &mut initial_state);
 
+        if let Some(entry) = program.entry {
+            initial_state.pc = entry;
+        }
+
+        initial_state.delay_slots = program.delay_slots;
+
         initial_state.registers[Register::Zero.to_number() as usize] = Safe::Valid(0);
-        initial_state.write_register(Register::Sp.to_number() as _, STACK_PTR as _);
-        initial_state.write_register(Register::Fp.to_number() as _, STACK_PTR as _);
-        initial_state.write_register(Register::Gp.to_number() as _, GLOBAL_PTR as _);
+        initial_state.write_register(Register::Sp.to_number() as _, layout.stack_ptr as _);
+        initial_state.write_register(Register::Fp.to_number() as _, layout.stack_ptr as _);
+        initial_state.write_register(Register::Gp.to_number() as _, layout.global_ptr as _);
 
         Self::include_args(&mut initial_state, args);
 
         Self {
             timeline: Timeline::new(initial_state),
+            data_bounds: None,
+            linux_syscalls: false,
         }
     }
 
@@ -1812,7 +2012,7 @@ impl Runtime {
         let total_strings_len = args.iter().fold(0, |len, string| len + string.len() + 1) as u32;
 
         // allocate total_strings_len on the stack
-        let strings_stack_addr = STACK_PTR - total_strings_len;
+        let strings_stack_addr = state.layout().stack_ptr - total_strings_len;
 
         // and then 4-byte align it
         let strings_stack_addr = strings_stack_addr - (strings_stack_addr % 4);
@@ -1851,6 +2051,21 @@ impl Runtime {
     }
 }
 
+fn check_bounds(
+    bounds: Option<&DataBounds>,
+    state: &State,
+    base_reg: u32,
+    base: u32,
+    addr: u32,
+    len: u32,
+    access: SegmentationFaultAccessType,
+) -> MipsyResult<()> {
+    match bounds {
+        Some(bounds) => bounds.check(state, base_reg, base, addr, len, access),
+        None => Ok(()),
+    }
+}
+
 fn checked_add(x: i32, y: i32) -> MipsyResult<i32> {
     match x.checked_add(y) {
         Some(z) => Ok(z),
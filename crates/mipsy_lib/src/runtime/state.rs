@@ -3,12 +3,13 @@ use std::{
     rc::Rc,
 };
 
+use serde::{Deserialize, Serialize};
+
 use super::{SafeToUninitResult, PAGE_SIZE};
 use crate::{
-    compile::TEXT_TOP,
     error::runtime::{self, RuntimeError, SegmentationFaultAccessType},
-    MipsyError, MipsyResult, Safe, Uninitialised, GLOBAL_BOT, HEAP_BOT, KTEXT_BOT, STACK_BOT,
-    STACK_TOP, TEXT_BOT,
+    inst::register::Register,
+    MemoryLayout, MipsyError, MipsyResult, Safe, Uninitialised, KTEXT_BOT,
 };
 
 pub const WRITE_MARKER_LO: u32 = 32;
@@ -107,14 +108,52 @@ impl Timeline {
     }
 }
 
+/// # A single call to `sbrk`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeapAllocation {
+    /// The address of the `syscall` instruction that called `sbrk`.
+    pub site: u32,
+    /// The number of bytes asked for (negative when shrinking the heap).
+    pub size: i32,
+    /// The address that was returned in `$v0`.
+    pub addr: u32,
+    /// The number of bytes at the end of the allocation that have since
+    /// been given back by shrinking the heap below them.
+    pub freed: u32,
+}
+
+impl HeapAllocation {
+    /// The number of bytes of the allocation that are still in the heap.
+    pub fn size_in_use(&self) -> u32 {
+        (self.size.max(0) as u32).saturating_sub(self.freed)
+    }
+
+    /// Whether an address is in the part of the allocation that's still in the heap.
+    pub fn contains(&self, addr: u32) -> bool {
+        addr >= self.addr && addr - self.addr < self.size_in_use()
+    }
+}
+
 pub struct State {
     pub(super) pages: HashMap<u32, Rc<[Safe<u8>; PAGE_SIZE]>>,
     pub(super) pc: u32,
+    /// The address of the instruction that led to this state.
+    pub(super) last_pc: u32,
+    /// Where a branch or jump is going, once the instruction in its delay slot has run.
+    pub(super) delayed_jump: Option<u32>,
+    /// Whether branches and jumps have delay slots, like on real hardware
+    /// (see [`Binary::delay_slots`](crate::Binary::delay_slots)).
+    pub(super) delay_slots: bool,
     pub(super) registers: [Safe<i32>; 32],
     pub(super) write_marker: u64,
+    pub(super) mem_write_marker: Option<(u32, u32)>,
+    pub(super) pointer_origins: [Option<u32>; 32],
     pub(super) hi: Safe<i32>,
     pub(super) lo: Safe<i32>,
     pub(super) heap_size: u32,
+    pub(super) heap_allocations: Rc<Vec<HeapAllocation>>,
+    pub(super) call_depth: u32,
+    pub(super) layout: MemoryLayout,
 }
 
 impl State {
@@ -126,6 +165,34 @@ impl State {
         self.pc = pc;
     }
 
+    /// The address of the instruction that was run to get to this state.
+    ///
+    /// This is usually just before the [`pc`](Self::pc),
+    /// other than after the delay slot of a branch that was taken.
+    pub fn last_pc(&self) -> u32 {
+        self.last_pc
+    }
+
+    /// Jumps to an address, which with delay slots
+    /// happens after the next instruction has run.
+    pub fn jump(&mut self, addr: u32) {
+        if self.delay_slots {
+            self.delayed_jump = Some(addr);
+        } else {
+            self.pc = addr;
+        }
+    }
+
+    /// The address that a `jal` (or any other instruction that links) returns to:
+    /// the next instruction, or the one after its delay slot.
+    pub fn return_addr(&self) -> u32 {
+        if self.delay_slots {
+            self.pc.wrapping_add(4)
+        } else {
+            self.pc
+        }
+    }
+
     pub fn heap_size(&self) -> u32 {
         self.heap_size
     }
@@ -134,6 +201,36 @@ impl State {
         self.heap_size = heap_size;
     }
 
+    /// The number of function calls (`jal`/`jalr`) made from user code
+    /// that haven't yet returned (with `jr $ra`).
+    pub fn call_depth(&self) -> u32 {
+        self.call_depth
+    }
+
+    pub fn layout(&self) -> &MemoryLayout {
+        &self.layout
+    }
+
+    /// Every call to `sbrk` so far, in the order they were made.
+    pub fn heap_allocations(&self) -> &[HeapAllocation] {
+        &self.heap_allocations
+    }
+
+    pub(super) fn record_heap_allocation(&mut self, allocation: HeapAllocation) {
+        Rc::make_mut(&mut self.heap_allocations).push(allocation);
+    }
+
+    /// Frees whatever part of each allocation is above `heap_top`,
+    /// once the heap has shrunk down to it.
+    pub(super) fn free_heap_allocations_above(&mut self, heap_top: u32) {
+        for allocation in Rc::make_mut(&mut self.heap_allocations) {
+            let end = allocation.addr + allocation.size.max(0) as u32;
+            let freed = end.saturating_sub(heap_top.max(allocation.addr));
+
+            allocation.freed = allocation.freed.max(freed);
+        }
+    }
+
     pub fn write_marker(&self) -> u64 {
         self.write_marker
     }
@@ -142,6 +239,19 @@ impl State {
         self.write_marker = write_marker;
     }
 
+    /// The contiguous range of memory (start address, length)
+    /// written to since this state was created, if any.
+    pub fn mem_write_marker(&self) -> Option<(u32, u32)> {
+        self.mem_write_marker
+    }
+
+    fn mark_mem_write(&mut self, address: u32) {
+        self.mem_write_marker = match self.mem_write_marker {
+            Some((start, len)) if start.wrapping_add(len) == address => Some((start, len + 1)),
+            _ => Some((address, 1)),
+        };
+    }
+
     pub fn registers(&self) -> &[Safe<i32>] {
         &self.registers
     }
@@ -199,19 +309,31 @@ impl State {
         address: u32,
         access: SegmentationFaultAccessType,
     ) -> MipsyResult<()> {
+        let layout = &self.layout;
+
         let segfault = match address {
             // TODO(zkol): Update this when exclusive range matching is stabilised
-            _ if address < TEXT_BOT => true,
-            _ if (TEXT_BOT..=TEXT_TOP).contains(&address) => false,
-            _ if (GLOBAL_BOT..HEAP_BOT).contains(&address) => false,
-            _ if (HEAP_BOT..STACK_BOT).contains(&address) => {
-                let heap_offset = address - HEAP_BOT;
+            _ if (layout.text_bot..=layout.text_top).contains(&address) => false,
+            _ if (layout.global_bot..layout.heap_bot).contains(&address) => false,
+            _ if (layout.heap_bot..layout.stack_bot).contains(&address) => {
+                let heap_offset = address - layout.heap_bot;
+
+                if heap_offset >= self.heap_size() && self.stack_overflowed() {
+                    return Err(MipsyError::Runtime(RuntimeError::new(
+                        runtime::Error::StackOverflow {
+                            addr: address,
+                            access,
+                            depth: self.call_depth,
+                            stack_size: layout.stack_size(),
+                        },
+                    )));
+                }
 
                 heap_offset >= self.heap_size()
             }
-            _ if (STACK_BOT..=STACK_TOP).contains(&address) => false,
+            _ if (layout.stack_bot..=layout.stack_top).contains(&address) => false,
             _ if address >= KTEXT_BOT => self.pc() < KTEXT_BOT,
-            _ => unreachable!(),
+            _ => true,
         };
 
         if segfault {
@@ -226,6 +348,17 @@ impl State {
         }
     }
 
+    /// Whether `$sp` has been moved below the bottom of the stack
+    /// (and isn't pointing into the heap instead).
+    fn stack_overflowed(&self) -> bool {
+        let sp = match self.registers[Register::Sp.to_number() as usize] {
+            Safe::Valid(sp) => sp as u32,
+            Safe::Uninitialised => return false,
+        };
+
+        sp < self.layout.stack_bot && sp >= self.layout.heap_bot + self.heap_size()
+    }
+
     pub fn read_mem_byte(&self, address: u32) -> MipsyResult<u8> {
         self.check_segfault(address, SegmentationFaultAccessType::Read)?;
 
@@ -318,6 +451,7 @@ impl State {
         let offset = Self::offset_in_page(address);
 
         page[offset as usize] = Safe::Valid(byte);
+        self.mark_mem_write(address);
 
         Ok(())
     }
@@ -349,6 +483,7 @@ impl State {
         let offset = Self::offset_in_page(address);
 
         page[offset as usize] = byte;
+        self.mark_mem_write(address);
 
         Ok(())
     }
@@ -408,11 +543,17 @@ impl State {
     }
 
     pub fn branch(&mut self, imm: i16) {
-        let imm = imm as i32 - 1; // branch offset is 1-based
+        // mipsy's branch offsets are from the branch itself,
+        // while hardware's are from the instruction after it (where the pc already is)
+        let imm = if self.delay_slots {
+            imm as i32
+        } else {
+            imm as i32 - 1
+        };
         let imm = imm * 4; // branch offset is in instructions
 
         let pc_offset = imm as u32;
-        self.pc = self.pc.wrapping_add(pc_offset);
+        self.jump(self.pc.wrapping_add(pc_offset));
     }
 
     fn get_page_index(address: u32) -> u32 {
@@ -464,11 +605,19 @@ impl Clone for State {
         Self {
             pages: cow_pages,
             pc: self.pc,
+            last_pc: self.last_pc,
+            delayed_jump: self.delayed_jump,
+            delay_slots: self.delay_slots,
             registers: self.registers,
             write_marker: 0,
+            mem_write_marker: None,
+            pointer_origins: self.pointer_origins,
             hi: self.hi,
             lo: self.lo,
             heap_size: self.heap_size,
+            heap_allocations: self.heap_allocations.clone(),
+            call_depth: self.call_depth,
+            layout: self.layout,
         }
     }
 }
@@ -478,9 +627,17 @@ impl Default for State {
         Self {
             pages: HashMap::new(),
             pc: KTEXT_BOT,
+            last_pc: KTEXT_BOT - 4,
+            delayed_jump: None,
+            delay_slots: false,
             heap_size: 0,
+            heap_allocations: Default::default(),
+            call_depth: 0,
+            layout: MemoryLayout::default(),
             registers: Default::default(),
             write_marker: 0,
+            mem_write_marker: None,
+            pointer_origins: [None; 32],
             hi: Default::default(),
             lo: Default::default(),
         }
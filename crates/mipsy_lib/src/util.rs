@@ -1,6 +1,7 @@
-use crate::{DATA_BOT, GLOBAL_BOT, KDATA_BOT, KTEXT_BOT, STACK_BOT, STACK_TOP, TEXT_BOT, TEXT_TOP};
 use serde::{Deserialize, Serialize};
 
+use crate::MemoryLayout;
+
 #[derive(Copy, Debug, Serialize, Deserialize)]
 pub enum Safe<T> {
     Valid(T),
@@ -85,27 +86,13 @@ pub enum Segment {
 }
 
 impl Segment {
+    #[deprecated(note = "segments can move, use `MemoryLayout::segment_start` instead")]
     pub fn get_lower_bound(&self) -> u32 {
-        match self {
-            Segment::None => 0,
-            Segment::Text => TEXT_BOT,
-            Segment::Data => DATA_BOT,
-            Segment::Stack => STACK_TOP,
-            Segment::KText => KTEXT_BOT,
-            Segment::KData => KDATA_BOT,
-        }
+        MemoryLayout::spim().segment_start(*self)
     }
 }
 
+#[deprecated(note = "segments can move, use `MemoryLayout::segment_of` instead")]
 pub fn get_segment(address: u32) -> Segment {
-    match address {
-        // TODO(zkol): Update this when exclusive range matching is stabilised
-        _ if address < TEXT_BOT => Segment::None,
-        _ if (TEXT_BOT..=TEXT_TOP).contains(&address) => Segment::Text,
-        _ if (GLOBAL_BOT..STACK_BOT).contains(&address) => Segment::Data,
-        _ if (STACK_BOT..=STACK_TOP).contains(&address) => Segment::Stack,
-        _ if (KTEXT_BOT..KDATA_BOT).contains(&address) => Segment::KText,
-        _ if address >= KDATA_BOT => Segment::KData,
-        _ => unreachable!(),
-    }
+    MemoryLayout::spim().segment_of(address)
 }
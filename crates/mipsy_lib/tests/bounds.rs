@@ -0,0 +1,162 @@
+mod common;
+
+use common::compile;
+use mipsy_lib::{error::runtime, MipsyError};
+
+const DATA: &str = "
+        .data
+numbers:
+        .word   1, 2, 3
+other:
+        .word   4
+";
+
+/// Runs `main` with the bounds check on, after which `numbers` and `other` are defined,
+/// giving back the runtime error it runs into, if any.
+fn run(main: &str) -> Result<(), runtime::Error> {
+    let source = format!("main:\n{}        jr      $ra\n{}", main, DATA);
+    let binary = compile(&source);
+
+    let mut runtime = mipsy_lib::runtime(&binary, &[]);
+    runtime.enable_bounds_check(&binary);
+
+    loop {
+        match runtime.step() {
+            Ok(Ok(next)) => runtime = next,
+            // the only syscall is the kernel's exit, once `main` returns
+            Ok(Err(_)) => return Ok(()),
+            Err((_, MipsyError::Runtime(error))) => return Err(error.error().clone()),
+            Err((_, error)) => panic!("not a runtime error: {:?}", error),
+        }
+    }
+}
+
+/// Where an out of bounds access of `numbers` was, and which label it landed in.
+fn out_of_bounds(result: Result<(), runtime::Error>) -> (u32, Option<String>) {
+    match result {
+        Err(runtime::Error::OutOfBounds {
+            addr,
+            label,
+            label_addr,
+            label_size,
+            into,
+            ..
+        }) => {
+            assert_eq!(label, "numbers");
+            assert_eq!(label_addr, 0x10010000);
+            assert_eq!(label_size, 12);
+
+            (addr, into)
+        }
+        result => panic!("expected an out of bounds access, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_in_bounds() {
+    assert_eq!(
+        run("
+        la      $t0, numbers
+        lw      $t1, 0($t0)
+        lw      $t1, 8($t0)
+        lb      $t1, 11($t0)
+        lw      $t1, other
+"),
+        Ok(())
+    );
+
+    // walking a pointer through the whole array
+    assert_eq!(
+        run("
+        la      $t0, numbers
+        addi    $t2, $t0, 12
+loop:
+        lw      $t1, ($t0)
+        addi    $t0, $t0, 4
+        bne     $t0, $t2, loop
+"),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_overrun() {
+    // into the next label
+    assert_eq!(
+        out_of_bounds(run("
+        la      $t0, numbers
+        lw      $t1, 12($t0)
+")),
+        (0x1001000c, Some("other".to_string()))
+    );
+
+    // by walking the pointer off the end, so that it points at `other`
+    assert_eq!(
+        out_of_bounds(run("
+        la      $t0, numbers
+        addi    $t0, $t0, 12
+        lw      $t1, ($t0)
+")),
+        (0x1001000c, Some("other".to_string()))
+    );
+}
+
+#[test]
+fn test_copied_pointer() {
+    // a copy still points into the object it was copied from
+    assert_eq!(
+        out_of_bounds(run("
+        la      $t0, numbers
+        move    $t1, $t0
+        addi    $t2, $t1, 8
+        lw      $t3, 4($t2)
+")),
+        (0x1001000c, Some("other".to_string()))
+    );
+
+    assert_eq!(
+        run("
+        la      $t0, numbers
+        move    $t1, $t0
+        lw      $t3, 8($t1)
+"),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_underrun() {
+    // a negative offset from the start of an object
+    let result = run("
+        la      $t0, other
+        lw      $t1, -4($t0)
+");
+    match result {
+        Err(runtime::Error::OutOfBounds {
+            addr, label, into, ..
+        }) => {
+            assert_eq!(addr, 0x10010008);
+            assert_eq!(label, "other");
+            assert_eq!(into.as_deref(), Some("numbers"));
+        }
+        result => panic!("expected an out of bounds access, got {:?}", result),
+    }
+
+    assert_eq!(
+        out_of_bounds(run("
+        la      $t0, numbers
+        lw      $t1, -4($t0)
+")),
+        (0x1000fffc, None)
+    );
+
+    // but not from the end of the object before it, which is the same address
+    assert_eq!(
+        run("
+        la      $t0, numbers
+        addi    $t0, $t0, 12
+        lw      $t1, -4($t0)
+"),
+        Ok(())
+    );
+}
@@ -0,0 +1,58 @@
+use mipsy_lib::error::codes::{explain, EXPLANATIONS};
+
+/// The sources of the parser, compiler and runtime errors,
+/// whose `code()`s give every code as a string literal.
+const ERRORS: &[(&str, &str)] = &[
+    ("parser", include_str!("../src/error/parser/mod.rs")),
+    ("compiler", include_str!("../src/error/compiler/mod.rs")),
+    ("runtime", include_str!("../src/error/runtime/mod.rs")),
+];
+
+/// Every `"Mxxxx"` in a source file.
+fn codes(source: &str) -> Vec<&str> {
+    source
+        .match_indices("\"M")
+        .filter_map(|(start, _)| source.get(start + 1..start + 7))
+        .filter_map(|code| code.strip_suffix('"'))
+        .filter(|code| code[1..].chars().all(|c| c.is_ascii_digit()))
+        .collect()
+}
+
+#[test]
+fn test_every_code_is_explained() {
+    for &(errors, source) in ERRORS {
+        let codes = codes(source);
+        assert!(!codes.is_empty(), "no codes found in the {} errors", errors);
+
+        for code in codes {
+            assert!(
+                explain(code).is_some(),
+                "{} (a {} error) has no explanation",
+                code,
+                errors
+            );
+        }
+    }
+
+    // including the newest, for labels only macros may define
+    assert!(codes(ERRORS[0].1).contains(&"M0025"));
+}
+
+#[test]
+fn test_explain() {
+    assert_eq!(explain("m0105"), explain("M0105"));
+    assert_eq!(explain(" M0105\n"), explain("M0105"));
+    assert_eq!(explain("M9999"), None);
+
+    // each code is explained once, in its own file
+    for (i, &(code, explanation)) in EXPLANATIONS.iter().enumerate() {
+        assert!(!explanation.trim().is_empty(), "{}", code);
+        assert!(
+            EXPLANATIONS[i + 1..]
+                .iter()
+                .all(|&(other, _)| other != code),
+            "{} is explained twice",
+            code
+        );
+    }
+}
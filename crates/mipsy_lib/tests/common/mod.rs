@@ -0,0 +1,32 @@
+//! Helpers shared by the integration tests, not all of which use every one.
+#![allow(dead_code)]
+
+use mipsy_lib::{compile::CompilerOptions, Binary, MipsyError};
+use mipsy_parser::TaggedFile;
+use mipsy_utils::MipsyConfig;
+
+/// Compiles a program as the single file `test.s`.
+pub fn compile(source: &str) -> Binary {
+    try_compile(source).unwrap()
+}
+
+/// Compiles a program as the single file `test.s`,
+/// giving back the error if it doesn't compile.
+pub fn try_compile(source: &str) -> Result<Binary, Box<MipsyError>> {
+    compile_with(source, &CompilerOptions::default(), &MipsyConfig::default())
+}
+
+/// Compiles a program as the single file `test.s`, with the given options and config.
+pub fn compile_with(
+    source: &str,
+    options: &CompilerOptions,
+    config: &MipsyConfig,
+) -> Result<Binary, Box<MipsyError>> {
+    mipsy_lib::compile(
+        &mipsy_instructions::inst_set(),
+        vec![TaggedFile::new(Some("test.s"), source)],
+        options,
+        config,
+    )
+    .map_err(Box::new)
+}
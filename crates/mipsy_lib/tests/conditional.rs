@@ -0,0 +1,87 @@
+mod common;
+
+use common::compile_with;
+use mipsy_lib::compile::CompilerOptions;
+use mipsy_utils::MipsyConfig;
+
+/// What `ANSWER` is defined as, given these `-D` defines.
+fn answer(source: &str, defines: Vec<(String, i64)>) -> Option<i64> {
+    let binary = compile_with(
+        source,
+        &CompilerOptions::new(vec![], defines),
+        &MipsyConfig::default(),
+    )
+    .unwrap();
+
+    binary.constants.get("ANSWER").copied()
+}
+
+#[test]
+fn test_constants_in_conditionals() {
+    let source = "
+        .ifdef SOLUTION
+ANSWER = 42
+        .else
+ANSWER = 0
+        .endif
+main:   jr $ra
+    ";
+
+    assert_eq!(answer(source, vec![("SOLUTION".to_string(), 1)]), Some(42));
+    assert_eq!(answer(source, vec![]), Some(0));
+}
+
+#[test]
+fn test_preprocessor_directives_in_conditionals() {
+    // `-D` decides which `.eqv` and macro are defined
+    let source = "
+        .ifdef SOLUTION
+        .eqv VALUE 42
+        .macro answer
+ANSWER = VALUE
+        .end_macro
+        .else
+        .eqv VALUE 0
+        .macro answer
+ANSWER = VALUE - 1
+        .end_macro
+        .endif
+
+        answer
+main:   jr $ra
+    ";
+
+    assert_eq!(answer(source, vec![("SOLUTION".to_string(), 1)]), Some(42));
+    assert_eq!(answer(source, vec![]), Some(-1));
+
+    // and whether a file is included at all
+    let source = ".ifdef DEBUG\n.include \"debug.s\"\n.endif\nANSWER = 1\nmain: jr $ra\n";
+
+    assert_eq!(answer(source, vec![]), Some(1));
+
+    let error = compile_with(
+        source,
+        &CompilerOptions::new(vec![], vec![("DEBUG".to_string(), 1)]),
+        &MipsyConfig::default(),
+    )
+    .unwrap_err();
+
+    assert_eq!(error.code(), "M0021");
+}
+
+#[test]
+fn test_set() {
+    let source = "
+        .set noreorder
+        .set SIZE, 4
+        .if SIZE - 4
+ANSWER = 0
+        .else
+ANSWER = SIZE * 2
+        .endif
+main:   jr $ra
+    ";
+
+    assert_eq!(answer(source, vec![]), Some(8));
+    assert_eq!(answer(source, vec![("SIZE".to_string(), 5)]), Some(0));
+}
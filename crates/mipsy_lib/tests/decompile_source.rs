@@ -0,0 +1,46 @@
+mod common;
+
+use common::{compile, try_compile};
+use mipsy_lib::decompile::source::decompile_to_source;
+
+#[test]
+fn test_round_trip() {
+    let iset = mipsy_instructions::inst_set();
+    let test_files = concat!(env!("CARGO_MANIFEST_DIR"), "/../../test_files/success");
+
+    let mut paths = std::fs::read_dir(test_files)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let binary = compile(&source);
+        let decompiled = decompile_to_source(&binary, &iset);
+
+        let recompiled = try_compile(&decompiled)
+            .unwrap_or_else(|err| panic!("{}: {:?}\n{}", path.display(), err, decompiled));
+
+        for (segment, original, recompiled) in [
+            ("text", &binary.text, &recompiled.text),
+            ("data", &binary.data, &recompiled.data),
+            ("ktext", &binary.ktext, &recompiled.ktext),
+            ("kdata", &binary.kdata, &recompiled.kdata),
+        ] {
+            assert_eq!(
+                original,
+                recompiled,
+                "{}: {} differs\n{}",
+                path.display(),
+                segment,
+                decompiled
+            );
+        }
+
+        assert_eq!(binary.globals, recompiled.globals, "{}", path.display());
+    }
+}
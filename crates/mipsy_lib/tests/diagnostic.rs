@@ -0,0 +1,214 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::{compile, try_compile};
+use mipsy_lib::{
+    diagnostic::{sarif, Diagnostic},
+    MipsyError, Runtime,
+};
+use mipsy_utils::MipsyConfig;
+use serde_json::{json, Value};
+
+/// The diagnostic for the error a program fails to compile with.
+fn compile_error(source: &str) -> Diagnostic {
+    let config = MipsyConfig::default();
+
+    match try_compile(source).map_err(|error| *error) {
+        Err(MipsyError::Parser(error)) => Diagnostic::from_parser_error(&error, &config, source),
+        Err(MipsyError::Compiler(error)) => {
+            Diagnostic::from_compiler_error(&error, &config, source)
+        }
+        result => panic!("expected a compile error, got {:?}", result.map(|_| ())),
+    }
+}
+
+/// The diagnostic for the first runtime error a program runs into.
+fn runtime_error(source: &str) -> Diagnostic {
+    let iset = mipsy_instructions::inst_set();
+    let binary = compile(source);
+    let mut runtime = mipsy_lib::runtime(&binary, &[]);
+
+    let (runtime, error): (Runtime, _) = loop {
+        match runtime.step() {
+            Ok(Ok(next)) => runtime = next,
+            Ok(Err(_)) => panic!("the program shouldn't make a syscall"),
+            Err((runtime, MipsyError::Runtime(error))) => break (runtime, error),
+            Err((_, error)) => panic!("not a runtime error: {:?}", error),
+        }
+    };
+
+    let sources = [(Rc::from("test.s"), Rc::from(source))];
+
+    Diagnostic::from_runtime_error(&error, &sources, &iset, &binary, &runtime)
+}
+
+/// The results of a SARIF log of the diagnostics, checking it has a rule for each of them.
+fn sarif_results(diagnostics: &[Diagnostic]) -> Value {
+    let log: Value = serde_json::from_str(&sarif(diagnostics)).unwrap();
+    let run = &log["runs"][0];
+
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(run["tool"]["driver"]["name"], "mipsy");
+
+    for diagnostic in diagnostics {
+        let rule = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|rule| rule["id"] == *diagnostic.code)
+            .unwrap();
+
+        // error codes are explained, lints aren't
+        assert_eq!(
+            rule.get("shortDescription").is_some(),
+            diagnostic.code.starts_with('M'),
+            "{}",
+            diagnostic.code
+        );
+    }
+
+    run["results"].clone()
+}
+
+#[test]
+fn test_parser_error() {
+    let diagnostic = compile_error("main:\n\tli $v0 1\n");
+
+    assert_eq!(
+        diagnostic.to_json(),
+        r#"{"code":"M0001","severity":"error","message":"expected a `,` between arguments, found `1`","location":{"file":"test.s","line":2,"col":9,"col_end":null},"expansion":[],"tips":["arguments are separated by commas, try adding a `,` before `1`"],"runtime":null}"#
+    );
+
+    // the column is counted in characters, not with the tab expanded
+    assert_eq!(
+        sarif_results(&[diagnostic]),
+        json!([{
+            "ruleId": "M0001",
+            "level": "error",
+            "message": {
+                "text": "expected a `,` between arguments, found `1`\ntip: arguments are separated by commas, try adding a `,` before `1`",
+            },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "test.s" },
+                    "region": { "startLine": 2, "startColumn": 9 },
+                },
+            }],
+        }])
+    );
+}
+
+#[test]
+fn test_compiler_error() {
+    let diagnostic = compile_error("main:\n    j    nowhere\n");
+
+    assert_eq!(
+        diagnostic.to_json(),
+        r#"{"code":"M0105","severity":"error","message":"cannot find label `nowhere` in program","location":{"file":"test.s","line":2,"col":10,"col_end":17},"expansion":[],"tips":[],"runtime":null}"#
+    );
+
+    assert_eq!(
+        sarif_results(&[diagnostic]),
+        json!([{
+            "ruleId": "M0105",
+            "level": "error",
+            "message": { "text": "cannot find label `nowhere` in program" },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "test.s" },
+                    "region": { "startLine": 2, "startColumn": 10, "endColumn": 17 },
+                },
+            }],
+        }])
+    );
+}
+
+#[test]
+fn test_warning() {
+    let source = "main:\n    jr   $ra\n\nunused:\n    jr   $ra\n";
+    let config = MipsyConfig::default();
+
+    let diagnostics: Vec<_> = compile(source)
+        .warnings
+        .iter()
+        .map(|warning| Diagnostic::from_warning(warning, &config, source))
+        .collect();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].to_json(),
+        r#"{"code":"unused_label","severity":"warning","message":"the label `unused` is never used","location":{"file":"test.s","line":4,"col":1,"col_end":8},"expansion":[],"tips":["if this label is used by another file, mark it with `.globl`"],"runtime":null}"#
+    );
+
+    assert_eq!(
+        sarif_results(&diagnostics),
+        json!([{
+            "ruleId": "unused_label",
+            "level": "warning",
+            "message": {
+                "text": "the label `unused` is never used\ntip: if this label is used by another file, mark it with `.globl`",
+            },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "test.s" },
+                    "region": { "startLine": 4, "startColumn": 1, "endColumn": 8 },
+                },
+            }],
+        }])
+    );
+}
+
+#[test]
+fn test_runtime_error() {
+    // fails on the second instruction `add` expands into,
+    // with `$ra` still returning into the kernel, past 0x80000000
+    let diagnostic = runtime_error("main:\n    li   $t1, 1\n    add  $t0, $t2, 5\n");
+
+    assert_eq!(
+        diagnostic.to_json(),
+        r#"{"code":"M0202","severity":"error","message":"your program tried to read uninitialised register $t2","location":{"file":"test.s","line":3,"col":null,"col_end":null},"expansion":[],"tips":[],"runtime":{"pc":4194312,"instruction":"add $t0, $t2, $at","registers":[{"name":"$zero","value":0},{"name":"$at","value":5},{"name":"$a0","value":0},{"name":"$a1","value":0},{"name":"$t1","value":1},{"name":"$k0","value":4194304},{"name":"$gp","value":268468224},{"name":"$sp","value":2147483644},{"name":"$fp","value":2147483644},{"name":"$ra","value":2147483660}],"memory":null}}"#
+    );
+
+    let runtime = serde_json::to_value(&diagnostic.runtime).unwrap();
+
+    assert_eq!(
+        sarif_results(&[diagnostic]),
+        json!([{
+            "ruleId": "M0202",
+            "level": "error",
+            "message": { "text": "your program tried to read uninitialised register $t2" },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "test.s" },
+                    "region": { "startLine": 3 },
+                },
+            }],
+            "properties": { "runtime": runtime },
+        }])
+    );
+}
+
+#[test]
+fn test_runtime_error_memory() {
+    let diagnostic =
+        runtime_error("main:\n    la   $t1, x\n    lw   $t0, 1($t1)\n\n    .data\nx:  .word 7\n");
+
+    assert_eq!(
+        diagnostic.to_json(),
+        r#"{"code":"M0203","severity":"error","message":"unaligned access: 0x10010001 is not divisible by 4","location":{"file":"test.s","line":3,"col":null,"col_end":null},"expansion":[],"tips":["you may have forgotten to multiply an index by 4 (or use a `lb` instruction instead)"],"runtime":{"pc":4194312,"instruction":"lw $t0, 1($t1)","registers":[{"name":"$zero","value":0},{"name":"$a0","value":0},{"name":"$a1","value":0},{"name":"$t1","value":268500992},{"name":"$k0","value":4194304},{"name":"$gp","value":268468224},{"name":"$sp","value":2147483644},{"name":"$fp","value":2147483644},{"name":"$ra","value":2147483660}],"memory":{"addr":268500993,"start":268500992,"bytes":[7,0,0,0,null,null,null,null,null,null,null,null,null,null,null,null]}}}"#
+    );
+}
+
+#[test]
+fn test_runtime_error_past_text() {
+    // running off the end of the program isn't on any line
+    let diagnostic = runtime_error("main:\n    li   $t0, 1\n");
+
+    assert_eq!(diagnostic.code, "M0201");
+    assert_eq!(
+        diagnostic.message,
+        "could not find instruction at 0x00400004"
+    );
+    assert_eq!(diagnostic.location, None);
+}
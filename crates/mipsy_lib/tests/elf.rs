@@ -0,0 +1,463 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::compile;
+use mipsy_lib::{
+    elf::{read_elf, write_elf, ElfError},
+    runtime::RuntimeSyscallGuard,
+    Binary, Runtime, Safe, KDATA_BOT, KTEXT_BOT,
+};
+
+const TEXT_BOT: u32 = 0x0040_0000;
+
+const PT_LOAD: u32 = 1;
+const PF_X_R: u32 = 5;
+const PF_W_R: u32 = 6;
+
+/// What `mipsel-linux-gnu-gcc -static -nostdlib -Wl,--strip-all` makes of some
+/// (already assembled) code: just the ELF header and some loadable segments,
+/// given as their address, flags and contents, starting at the first.
+fn executable(segments: &[(u32, u32, &[u8])]) -> Vec<u8> {
+    let mut file = vec![];
+
+    file.extend_from_slice(b"\x7fELF\x01\x01\x01");
+    file.resize(16, 0);
+    file.extend_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    file.extend_from_slice(&8u16.to_le_bytes()); // EM_MIPS
+    file.extend_from_slice(&1u32.to_le_bytes());
+    file.extend_from_slice(&segments[0].0.to_le_bytes());
+    file.extend_from_slice(&52u32.to_le_bytes());
+    file.extend_from_slice(&0u32.to_le_bytes());
+    file.extend_from_slice(&0x7000_1007u32.to_le_bytes()); // as gcc sets for mips32r2
+    file.extend_from_slice(&52u16.to_le_bytes());
+    file.extend_from_slice(&32u16.to_le_bytes());
+    file.extend_from_slice(&(segments.len() as u16).to_le_bytes());
+    file.extend_from_slice(&40u16.to_le_bytes());
+    file.extend_from_slice(&[0; 4]);
+
+    let mut offset = 52 + 32 * segments.len() as u32;
+
+    for &(addr, flags, contents) in segments {
+        let size = contents.len() as u32;
+
+        for field in [PT_LOAD, offset, addr, addr, size, size, flags, 4] {
+            file.extend_from_slice(&field.to_le_bytes());
+        }
+
+        offset += size;
+    }
+
+    for &(_, _, contents) in segments {
+        file.extend_from_slice(contents);
+    }
+
+    file
+}
+
+fn words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+fn u16_at(elf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(elf[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(elf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(elf[offset..offset + 4].try_into().unwrap())
+}
+
+fn string_at(elf: &[u8], offset: usize) -> String {
+    let len = elf[offset..].iter().position(|&byte| byte == 0).unwrap();
+
+    String::from_utf8(elf[offset..offset + len].to_vec()).unwrap()
+}
+
+/// The name of each section, and where its header is.
+fn sections(elf: &[u8]) -> Vec<(String, usize)> {
+    let headers = u32_at(elf, 32) as usize;
+    let names = headers + u16_at(elf, 50) as usize * 40;
+    let names = u32_at(elf, names + 16) as usize;
+
+    (0..u16_at(elf, 48) as usize)
+        .map(|index| headers + index * 40)
+        .map(|header| (string_at(elf, names + u32_at(elf, header) as usize), header))
+        .collect()
+}
+
+/// Where the header of the section with some name is.
+fn section(elf: &[u8], name: &str) -> usize {
+    sections(elf)
+        .into_iter()
+        .find(|(section, _)| section == name)
+        .unwrap()
+        .1
+}
+
+/// Runs a program until it exits, giving back its exit status and the final `$ra`.
+fn run(binary: &Binary) -> (i32, i32) {
+    let mut runtime = Runtime::new(binary, &[]);
+    runtime.enable_linux_syscalls();
+
+    for _ in 0..1000 {
+        runtime = match runtime.step() {
+            Ok(Ok(runtime)) => runtime,
+            Ok(Err(RuntimeSyscallGuard::ExitStatus(args, runtime))) => {
+                let ra = runtime.timeline().state().read_register(31).unwrap();
+
+                return (args.exit_code, ra);
+            }
+            Ok(Err(_)) => panic!("unexpected syscall"),
+            Err((_, err)) => panic!("{:?}", err),
+        };
+    }
+
+    panic!("the program never exited");
+}
+
+#[test]
+fn test_hardware_delay_slots() {
+    // the encodings are from `llvm-mc -triple=mipsel-linux-gnu -show-encoding`
+    let code = words(&[
+        0x2408_0003, // __start: li    $t0, 3
+        0x2409_0000, //          li    $t1, 0
+        0x2508_ffff, // loop:    addiu $t0, $t0, -1
+        0x1500_fffe, //          bne   $t0, $zero, loop
+        0x2529_0001, //          addiu $t1, $t1, 1      (delay slot)
+        0x0c10_0009, //          jal   f
+        0x0120_2025, //          move  $a0, $t1         (delay slot)
+        0x2402_0fa1, //          li    $v0, 4001        (exit)
+        0x0000_000c, //          syscall
+        0x03e0_0008, // f:       jr    $ra
+        0x0004_2040, //          sll   $a0, $a0, 1      (delay slot)
+    ]);
+    let elf = executable(&[(TEXT_BOT, PF_X_R, &code)]);
+
+    let binary = read_elf(&elf).unwrap();
+    assert!(binary.delay_slots);
+
+    // the loop's delay slot runs each of the 3 times around,
+    // then `f` doubles that and returns past the `jal`'s delay slot
+    assert_eq!(run(&binary), (6, TEXT_BOT as i32 + 7 * 4));
+}
+
+#[test]
+fn test_mipsy_elf_has_delay_slots() {
+    let source = "
+main:
+    li    $t0, 3
+    li    $a0, 0
+loop:
+    addiu $t0, $t0, -1
+    addiu $a0, $a0, 1
+    bne   $t0, $zero, loop
+
+    jal   f
+    li    $v0, 4001
+    syscall
+
+f:
+    sll   $a0, $a0, 1
+    jr    $ra
+";
+
+    let binary = compile(source);
+    assert!(!binary.delay_slots);
+
+    let elf = read_elf(&write_elf(&binary)).unwrap();
+    assert!(elf.delay_slots);
+
+    // both return to the `li` after the `jal`, which is past a `nop` after the `bne`
+    // and another in the `jal`'s delay slot once written out
+    assert_eq!(run(&binary), (6, binary.labels["loop"] as i32 + 4 * 4));
+    assert_eq!(run(&elf), (6, elf.labels["loop"] as i32 + 6 * 4));
+}
+
+#[test]
+fn test_write_delay_slots() {
+    let binary = compile(
+        "
+main:
+    la    $t0, f
+loop:
+    addiu $t1, $t1, 1
+    bne   $t1, $t2, loop
+    jal   f
+    jr    $ra
+f:
+    jr    $ra
+",
+    );
+
+    let elf = read_elf(&write_elf(&binary)).unwrap();
+    let main = binary.labels["main"];
+
+    let text = |binary: &Binary| {
+        binary.text[(main - TEXT_BOT) as usize..]
+            .chunks_exact(4)
+            .map(|word| {
+                let bytes = word.iter().map(|byte| *byte.as_option().unwrap());
+
+                u32::from_le_bytes(bytes.collect::<Vec<_>>().try_into().unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // `f` moves down past the three delay slots before it
+    let f = binary.labels["f"] + 3 * 4;
+    assert_eq!(elf.labels["f"], f);
+    assert_eq!(elf.labels["loop"], binary.labels["loop"]);
+
+    assert_eq!(
+        text(&elf),
+        [
+            0x3c08_0000 | (f >> 16),    // lui   $t0, %hi(f)
+            0x3508_0000 | (f & 0xffff), // ori   $t0, $t0, %lo(f)
+            0x2529_0001,                // loop: addiu $t1, $t1, 1
+            0x152a_fffe,                //       bne   $t1, $t2, loop
+            0x0000_0000,                //       nop
+            0x0c00_0000 | (f >> 2),     //       jal   f
+            0x0000_0000,                //       nop
+            0x03e0_0008,                //       jr    $ra
+            0x0000_0000,                //       nop
+            0x03e0_0008,                // f:    jr    $ra
+            0x0000_0000,                //       nop
+        ]
+    );
+
+    // whereas mipsy's own branches count from the branch itself
+    assert_eq!(text(&binary)[3], 0x152a_ffff);
+}
+
+#[test]
+fn test_write_elf() {
+    let binary = compile(
+        "
+    .globl main
+main:
+    la    $t0, value
+    lw    $t1, ($t0)
+    beq   $t1, $zero, .Ldone
+    jal   helper
+.Ldone:
+    jr    $ra
+
+helper:
+    jr    $ra
+
+    .data
+value:
+    .word 42
+",
+    );
+
+    let elf = write_elf(&binary);
+    let written = read_elf(&elf).unwrap();
+
+    assert_eq!(&elf[..7], b"\x7fELF\x01\x01\x01");
+    assert_eq!(u16_at(&elf, 16), 2); // ET_EXEC
+    assert_eq!(u16_at(&elf, 18), 8); // EM_MIPS
+    assert_eq!(u32_at(&elf, 24), KTEXT_BOT);
+    assert_eq!(u32_at(&elf, 36), 0x5000_1001); // mips32, o32 and noreorder
+
+    // the code has delay slots added, so it's compared with what's read back
+    let segments = [
+        (binary.layout.text_bot, &written.text, PF_X_R),
+        (binary.layout.data_bot, &binary.data, PF_W_R),
+        (KTEXT_BOT, &written.ktext, PF_X_R),
+        (KDATA_BOT, &binary.kdata, PF_W_R),
+    ];
+    assert_eq!(u16_at(&elf, 44) as usize, segments.len());
+
+    for (index, (addr, bytes, flags)) in segments.into_iter().enumerate() {
+        let header = u32_at(&elf, 28) as usize + index * 32;
+        let offset = u32_at(&elf, header + 4);
+
+        assert_eq!(u32_at(&elf, header), PT_LOAD);
+        assert_eq!(offset % 0x1000, addr % 0x1000);
+        assert_eq!(u32_at(&elf, header + 8), addr);
+        assert_eq!(u32_at(&elf, header + 16) as usize, bytes.len());
+        assert_eq!(u32_at(&elf, header + 24), flags);
+
+        let offset = offset as usize;
+        for (byte, &expected) in elf[offset..offset + bytes.len()].iter().zip(bytes) {
+            assert_eq!(*byte, expected.as_option().copied().unwrap_or(0));
+        }
+    }
+
+    let names = sections(&elf)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "",
+            ".text",
+            ".data",
+            ".ktext",
+            ".kdata",
+            ".symtab",
+            ".strtab",
+            ".debug_line",
+            ".comment",
+            ".shstrtab",
+        ]
+    );
+
+    let symtab = section(&elf, ".symtab");
+    let strtab = u32_at(&elf, section(&elf, ".strtab") + 16) as usize;
+    let first_global = u32_at(&elf, symtab + 28) as usize;
+
+    let symbols = (u32_at(&elf, symtab + 16) as usize..)
+        .step_by(16)
+        .take(u32_at(&elf, symtab + 20) as usize / 16)
+        .enumerate()
+        .map(|(index, symbol)| {
+            let name = string_at(&elf, strtab + u32_at(&elf, symbol) as usize);
+            let addr = u32_at(&elf, symbol + 4);
+            let info = elf[symbol + 12];
+            let section = u16_at(&elf, symbol + 14);
+
+            // locals all come before the globals
+            assert_eq!(info >> 4 == 1, index >= first_global, "{name}");
+
+            (name, (addr, info, section))
+        })
+        .collect::<HashMap<_, _>>();
+
+    // STB_GLOBAL and STT_NOTYPE, in `.text`
+    assert_eq!(symbols["main"], (binary.labels["main"], 0x10, 1));
+    // STB_LOCAL and STT_NOTYPE, in `.text`, after the delay slots of the `beq`,
+    // `jal` and `jr` before it
+    assert_eq!(
+        symbols["helper"],
+        (binary.labels["helper"] + 3 * 4, 0x00, 1)
+    );
+    // STB_LOCAL and STT_OBJECT, in `.data`
+    assert_eq!(symbols["value"], (binary.labels["value"], 0x01, 2));
+    assert!(!symbols.contains_key(".Ldone"));
+}
+
+#[test]
+fn test_round_trip() {
+    let binary = compile(
+        "
+    .globl main
+main:
+    la    $t0, numbers
+    lw    $a0, 4($t0)
+    li    $v0, 1
+    syscall
+    jr    $ra
+
+    .data
+numbers:
+    .word 1, 2, 3
+    .space 6
+message:
+    .asciiz \"hi\"
+",
+    );
+
+    let elf = read_elf(&write_elf(&binary)).unwrap();
+
+    // uninitialised bytes are written as zero
+    let zeroed = |bytes: &[Safe<u8>]| {
+        bytes
+            .iter()
+            .map(|byte| byte.as_option().copied().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+
+    // the only jump in `main` is its last instruction, which gets a delay slot
+    let mut text = binary.text.clone();
+    text.extend([Safe::Valid(0); 4]);
+
+    assert_eq!(elf.text, text);
+    assert_eq!(zeroed(&elf.data), zeroed(&binary.data));
+    assert!(elf.ktext.len() > binary.ktext.len());
+    assert_eq!(zeroed(&elf.kdata), zeroed(&binary.kdata));
+
+    assert_eq!(elf.layout.text_bot, binary.layout.text_bot);
+    assert_eq!(elf.layout.data_bot, binary.layout.data_bot);
+    assert_eq!(elf.entry, Some(KTEXT_BOT));
+
+    for label in ["main", "numbers", "message"] {
+        assert_eq!(elf.labels[label], binary.labels[label], "{label}");
+    }
+    assert!(elf.globals.contains(&"main".to_string()));
+    assert!(!elf.globals.contains(&"numbers".to_string()));
+}
+
+#[test]
+fn test_truncated() {
+    let elf = write_elf(&compile("main: jr $ra"));
+    assert!(read_elf(&elf).is_ok());
+
+    assert_eq!(read_elf(&elf[..3]), Err(ElfError::NotElf));
+
+    // cutting the file off anywhere is never worse than an error
+    for len in (4..elf.len()).step_by(7) {
+        match read_elf(&elf[..len]) {
+            Ok(_) | Err(ElfError::Truncated) => {}
+            Err(err) => panic!("{len}: {err}"),
+        }
+    }
+
+    assert_eq!(read_elf(&elf[..60]), Err(ElfError::Truncated));
+}
+
+#[test]
+fn test_overflowing_offsets() {
+    let elf = write_elf(&compile("main: jr $ra"));
+
+    let patched = |offset: usize, value: u32| {
+        let mut elf = elf.clone();
+        elf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+
+        read_elf(&elf)
+    };
+
+    // the program headers, section headers and symbol table
+    assert_eq!(patched(28, 0xffff_fff0), Err(ElfError::Truncated));
+    assert_eq!(patched(32, 0xffff_fff0), Err(ElfError::Truncated));
+    assert_eq!(
+        patched(section(&elf, ".symtab") + 16, 0xffff_fff0),
+        Err(ElfError::Truncated)
+    );
+    assert_eq!(
+        patched(section(&elf, ".symtab") + 20, 0xffff_fff0),
+        Err(ElfError::Truncated)
+    );
+}
+
+#[test]
+fn test_sparse_segments() {
+    let code = words(&[0x0000_000c]);
+    let data = words(&[1, 2]);
+
+    let elf = read_elf(&executable(&[
+        (TEXT_BOT, PF_X_R, &code),
+        (0x1001_0000, PF_W_R, &data),
+        (0x1001_1000, PF_W_R, &data),
+    ]))
+    .unwrap();
+
+    // the gap between the data segments is there, but uninitialised
+    assert_eq!(elf.layout.data_bot, 0x1001_0000);
+    assert_eq!(elf.data.len(), 0x1008);
+    assert_eq!(elf.data[4], Safe::Valid(2));
+    assert_eq!(elf.data[8], Safe::Uninitialised);
+
+    let far_apart = executable(&[
+        (TEXT_BOT, PF_X_R, &code),
+        (0x1001_0000, PF_W_R, &data),
+        (0x7001_0000, PF_W_R, &data),
+    ]);
+
+    assert!(matches!(
+        read_elf(&far_apart),
+        Err(ElfError::Unsupported { .. })
+    ));
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::compile;
+use mipsy_lib::{
+    compile::TEXT_BOT,
+    decompile::{decompile_into_parts, fold_pseudo_instructions},
+    Binary, KTEXT_BOT,
+};
+
+/// The text segment's instructions after folding, with `...` for each one folded away.
+fn fold(binary: &Binary) -> Vec<String> {
+    let iset = mipsy_instructions::inst_set();
+
+    let mut parts = decompile_into_parts(binary, &iset)
+        .into_iter()
+        .filter(|&(addr, _)| addr < KTEXT_BOT)
+        .filter_map(|(_, part)| part.ok())
+        .collect::<Vec<_>>();
+
+    fold_pseudo_instructions(binary, &iset, &mut parts);
+    parts.sort_by_key(|part| part.addr);
+
+    parts
+        .into_iter()
+        .map(|part| match part.folded {
+            true => String::from("..."),
+            false => format!(
+                "{} {}",
+                part.inst_name.unwrap_or_default(),
+                part.arguments.join(", ")
+            )
+            .trim_end()
+            .to_string(),
+        })
+        .collect()
+}
+
+const PROGRAM: &str = "
+main:
+    la   $t0, msg
+    li   $t1, 0x12345678
+    li   $t2, 4
+    move $t3, $t2
+    jr   $ra
+
+    .data
+msg: .word 1
+";
+
+#[test]
+fn test_pseudo_instructions() {
+    assert_eq!(
+        fold(&compile(PROGRAM)),
+        [
+            "la $t0, msg",
+            "...",
+            "li $t1, 305419896",
+            "...",
+            "li $t2, 4",
+            "move $t3, $t2",
+            "jr $ra",
+        ]
+    );
+}
+
+#[test]
+fn test_line_mismatch() {
+    // the second half of `la` on a line of its own
+    let mut binary = compile(PROGRAM);
+    binary
+        .line_numbers
+        .insert(TEXT_BOT + 4, (Rc::from("test.s"), 3));
+
+    assert_eq!(
+        fold(&binary),
+        [
+            "lui $t0, 4097",
+            "ori $t0, $t0, 0",
+            "li $t1, 305419896",
+            "...",
+            "li $t2, 4",
+            "move $t3, $t2",
+            "jr $ra",
+        ]
+    );
+
+    // `la` and `li` as if they were one line
+    let mut binary = compile(PROGRAM);
+    binary.line_numbers.remove(&(TEXT_BOT + 8));
+
+    assert_eq!(
+        fold(&binary),
+        [
+            "lui $t0, 4097",
+            "ori $t0, $t0, 0",
+            "lui $t1, 4660",
+            "ori $t1, $t1, 22136",
+            "li $t2, 4",
+            "move $t3, $t2",
+            "jr $ra",
+        ]
+    );
+}
+
+#[test]
+fn test_label_inside_expansion() {
+    // something can jump to the `ori` on its own, so it's left to be seen
+    let mut binary = compile(PROGRAM);
+    binary.labels.insert(String::from("inside"), TEXT_BOT + 12);
+
+    assert_eq!(
+        fold(&binary),
+        [
+            "la $t0, msg",
+            "...",
+            "lui $t1, 4660",
+            "ori $t1, $t1, 22136",
+            "li $t2, 4",
+            "move $t3, $t2",
+            "jr $ra",
+        ]
+    );
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::try_compile;
+use mipsy_lib::format::format;
+use mipsy_utils::{FormatConfig, MipsyConfig, RegisterStyle};
+
+fn config(format: FormatConfig) -> MipsyConfig {
+    MipsyConfig {
+        format,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_test_files() {
+    let config = MipsyConfig::default();
+    let test_files = concat!(env!("CARGO_MANIFEST_DIR"), "/../../test_files");
+
+    let compile = |source: &str| {
+        try_compile(source)
+            .ok()
+            .map(|binary| (binary.text, binary.data, binary.labels))
+    };
+
+    for dir in [
+        "success",
+        "compile_error",
+        "runtime_error",
+        "spim_divergent",
+    ] {
+        for entry in std::fs::read_dir(format!("{}/{}", test_files, dir)).unwrap() {
+            let path = entry.unwrap().path();
+            let Ok(source) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            let formatted = format(&source, &config);
+
+            // formatting again doesn't change anything
+            assert_eq!(format(&formatted, &config), formatted, "{}", path.display());
+
+            // and the program is the same as it was
+            if dir == "success" {
+                assert_eq!(
+                    compile(&formatted),
+                    compile(&source),
+                    "{}\n{}",
+                    path.display(),
+                    formatted
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_registers() {
+    let source = "main: add $8, $sp, $t0 # sum\n";
+
+    for (registers, formatted) in [
+        (
+            RegisterStyle::Names,
+            "main:\n        add     $t0, $sp, $t0   # sum\n",
+        ),
+        (
+            RegisterStyle::Numbers,
+            "main:\n        add     $8, $29, $8     # sum\n",
+        ),
+        (
+            RegisterStyle::Keep,
+            "main:\n        add     $8, $sp, $t0    # sum\n",
+        ),
+    ] {
+        let config = config(FormatConfig {
+            registers,
+            ..Default::default()
+        });
+
+        assert_eq!(format(source, &config), formatted, "{:?}", registers);
+    }
+}
+
+#[test]
+fn test_hard_tabs() {
+    let config = config(FormatConfig {
+        hard_tabs: true,
+        ..Default::default()
+    });
+
+    assert_eq!(
+        format("main:\n  li $v0, 1 # print\n  syscall\n", &config),
+        "main:\n\tli\t$v0, 1\t# print\n\tsyscall\n"
+    );
+}
+
+#[test]
+fn test_comments() {
+    // comments line up until the next blank line, and blank lines are squashed
+    assert_eq!(
+        format(
+            "main:\n  li $v0, 1 # print\n  move $a0, $t0    # what\n\n\n\n  syscall # go\n",
+            &MipsyConfig::default(),
+        ),
+        "main:\n        li      $v0, 1          # print\n        move    $a0, $t0        # what\n\n        syscall # go\n"
+    );
+}
+
+#[test]
+fn test_unparseable() {
+    // only the trailing whitespace goes
+    assert_eq!(
+        format(
+            "main:\n   li $v0,,, 1   \n  syscall\n",
+            &MipsyConfig::default()
+        ),
+        "main:\n   li $v0,,, 1\n        syscall\n"
+    );
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use mipsy_lib::{
+    runtime::{HeapAllocation, RuntimeSyscallGuard},
+    Binary, Runtime,
+};
+
+/// Compiles a program whose `main` calls `sbrk` with each of `sizes`, then exits.
+fn compile(sizes: &[i32]) -> Binary {
+    let calls: String = sizes
+        .iter()
+        .map(|size| {
+            format!(
+                "        li      $a0, {}\n        li      $v0, 9\n        syscall\n",
+                size
+            )
+        })
+        .collect();
+    let source = format!("main:\n{}        li      $v0, 10\n        syscall\n", calls);
+
+    common::compile(&source)
+}
+
+/// Runs a program until it exits, giving back its allocations and where the heap starts.
+fn allocations(binary: &Binary) -> (Vec<HeapAllocation>, u32) {
+    let mut runtime = Runtime::new(binary, &[]);
+
+    loop {
+        runtime = match runtime.step() {
+            Ok(Ok(runtime)) | Ok(Err(RuntimeSyscallGuard::Sbrk(_, runtime))) => runtime,
+            Ok(Err(RuntimeSyscallGuard::Exit(runtime))) => {
+                let state = runtime.timeline().state();
+
+                return (state.heap_allocations().to_vec(), state.layout().heap_bot);
+            }
+            Ok(Err(_)) => panic!("the program should only call sbrk and exit"),
+            Err((_, err)) => panic!("{:?}", err),
+        };
+    }
+}
+
+/// The line each allocation was made on.
+fn lines(binary: &Binary, allocations: &[HeapAllocation]) -> Vec<u32> {
+    allocations
+        .iter()
+        .map(|allocation| binary.line_numbers[&allocation.site].1)
+        .collect()
+}
+
+#[test]
+fn test_allocations() {
+    let binary = compile(&[16, 0, 8]);
+    let (allocations, heap_bot) = allocations(&binary);
+
+    let allocation = |size, addr| HeapAllocation {
+        site: 0,
+        size,
+        addr,
+        freed: 0,
+    };
+    let without_sites = allocations
+        .iter()
+        .map(|allocation| HeapAllocation {
+            site: 0,
+            ..allocation.clone()
+        })
+        .collect::<Vec<_>>();
+
+    // in the order they were made, from each `syscall`
+    assert_eq!(
+        without_sites,
+        [
+            allocation(16, heap_bot),
+            allocation(0, heap_bot + 16),
+            allocation(8, heap_bot + 16),
+        ]
+    );
+    assert_eq!(lines(&binary, &allocations), [4, 7, 10]);
+
+    let [first, empty, second] = &allocations[..] else {
+        panic!("expected 3 allocations, got {:?}", allocations)
+    };
+
+    assert!(first.contains(heap_bot));
+    assert!(first.contains(heap_bot + 15));
+    assert!(!first.contains(heap_bot + 16));
+    assert!(!first.contains(heap_bot - 1));
+
+    assert!(!empty.contains(heap_bot + 16));
+
+    assert!(second.contains(heap_bot + 16));
+    assert!(second.contains(heap_bot + 23));
+    assert!(!second.contains(heap_bot + 24));
+}
+
+#[test]
+fn test_shrinking() {
+    let (allocations, heap_bot) = allocations(&compile(&[16, 8, -12, 8]));
+    let [first, second, shrink, third] = &allocations[..] else {
+        panic!("expected 4 allocations, got {:?}", allocations)
+    };
+
+    // shrinking the heap back to `heap_bot + 12` frees the end of the first
+    // allocation, and all of the second
+    assert_eq!((first.freed, first.size_in_use()), (4, 12));
+    assert_eq!((second.freed, second.size_in_use()), (8, 0));
+    assert_eq!((shrink.size, shrink.addr), (-12, heap_bot + 24));
+    assert_eq!(shrink.size_in_use(), 0);
+
+    assert!(first.contains(heap_bot + 11));
+    assert!(!first.contains(heap_bot + 12));
+    assert!(!second.contains(heap_bot + 16));
+    assert!(!shrink.contains(heap_bot + 23));
+
+    // and what's allocated next belongs to the new allocation alone
+    assert_eq!((third.addr, third.freed), (heap_bot + 12, 0));
+    assert!(third.contains(heap_bot + 12));
+    assert!(third.contains(heap_bot + 19));
+    assert!(!third.contains(heap_bot + 20));
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use mipsy_lib::{
+    image::{write_image, ImageError, ImageFormat, ImageSegment},
+    Binary,
+};
+
+const DATA_BOT: u32 = 0x1001_0000;
+
+/// A word, 6 uninitialised bytes, then one more byte.
+fn compile() -> Binary {
+    let source = "
+main:
+    jr    $ra
+
+    .data
+    .word 0x11223344
+    .space 6
+    .byte 0xaa
+";
+
+    common::compile(source)
+}
+
+fn image(format: ImageFormat, base: Option<u32>) -> String {
+    let bytes = write_image(&compile(), ImageSegment::Data, format, base, 0xff).unwrap();
+
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn test_logisim() {
+    assert_eq!(
+        image(ImageFormat::Logisim, None),
+        "v2.0 raw\n11223344 ffffffff ffaaffff\n"
+    );
+}
+
+#[test]
+fn test_intel_hex() {
+    // the uninitialised bytes are skipped rather than padded
+    assert_eq!(
+        image(ImageFormat::IntelHex, None),
+        ":040000004433221152\n:01000A00AA4B\n:00000001FF\n"
+    );
+}
+
+#[test]
+fn test_raw() {
+    let binary = compile();
+
+    assert_eq!(
+        write_image(
+            &binary,
+            ImageSegment::Data,
+            ImageFormat::RawLittleEndian,
+            None,
+            0
+        )
+        .unwrap(),
+        [0x44, 0x33, 0x22, 0x11, 0, 0, 0, 0, 0, 0, 0xaa, 0]
+    );
+
+    assert_eq!(
+        write_image(
+            &binary,
+            ImageSegment::Data,
+            ImageFormat::RawBigEndian,
+            None,
+            0
+        )
+        .unwrap(),
+        [0x11, 0x22, 0x33, 0x44, 0, 0, 0, 0, 0, 0xaa, 0, 0]
+    );
+}
+
+#[test]
+fn test_mif() {
+    assert_eq!(
+        image(ImageFormat::Mif, None),
+        "DEPTH = 3;\nWIDTH = 32;\nADDRESS_RADIX = HEX;\nDATA_RADIX = HEX;\nCONTENT\nBEGIN\n\
+         0 : 11223344;\n1 : ffffffff;\n2 : ffaaffff;\nEND;\n"
+    );
+}
+
+#[test]
+fn test_coe() {
+    assert_eq!(
+        image(ImageFormat::Coe, None),
+        "memory_initialization_radix=16;\nmemory_initialization_vector=\n\
+         11223344,\nffffffff,\nffaaffff;\n"
+    );
+}
+
+#[test]
+fn test_base_address() {
+    // two words of padding before the segment starts
+    assert_eq!(
+        image(ImageFormat::Logisim, Some(DATA_BOT - 8)),
+        "v2.0 raw\nffffffff ffffffff 11223344 ffffffff ffaaffff\n"
+    );
+
+    // Intel HEX says where each record goes instead,
+    // including past 64KiB with an extended linear address
+    assert_eq!(
+        image(ImageFormat::IntelHex, Some(DATA_BOT - 8)),
+        ":04000800443322114A\n:01001200AA43\n:00000001FF\n"
+    );
+    assert_eq!(
+        image(ImageFormat::IntelHex, Some(DATA_BOT - 0x10000)),
+        ":020000040001F9\n:040000004433221152\n:01000A00AA4B\n:00000001FF\n"
+    );
+
+    assert_eq!(
+        write_image(
+            &compile(),
+            ImageSegment::Data,
+            ImageFormat::Logisim,
+            Some(DATA_BOT + 4),
+            0
+        ),
+        Err(ImageError::BaseAboveSegment {
+            base: DATA_BOT + 4,
+            segment_start: DATA_BOT,
+        })
+    );
+}
+
+#[test]
+fn test_padding() {
+    let binary = compile();
+
+    for pad in [0x00, 0x5a] {
+        let image = write_image(
+            &binary,
+            ImageSegment::Data,
+            ImageFormat::RawLittleEndian,
+            Some(DATA_BOT - 2),
+            pad,
+        )
+        .unwrap();
+
+        // the gap before the segment, the `.space`, and up to a whole word at the end
+        assert_eq!(
+            image,
+            [pad, pad, 0x44, 0x33, 0x22, 0x11, pad, pad, pad, pad, pad, pad, 0xaa, pad, pad, pad]
+        );
+    }
+}
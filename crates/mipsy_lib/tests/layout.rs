@@ -0,0 +1,168 @@
+mod common;
+
+use common::compile_with;
+use mipsy_lib::{
+    compile::CompilerOptions,
+    error::{compiler, runtime},
+    runtime::RuntimeSyscallGuard,
+    Binary, MemoryLayout, MipsyError,
+};
+use mipsy_utils::MipsyConfig;
+
+const STACK_OVERFLOW: &str = include_str!("../../../test_files/runtime_error/stack_overflow.s");
+
+fn compile(source: &str, config: &MipsyConfig) -> Binary {
+    compile_result(source, config).unwrap()
+}
+
+/// The error a program fails to compile with.
+fn compile_error(source: &str, config: &MipsyConfig) -> compiler::Error {
+    match compile_result(source, config).map_err(|error| *error) {
+        Err(MipsyError::Compiler(error)) => error.error().clone(),
+        result => panic!("expected a compile error, got {:?}", result.map(|_| ())),
+    }
+}
+
+fn compile_result(source: &str, config: &MipsyConfig) -> Result<Binary, Box<MipsyError>> {
+    compile_with(source, &CompilerOptions::default(), config)
+}
+
+/// The config `--layout <layout> [--stack-size <stack_size>]` gives.
+fn config(layout: &str, stack_size: Option<u32>) -> MipsyConfig {
+    MipsyConfig {
+        memory_layout: layout.to_string(),
+        stack_size,
+        ..MipsyConfig::default()
+    }
+}
+
+/// Runs a program until it exits, giving back its exit status,
+/// or until it runs into a runtime error.
+fn run(binary: &Binary) -> Result<i32, runtime::Error> {
+    let mut runtime = mipsy_lib::runtime(binary, &[]);
+
+    loop {
+        match runtime.step() {
+            Ok(Ok(next)) => runtime = next,
+            Ok(Err(RuntimeSyscallGuard::ExitStatus(args, _))) => return Ok(args.exit_code),
+            Ok(Err(_)) => panic!("the program should only exit"),
+            Err((_, MipsyError::Runtime(error))) => return Err(error.error().clone()),
+            Err((_, error)) => panic!("not a runtime error: {:?}", error),
+        }
+    }
+}
+
+#[test]
+fn test_presets() {
+    let source = "
+        .data
+x:      .word 42
+        .text
+main:
+        addi    $sp, $sp, -4
+        lw      $t0, x
+        sw      $t0, ($sp)
+        lw      $a0, ($sp)
+        li      $v0, 17
+        syscall
+";
+
+    for &preset in MemoryLayout::PRESETS {
+        let binary = compile(source, &config(preset, None));
+        let layout = MemoryLayout::preset(preset).unwrap();
+
+        assert_eq!(binary.layout, layout, "{}", preset);
+        assert_eq!(run(&binary), Ok(42), "{}", preset);
+    }
+
+    // a preset that doesn't exist falls back to SPIM's
+    let binary = compile(source, &config("nowhere", None));
+    assert_eq!(binary.layout, MemoryLayout::spim());
+}
+
+#[test]
+fn test_stack_overflow() {
+    for &preset in MemoryLayout::PRESETS {
+        for stack_size in [None, Some(256)] {
+            let binary = compile(STACK_OVERFLOW, &config(preset, stack_size));
+            let layout = MemoryLayout::preset(preset).unwrap();
+            let layout = match stack_size {
+                Some(size) => layout.with_stack_size(size),
+                None => layout,
+            };
+
+            assert_eq!(binary.layout, layout, "{} {:?}", preset, stack_size);
+            if let Some(size) = stack_size {
+                assert_eq!(layout.stack_size(), size);
+            }
+
+            match run(&binary) {
+                Err(runtime::Error::StackOverflow {
+                    addr,
+                    stack_size: reported,
+                    ..
+                }) => {
+                    assert_eq!(reported, layout.stack_size(), "{} {:?}", preset, stack_size);
+                    assert_eq!(addr, layout.stack_bot - 4, "{} {:?}", preset, stack_size);
+                }
+                result => panic!(
+                    "{} {:?}: expected a stack overflow, got {:?}",
+                    preset, stack_size, result
+                ),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_too_much_text() {
+    // 1025 instructions don't fit in the 4KiB of text `tiny` has, but do in SPIM's
+    let source = format!("main:\n{}", "        nop\n".repeat(1025));
+
+    compile(&source, &config("spim", None));
+
+    match compile_error(&source, &config("tiny", None)) {
+        compiler::Error::TooMuchText {
+            text_size,
+            max_size,
+        } => {
+            assert_eq!(text_size, 1025 * 4);
+            assert_eq!(max_size, MemoryLayout::tiny().max_text_size());
+        }
+        error => panic!("expected too much text, got {:?}", error),
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_segments() {
+    use mipsy_lib::util::{get_segment, Segment};
+
+    let spim = MemoryLayout::spim();
+
+    for addr in [
+        0, 0x00400000, 0x0FFFFFFF, 0x10000000, 0x10010000, 0x7FFEFFFF, 0x7FFF0000, 0x7FFFFFFF,
+        0x80000000, 0x90000000, 0xFFFFFFFF,
+    ] {
+        assert!(get_segment(addr) == spim.segment_of(addr), "{:#010x}", addr);
+    }
+
+    for segment in [
+        Segment::None,
+        Segment::Text,
+        Segment::Data,
+        Segment::Stack,
+        Segment::KText,
+        Segment::KData,
+    ] {
+        assert_eq!(segment.get_lower_bound(), spim.segment_start(segment));
+    }
+
+    // other layouts move the user segments, but not the kernel's
+    let tiny = MemoryLayout::tiny();
+    assert!(tiny.segment_of(0) == Segment::None);
+    assert!(tiny.segment_of(0x400) == Segment::Text);
+    assert!(tiny.segment_of(0x1000) == Segment::Data);
+    assert!(tiny.segment_of(0x3FFC) == Segment::Stack);
+    assert!(tiny.segment_of(0x80000000) == Segment::KText);
+}
@@ -0,0 +1,272 @@
+mod common;
+
+use common::{compile, try_compile};
+use mipsy_lib::{
+    error::compiler::{Error, Warning},
+    MipsyError,
+};
+
+/// The warnings a program compiles with for one lint, along with the line each is on.
+fn lints(source: &str, lint: &str) -> Vec<(u32, Warning)> {
+    compile(source)
+        .warnings
+        .iter()
+        .filter(|warning| warning.warning().lint() == lint)
+        .map(|warning| (warning.line(), warning.warning().clone()))
+        .collect()
+}
+
+/// The denied warning a program fails to compile with, along with the line it's on.
+fn denied(source: &str) -> (u32, Warning) {
+    match try_compile(source).map_err(|error| *error) {
+        Err(MipsyError::Compiler(error)) => match error.error() {
+            Error::DeniedLint { warning } => (error.line(), warning.clone()),
+            error => panic!("expected a denied lint, got {:?}", error),
+        },
+        result => panic!("expected a compile error, got {:?}", result.map(|_| ())),
+    }
+}
+
+fn unused(label: &str) -> Warning {
+    Warning::UnusedLabel {
+        label: label.to_string(),
+    }
+}
+
+fn expensive(inst_name: &str, native_count: usize, alternative: &str) -> Warning {
+    Warning::ExpensivePseudoInstruction {
+        inst_name: inst_name.to_string(),
+        native_count,
+        alternative: alternative.to_string(),
+    }
+}
+
+#[test]
+fn test_unused_label() {
+    let source = "
+.macro twice()
+again:
+        nop
+        nop
+.end_macro
+
+main:
+        jal     used
+        twice()
+        jr      $ra
+
+used:
+        la      $t0, value
+        jr      $ra
+
+unused:
+        jr      $ra
+
+        .globl  exported
+exported:
+        jr      $ra
+
+1:
+        jr      $ra
+
+        .data
+value:  .word   0
+";
+
+    // `main`, `.globl` labels, numeric labels and the labels macros make are all fine
+    assert_eq!(lints(source, "unused_label"), [(17, unused("unused"))]);
+}
+
+#[test]
+fn test_indentation() {
+    let source = "
+main:
+        li      $t0, 1
+li      $t1, 2
+
+    indented:
+        jal     indented
+        jr      $ra
+";
+
+    assert_eq!(
+        lints(source, "indentation"),
+        [
+            (4, Warning::UnindentedInstruction),
+            (
+                6,
+                Warning::IndentedLabel {
+                    label: "indented".to_string()
+                }
+            ),
+        ]
+    );
+
+    assert_eq!(lints("\nmain:\n        jr      $ra\n", "indentation"), []);
+}
+
+#[test]
+fn test_blank_line_before_label() {
+    let source = "
+main:
+        jal     after
+        jal     spaced
+        jr      $ra
+after:
+        li      $t0, 3
+.Lloop:
+        addi    $t0, $t0, -1
+        bnez    $t0, .Lloop
+1:
+        bnez    $t0, 1b
+
+spaced:
+        jr      $ra
+        .data
+data:
+        .word   0
+";
+
+    // local labels are inside a function, so don't need a blank line,
+    // and neither does a label after a directive
+    assert_eq!(
+        lints(source, "blank_line_before_label"),
+        [(
+            6,
+            Warning::NoBlankLineBeforeLabel {
+                label: "after".to_string()
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_pseudo_cost() {
+    let source = "
+main:
+        add     $t0, $t1, 5
+        addu    $t0, $t1, 5
+        and     $t0, $t1, 5
+        sltu    $t0, $t1, 5
+        add     $t0, $t1, 100000
+        sub     $t0, $t1, 5
+        addi    $t0, $t1, 5
+        jr      $ra
+";
+
+    // an immediate too big for `addi` still needs several instructions,
+    // and there isn't a `subi` to suggest
+    assert_eq!(
+        lints(source, "pseudo_cost"),
+        [
+            (3, expensive("add", 2, "addi")),
+            (4, expensive("addu", 2, "addiu")),
+            (5, expensive("and", 2, "andi")),
+            (6, expensive("sltu", 2, "sltiu")),
+        ]
+    );
+}
+
+#[test]
+fn test_allow() {
+    let source = "
+#![allow(unused_label)]
+
+main:
+        jr      $ra
+
+unused:
+        jr      $ra
+";
+
+    assert_eq!(lints(source, "unused_label"), []);
+    assert_eq!(
+        lints(&source.replace("allow", "warn"), "unused_label"),
+        [(7, unused("unused"))]
+    );
+}
+
+#[test]
+fn test_deny() {
+    let source = "
+main:
+        jr      $ra
+
+#[deny(unused_label)]
+unused:
+        jr      $ra
+";
+
+    assert_eq!(denied(source), (6, unused("unused")));
+}
+
+#[test]
+fn test_groups() {
+    let source = "
+main:
+li      $t0, 1
+    after:
+        add     $t0, $t0, 5
+        jr      $ra
+";
+
+    // `style` covers both `indentation` and `blank_line_before_label`, but nothing else
+    let allowed = format!("#![allow(style)]{}", source);
+    assert_eq!(lints(&allowed, "indentation"), []);
+    assert_eq!(lints(&allowed, "blank_line_before_label"), []);
+    assert_eq!(lints(&allowed, "unused_label"), [(4, unused("after"))]);
+    assert_eq!(
+        lints(&allowed, "pseudo_cost"),
+        [(5, expensive("add", 2, "addi"))]
+    );
+
+    // and `warnings` covers every lint
+    assert_eq!(
+        lints(&format!("#![allow(warnings)]{}", source), "pseudo_cost"),
+        []
+    );
+    assert_eq!(
+        denied(&format!("#![deny(unused)]{}", source)),
+        (4, unused("after"))
+    );
+}
+
+#[test]
+fn test_item_overrides_file() {
+    let source = "
+#![deny(unused)]
+
+main:
+        jr      $ra
+
+#[allow(unused_label)]
+allowed:
+        jr      $ra
+
+#[warn(unused_label)]
+warned:
+        jr      $ra
+";
+
+    assert_eq!(lints(source, "unused_label"), [(12, unused("warned"))]);
+
+    // but only on the item's own line
+    assert_eq!(
+        denied(&format!("{}\nunused:\n        jr      $ra\n", source)),
+        (15, unused("unused"))
+    );
+
+    // and the other way around
+    let source = "
+#![allow(warnings)]
+
+main:
+        jr      $ra
+
+#[deny(unused_label)]
+unused:
+        jr      $ra
+";
+
+    assert_eq!(denied(source), (8, unused("unused")));
+}
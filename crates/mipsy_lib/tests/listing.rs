@@ -0,0 +1,222 @@
+use std::rc::Rc;
+
+use mipsy_lib::{compile::CompilerOptions, listing::listing};
+use mipsy_parser::TaggedFile;
+use mipsy_utils::MipsyConfig;
+
+/// The listing of a program, including any files it includes.
+fn list(tag: &str, source: &str) -> String {
+    let iset = mipsy_instructions::inst_set();
+    let config = MipsyConfig::default();
+
+    let binary = mipsy_lib::compile(
+        &iset,
+        vec![TaggedFile::new(Some(tag), source)],
+        &CompilerOptions::default(),
+        &config,
+    )
+    .unwrap();
+
+    let files = [(Rc::from(tag), Rc::from(source))]
+        .into_iter()
+        .chain(binary.included_files.iter().cloned())
+        .collect::<Vec<_>>();
+
+    listing(&iset, &binary, &files, &config)
+}
+
+#[test]
+fn test_pseudo_instructions() {
+    let listing = list(
+        "test.s",
+        "
+main:
+    la   $a0, msg
+    li   $t0, 0x12345678
+    li   $v0, 4
+    jr   $ra
+
+    .data
+msg:
+    .byte 1
+",
+    );
+
+    assert_eq!(
+        listing,
+        r#"
+test.s
+    1
+    2                                                  main:
+    3  0x00400000  0x3c041001  lui    $a0, 4097            la   $a0, msg
+       0x00400004  0x34840000  ori    $a0, $a0, 0
+    4  0x00400008  0x3c081234  lui    $t0, 4660            li   $t0, 0x12345678
+       0x0040000c  0x35085678  ori    $t0, $t0, 22136
+    5  0x00400010  0x20020004  addi   $v0, $zero, 4        li   $v0, 4
+    6  0x00400014  0x03e00008  jr     $ra                  jr   $ra
+    7
+    8                                                      .data
+    9                                                  msg:
+   10  0x10010000  01                                      .byte 1
+
+text labels
+    main  0x00400000
+
+data labels
+    msg   0x10010000
+
+"#
+        .strip_prefix('\n')
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_data() {
+    let listing = list(
+        "test.s",
+        "
+main:
+    jr   $ra
+
+    .data
+msg:  .asciiz \"hello\"
+val:  .word 42, 7
+big:  .space 100
+h:    .half 3
+",
+    );
+
+    assert_eq!(
+        listing,
+        r#"
+test.s
+    1
+    2                                                  main:
+    3  0x00400000  0x03e00008  jr     $ra                  jr   $ra
+    4
+    5                                                      .data
+    6  0x10010000  68 65 6c 6c                         msg:  .asciiz "hello"
+       0x10010004  6f 00 .. ..
+    7  0x10010008  2a 00 00 00                         val:  .word 42, 7
+       0x1001000c  07 00 00 00
+    8  0x10010010  .. .. .. ..                         big:  .space 100
+       0x10010014  .. .. .. ..
+       0x10010018  .. .. .. ..
+       0x1001001c  .. .. .. ..
+       ... (84 more bytes)
+    9  0x10010074  03 00                               h:    .half 3
+
+text labels
+    main  0x00400000
+
+data labels
+    msg   0x10010000
+    val   0x10010008
+    big   0x10010010
+    h     0x10010074
+
+"#
+        .strip_prefix('\n')
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_included_file() {
+    let dir = std::env::temp_dir().join(format!("mipsy-listing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let included = dir.join("helper.s");
+    std::fs::write(
+        &included,
+        "helper:\n    jr   $ra\n\n    .data\nflag: .word 1\n",
+    )
+    .unwrap();
+
+    let main = dir.join("main.s");
+    let listing = list(
+        &main.to_string_lossy(),
+        "main:\n    jal  helper\n    jr   $ra\n\n    .include \"helper.s\"\n",
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let listing = listing.replace(&*dir.to_string_lossy(), "DIR");
+
+    assert_eq!(
+        listing,
+        r#"
+DIR/main.s
+    1                                                  main:
+    2  0x00400000  0x0c100002  jal    helper               jal  helper
+    3  0x00400004  0x03e00008  jr     $ra                  jr   $ra
+    4
+    5                                                      .include "helper.s"
+
+DIR/helper.s
+    1                                                  helper:
+    2  0x00400008  0x03e00008  jr     $ra                  jr   $ra
+    3
+    4                                                      .data
+    5  0x10010000  01 00 00 00                         flag: .word 1
+
+text labels
+    main    0x00400000
+    helper  0x00400008
+
+data labels
+    flag    0x10010000
+
+"#
+        .strip_prefix('\n')
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_symbols() {
+    let listing = list(
+        "test.s",
+        "
+SIZE = 4
+OFFSET = -8
+
+    .globl main
+main:
+    jr   $ra
+
+    .data
+array: .space SIZE
+",
+    );
+
+    assert_eq!(
+        listing,
+        r#"
+test.s
+    1
+    2                                                  SIZE = 4
+    3                                                  OFFSET = -8
+    4
+    5                                                      .globl main
+    6                                                  main:
+    7  0x00400000  0x03e00008  jr     $ra                  jr   $ra
+    8
+    9                                                      .data
+   10  0x10010000  .. .. .. ..                         array: .space SIZE
+
+text labels
+    main    0x00400000  (global)
+
+data labels
+    array   0x10010000
+
+constants
+    OFFSET  -8
+    SIZE    4
+"#
+        .strip_prefix('\n')
+        .unwrap()
+    );
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::compile;
+use mipsy_lib::{MipsyError, Runtime, RuntimeError};
+
+/// Runs a program up to its first runtime error,
+/// giving back the tips shown for it, without colours.
+fn tips(source: &str) -> Vec<String> {
+    let iset = mipsy_instructions::inst_set();
+    let binary = compile(source);
+
+    let mut runtime = mipsy_lib::runtime(&binary, &[]);
+
+    let (runtime, error): (Runtime, RuntimeError) = loop {
+        match runtime.step() {
+            Ok(Ok(next)) => runtime = next,
+            Ok(Err(_)) => panic!("the program shouldn't make a syscall"),
+            Err((runtime, MipsyError::Runtime(error))) => break (runtime, error),
+            Err((_, error)) => panic!("not a runtime error: {:?}", error),
+        }
+    };
+
+    let sources = [(Rc::from("test.s"), Rc::from(source))];
+
+    error
+        .error()
+        .tips(&sources, &iset, &binary, &runtime)
+        .into_iter()
+        .map(|tip| String::from_utf8(strip_ansi_escapes::strip(tip).unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_trace_through_memory() {
+    let tips = tips(
+        "
+        .data
+x:      .space 4
+        .text
+main:
+        lw      $t0, x
+        sw      $t0, -4($sp)
+        lw      $t1, -4($sp)
+        move    $a0, $t1
+        ",
+    );
+
+    assert_eq!(tips.len(), 1);
+    assert!(tips[0].starts_with("this is how the uninitialised value got here, through memory:"));
+    assert!(tips[0].contains("$t1 was loaded by `lw $t1, -4($sp)` on line 8"));
+    assert!(
+        tips[0].contains("was written by `sw $t0, -4($sp)` on line 7, while $t0 was uninitialised")
+    );
+    assert!(tips[0].contains("$t0 was loaded by `lw $t0, ($at)` on line 6 from 0x10010000"));
+    assert!(tips[0].contains("0x10010000 is `x`, which was never given an initial value"));
+}
+
+#[test]
+fn test_trace_stops_at_register_copy() {
+    // copying the value is where it's first read, so it's what fails,
+    // and there's nothing more to follow than the load before it
+    let tips = tips(
+        "
+        .data
+x:      .space 4
+        .text
+main:
+        lw      $t0, x
+        move    $t1, $t0
+        addu    $t2, $t1, $zero
+        ",
+    );
+
+    assert_eq!(tips.len(), 1);
+    assert!(tips[0].contains("$t0 was loaded by `lw $t0, ($at)` on line 6 from 0x10010000"));
+    assert!(!tips[0].contains("$t1"));
+}
+
+#[test]
+fn test_no_trace_for_unwritten_register() {
+    assert!(tips("main: move $a0, $t0").is_empty());
+}
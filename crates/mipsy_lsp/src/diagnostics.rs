@@ -0,0 +1,165 @@
+use std::rc::Rc;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+use mipsy_lib::{
+    compile::{get_kernel, CompilerOptions},
+    CompilerWarning, InstSet, MipsyError,
+};
+use mipsy_parser::{file_tab_size, MpMacroExpansion, TaggedFile};
+use mipsy_utils::MipsyConfig;
+
+use crate::document::Document;
+
+/// Where something went wrong, as the parser and compiler report it.
+struct Location<'a> {
+    file_tag: Rc<str>,
+    line: u32,
+    col: u32,
+    /// Where the problem ends, if it's known.
+    col_end: Option<u32>,
+    expansion: Option<&'a MpMacroExpansion>,
+}
+
+/// Compiles a document the same way as `mipsy --check`,
+/// giving back every error and warning in it.
+pub fn diagnostics(
+    iset: &InstSet,
+    config: &MipsyConfig,
+    file_tag: &str,
+    document: &Document,
+) -> Vec<Diagnostic> {
+    let files = vec![TaggedFile::new(Some(file_tag), document.text())];
+    let options = CompilerOptions::default();
+
+    let checked = mipsy_lib::check_with_kernel(iset, files, &mut get_kernel(), &options, config);
+    let tab_size = file_tab_size(document.text(), config.tab_size);
+
+    let diagnostic = |location: Location<'_>, severity, message, tips: Vec<String>, code| {
+        let (range, message) = range_of(document, file_tag, tab_size, location, message);
+
+        let message = std::iter::once(message)
+            .chain(tips.iter().map(|tip| tip.trim_end().to_string()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Diagnostic {
+            range,
+            severity: Some(severity),
+            code,
+            source: Some(String::from("mipsy")),
+            message,
+            ..Default::default()
+        }
+    };
+
+    match checked {
+        Ok(binary) => binary
+            .warnings
+            .iter()
+            .map(|warning| {
+                diagnostic(
+                    warning_location(warning),
+                    DiagnosticSeverity::WARNING,
+                    warning.warning().message(),
+                    warning.warning().tips(),
+                    Some(NumberOrString::String(warning.warning().lint().to_string())),
+                )
+            })
+            .collect(),
+        Err(errors) => errors
+            .iter()
+            .filter_map(|error| match error {
+                MipsyError::Parser(error) => Some(diagnostic(
+                    Location {
+                        file_tag: error.file_tag(),
+                        line: error.line(),
+                        col: error.col(),
+                        col_end: None,
+                        expansion: error.expansion(),
+                    },
+                    DiagnosticSeverity::ERROR,
+                    error.error().message(),
+                    error.error().tips(),
+                    Some(NumberOrString::String(error.error().code().to_string())),
+                )),
+                MipsyError::Compiler(error) => Some(diagnostic(
+                    Location {
+                        file_tag: error.file_tag(),
+                        line: error.line(),
+                        col: error.col(),
+                        col_end: Some(error.col_end()),
+                        expansion: error.expansion(),
+                    },
+                    DiagnosticSeverity::ERROR,
+                    error.error().message(),
+                    error.error().tips(),
+                    Some(NumberOrString::String(error.error().code().to_string())),
+                )),
+                MipsyError::Runtime(_) => None,
+            })
+            .collect(),
+    }
+}
+
+fn warning_location(warning: &CompilerWarning) -> Location<'_> {
+    Location {
+        file_tag: warning.file_tag(),
+        line: warning.line(),
+        col: warning.col(),
+        col_end: Some(warning.col_end()),
+        expansion: warning.expansion(),
+    }
+}
+
+/// Works out which part of the document a problem is in, which is where
+/// the outermost macro was called if it came from a macro, or the start of the
+/// document if it came from somewhere else entirely (like an included file).
+fn range_of(
+    document: &Document,
+    file_tag: &str,
+    tab_size: u32,
+    location: Location<'_>,
+    message: String,
+) -> (lsp_types::Range, String) {
+    let mut location = location;
+    let mut message = message;
+
+    if let Some(mut expansion) = location.expansion {
+        while let Some(parent) = expansion.parent() {
+            expansion = parent;
+        }
+
+        message = format!("{} (in an expansion of `{}`)", message, expansion.name());
+        location = Location {
+            file_tag: expansion.file_tag().unwrap_or(location.file_tag),
+            line: expansion.line(),
+            col: expansion.col(),
+            col_end: Some(expansion.col_end()),
+            expansion: None,
+        };
+    }
+
+    if !location.file_tag.is_empty() && &*location.file_tag != file_tag {
+        let range = document.range(0..0);
+
+        return (range, format!("{}: {}", location.file_tag, message));
+    }
+
+    let start = document.column_offset(location.line, location.col, tab_size);
+    let end = match location.col_end {
+        Some(col_end) if col_end > location.col => {
+            document.column_offset(location.line, col_end, tab_size)
+        }
+        // otherwise up to the end of whatever it starts at
+        _ => {
+            let rest = &document.text()[start..];
+
+            start
+                + rest
+                    .find(|c: char| c.is_whitespace() || c == ',' || c == '#')
+                    .unwrap_or(rest.len())
+        }
+    };
+
+    (document.range(start..end), message)
+}
@@ -0,0 +1,234 @@
+use std::ops::Range;
+
+use lsp_types::Position;
+use mipsy_parser::cst::{Cst, CstNode, CstToken, NodeKind, TokenKind};
+
+/// An open file, along with its syntax tree.
+pub struct Document {
+    text: String,
+    cst: Cst,
+    line_starts: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    TextLabel,
+    DataLabel,
+    /// A constant, from either `N = ...` or `.eqv N ...`.
+    Constant,
+    Macro,
+}
+
+/// Something defined in a document that can be referred to by name.
+pub struct Symbol<'a> {
+    pub kind: SymbolKind,
+    pub name: &'a CstToken,
+    /// The whole item that defines it.
+    pub item: &'a CstNode,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let cst = Cst::parse(&text);
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+
+        Self {
+            text,
+            cst,
+            line_starts,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// A line of the document (from 0), without its line ending.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.text.len());
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// The byte offset of a position, which counts UTF-16 code units along the line.
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+
+        let mut units = 0;
+        for (pos, c) in self.line(position.line as usize).char_indices() {
+            if units >= position.character as usize {
+                return start + pos;
+            }
+
+            units += c.len_utf16();
+        }
+
+        start + self.line(position.line as usize).len()
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let character = self.text[start..offset.min(self.text.len())]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, span: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    /// The byte offset of a column reported by the parser or compiler,
+    /// which count from 1, with tabs expanded to `tab_size`.
+    pub fn column_offset(&self, line: u32, col: u32, tab_size: u32) -> usize {
+        let line = (line as usize).saturating_sub(1);
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.text.len();
+        };
+
+        let target = col.saturating_sub(1) as usize;
+        let tab_size = tab_size.max(1) as usize;
+        let mut expanded = 0;
+
+        for (pos, c) in self.line(line).char_indices() {
+            if expanded >= target {
+                return start + pos;
+            }
+
+            expanded += match c {
+                '\t' => tab_size - expanded % tab_size,
+                _ => c.len_utf8(),
+            };
+        }
+
+        start + self.line(line).len()
+    }
+
+    /// The token the cursor is in (or just after, if it's at the end of a word).
+    pub fn token_at(&self, offset: usize) -> Option<&CstToken> {
+        let tokens = self.cst.tokens();
+
+        let containing = tokens
+            .iter()
+            .position(|token| token.span().contains(&offset))
+            .unwrap_or(tokens.len());
+
+        let before = containing
+            .checked_sub(1)
+            .map(|before| tokens[before])
+            .filter(|token| token.span().end == offset && !token.kind().is_trivia());
+
+        match tokens.get(containing) {
+            Some(token) if !token.kind().is_trivia() => Some(token),
+            _ => before,
+        }
+    }
+
+    /// The item (label, directive, instruction, ...) a byte offset falls in.
+    pub fn item_at(&self, offset: usize) -> Option<&CstNode> {
+        self.cst
+            .items()
+            .find(|item| item.span().start <= offset && offset <= item.span().end)
+    }
+
+    /// Everything defined in the document, in order.
+    pub fn symbols(&self) -> Vec<Symbol<'_>> {
+        let mut symbols = vec![];
+        let mut in_text = true;
+
+        for item in self.cst.items() {
+            let Some(first) = item.first_token() else {
+                continue;
+            };
+
+            let argument_name = || {
+                item.child_nodes()
+                    .next()
+                    .and_then(CstNode::first_token)
+                    .filter(|token| token.kind() == TokenKind::Ident)
+            };
+
+            let (kind, name) = match item.kind() {
+                NodeKind::Label if first.kind() != TokenKind::Number => {
+                    let kind = if in_text {
+                        SymbolKind::TextLabel
+                    } else {
+                        SymbolKind::DataLabel
+                    };
+
+                    (kind, first)
+                }
+                NodeKind::Constant => (SymbolKind::Constant, first),
+                NodeKind::Directive => match &*first.text().to_ascii_lowercase() {
+                    ".text" | ".ktext" => {
+                        in_text = true;
+                        continue;
+                    }
+                    ".data" | ".kdata" => {
+                        in_text = false;
+                        continue;
+                    }
+                    ".eqv" => match argument_name() {
+                        Some(name) => (SymbolKind::Constant, name),
+                        None => continue,
+                    },
+                    ".macro" => match argument_name() {
+                        Some(name) => (SymbolKind::Macro, name),
+                        None => continue,
+                    },
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            symbols.push(Symbol { kind, name, item });
+        }
+
+        symbols
+    }
+
+    /// Where the symbol with a given name is defined.
+    pub fn definition(&self, name: &str) -> Option<Symbol<'_>> {
+        self.symbols()
+            .into_iter()
+            .find(|symbol| symbol.name.text() == name)
+    }
+
+    /// Every use of a name, including where it's defined.
+    pub fn references(&self, name: &str) -> Vec<&CstToken> {
+        let mut references = vec![];
+
+        for item in self.cst.items() {
+            let tokens = match item.kind() {
+                // the name of an instruction can only refer to a macro
+                NodeKind::Instruction => item.tokens(),
+                NodeKind::Directive => item.child_nodes().flat_map(CstNode::tokens).collect(),
+                NodeKind::Label | NodeKind::Constant => item.first_token().into_iter().collect(),
+                _ => continue,
+            };
+
+            references.extend(tokens.into_iter().filter(|token| {
+                matches!(token.kind(), TokenKind::Ident | TokenKind::Directive)
+                    && token.text() == name
+            }));
+        }
+
+        references
+    }
+}
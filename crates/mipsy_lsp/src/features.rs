@@ -0,0 +1,364 @@
+use std::str::FromStr;
+
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, CompletionTextEdit,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind,
+    ReferenceParams, TextEdit,
+};
+use mipsy_lib::{InstSet, Register};
+use mipsy_parser::cst::TokenKind;
+
+use crate::{
+    document::{Document, SymbolKind},
+    Server,
+};
+
+pub fn hover(server: &Server, params: HoverParams) -> Option<Hover> {
+    let position = params.text_document_position_params;
+    let document = server.document(&position.text_document.uri)?;
+    let offset = document.offset(position.position);
+    let token = document.token_at(offset)?;
+
+    let docs = match token.kind() {
+        TokenKind::Register => register_docs(token.text())?,
+        TokenKind::Ident | TokenKind::Directive => {
+            let is_mnemonic = document
+                .item_at(offset)
+                .and_then(|item| item.first_token())
+                .is_some_and(|first| first.span() == token.span());
+
+            let instruction = is_mnemonic
+                .then(|| instruction_docs(&server.iset, token.text()))
+                .flatten();
+
+            instruction.or_else(|| symbol_docs(document, token.text()))?
+        }
+        _ => return None,
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: docs,
+        }),
+        range: Some(document.range(token.span())),
+    })
+}
+
+pub fn definition(server: &Server, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let position = params.text_document_position_params;
+    let uri = position.text_document.uri;
+    let document = server.document(&uri)?;
+
+    let token = document.token_at(document.offset(position.position))?;
+    let symbol = document.definition(token.text())?;
+
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        uri,
+        document.range(symbol.name.span()),
+    )))
+}
+
+pub fn references(server: &Server, params: ReferenceParams) -> Option<Vec<Location>> {
+    let position = params.text_document_position;
+    let uri = position.text_document.uri;
+    let document = server.document(&uri)?;
+
+    let token = document.token_at(document.offset(position.position))?;
+    let definition = document.definition(token.text())?;
+
+    let references = document
+        .references(token.text())
+        .into_iter()
+        .filter(|reference| {
+            params.context.include_declaration || reference.span() != definition.name.span()
+        })
+        .map(|reference| Location::new(uri.clone(), document.range(reference.span())))
+        .collect();
+
+    Some(references)
+}
+
+pub fn completion(server: &Server, params: CompletionParams) -> Option<CompletionResponse> {
+    let position = params.text_document_position;
+    let document = server.document(&position.text_document.uri)?;
+    let offset = document.offset(position.position);
+
+    // whatever's been typed so far is replaced by the completion
+    let replacing = document
+        .token_at(offset)
+        .filter(|token| {
+            matches!(
+                token.kind(),
+                TokenKind::Ident | TokenKind::Directive | TokenKind::Register
+            )
+        })
+        .map_or(offset..offset, |token| token.span());
+
+    let line_start = document.offset(lsp_types::Position::new(position.position.line, 0));
+    let before = &document.text()[line_start..replacing.start];
+
+    let item = |label: String, kind, detail: Option<&str>| CompletionItem {
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+            document.range(replacing.clone()),
+            label.clone(),
+        ))),
+        label,
+        kind: Some(kind),
+        detail: detail.map(String::from),
+        ..Default::default()
+    };
+
+    let mut items = vec![];
+    let symbols = document.symbols();
+
+    if is_mnemonic_position(before) {
+        let mut names: Vec<(String, Option<&str>)> = vec![];
+
+        for inst in server.iset.native_set() {
+            names.push((
+                inst.name().to_ascii_lowercase(),
+                inst.metadata().desc_short(),
+            ));
+        }
+
+        for inst in server.iset.pseudo_set() {
+            names.push((inst.name().to_ascii_lowercase(), None));
+        }
+
+        // the first signature with a description wins
+        names.sort_by(|(a, a_desc), (b, b_desc)| {
+            a.cmp(b).then(b_desc.is_some().cmp(&a_desc.is_some()))
+        });
+        names.dedup_by(|(a, _), (b, _)| a == b);
+
+        for (name, desc) in names {
+            items.push(item(name, CompletionItemKind::KEYWORD, desc));
+        }
+
+        for symbol in symbols
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::Macro)
+        {
+            items.push(item(
+                symbol.name.text().to_string(),
+                CompletionItemKind::FUNCTION,
+                Some("macro"),
+            ));
+        }
+    } else {
+        for register in Register::all() {
+            let detail = format!("${}", register.to_number());
+
+            items.push(item(
+                format!("${}", register.to_lower_str()),
+                CompletionItemKind::VARIABLE,
+                Some(&detail),
+            ));
+        }
+
+        for symbol in symbols
+            .iter()
+            .filter(|symbol| symbol.kind != SymbolKind::Macro)
+        {
+            let (kind, detail) = match symbol.kind {
+                SymbolKind::TextLabel => (CompletionItemKind::FUNCTION, "label"),
+                SymbolKind::DataLabel => (CompletionItemKind::FIELD, "label"),
+                _ => (CompletionItemKind::CONSTANT, "constant"),
+            };
+
+            items.push(item(symbol.name.text().to_string(), kind, Some(detail)));
+        }
+    }
+
+    Some(CompletionResponse::Array(items))
+}
+
+#[allow(deprecated)]
+pub fn document_symbols(
+    server: &Server,
+    params: DocumentSymbolParams,
+) -> Option<DocumentSymbolResponse> {
+    let document = server.document(&params.text_document.uri)?;
+
+    let symbols = document
+        .symbols()
+        .into_iter()
+        .map(|symbol| {
+            let (kind, detail) = match symbol.kind {
+                SymbolKind::TextLabel => (lsp_types::SymbolKind::FUNCTION, "label"),
+                SymbolKind::DataLabel => (lsp_types::SymbolKind::VARIABLE, "label"),
+                SymbolKind::Constant => (lsp_types::SymbolKind::CONSTANT, "constant"),
+                SymbolKind::Macro => (lsp_types::SymbolKind::FUNCTION, "macro"),
+            };
+
+            DocumentSymbol {
+                name: symbol.name.text().to_string(),
+                detail: Some(detail.to_string()),
+                kind,
+                tags: None,
+                deprecated: None,
+                range: document.range(symbol.item.span()),
+                selection_range: document.range(symbol.name.span()),
+                children: None,
+            }
+        })
+        .collect();
+
+    Some(DocumentSymbolResponse::Nested(symbols))
+}
+
+/// Whether what's before the cursor on a line leaves it
+/// at the name of an instruction, rather than an operand.
+fn is_mnemonic_position(before: &str) -> bool {
+    let mut rest = before.trim_start();
+
+    // skip over any labels
+    while let Some((label, after)) = rest.split_once(':') {
+        if label.trim().contains(char::is_whitespace) {
+            break;
+        }
+
+        rest = after.trim_start();
+    }
+
+    rest.is_empty() || rest.ends_with(';')
+}
+
+/// Every form of an instruction, along with what it does.
+fn instruction_docs(iset: &InstSet, name: &str) -> Option<String> {
+    let mut forms = vec![];
+
+    for inst in iset.native_set() {
+        if !inst.name().eq_ignore_ascii_case(name) {
+            continue;
+        }
+
+        let mut docs = format!(
+            "```mips\n{}\n```\n",
+            signature(inst.name(), inst.compile_signature().format())
+        );
+
+        if let Some(desc) = inst.metadata().desc_short() {
+            docs.push_str(&format!("{}\n", desc.trim()));
+        }
+
+        if let Some(desc) = inst.metadata().desc_long() {
+            docs.push_str(&format!("\n{}\n", desc.trim()));
+        }
+
+        forms.push(docs);
+    }
+
+    for inst in iset.pseudo_set() {
+        if !inst.name().eq_ignore_ascii_case(name) {
+            continue;
+        }
+
+        let expansion: Vec<String> = inst
+            .expansion()
+            .iter()
+            .map(|expand| {
+                format!(
+                    "{:7} {}",
+                    expand.inst().to_ascii_lowercase(),
+                    expand.data().join(", ")
+                )
+            })
+            .collect();
+
+        let docs = format!(
+            "```mips\n{}\n```\npseudo-instruction, which expands to:\n```mips\n{}\n```\n",
+            signature(inst.name(), inst.compile_signature().format()),
+            expansion.join("\n")
+        );
+
+        // derived forms can end up the same as a native instruction
+        if !forms
+            .iter()
+            .any(|form| form.starts_with(&docs[..docs.find("\n```\n").unwrap()]))
+        {
+            forms.push(docs);
+        }
+    }
+
+    if forms.is_empty() {
+        return None;
+    }
+
+    Some(forms.join("\n---\n\n"))
+}
+
+fn signature(name: &str, format: &[mipsy_lib::ArgumentType]) -> String {
+    let args: Vec<String> = format.iter().map(ToString::to_string).collect();
+
+    format!("{:7} {}", name.to_ascii_lowercase(), args.join(", "))
+        .trim_end()
+        .to_string()
+}
+
+fn register_docs(register: &str) -> Option<String> {
+    let parsed = Register::from_str(&register[1..]).ok()?;
+
+    let role = match parsed {
+        Register::Zero => "always holds 0, and ignores anything written to it",
+        Register::At => "reserved for the assembler, to expand pseudo-instructions",
+        Register::V0 | Register::V1 => {
+            "holds a function's return value (and `$v0` picks a syscall)"
+        }
+        Register::A0 | Register::A1 | Register::A2 | Register::A3 => "holds a function argument",
+        Register::T0
+        | Register::T1
+        | Register::T2
+        | Register::T3
+        | Register::T4
+        | Register::T5
+        | Register::T6
+        | Register::T7
+        | Register::T8
+        | Register::T9 => "a temporary, which isn't preserved across function calls",
+        Register::S0
+        | Register::S1
+        | Register::S2
+        | Register::S3
+        | Register::S4
+        | Register::S5
+        | Register::S6
+        | Register::S7 => "a saved register, which functions must preserve",
+        Register::K0 | Register::K1 => "reserved for the kernel",
+        Register::Gp => "the global pointer",
+        Register::Sp => "the stack pointer",
+        Register::Fp => "the frame pointer",
+        Register::Ra => "the return address, set by `jal`",
+    };
+
+    Some(format!(
+        "`${}` (`${}`): {}",
+        parsed.to_lower_str(),
+        parsed.to_number(),
+        role
+    ))
+}
+
+/// The line a label, constant or macro is defined on.
+fn symbol_docs(document: &Document, name: &str) -> Option<String> {
+    let symbol = document.definition(name)?;
+
+    let kind = match symbol.kind {
+        SymbolKind::TextLabel | SymbolKind::DataLabel => "label",
+        SymbolKind::Constant => "constant",
+        SymbolKind::Macro => "macro",
+    };
+
+    let line = document.position(symbol.item.span().start).line;
+    let source = document.line(line as usize).trim();
+
+    Some(format!(
+        "{} `{}`, defined on line {}\n```mips\n{}\n```",
+        kind,
+        name,
+        line + 1,
+        source
+    ))
+}
@@ -0,0 +1,444 @@
+//! # A language server for MIPS assembly.
+//!
+//! This speaks the Language Server Protocol over stdio (see `main.rs`),
+//! so that editors such as VS Code and Vim can show the same errors,
+//! warnings and tips as `mipsy --check` while a program is being written.
+//!
+//! It also provides:
+//! - hover documentation for instructions (from `mips.yaml`),
+//!   registers, labels and constants,
+//! - go-to-definition and find-references for labels, constants and macros,
+//! - completion of instruction names, registers, labels and constants,
+//! - the labels, constants and macros in a file, as document symbols.
+//!
+//! Files are always synced in full, and each is compiled on its own.
+
+mod diagnostics;
+mod document;
+mod features;
+
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as LspNotification, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References,
+        Request as LspRequest,
+    },
+    CompletionOptions, HoverProviderCapability, OneOf, PublishDiagnosticsParams,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use mipsy_lib::InstSet;
+use mipsy_utils::MipsyConfig;
+
+use crate::document::Document;
+
+pub type LspResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+struct Server {
+    iset: InstSet,
+    config: MipsyConfig,
+    documents: HashMap<Url, Document>,
+}
+
+/// Serves a client until it asks the server to shut down.
+pub fn run(connection: Connection, config: MipsyConfig) -> LspResult<()> {
+    // messages are shown as plain text
+    colored::control::set_override(false);
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![String::from("$"), String::from(".")]),
+            ..Default::default()
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        iset: mipsy_instructions::inst_set(),
+        config,
+        documents: HashMap::new(),
+    };
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.handle_notification(notification)? {
+                    connection.sender.send(Message::Notification(diagnostics))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl Server {
+    fn handle_request(&self, request: Request) -> Response {
+        let Request { id, method, params } = request;
+
+        let result = match method.as_str() {
+            HoverRequest::METHOD => serde_json::from_value(params)
+                .map(|params| serde_json::to_value(features::hover(self, params))),
+            GotoDefinition::METHOD => serde_json::from_value(params)
+                .map(|params| serde_json::to_value(features::definition(self, params))),
+            References::METHOD => serde_json::from_value(params)
+                .map(|params| serde_json::to_value(features::references(self, params))),
+            Completion::METHOD => serde_json::from_value(params)
+                .map(|params| serde_json::to_value(features::completion(self, params))),
+            DocumentSymbolRequest::METHOD => serde_json::from_value(params)
+                .map(|params| serde_json::to_value(features::document_symbols(self, params))),
+            _ => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(Ok(result)) => Response::new_ok(id, result),
+            Ok(Err(err)) | Err(err) => {
+                Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string())
+            }
+        }
+    }
+
+    /// Keeps track of open documents, giving back any new diagnostics to publish.
+    fn handle_notification(
+        &mut self,
+        notification: Notification,
+    ) -> LspResult<Option<Notification>> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: <DidOpenTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+
+                self.documents
+                    .insert(document.uri.clone(), Document::new(document.text));
+
+                document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: <DidChangeTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params)?;
+
+                // only full syncs are asked for, so the last change is the whole document
+                let Some(change) = params.content_changes.into_iter().last() else {
+                    return Ok(None);
+                };
+
+                self.documents
+                    .insert(params.text_document.uri.clone(), Document::new(change.text));
+
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: <DidCloseTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params)?;
+
+                self.documents.remove(&params.text_document.uri);
+
+                params.text_document.uri
+            }
+            _ => return Ok(None),
+        };
+
+        let diagnostics = match self.documents.get(&uri) {
+            Some(document) => {
+                diagnostics::diagnostics(&self.iset, &self.config, &file_tag(&uri), document)
+            }
+            None => vec![],
+        };
+
+        Ok(Some(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams::new(uri, diagnostics, None),
+        )))
+    }
+
+    fn document(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+}
+
+/// The name a document is compiled under, which `.include`s are relative to.
+fn file_tag(uri: &Url) -> String {
+    match uri.to_file_path() {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(()) => uri.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use lsp_server::RequestId;
+    use lsp_types::{
+        notification::Initialized, request::Initialize, CompletionParams, CompletionResponse,
+        DidOpenTextDocumentParams, DocumentSymbolParams, DocumentSymbolResponse,
+        GotoDefinitionParams, GotoDefinitionResponse, HoverContents, HoverParams, InitializeParams,
+        InitializedParams, NumberOrString, Position, ReferenceContext, ReferenceParams,
+        TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    };
+
+    use super::*;
+
+    const PROGRAM: &str = "\
+N = 10
+
+main:
+\tli\t$t0, N
+\tjal\tprint
+\tlw\t$t1, nums
+\tjr\t$ra
+
+print:
+\tmove\t$a0, $t0
+\tli\t$v0, 1
+\tsyscall
+\tjr\t$ra
+
+\t.data
+nums:\t.word 1, 2
+";
+
+    /// Talks to a server running on another thread, as an editor would.
+    struct Client {
+        connection: Connection,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> (Self, thread::JoinHandle<()>) {
+            let (server, client) = Connection::memory();
+            let handle = thread::spawn(move || run(server, MipsyConfig::default()).unwrap());
+
+            let mut client = Client {
+                connection: client,
+                next_id: 0,
+            };
+
+            client.request::<Initialize>(InitializeParams::default());
+            client.notify::<Initialized>(InitializedParams {});
+
+            (client, handle)
+        }
+
+        fn request<R: LspRequest>(&mut self, params: R::Params) -> R::Result {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+
+            let request = Request::new(id.clone(), R::METHOD.to_string(), params);
+            self.connection.sender.send(request.into()).unwrap();
+
+            for message in &self.connection.receiver {
+                if let Message::Response(response) = message {
+                    assert_eq!(response.id, id);
+                    assert!(response.error.is_none(), "{:?}", response.error);
+
+                    return serde_json::from_value(response.result.unwrap()).unwrap();
+                }
+            }
+
+            panic!("the server stopped before responding");
+        }
+
+        fn notify<N: LspNotification>(&self, params: N::Params) {
+            let notification = Notification::new(N::METHOD.to_string(), params);
+            self.connection.sender.send(notification.into()).unwrap();
+        }
+
+        fn open(&self, uri: &Url, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    String::from("mips"),
+                    1,
+                    text.to_string(),
+                ),
+            });
+
+            match self.connection.receiver.recv().unwrap() {
+                Message::Notification(notification) => {
+                    assert_eq!(notification.method, PublishDiagnostics::METHOD);
+                    serde_json::from_value(notification.params).unwrap()
+                }
+                message => panic!("expected diagnostics, got {:?}", message),
+            }
+        }
+
+        fn shutdown(mut self, handle: thread::JoinHandle<()>) {
+            self.request::<lsp_types::request::Shutdown>(());
+            self.notify::<lsp_types::notification::Exit>(());
+            handle.join().unwrap();
+        }
+    }
+
+    fn at(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(line, character),
+        )
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let (client, handle) = Client::start();
+        let uri = Url::parse("file:///tmp/test.s").unwrap();
+
+        assert!(client.open(&uri, PROGRAM).diagnostics.is_empty());
+
+        let diagnostics = client
+            .open(&uri, "main:\n\tlw\t$t0 4($sp)\n\tjr\t$ra\n")
+            .diagnostics;
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            lsp_types::Range::new(Position::new(1, 8), Position::new(1, 14))
+        );
+        assert!(diagnostics[0].message.contains("try adding a `,`"));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(String::from("M0001")))
+        );
+
+        client.shutdown(handle);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let (mut client, handle) = Client::start();
+        let uri = Url::parse("file:///tmp/test.s").unwrap();
+        client.open(&uri, PROGRAM);
+
+        // `print` in `jal print`
+        let definition = client.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: at(&uri, 4, 7),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+
+        let Some(GotoDefinitionResponse::Scalar(location)) = definition else {
+            panic!("expected a single definition, got {:?}", definition);
+        };
+        assert_eq!(location.range.start, Position::new(8, 0));
+
+        let references = client
+            .request::<References>(ReferenceParams {
+                text_document_position: at(&uri, 0, 0),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: ReferenceContext {
+                    include_declaration: false,
+                },
+            })
+            .unwrap();
+
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].range.start, Position::new(3, 9));
+
+        let symbols = client.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+
+        let Some(DocumentSymbolResponse::Nested(symbols)) = symbols else {
+            panic!("expected document symbols, got {:?}", symbols);
+        };
+
+        let symbols: Vec<_> = symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect();
+
+        assert_eq!(
+            symbols,
+            vec![
+                ("N", lsp_types::SymbolKind::CONSTANT),
+                ("main", lsp_types::SymbolKind::FUNCTION),
+                ("print", lsp_types::SymbolKind::FUNCTION),
+                ("nums", lsp_types::SymbolKind::VARIABLE),
+            ]
+        );
+
+        client.shutdown(handle);
+    }
+
+    #[test]
+    fn test_hover_and_completion() {
+        let (mut client, handle) = Client::start();
+        let uri = Url::parse("file:///tmp/test.s").unwrap();
+        client.open(&uri, PROGRAM);
+
+        let hover = |client: &mut Client, line, character| {
+            let hover = client.request::<HoverRequest>(HoverParams {
+                text_document_position_params: at(&uri, line, character),
+                work_done_progress_params: Default::default(),
+            });
+
+            match hover.map(|hover| hover.contents) {
+                Some(HoverContents::Markup(markup)) => markup.value,
+                hover => panic!("expected markdown, got {:?}", hover),
+            }
+        };
+
+        assert!(hover(&mut client, 9, 2).contains("move"));
+        assert!(hover(&mut client, 9, 7).contains("function argument"));
+        assert!(hover(&mut client, 5, 11).contains("defined on line 16"));
+
+        let completion = |client: &mut Client, line, character| {
+            let completion = client.request::<Completion>(CompletionParams {
+                text_document_position: at(&uri, line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            });
+
+            match completion {
+                Some(CompletionResponse::Array(items)) => {
+                    items.into_iter().map(|item| item.label).collect::<Vec<_>>()
+                }
+                completion => panic!("expected completions, got {:?}", completion),
+            }
+        };
+
+        // in the middle of `jal`
+        let instructions = completion(&mut client, 4, 2);
+        assert!(instructions.contains(&String::from("jal")));
+        assert!(!instructions.contains(&String::from("$ra")));
+
+        // after `lw $t1, `
+        let operands = completion(&mut client, 5, 9);
+        assert!(operands.contains(&String::from("$ra")));
+        assert!(operands.contains(&String::from("nums")));
+        assert!(!operands.contains(&String::from("jal")));
+
+        client.shutdown(handle);
+    }
+}
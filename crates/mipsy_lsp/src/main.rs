@@ -0,0 +1,15 @@
+use lsp_server::Connection;
+use mipsy_utils::{read_config, MipsyConfigError};
+
+fn main() -> mipsy_lsp::LspResult<()> {
+    // a broken config has already been replaced with the default,
+    // and there's nowhere to warn about it
+    let config = read_config().unwrap_or_else(|MipsyConfigError::InvalidConfig(_, config)| config);
+
+    let (connection, io_threads) = Connection::stdio();
+
+    mipsy_lsp::run(connection, config)?;
+    io_threads.join()?;
+
+    Ok(())
+}
@@ -6,12 +6,17 @@ use nom::{
     IResult,
 };
 
+use nom_locate::position;
+
 use crate::{misc::parse_ident, Span};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Attribute {
     key: String,
     value: Option<String>,
+    line: u32,
+    col: u32,
+    col_end: u32,
 }
 
 impl Attribute {
@@ -22,6 +27,18 @@ impl Attribute {
     pub fn value(&self) -> Option<&str> {
         self.value.as_deref()
     }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    pub fn col_end(&self) -> u32 {
+        self.col_end
+    }
 }
 
 fn parse_attribute<'a>(
@@ -31,6 +48,7 @@ fn parse_attribute<'a>(
         map(
             tuple((
                 multispace0,
+                position,
                 tag(attribute_header),
                 multispace0,
                 parse_ident,
@@ -44,18 +62,19 @@ fn parse_attribute<'a>(
                 ))),
                 multispace0,
                 tag("]"),
+                position,
             )),
-            |(_, _, _, key, _, value, _, _)| {
-                if let Some((_, _, value, _, _)) = value {
-                    let text = String::from_utf8_lossy(&value.iter().copied().collect::<Vec<_>>())
-                        .to_string();
-
-                    Attribute {
-                        key,
-                        value: Some(text),
-                    }
-                } else {
-                    Attribute { key, value: None }
+            |(_, start, _, _, key, _, value, _, _, end)| {
+                let value = value.map(|(_, _, value, _, _)| {
+                    String::from_utf8_lossy(&value.iter().copied().collect::<Vec<_>>()).to_string()
+                });
+
+                Attribute {
+                    key,
+                    value,
+                    line: start.location_line(),
+                    col: start.get_column() as u32,
+                    col_end: end.get_column() as u32,
                 }
             },
         )(i)
@@ -69,3 +88,28 @@ pub fn parse_outer_attribute(i: Span<'_>) -> IResult<Span<'_>, Attribute> {
 pub fn parse_inner_attribute(i: Span<'_>) -> IResult<Span<'_>, Attribute> {
     parse_attribute("#[")(i)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_attribute_position() {
+        let (_, attribute) = parse_inner_attribute(Span::new(b"\n  #[allow(unused)]")).unwrap();
+
+        assert_eq!(attribute.key(), "allow");
+        assert_eq!(attribute.value(), Some("unused"));
+        assert_eq!(
+            (attribute.line(), attribute.col(), attribute.col_end()),
+            (2, 3, 19)
+        );
+    }
+
+    #[test]
+    fn test_attribute_without_value() {
+        let (_, attribute) = parse_outer_attribute(Span::new(b"#![frobnicate]")).unwrap();
+
+        assert_eq!(attribute.key(), "frobnicate");
+        assert_eq!(attribute.value(), None);
+    }
+}
@@ -10,6 +10,7 @@ use nom::IResult;
 use nom_locate::position;
 use serde::{Deserialize, Serialize};
 
+use crate::label::parse_local_label_ref;
 use crate::misc::{comment_multispace0, parse_ident};
 use crate::number::{parse_char, parse_u32};
 use crate::parser::Position;
@@ -72,6 +73,33 @@ pub enum MpConstValue {
     Shr(Box<MpConstValueLoc>, Box<MpConstValueLoc>),
 }
 
+impl MpConstValueLoc {
+    /// Works out the value of a constant expression,
+    /// looking up any names it uses with `lookup`.
+    ///
+    /// Gives back the first name that couldn't be looked up (and where it was) if there is one.
+    pub fn eval(&self, lookup: &impl Fn(&str) -> Option<i64>) -> Result<i64, (String, Position)> {
+        Ok(match &self.0 {
+            &MpConstValue::Value(value) => value as _,
+            MpConstValue::Const(name) => {
+                lookup(name).ok_or_else(|| (name.to_string(), self.1.clone()))?
+            }
+            MpConstValue::Minus(value) => -value.eval(lookup)?,
+            MpConstValue::Sum(v1, v2) => v1.eval(lookup)? + v2.eval(lookup)?,
+            MpConstValue::Sub(v1, v2) => v1.eval(lookup)? - v2.eval(lookup)?,
+            MpConstValue::Div(v1, v2) => v1.eval(lookup)? / v2.eval(lookup)?,
+            MpConstValue::Mult(v1, v2) => v1.eval(lookup)? * v2.eval(lookup)?,
+            MpConstValue::Mod(v1, v2) => v1.eval(lookup)? % v2.eval(lookup)?,
+            MpConstValue::And(v1, v2) => v1.eval(lookup)? & v2.eval(lookup)?,
+            MpConstValue::Or(v1, v2) => v1.eval(lookup)? | v2.eval(lookup)?,
+            MpConstValue::Xor(v1, v2) => v1.eval(lookup)? ^ v2.eval(lookup)?,
+            MpConstValue::Neg(value) => !value.eval(lookup)?,
+            MpConstValue::Shl(v1, v2) => v1.eval(lookup)? << v2.eval(lookup)?,
+            MpConstValue::Shr(v1, v2) => v1.eval(lookup)? >> v2.eval(lookup)?,
+        })
+    }
+}
+
 impl Display for MpConstValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -369,6 +397,15 @@ pub fn parse_neg_value(i: Span<'_>) -> IResult<Span<'_>, MpConstValueLoc> {
 
 pub fn parse_value(i: Span<'_>) -> IResult<Span<'_>, MpConstValueLoc> {
     alt((
+        map(
+            tuple((position, parse_local_label_ref, position)),
+            |(pos_start, value, pos_end)| {
+                MpConstValueLoc(
+                    MpConstValue::Const(value),
+                    Position::from_positions(pos_start, pos_end),
+                )
+            },
+        ),
         map(
             tuple((position, parse_u32, position)),
             |(pos_start, value, pos_end)| {
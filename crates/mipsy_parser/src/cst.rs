@@ -0,0 +1,759 @@
+//! # A lossless concrete syntax tree.
+//!
+//! Unlike [`MpProgram`], which only keeps what's needed to compile a program,
+//! the concrete syntax tree keeps every byte of the source: comments,
+//! whitespace, blank lines, and exactly how each token was spelled
+//! (e.g. `0x2a` rather than `42`, or `$29` rather than `$sp`).
+//! This makes it suitable for formatters, editors and anything else that needs
+//! to point back at (or rewrite) the original text.
+//!
+//! Each line is split into items in much the same way as the parser does,
+//! with any trivia (whitespace, newlines and comments) between items left
+//! at the top level of the tree:
+//!
+//! ```text
+//! File
+//!   Label        main:
+//!   Whitespace
+//!   Instruction  li $v0, 1
+//!     Argument   $v0
+//!     Argument   1
+//!   Whitespace
+//!   Comment      # print_int
+//!   Newline
+//! ```
+//!
+//! Writing out every token in order gives back the source byte-for-byte.
+//! The tree is only syntax though: [`Cst::lower`] gets an [`MpProgram`]
+//! by parsing that source again, as preprocessing (macros, `.eqv` and `.include`)
+//! can change what a line means in ways the tree doesn't know about.
+
+use std::{fmt, ops::Range};
+
+use crate::{
+    diagnose::DIRECTIVES, parse_mips, preprocess::is_ident_contd, ErrorLocation, MpProgram,
+    TaggedFile,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Spaces and tabs (and any other whitespace that isn't a newline).
+    Whitespace,
+    /// `\n` or `\r\n`.
+    Newline,
+    /// A `#` comment, up to (but not including) the end of the line.
+    Comment,
+    /// `#[` or `#![`, the start of an attribute.
+    AttributeStart,
+    /// An instruction, label or constant name, such as `main` or `li`.
+    Ident,
+    /// A directive, or a scoped label, such as `.word` or `.Lloop`.
+    Directive,
+    /// `$` followed by a register name or number, such as `$sp` or `$29`.
+    Register,
+    /// `%` followed by a name: a macro parameter, or a relocation such as `%hi`.
+    Percent,
+    /// A number as it was written, such as `42`, `0x2a` or `1.5`.
+    Number,
+    /// A string literal, including its quotes (other than a closing quote that's missing).
+    String,
+    /// A character literal, including its quotes (other than a closing quote that's missing).
+    Char,
+    Comma,
+    Colon,
+    Semicolon,
+    Equals,
+    LParen,
+    RParen,
+    RBracket,
+    /// An arithmetic or bitwise operator, such as `+` or `<<`.
+    Operator,
+    /// Anything else, which the parser won't accept either.
+    Unknown,
+}
+
+impl TokenKind {
+    /// Whether a token is only there for whoever's reading the source.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::Newline | TokenKind::Comment
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    File,
+    Label,
+    Directive,
+    Instruction,
+    /// A constant definition, such as `N = 10`.
+    Constant,
+    /// A `#[...]` or `#![...]` attribute.
+    Attribute,
+    /// One of the comma-separated arguments to a directive or instruction.
+    Argument,
+    /// Something that can't start an item, up to the end of the line.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstToken {
+    kind: TokenKind,
+    text: String,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstNode {
+    kind: NodeKind,
+    span: Range<usize>,
+    children: Vec<CstElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CstElement {
+    Node(CstNode),
+    Token(CstToken),
+}
+
+/// The concrete syntax tree of a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cst {
+    root: CstNode,
+    line_starts: Vec<usize>,
+}
+
+impl CstToken {
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Where the token is in the source, in bytes.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl CstNode {
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// Where the node is in the source, in bytes.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn children(&self) -> &[CstElement] {
+        &self.children
+    }
+
+    /// The nodes directly inside this one.
+    pub fn child_nodes(&self) -> impl Iterator<Item = &CstNode> {
+        self.children.iter().filter_map(|child| match child {
+            CstElement::Node(node) => Some(node),
+            CstElement::Token(_) => None,
+        })
+    }
+
+    /// Every token in this node, in order.
+    pub fn tokens(&self) -> Vec<&CstToken> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a CstToken>) {
+        for child in &self.children {
+            match child {
+                CstElement::Node(node) => node.collect_tokens(tokens),
+                CstElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// The first token in this node that isn't trivia,
+    /// such as the name of an instruction.
+    pub fn first_token(&self) -> Option<&CstToken> {
+        self.tokens()
+            .into_iter()
+            .find(|token| !token.kind.is_trivia())
+    }
+
+    /// The source of this node, exactly as it was written.
+    pub fn text(&self) -> String {
+        self.tokens().into_iter().map(CstToken::text).collect()
+    }
+
+    fn new(kind: NodeKind, children: Vec<CstElement>) -> Self {
+        let start = children.first().map_or(0, |child| child.span().start);
+        let end = children.last().map_or(start, |child| child.span().end);
+
+        Self {
+            kind,
+            span: start..end,
+            children,
+        }
+    }
+}
+
+impl CstElement {
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            CstElement::Node(node) => node.span(),
+            CstElement::Token(token) => token.span(),
+        }
+    }
+}
+
+impl Cst {
+    /// Builds the syntax tree of some source, which never fails:
+    /// anything that can't be made sense of ends up in an [`Error`](NodeKind::Error) node.
+    pub fn parse(source: &str) -> Self {
+        let tokens = lex(source);
+
+        let mut children = vec![];
+        let mut rest = &tokens[..];
+
+        while !rest.is_empty() {
+            let line_len = rest
+                .iter()
+                .position(|token| token.kind == TokenKind::Newline)
+                .map_or(rest.len(), |pos| pos + 1);
+
+            group_line(&rest[..line_len], &mut children);
+            rest = &rest[line_len..];
+        }
+
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+
+        Self {
+            root: CstNode {
+                kind: NodeKind::File,
+                span: 0..source.len(),
+                children,
+            },
+            line_starts,
+        }
+    }
+
+    pub fn root(&self) -> &CstNode {
+        &self.root
+    }
+
+    /// The items in the file (labels, directives, instructions,
+    /// constants, attributes, and anything unparseable), in order.
+    pub fn items(&self) -> impl Iterator<Item = &CstNode> {
+        self.root.child_nodes()
+    }
+
+    /// Every token in the file, in order.
+    pub fn tokens(&self) -> Vec<&CstToken> {
+        self.root.tokens()
+    }
+
+    /// The token that a byte offset into the source falls in.
+    pub fn token_at(&self, offset: usize) -> Option<&CstToken> {
+        self.tokens()
+            .into_iter()
+            .find(|token| token.span.contains(&offset))
+    }
+
+    /// The line (from 1) and column (in bytes, from 1) of a byte offset into the source,
+    /// which can be matched up with the line numbers in an [`MpProgram`].
+    pub fn line_col(&self, offset: usize) -> (u32, u32) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let col = offset - self.line_starts[line - 1] + 1;
+
+        (line as u32, col as u32)
+    }
+
+    /// Lowers the tree to a program by writing out its source and parsing it again
+    /// with [`parse_mips`], rather than working from the nodes,
+    /// so that it goes through the same preprocessing (`.include`, `.eqv` and macros).
+    ///
+    /// Outside of preprocessing, the program's items match up with the tree's
+    /// (other than attributes, which are attached to the items after them).
+    pub fn lower(
+        &self,
+        file_tag: Option<&str>,
+        default_tab_size: u32,
+    ) -> Result<MpProgram, ErrorLocation> {
+        let source = self.to_string();
+
+        parse_mips(vec![TaggedFile::new(file_tag, &source)], default_tab_size)
+    }
+}
+
+impl fmt::Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens() {
+            f.write_str(&token.text)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a line's tokens into items, leaving any trivia between them at the top level.
+fn group_line(line: &[CstToken], children: &mut Vec<CstElement>) {
+    let mut rest = line;
+
+    loop {
+        // leading trivia
+        let trivia = rest
+            .iter()
+            .take_while(|token| token.kind.is_trivia())
+            .count();
+        children.extend(rest[..trivia].iter().cloned().map(CstElement::Token));
+        rest = &rest[trivia..];
+
+        if rest.is_empty() {
+            return;
+        }
+
+        let len = item_len(rest);
+        children.push(CstElement::Node(item(&rest[..len])));
+        rest = &rest[len..];
+    }
+}
+
+/// How many tokens make up the item at the start of `tokens`,
+/// which never includes any trivia after it.
+fn item_len(tokens: &[CstToken]) -> usize {
+    let next_significant = |from: usize| {
+        tokens[from..]
+            .iter()
+            .position(|token| token.kind != TokenKind::Whitespace)
+            .map(|pos| from + pos)
+    };
+
+    // everything up to the end of the line (or a `;`), other than trailing trivia
+    let until_end = |semicolon: bool| {
+        let end = tokens
+            .iter()
+            .position(|token| {
+                matches!(token.kind, TokenKind::Comment | TokenKind::Newline)
+                    || (token.kind == TokenKind::Semicolon && semicolon)
+            })
+            .map_or(tokens.len(), |pos| {
+                pos + usize::from(semicolon && tokens[pos].kind == TokenKind::Semicolon)
+            });
+
+        tokens[..end]
+            .iter()
+            .rposition(|token| !token.kind.is_trivia())
+            .map_or(1, |pos| pos + 1)
+    };
+
+    let followed_by = |kind| next_significant(1).filter(|&pos| tokens[pos].kind == kind);
+
+    match tokens[0].kind {
+        TokenKind::AttributeStart => tokens
+            .iter()
+            .position(|token| matches!(token.kind, TokenKind::RBracket | TokenKind::Newline))
+            .map_or(tokens.len(), |pos| {
+                pos + usize::from(tokens[pos].kind == TokenKind::RBracket)
+            }),
+        TokenKind::Ident | TokenKind::Directive | TokenKind::Number
+            if followed_by(TokenKind::Colon).is_some() =>
+        {
+            followed_by(TokenKind::Colon).unwrap() + 1
+        }
+        TokenKind::Ident if followed_by(TokenKind::Equals).is_some() => until_end(false),
+        TokenKind::Directive => {
+            let end = until_end(false);
+
+            // another directive can follow on the same line, as in `.space 4 .word 42`
+            let next = (2..end).find(|&i| {
+                tokens[i].kind == TokenKind::Directive
+                    && tokens[i - 1].kind == TokenKind::Whitespace
+                    && DIRECTIVES.contains(&tokens[i].text.as_str())
+            });
+
+            match next {
+                Some(next) => next - 1,
+                None => end,
+            }
+        }
+        TokenKind::Ident => until_end(true),
+        _ => until_end(false),
+    }
+}
+
+/// Makes an item node out of its tokens, splitting up the arguments of
+/// directives and instructions.
+fn item(tokens: &[CstToken]) -> CstNode {
+    let kind = match tokens[0].kind {
+        TokenKind::AttributeStart => NodeKind::Attribute,
+        _ if tokens.last().unwrap().kind == TokenKind::Colon => NodeKind::Label,
+        TokenKind::Ident if tokens.iter().any(|token| token.kind == TokenKind::Equals) => {
+            NodeKind::Constant
+        }
+        TokenKind::Directive => NodeKind::Directive,
+        TokenKind::Ident => NodeKind::Instruction,
+        _ => NodeKind::Error,
+    };
+
+    let tokens_to_elements = |tokens: &[CstToken]| -> Vec<CstElement> {
+        tokens.iter().cloned().map(CstElement::Token).collect()
+    };
+
+    if !matches!(kind, NodeKind::Directive | NodeKind::Instruction) {
+        return CstNode::new(kind, tokens_to_elements(tokens));
+    }
+
+    let mut children = tokens_to_elements(&tokens[..1]);
+    let mut rest = &tokens[1..];
+    let mut depth = 0;
+
+    loop {
+        let leading = rest
+            .iter()
+            .take_while(|token| token.kind == TokenKind::Whitespace)
+            .count();
+        children.extend(tokens_to_elements(&rest[..leading]));
+        rest = &rest[leading..];
+
+        match rest.first().map(|token| token.kind) {
+            None => break,
+            Some(TokenKind::Comma | TokenKind::Semicolon) => {
+                children.extend(tokens_to_elements(&rest[..1]));
+                rest = &rest[1..];
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        // an argument runs up to the next comma that isn't in parentheses
+        let mut len = rest.len();
+        for (i, token) in rest.iter().enumerate() {
+            match token.kind {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen => depth -= 1,
+                TokenKind::Comma | TokenKind::Semicolon if depth <= 0 => {
+                    len = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let trailing = rest[..len]
+            .iter()
+            .rev()
+            .take_while(|token| token.kind == TokenKind::Whitespace)
+            .count();
+
+        children.push(CstElement::Node(CstNode::new(
+            NodeKind::Argument,
+            tokens_to_elements(&rest[..len - trailing]),
+        )));
+        children.extend(tokens_to_elements(&rest[len - trailing..len]));
+        rest = &rest[len..];
+    }
+
+    CstNode::new(kind, children)
+}
+
+/// Splits some source into tokens, which together cover every byte of it.
+fn lex(source: &str) -> Vec<CstToken> {
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    while pos < source.len() {
+        let rest = &source[pos..];
+        let (kind, len) = next_token(rest);
+
+        tokens.push(CstToken {
+            kind,
+            text: rest[..len].to_string(),
+            span: pos..pos + len,
+        });
+
+        pos += len;
+    }
+
+    tokens
+}
+
+fn next_token(rest: &str) -> (TokenKind, usize) {
+    let mut chars = rest.chars();
+    let first = chars.next().unwrap();
+    let second = chars.next();
+
+    // the length of a run of characters, starting after the first `skip` bytes
+    let run = |skip: usize, pred: &dyn Fn(char) -> bool| {
+        skip + rest[skip..]
+            .find(|c: char| !pred(c))
+            .unwrap_or(rest.len() - skip)
+    };
+
+    match first {
+        '\n' => (TokenKind::Newline, 1),
+        '\r' if second == Some('\n') => (TokenKind::Newline, 2),
+        _ if first.is_whitespace() => (
+            TokenKind::Whitespace,
+            run(0, &|c| c.is_whitespace() && c != '\n'),
+        ),
+        '#' if rest.starts_with("#![") => (TokenKind::AttributeStart, 3),
+        '#' if rest.starts_with("#[") => (TokenKind::AttributeStart, 2),
+        '#' => (TokenKind::Comment, run(0, &|c| c != '\n' && c != '\r')),
+        '"' => (TokenKind::String, literal_len(rest)),
+        '\'' => (TokenKind::Char, literal_len(rest)),
+        '$' => (TokenKind::Register, run(1, &|c| c.is_ascii_alphanumeric())),
+        '%' if second.is_some_and(is_ident_contd) => (TokenKind::Percent, run(1, &is_ident_contd)),
+        '.' if second.is_some_and(|c| c.is_ascii_digit()) => {
+            (TokenKind::Number, run(1, &is_ident_contd))
+        }
+        '.' if second.is_some_and(is_ident_contd) => {
+            (TokenKind::Directive, run(1, &is_ident_contd))
+        }
+        _ if first.is_ascii_digit() => (TokenKind::Number, run(0, &is_ident_contd)),
+        _ if first.is_ascii_alphabetic() || first == '_' => {
+            (TokenKind::Ident, run(0, &is_ident_contd))
+        }
+        ',' => (TokenKind::Comma, 1),
+        ':' => (TokenKind::Colon, 1),
+        ';' => (TokenKind::Semicolon, 1),
+        '=' if second == Some('=') => (TokenKind::Operator, 2),
+        '=' => (TokenKind::Equals, 1),
+        '(' => (TokenKind::LParen, 1),
+        ')' => (TokenKind::RParen, 1),
+        ']' => (TokenKind::RBracket, 1),
+        '<' | '>' | '!' | '&' | '|' if second.is_some_and(|c| "<>=&|".contains(c)) => {
+            (TokenKind::Operator, 2)
+        }
+        '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~' | '<' | '>' | '!' => {
+            (TokenKind::Operator, 1)
+        }
+        _ => (TokenKind::Unknown, first.len_utf8()),
+    }
+}
+
+/// The length of the string or character literal at the start of some source,
+/// up to the end of the line if it's never closed.
+fn literal_len(rest: &str) -> usize {
+    let quote = rest.chars().next().unwrap();
+    let mut escaped = false;
+
+    for (pos, c) in rest.char_indices().skip(1) {
+        match c {
+            '\n' | '\r' => return pos,
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return pos + 1,
+            _ => {}
+        }
+    }
+
+    rest.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MpItem;
+
+    fn items(source: &str) -> Vec<(NodeKind, String)> {
+        Cst::parse(source)
+            .items()
+            .map(|item| (item.kind(), item.text()))
+            .collect()
+    }
+
+    #[test]
+    fn test_items() {
+        assert_eq!(
+            items("main:\tli $v0, 1 # print_int\n"),
+            vec![
+                (NodeKind::Label, "main:".to_string()),
+                (NodeKind::Instruction, "li $v0, 1".to_string()),
+            ]
+        );
+        assert_eq!(
+            items("#![tabsize(8)]\nN = 4 * 2  \n.Lloop: .word 1, 2:3\n"),
+            vec![
+                (NodeKind::Attribute, "#![tabsize(8)]".to_string()),
+                (NodeKind::Constant, "N = 4 * 2".to_string()),
+                (NodeKind::Label, ".Lloop:".to_string()),
+                (NodeKind::Directive, ".word 1, 2:3".to_string()),
+            ]
+        );
+        assert_eq!(
+            items("    ) oops\n"),
+            vec![(NodeKind::Error, ") oops".to_string())]
+        );
+        assert_eq!(
+            items("x: .space 4 .word .Lx\n"),
+            vec![
+                (NodeKind::Label, "x:".to_string()),
+                (NodeKind::Directive, ".space 4".to_string()),
+                (NodeKind::Directive, ".word .Lx".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arguments() {
+        let cst = Cst::parse("lw $t0, %lo(x) ( $sp ) # load\n");
+        let inst = cst.items().next().unwrap();
+
+        let args: Vec<_> = inst
+            .child_nodes()
+            .map(|arg| (arg.text(), arg.span()))
+            .collect();
+
+        assert_eq!(
+            args,
+            vec![
+                ("$t0".to_string(), 3..6),
+                ("%lo(x) ( $sp )".to_string(), 8..22)
+            ]
+        );
+        assert_eq!(inst.first_token().unwrap().text(), "lw");
+        assert_eq!(cst.token_at(9).unwrap().kind(), TokenKind::Percent);
+        assert_eq!(cst.line_col(23), (1, 24));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "",
+            "\n\n",
+            "main:\r\n\tli\t$t0, 0x2A\t# x = 42\r\n",
+            "msg: .asciiz \"unterminated\n\tsyscall",
+            "  li $a0, '\\n' ; li $v0, 11\n.ascii \"é\"\n",
+            ".macro print(%reg)\n\tmove $a0, %reg\n.end_macro\n",
+        ];
+
+        for source in sources {
+            assert_eq!(Cst::parse(source).to_string(), source);
+        }
+
+        let test_files = concat!(env!("CARGO_MANIFEST_DIR"), "/../../test_files");
+        for dir in [
+            "success",
+            "compile_error",
+            "runtime_error",
+            "spim_divergent",
+        ] {
+            for entry in std::fs::read_dir(format!("{}/{}", test_files, dir)).unwrap() {
+                let path = entry.unwrap().path();
+                let Ok(source) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+
+                let cst = Cst::parse(&source);
+                assert_eq!(cst.to_string(), source, "{}", path.display());
+            }
+        }
+    }
+
+    /// The kind, line and column of each item that ends up in a program.
+    fn program_items(program: &MpProgram) -> Vec<(NodeKind, u32, u32)> {
+        program
+            .items()
+            .iter()
+            .map(|item| {
+                let (kind, col) = match item.item() {
+                    MpItem::Instruction(instruction) => (NodeKind::Instruction, instruction.col()),
+                    MpItem::Directive((_, position)) => (NodeKind::Directive, position.col()),
+                    MpItem::Label(label) => (NodeKind::Label, label.col()),
+                    MpItem::Constant(constant) => (NodeKind::Constant, constant.col()),
+                    MpItem::Error((_, position)) => (NodeKind::Error, position.col()),
+                };
+
+                (kind, item.line_number(), col)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_items_match_program() {
+        let test_files = concat!(env!("CARGO_MANIFEST_DIR"), "/../../test_files");
+
+        for dir in [
+            "success",
+            "compile_error",
+            "runtime_error",
+            "spim_divergent",
+        ] {
+            for entry in std::fs::read_dir(format!("{}/{}", test_files, dir)).unwrap() {
+                let path = entry.unwrap().path();
+                let Ok(source) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+
+                // preprocessing changes which items there are
+                if [".macro", ".eqv", ".include"]
+                    .iter()
+                    .any(|directive| source.contains(directive))
+                {
+                    continue;
+                }
+
+                let Ok(program) = parse_mips(vec![TaggedFile::new(None, &source)], 8) else {
+                    continue;
+                };
+
+                let cst = Cst::parse(&source);
+                let cst_items: Vec<_> = cst
+                    .items()
+                    .filter(|item| item.kind() != NodeKind::Attribute)
+                    .map(|item| {
+                        let (line, col) = cst.line_col(item.span().start);
+
+                        // the parser's columns are in characters, with tabs to the next tab stop
+                        let line_start = item.span().start + 1 - col as usize;
+                        let col =
+                            source[line_start..item.span().start]
+                                .chars()
+                                .fold(0, |col, chr| match chr {
+                                    '\t' => (col / 8 + 1) * 8,
+                                    _ => col + 1,
+                                });
+
+                        (item.kind(), line, col + 1)
+                    })
+                    .collect();
+
+                // the tree doesn't know what's valid, only how a line is laid out,
+                // so where the parser gave up partway through a line (e.g. on a bad argument),
+                // that's somewhere inside an item in the tree
+                let (errors, program_items): (Vec<_>, Vec<_>) = program_items(&program)
+                    .into_iter()
+                    .partition(|&(kind, _, _)| kind == NodeKind::Error);
+
+                for &(_, line, col) in &errors {
+                    assert!(
+                        cst_items.iter().any(|&(_, item_line, item_col)| {
+                            item_line == line && item_col <= col
+                        }),
+                        "{}: error on line {} isn't in an item",
+                        path.display(),
+                        line
+                    );
+                }
+
+                let cst_items: Vec<_> = cst_items
+                    .into_iter()
+                    .filter(|&(_, line, _)| {
+                        errors.iter().all(|&(_, error_line, _)| error_line != line)
+                    })
+                    .collect();
+
+                assert_eq!(cst_items, program_items, "{}", path.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Working out why a line couldn't be parsed.
+//!
+//! The parser only knows where it got stuck, so this looks at the rest of
+//! the line from there (and at whatever was parsed before it on the same line)
+//! to say what should have been there, and what was found instead.
+
+use crate::misc::SyntaxError;
+
+/// Every directive, including those handled before parsing.
+pub(crate) const DIRECTIVES: &[&str] = &[
+    ".text",
+    ".data",
+    ".ktext",
+    ".kdata",
+    ".ascii",
+    ".asciiz",
+    ".byte",
+    ".half",
+    ".word",
+    ".float",
+    ".double",
+    ".space",
+    ".align",
+    ".globl",
+    ".if",
+    ".ifdef",
+    ".else",
+    ".endif",
+    ".macro",
+    ".end_macro",
+    ".eqv",
+    ".include",
+    ".set",
+];
+
+/// Directives that don't take any arguments.
+const BARE_DIRECTIVES: &[&str] = &[".text", ".data", ".ktext", ".kdata", ".else", ".endif"];
+
+/// Directives that take a single string.
+const STRING_DIRECTIVES: &[&str] = &[".ascii", ".asciiz"];
+
+/// Works out what went wrong at byte `start` of `line`,
+/// giving back where the problem starts, and what it is.
+pub(crate) fn diagnose(line: &str, start: usize) -> (usize, SyntaxError) {
+    let mut start = start.min(line.len());
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+
+    // anything stuck on to the end of what was parsed (like the `abc` of `12abc`)
+    // is part of the same word
+    if line[start..].starts_with(is_word_char) && line[..start].ends_with(is_word_char) {
+        start = line[..start]
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| !is_word_char(c))
+            .map_or(0, |(pos, c)| pos + c.len_utf8());
+
+        let word = token(&line[start..]).unwrap_or_default();
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            return (
+                start,
+                SyntaxError::InvalidNumber {
+                    found: word.to_string(),
+                },
+            );
+        }
+    }
+
+    let before = strip_labels(&line[..start]);
+    if before.is_empty() {
+        return item_error(line, start);
+    }
+
+    let rest = &line[start..];
+    let found = token(rest).unwrap_or_default();
+    let item = token(before).unwrap_or_default();
+
+    let error = match found {
+        "," | "=" if before.ends_with(',') => SyntaxError::UnexpectedToken {
+            found: found.to_string(),
+        },
+        "," | "=" => {
+            let at = skip_spaces(line, start + 1);
+
+            return match token(&line[at..]) {
+                Some(_) => (at, argument_error(&line[at..])),
+                None => (
+                    start,
+                    SyntaxError::MissingArgument {
+                        after: found.to_string(),
+                    },
+                ),
+            };
+        }
+        _ if BARE_DIRECTIVES.contains(&item) || STRING_DIRECTIVES.contains(&item) => {
+            SyntaxError::UnexpectedArgument {
+                directive: item.to_string(),
+                found: argument(rest).to_string(),
+            }
+        }
+        "(" if !rest.contains(')') => SyntaxError::UnclosedParen,
+        _ if found.starts_with(is_argument_start) => {
+            literal_error(found).unwrap_or_else(|| SyntaxError::MissingComma {
+                found: argument(rest).to_string(),
+            })
+        }
+        _ => SyntaxError::UnexpectedToken {
+            found: found.to_string(),
+        },
+    };
+
+    (start, error)
+}
+
+/// What went wrong with an item that couldn't be parsed from its very start.
+fn item_error(line: &str, start: usize) -> (usize, SyntaxError) {
+    let found = token(&line[start..]).unwrap_or_default();
+
+    if let Some(error) = literal_error(found) {
+        return (start, error);
+    }
+
+    if !found.starts_with('.') || found.len() == 1 {
+        return (
+            start,
+            SyntaxError::ExpectedItem {
+                found: found.to_string(),
+            },
+        );
+    }
+
+    if !DIRECTIVES.contains(&found) {
+        return (
+            start,
+            SyntaxError::UnknownDirective {
+                directive: found.to_string(),
+                similar: similar_directive(found),
+            },
+        );
+    }
+
+    if found == ".end_macro" {
+        return (start, SyntaxError::UnmatchedEndMacro);
+    }
+
+    // the directive is fine, so it must be its arguments that aren't
+    let at = skip_spaces(line, start + found.len());
+    let args = &line[at..];
+
+    match token(args) {
+        None => (
+            start,
+            SyntaxError::MissingArgument {
+                after: found.to_string(),
+            },
+        ),
+        Some(arg) if STRING_DIRECTIVES.contains(&found) && !arg.starts_with('"') => (
+            at,
+            SyntaxError::ExpectedString {
+                directive: found.to_string(),
+                found: argument(args).to_string(),
+            },
+        ),
+        Some(_) => (at, argument_error(args)),
+    }
+}
+
+/// What's wrong with an argument that couldn't be parsed.
+fn argument_error(code: &str) -> SyntaxError {
+    let found = argument(code);
+
+    if let Some(error) = literal_error(found) {
+        return error;
+    }
+
+    if found.starts_with('(') && !code.contains(')') {
+        return SyntaxError::UnclosedParen;
+    }
+
+    SyntaxError::InvalidArgument {
+        found: found.to_string(),
+    }
+}
+
+/// What's wrong with a string or character literal, if anything.
+fn literal_error(literal: &str) -> Option<SyntaxError> {
+    match literal.chars().next()? {
+        '"' if literal_len(literal).is_none() => Some(SyntaxError::UnterminatedString),
+        '\'' => {
+            let len = match literal_len(literal) {
+                Some(len) => len,
+                None => return Some(SyntaxError::UnterminatedChar),
+            };
+
+            let mut contents = literal[1..len - 1].chars();
+            let valid = match contents.next() {
+                Some('\\') => matches!(
+                    contents.next(),
+                    Some('0' | 'r' | 'n' | 't' | '\\' | '"' | '\'')
+                ),
+                Some(_) => true,
+                None => false,
+            } && contents.next().is_none();
+
+            (!valid).then(|| SyntaxError::InvalidChar {
+                found: literal.to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The first token in some code: a word, a quoted literal
+/// (up to the end of the line if it's never closed), or a single character.
+fn token(code: &str) -> Option<&str> {
+    let code = code.trim_start();
+    let first = code.chars().next()?;
+
+    let len = match first {
+        '#' if !code.starts_with("#[") && !code.starts_with("#![") => return None,
+        '"' | '\'' => literal_len(code).unwrap_or(code.len()),
+        _ if is_word_char(first) => code.find(|c| !is_word_char(c)).unwrap_or(code.len()),
+        _ => first.len_utf8(),
+    };
+
+    Some(&code[..len])
+}
+
+/// The text of the argument at the start of some code,
+/// which runs up to the next space or comma.
+fn argument(code: &str) -> &str {
+    let code = code.trim_start();
+
+    if code.starts_with(['"', '\'']) {
+        return token(code).unwrap_or_default();
+    }
+
+    let len = code
+        .find(|c: char| c.is_whitespace() || c == ',' || c == '#')
+        .unwrap_or(code.len());
+
+    &code[..len]
+}
+
+/// The length of the quoted literal at the start of some code, if it's ever closed.
+fn literal_len(code: &str) -> Option<usize> {
+    let quote = code.chars().next()?;
+    let mut escaped = false;
+
+    for (pos, c) in code.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return Some(pos + 1),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Strips any labels (`main:`) from the start of a line.
+fn strip_labels(mut code: &str) -> &str {
+    loop {
+        code = code.trim();
+
+        let label = token(code).unwrap_or_default();
+        match code[label.len()..].trim_start().strip_prefix(':') {
+            Some(rest) if !label.is_empty() && label.chars().all(is_word_char) => code = rest,
+            _ => return code,
+        }
+    }
+}
+
+fn skip_spaces(line: &str, at: usize) -> usize {
+    line.len() - line[at..].trim_start().len()
+}
+
+fn similar_directive(directive: &str) -> Option<String> {
+    DIRECTIVES
+        .iter()
+        .map(|&known| (strsim::jaro_winkler(known, directive), known))
+        .filter(|&(similarity, _)| similarity >= 0.85)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, known)| known.to_string())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.$%".contains(c)
+}
+
+fn is_argument_start(c: char) -> bool {
+    is_word_char(c) || "\"'-+(".contains(c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_arguments() {
+        assert_eq!(
+            diagnose("    lw   $t0 4($sp)", 13),
+            (
+                13,
+                SyntaxError::MissingComma {
+                    found: "4($sp)".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            diagnose("add $t0, $t1,", 12),
+            (
+                12,
+                SyntaxError::MissingArgument {
+                    after: ",".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            diagnose("li $a0, '\\nn'  # newline", 6),
+            (
+                8,
+                SyntaxError::InvalidChar {
+                    found: "'\\nn'".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            diagnose("li $t0, 12abc", 10),
+            (
+                8,
+                SyntaxError::InvalidNumber {
+                    found: "12abc".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            diagnose("lw $t0, 4($sp", 9),
+            (9, SyntaxError::UnclosedParen)
+        );
+    }
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(
+            diagnose("msg: .asciiz \"hello", 5),
+            (13, SyntaxError::UnterminatedString)
+        );
+        assert_eq!(
+            diagnose(".asciiz hello", 0),
+            (
+                8,
+                SyntaxError::ExpectedString {
+                    directive: ".asciiz".to_string(),
+                    found: "hello".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            diagnose(".wrod 1", 0),
+            (
+                0,
+                SyntaxError::UnknownDirective {
+                    directive: ".wrod".to_string(),
+                    similar: Some(".word".to_string())
+                }
+            )
+        );
+        assert_eq!(
+            diagnose(".data 0x10010000", 6),
+            (
+                6,
+                SyntaxError::UnexpectedArgument {
+                    directive: ".data".to_string(),
+                    found: "0x10010000".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            diagnose(".word", 0),
+            (
+                0,
+                SyntaxError::MissingArgument {
+                    after: ".word".to_string()
+                }
+            )
+        );
+    }
+}
@@ -37,6 +37,10 @@ pub enum MpDirective {
     Align(MpConstValueLoc),
     Space(MpConstValueLoc),
     Globl(String),
+    If(MpConstValueLoc),
+    IfDef(String),
+    Else,
+    EndIf,
 }
 
 impl Display for MpDirective {
@@ -61,6 +65,10 @@ impl Display for MpDirective {
                 Data => "data",
                 KText => "ktext",
                 KData => "kdata",
+                If(_) => "if",
+                IfDef(_) => "ifdef",
+                Else => "else",
+                EndIf => "endif",
             }
         )
     }
@@ -85,6 +93,10 @@ pub fn parse_directive(i: Span<'_>) -> IResult<Span<'_>, MpDirectiveLoc> {
                 parse_space,
                 parse_align,
                 parse_globl,
+                parse_ifdef,
+                parse_if,
+                parse_else,
+                parse_endif,
             )),
             position,
         )),
@@ -221,11 +233,52 @@ fn parse_globl(i: Span<'_>) -> IResult<Span<'_>, MpDirective> {
     Ok((remaining_data, MpDirective::Globl(ident)))
 }
 
+fn parse_if(i: Span<'_>) -> IResult<Span<'_>, MpDirective> {
+    map(parse_u32_type(".if"), MpDirective::If)(i)
+}
+
+fn parse_ifdef(i: Span<'_>) -> IResult<Span<'_>, MpDirective> {
+    let (remaining_data, (_, _, ident)) =
+        tuple((tag(".ifdef"), comment_multispace1, parse_ident))(i)?;
+
+    Ok((remaining_data, MpDirective::IfDef(ident)))
+}
+
+fn parse_else(i: Span<'_>) -> IResult<Span<'_>, MpDirective> {
+    let (remaining_data, ..) = tag(".else")(i)?;
+
+    Ok((remaining_data, MpDirective::Else))
+}
+
+fn parse_endif(i: Span<'_>) -> IResult<Span<'_>, MpDirective> {
+    let (remaining_data, ..) = tag(".endif")(i)?;
+
+    Ok((remaining_data, MpDirective::EndIf))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::misc::{span, unspan};
 
+    #[test]
+    fn test_conditionals() {
+        assert_eq!(
+            unspan(parse_ifdef(span(".ifdef DEBUG")).unwrap()),
+            ("".to_string(), MpDirective::IfDef("DEBUG".to_string()))
+        );
+
+        assert!(matches!(
+            parse_directive(span(".if DEBUG")).unwrap().1 .0,
+            MpDirective::If(_)
+        ));
+
+        assert!(matches!(
+            parse_directive(span(".ifdef DEBUG")).unwrap().1 .0,
+            MpDirective::IfDef(_)
+        ));
+    }
+
     #[test]
     fn test_ascii() {
         assert_eq!(
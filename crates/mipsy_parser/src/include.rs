@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+/// The path given to an `.include "path"` directive.
+pub(crate) fn include_path(args: &str) -> Option<&str> {
+    args.strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+        .filter(|path| !path.is_empty() && !path.contains('"'))
+}
+
+/// Where an included file lives, relative to the file that included it.
+pub(crate) fn resolve(including_file: Option<&str>, path: &str) -> PathBuf {
+    let path = Path::new(path);
+
+    match including_file.and_then(|file| Path::new(file).parent()) {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// A key that's the same for every path to the same file,
+/// so that include cycles are caught however the file was named.
+pub(crate) fn identity(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_include_path() {
+        assert_eq!(include_path("\"io_helpers.s\""), Some("io_helpers.s"));
+        assert_eq!(include_path("io_helpers.s"), None);
+        assert_eq!(include_path("\"\""), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve(Some("labs/lab01/main.s"), "../io_helpers.s"),
+            PathBuf::from("labs/lab01/../io_helpers.s")
+        );
+        assert_eq!(
+            resolve(Some("main.s"), "io_helpers.s"),
+            PathBuf::from("io_helpers.s")
+        );
+        assert_eq!(
+            resolve(Some("labs/main.s"), "/usr/share/io_helpers.s"),
+            PathBuf::from("/usr/share/io_helpers.s")
+        );
+        assert_eq!(resolve(None, "io_helpers.s"), PathBuf::from("io_helpers.s"));
+    }
+}
@@ -2,16 +2,16 @@ use std::fmt;
 
 use crate::{
     misc::{comment_multispace0, parse_ident},
-    number::{parse_number, MpNumber},
+    number::{parse_expression, parse_number, parse_relocation, MpNumber},
     register::{parse_register, MpRegister},
     Span,
 };
 use nom::{
     branch::alt,
-    character::complete::{char, space0},
-    combinator::{map, opt},
+    character::complete::{char, one_of, space0},
+    combinator::{eof, map, opt, peek},
     multi::separated_list0,
-    sequence::tuple,
+    sequence::{terminated, tuple},
     IResult,
 };
 use nom_locate::position;
@@ -109,5 +109,23 @@ fn parse_argument_reg(i: Span<'_>) -> IResult<Span<'_>, MpArgument> {
 }
 
 fn parse_argument_num(i: Span<'_>) -> IResult<Span<'_>, MpArgument> {
-    map(parse_number, MpArgument::Number)(i)
+    map(
+        alt((
+            parse_relocation,
+            // only fall back to an expression if it's more than a number
+            terminated(parse_number, peek(parse_argument_end)),
+            parse_expression,
+        )),
+        MpArgument::Number,
+    )(i)
+}
+
+fn parse_argument_end(i: Span<'_>) -> IResult<Span<'_>, ()> {
+    map(
+        tuple((
+            space0,
+            alt((map(one_of(",;#\r\n"), |_| ()), map(eof, |_| ()))),
+        )),
+        |_| (),
+    )(i)
 }
@@ -1,11 +1,21 @@
 use crate::{misc::parse_ident, Span};
 use nom::{
-    character::complete::{char, space0},
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, digit1, one_of, satisfy, space0},
+    combinator::{map, not, verify},
     sequence::tuple,
     IResult,
 };
 use nom_locate::position;
 
+/// Separates the parts of the unique name a local label is given once
+/// it's been resolved (e.g. `main::.Lloop`, or `1::0` for the first `1:`),
+/// or a label in a macro is given when it's expanded (e.g. `loop::M0`).
+///
+/// This can't appear in a label, so these names never clash with the program's own.
+pub const LOCAL_LABEL_SEPARATOR: &str = "::";
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MpLabel {
     label: String,
@@ -18,6 +28,10 @@ impl MpLabel {
         self.label.to_string()
     }
 
+    pub fn set_label(&mut self, label: String) {
+        self.label = label;
+    }
+
     pub fn col(&self) -> u32 {
         self.col
     }
@@ -27,9 +41,33 @@ impl MpLabel {
     }
 }
 
+/// Whether a label is only visible near where it's defined:
+/// a numeric label (`1:`), a scoped label (`.Lloop:`), a label in a macro,
+/// or the unique name one of these is given once it's resolved.
+pub fn is_local_label(label: &str) -> bool {
+    is_numeric_label(label) || is_scoped_label(label) || label.contains(LOCAL_LABEL_SEPARATOR)
+}
+
+/// Whether a label is a numeric label, such as `1`,
+/// which can be defined many times and is referred to as `1f` or `1b`.
+pub fn is_numeric_label(label: &str) -> bool {
+    !label.is_empty() && label.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether a label is a scoped label, such as `.Lloop`,
+/// which is only visible until the next ordinary label.
+pub fn is_scoped_label(label: &str) -> bool {
+    label.starts_with(".L")
+}
+
+/// The unique name a label in a macro is given in its `id`th expansion.
+pub(crate) fn macro_label_name(label: &str, id: impl std::fmt::Display) -> String {
+    format!("{}{}M{}", label, LOCAL_LABEL_SEPARATOR, id)
+}
+
 pub fn parse_label(i: Span<'_>) -> IResult<Span<'_>, MpLabel> {
     let (remaining_data, (pos_start, label, _, _, pos_end)) =
-        tuple((position, parse_ident, space0, char(':'), position))(i)?;
+        tuple((position, parse_label_name, space0, char(':'), position))(i)?;
 
     let col = pos_start.get_column() as u32;
     let col_end = pos_end.get_column() as u32;
@@ -43,3 +81,110 @@ pub fn parse_label(i: Span<'_>) -> IResult<Span<'_>, MpLabel> {
         },
     ))
 }
+
+fn parse_label_name(i: Span<'_>) -> IResult<Span<'_>, String> {
+    alt((
+        parse_macro_label,
+        parse_ident,
+        parse_scoped_label,
+        map(digit1, |digits: Span<'_>| {
+            String::from_utf8_lossy(digits.fragment()).to_string()
+        }),
+    ))(i)
+}
+
+fn parse_scoped_label(i: Span<'_>) -> IResult<Span<'_>, String> {
+    map(
+        tuple((
+            char('.'),
+            verify(parse_ident, |ident: &str| {
+                ident.len() > 1 && ident.starts_with('L')
+            }),
+        )),
+        |(_, ident)| format!(".{}", ident),
+    )(i)
+}
+
+/// Parses the name a label in a macro was given when it was expanded, such as `loop::M0`.
+///
+/// Only a macro's expansion should have these in it,
+/// which is checked once it's been parsed.
+fn parse_macro_label(i: Span<'_>) -> IResult<Span<'_>, String> {
+    map(
+        tuple((
+            alt((parse_ident, parse_scoped_label)),
+            tag(LOCAL_LABEL_SEPARATOR),
+            char('M'),
+            digit1,
+        )),
+        |(label, _, _, id): (String, _, _, Span<'_>)| {
+            macro_label_name(&label, String::from_utf8_lossy(id.fragment()))
+        },
+    )(i)
+}
+
+/// Parses a reference to a local label: `1f` or `1b` for the next
+/// or previous `1:` label, a scoped label such as `.Lloop`,
+/// or a label in a macro once it's been expanded.
+pub fn parse_local_label_ref(i: Span<'_>) -> IResult<Span<'_>, String> {
+    alt((
+        parse_macro_label,
+        map(
+            tuple((
+                digit1,
+                one_of("fb"),
+                not(satisfy(|c| {
+                    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+                })),
+            )),
+            |(digits, direction, _): (Span<'_>, char, ())| {
+                format!(
+                    "{}{}",
+                    String::from_utf8_lossy(digits.fragment()),
+                    direction
+                )
+            },
+        ),
+        parse_scoped_label,
+    ))(i)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn label(input: &str) -> Option<String> {
+        parse_label(Span::new(input.as_bytes()))
+            .ok()
+            .map(|(_, label)| label.label())
+    }
+
+    fn local_ref(input: &str) -> Option<String> {
+        parse_local_label_ref(Span::new(input.as_bytes()))
+            .ok()
+            .map(|(_, label)| label)
+    }
+
+    #[test]
+    fn test_local_labels() {
+        assert_eq!(label("main:"), Some("main".to_string()));
+        assert_eq!(label("1:"), Some("1".to_string()));
+        assert_eq!(label(".Lloop :"), Some(".Lloop".to_string()));
+        assert_eq!(label(".L:"), None);
+        assert_eq!(label(".data:"), None);
+        assert_eq!(label("loop::M0:"), Some("loop::M0".to_string()));
+        assert_eq!(label(".Lloop::M12:"), Some(".Lloop::M12".to_string()));
+    }
+
+    #[test]
+    fn test_local_label_refs() {
+        assert_eq!(local_ref("1f"), Some("1f".to_string()));
+        assert_eq!(local_ref("12b, $t0"), Some("12b".to_string()));
+        assert_eq!(local_ref(".Lend"), Some(".Lend".to_string()));
+        assert_eq!(local_ref("0b101"), None);
+        assert_eq!(local_ref("1"), None);
+        assert_eq!(local_ref("foo"), None);
+        assert_eq!(local_ref("loop::M3"), Some("loop::M3".to_string()));
+        assert_eq!(local_ref("loop::0"), None);
+    }
+}
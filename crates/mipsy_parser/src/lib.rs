@@ -6,15 +6,20 @@ use nom_locate::LocatedSpan;
 
 pub type Span<'a> = LocatedSpan<&'a [u8]>;
 
+pub use attribute::Attribute;
 pub use constant::{MpConst, MpConstValue, MpConstValueLoc};
 pub use directive::MpDirective;
 pub use instruction::{MpArgument, MpInstruction};
-pub use misc::{tabs_to_spaces, ErrorLocation};
-pub use number::{MpImmediate, MpImmediateBinaryOp, MpNumber};
+pub use label::{
+    is_local_label, is_numeric_label, is_scoped_label, MpLabel, LOCAL_LABEL_SEPARATOR,
+};
+pub use macros::MpMacroExpansion;
+pub use misc::{tabs_to_spaces, ErrorKind, ErrorLocation, SyntaxError};
+pub use number::{MpImmediate, MpImmediateBinaryOp, MpNumber, MpRelocation};
 pub use parser::{MpItem, MpProgram, TaggedFile};
 pub use register::{MpOffsetOperator, MpRegister, MpRegisterIdentifier};
 
-pub use parser::parse_mips;
+pub use parser::{file_tab_size, parse_mips, parse_mips_with_defines};
 
 pub fn parse_instruction<T>(input: T, tab_size: u32) -> Result<MpInstruction, ErrorLocation>
 where
@@ -44,10 +49,15 @@ where
 
 mod attribute;
 mod constant;
+pub mod cst;
+mod diagnose;
 mod directive;
+mod include;
 mod instruction;
 mod label;
+mod macros;
 mod misc;
 mod number;
 pub mod parser;
+mod preprocess;
 mod register;
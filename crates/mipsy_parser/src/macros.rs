@@ -0,0 +1,419 @@
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    label::macro_label_name,
+    misc::ErrorKind,
+    preprocess::{
+        is_ident_contd, label_end, leading_spaces, literal_end, rewrite_idents, strip_comment,
+        IDENT_FIRST_CHAR,
+    },
+    ErrorLocation, SyntaxError,
+};
+
+/// How deeply macros may invoke other macros before we
+/// assume they're recursing forever.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// # Where an item produced by a macro came from.
+///
+/// Items expanded from a macro keep the line numbers of the
+/// macro's definition, and point to the invocation that
+/// expanded them with one of these.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MpMacroExpansion {
+    name: String,
+    file_tag: Option<Rc<str>>,
+    line: u32,
+    col: u32,
+    col_end: u32,
+    parent: Option<Rc<MpMacroExpansion>>,
+}
+
+impl MpMacroExpansion {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn file_tag(&self) -> Option<Rc<str>> {
+        self.file_tag.clone()
+    }
+
+    /// The line the macro was invoked on.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    pub fn col_end(&self) -> u32 {
+        self.col_end
+    }
+
+    /// The expansion this invocation was itself a part of,
+    /// if a macro was invoked from inside another macro.
+    pub fn parent(&self) -> Option<&MpMacroExpansion> {
+        self.parent.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MacroDefinition {
+    name: String,
+    file_tag: Option<Rc<str>>,
+    params: Vec<String>,
+    body: Vec<String>,
+    line: u32,
+}
+
+/// A single macro invocation found in some source,
+/// already expanded out to the text it should be replaced with.
+pub(crate) struct Invocation {
+    pub(crate) expansion: Rc<MpMacroExpansion>,
+    /// The file the macro was defined in, which its expansion belongs to.
+    pub(crate) file_tag: Option<Rc<str>>,
+    pub(crate) text: String,
+    /// The unique names the macro's labels were given in this expansion.
+    pub(crate) labels: Vec<String>,
+}
+
+impl MacroDefinition {
+    /// Starts a macro from the arguments to its `.macro` directive,
+    /// i.e. `name(%a, %b)`, `name %a, %b` or `name %a %b`.
+    pub(crate) fn new(header: &str, line: u32, file_tag: Option<Rc<str>>) -> Option<Self> {
+        let (name, params) = parse_header(header)?;
+
+        Some(Self {
+            name,
+            file_tag,
+            params,
+            body: vec![],
+            line,
+        })
+    }
+
+    pub(crate) fn push_line(&mut self, line: String) {
+        self.body.push(line);
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The line of the `.macro` directive.
+    pub(crate) fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The labels defined in the macro's body.
+    fn labels(&self) -> Vec<String> {
+        self.body
+            .iter()
+            .flat_map(|line| defined_labels(strip_comment(line)))
+            .collect()
+    }
+}
+
+/// Finds every macro invocation in `source`, blanking each one out, and
+/// returns the expanded text for each of them (in order).
+///
+/// Any labels before the invocation on the same line are left in place,
+/// so that they still point to the start of the expansion.
+pub(crate) fn expand_invocations(
+    source: &str,
+    file_tag: Option<Rc<str>>,
+    definitions: &[MacroDefinition],
+    parent: Option<Rc<MpMacroExpansion>>,
+    depth: usize,
+    expansion_count: &mut usize,
+) -> Result<(String, Vec<Invocation>), ErrorLocation> {
+    let mut lines = vec![];
+    let mut invocations = vec![];
+
+    for (index, line) in source.split('\n').enumerate() {
+        let line_number = index as u32 + 1;
+
+        let (start, end, definition, args) = match find_invocation(line, definitions) {
+            Some(invocation) => invocation,
+            None => {
+                lines.push(line.to_string());
+                continue;
+            }
+        };
+
+        if depth >= MAX_EXPANSION_DEPTH {
+            return Err(ErrorLocation {
+                file_name: file_tag,
+                line: line_number,
+                col: start + 1,
+                expansion: parent,
+                kind: ErrorKind::Syntax(SyntaxError::RecursiveMacro {
+                    name: definition.name.clone(),
+                }),
+            });
+        }
+
+        let expansion = Rc::new(MpMacroExpansion {
+            name: definition.name.clone(),
+            file_tag: file_tag.clone(),
+            line: line_number,
+            col: start as u32 + 1,
+            col_end: (start + definition.name.len()) as u32 + 1,
+            parent: parent.clone(),
+        });
+
+        invocations.push(Invocation {
+            expansion,
+            file_tag: definition.file_tag.clone(),
+            text: instantiate(definition, &args, *expansion_count),
+            labels: definition
+                .labels()
+                .iter()
+                .map(|label| macro_label_name(label, *expansion_count))
+                .collect(),
+        });
+        *expansion_count += 1;
+
+        lines.push(format!("{}{}", &line[..start], " ".repeat(end - start)));
+    }
+
+    Ok((lines.join("\n"), invocations))
+}
+
+/// Substitutes the arguments into a macro's body, and gives each label it
+/// defines a unique name for this expansion (`label` becomes `label::M<id>`).
+///
+/// The body is padded out so that its lines keep the definition's line numbers.
+fn instantiate(definition: &MacroDefinition, args: &[String], id: usize) -> String {
+    let local_labels = definition.labels();
+
+    let mut text = "\n".repeat(definition.line as usize);
+
+    for line in &definition.body {
+        text.push_str(&rewrite_idents(line, |ident| {
+            if let Some(param) = ident.strip_prefix('%') {
+                let index = definition.params.iter().position(|p| p == param)?;
+                return Some(args[index].clone());
+            }
+
+            local_labels
+                .iter()
+                .any(|label| label == ident)
+                .then(|| macro_label_name(ident, id))
+        }));
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Looks for an invocation of one of `definitions` on a line,
+/// returning where it starts and ends, which macro it is, and its arguments.
+fn find_invocation<'a>(
+    line: &str,
+    definitions: &'a [MacroDefinition],
+) -> Option<(usize, usize, &'a MacroDefinition, Vec<String>)> {
+    let code = strip_comment(line);
+    let mut start = leading_spaces(code);
+
+    // skip past any labels
+    while let Some(label_end) = label_end(&code[start..]) {
+        start += label_end;
+        start += leading_spaces(&code[start..]);
+    }
+
+    let rest = &code[start..];
+    let name_len = rest
+        .find(|c: char| !is_ident_contd(c))
+        .unwrap_or(rest.len());
+    let name = &rest[..name_len];
+
+    if !definitions.iter().any(|definition| definition.name == name) {
+        return None;
+    }
+
+    let after_name = &rest[name_len..];
+    let args = after_name.trim();
+
+    let args = if let Some(inner) = args.strip_prefix('(').and_then(|a| a.strip_suffix(')')) {
+        split_args(inner, false)
+    } else if after_name.is_empty()
+        || after_name.starts_with(char::is_whitespace) && !args.starts_with('=')
+    {
+        split_args(args, true)
+    } else {
+        return None;
+    };
+
+    let definition = definitions
+        .iter()
+        .find(|definition| definition.name == name && definition.params.len() == args.len())?;
+
+    Some((start, code.trim_end().len(), definition, args))
+}
+
+fn parse_header(header: &str) -> Option<(String, Vec<String>)> {
+    let name_len = header
+        .find(|c: char| !is_ident_contd(c))
+        .unwrap_or(header.len());
+    let name = &header[..name_len];
+
+    if name.is_empty() || !IDENT_FIRST_CHAR.contains(name.chars().next()?) {
+        return None;
+    }
+
+    let params = header[name_len..].trim();
+    let params = match params.strip_prefix('(') {
+        Some(params) => split_args(params.strip_suffix(')')?, false),
+        None => split_args(params, true),
+    };
+
+    let params = params
+        .into_iter()
+        .map(|param| {
+            let ident = param.strip_prefix('%')?;
+
+            if !ident.is_empty() && ident.chars().all(is_ident_contd) {
+                Some(ident.to_string())
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((name.to_string(), params))
+}
+
+/// Splits a list of arguments on commas (and optionally whitespace),
+/// keeping anything in brackets or quotes together.
+fn split_args(args: &str, split_whitespace: bool) -> Vec<String> {
+    let chars: Vec<char> = args.chars().collect();
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut brackets = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let char = chars[i];
+
+        match char {
+            '"' | '\'' => {
+                let end = literal_end(&chars, i);
+                current.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '(' => brackets += 1,
+            ')' => brackets -= 1,
+            ',' if brackets == 0 => {
+                parts.push(std::mem::take(&mut current));
+                i += 1;
+                continue;
+            }
+            _ if split_whitespace && brackets == 0 && char.is_whitespace() => {
+                if !current.trim().is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        current.push(char);
+        i += 1;
+    }
+
+    parts.push(current);
+
+    let parts: Vec<String> = parts
+        .into_iter()
+        .map(|part| part.trim().to_string())
+        .collect();
+
+    if parts.iter().all(|part| part.is_empty()) {
+        vec![]
+    } else {
+        parts.into_iter().filter(|part| !part.is_empty()).collect()
+    }
+}
+
+/// The labels defined at the start of a line.
+fn defined_labels(line: &str) -> Vec<String> {
+    let mut labels = vec![];
+    let mut rest = line.trim_start();
+
+    while let Some(end) = label_end(rest) {
+        labels.push(rest[..end - 1].trim_end().to_string());
+        rest = rest[end..].trim_start();
+    }
+
+    labels
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_header_forms() {
+        assert_eq!(
+            parse_header("swap(%a, %b)"),
+            Some(("swap".to_string(), vec!["a".to_string(), "b".to_string()]))
+        );
+        assert_eq!(
+            parse_header("swap %a, %b"),
+            Some(("swap".to_string(), vec!["a".to_string(), "b".to_string()]))
+        );
+        assert_eq!(
+            parse_header("swap %a %b"),
+            Some(("swap".to_string(), vec!["a".to_string(), "b".to_string()]))
+        );
+        assert_eq!(parse_header("done"), Some(("done".to_string(), vec![])));
+        assert_eq!(parse_header("swap(a, b)"), None);
+    }
+
+    #[test]
+    fn test_expansion() {
+        let mut definition = MacroDefinition::new("print(%reg, %kind)", 1, None).unwrap();
+        definition.push_line("loop: li $v0, %kind # print %reg".to_string());
+        definition.push_line("move $a0, %reg".to_string());
+        definition.push_line("beqz $a0, loop".to_string());
+        let definitions = vec![definition];
+
+        let mut count = 0;
+        let (source, invocations) = expand_invocations(
+            "start: print($t0, 1)\nprint $t1 11\nprint($t2)",
+            Some(Rc::from("foo.s")),
+            &definitions,
+            None,
+            0,
+            &mut count,
+        )
+        .unwrap();
+
+        // the labels stay where they are, and the wrong number of
+        // arguments isn't treated as an invocation
+        assert_eq!(source, "start:              \n            \nprint($t2)");
+        assert_eq!(count, 2);
+
+        assert_eq!(
+            invocations[0].text,
+            "\nloop::M0: li $v0, 1 # print %reg\nmove $a0, $t0\nbeqz $a0, loop::M0\n"
+        );
+        assert_eq!(invocations[0].expansion.line(), 1);
+        assert_eq!(invocations[0].expansion.col(), 8);
+        assert_eq!(invocations[0].expansion.col_end(), 13);
+        assert_eq!(invocations[0].labels, ["loop::M0"]);
+
+        assert_eq!(
+            invocations[1].text,
+            "\nloop::M1: li $v0, 11 # print %reg\nmove $a0, $t1\nbeqz $a0, loop::M1\n"
+        );
+        assert_eq!(invocations[1].expansion.line(), 2);
+    }
+}
@@ -1,6 +1,8 @@
 use std::rc::Rc;
 
-use crate::Span;
+use crate::{diagnose::diagnose, macros::MpMacroExpansion, Span};
+use serde::{Deserialize, Serialize};
+
 use nom::{
     branch::alt,
     bytes::complete::{is_a, tag},
@@ -16,6 +18,84 @@ pub struct ErrorLocation {
     pub file_name: Option<Rc<str>>,
     pub line: u32,
     pub col: usize,
+    pub expansion: Option<Rc<MpMacroExpansion>>,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    Syntax(SyntaxError),
+    CannotInclude { path: String, os_error: String },
+    IncludeCycle { path: String },
+    InvalidTabSize { value: Option<String> },
+}
+
+/// What was wrong with a line that couldn't be parsed.
+///
+/// Anything that was found instead of what was expected is given as it
+/// appears in the source, or `None` if the line ended first.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SyntaxError {
+    /// Two arguments with no comma between them, as in `lw $t0 4($sp)`.
+    MissingComma {
+        found: String,
+    },
+    /// Nothing after a comma, an `=`, or a directive that needs an argument.
+    MissingArgument {
+        after: String,
+    },
+    InvalidArgument {
+        found: String,
+    },
+    /// A number with something stuck to the end of it, as in `12abc` or `0x`.
+    InvalidNumber {
+        found: String,
+    },
+    /// A character literal holding more (or less) than one character.
+    InvalidChar {
+        found: String,
+    },
+    UnterminatedString,
+    UnterminatedChar,
+    UnclosedParen,
+    /// Something that can't go where it is, such as an argument to `.text`.
+    UnexpectedArgument {
+        directive: String,
+        found: String,
+    },
+    /// Something that can't follow what came before it on the line.
+    UnexpectedToken {
+        found: String,
+    },
+    /// Something other than a label, directive or instruction at the start of an item.
+    ExpectedItem {
+        found: String,
+    },
+    ExpectedString {
+        directive: String,
+        found: String,
+    },
+    UnknownDirective {
+        directive: String,
+        similar: Option<String>,
+    },
+    UnmatchedEndMacro,
+    NestedMacro,
+    UnterminatedMacro {
+        name: String,
+    },
+    InvalidMacroHeader,
+    /// A macro that (maybe indirectly) expands into itself.
+    RecursiveMacro {
+        name: String,
+    },
+    InvalidEqv,
+    InvalidInclude,
+    InvalidSet,
+    /// A label named like the ones a macro's labels are renamed to, such as `loop::M0`.
+    ReservedLabel {
+        label: String,
+    },
 }
 
 pub(crate) fn parse_result<'a, T, P>(
@@ -40,11 +120,19 @@ where
 
 fn leftover_tokens_strip_multispace(i: Span<'_>, file_name: Option<Rc<str>>) -> ErrorLocation {
     match comment_multispace0(i) {
-        Ok((leftover, _)) => ErrorLocation {
-            file_name,
-            line: leftover.location_line(),
-            col: leftover.get_column(),
-        },
+        Ok((leftover, _)) => {
+            // the rest of the line is looked at to work out what went wrong
+            let line = String::from_utf8_lossy(leftover.get_line_beginning());
+            let (index, error) = diagnose(&line, leftover.get_column() - 1);
+
+            ErrorLocation {
+                file_name,
+                line: leftover.location_line(),
+                col: index + 1,
+                expansion: None,
+                kind: ErrorKind::Syntax(error),
+            }
+        }
         Err(err) => {
             eprintln!("ERROR: {}", err);
             panic!("this should never happen - please report an issue at https://github.com/insou22/mipsy")
@@ -1,6 +1,8 @@
 use std::fmt;
 
 use crate::{
+    constant::{parse_constant_value, MpConstValueLoc},
+    label::parse_local_label_ref,
     misc::{escape_char, parse_escaped_char, parse_ident},
     Span,
 };
@@ -22,6 +24,31 @@ pub enum MpNumber {
     Float32(f32),
     Float64(f64),
     Char(char),
+    /// `%hi(value)` or `%lo(value)`, where the value can refer to labels
+    Relocation(MpRelocation, MpConstValueLoc),
+    /// A constant expression that can refer to labels, such as `array + 4 * N`
+    Expression(MpConstValueLoc),
+}
+
+/// Which half of a 32-bit value a relocation operator takes.
+///
+/// As `%lo` is sign-extended by instructions like `addiu` and `lw`,
+/// `%hi` is rounded up whenever bit 15 is set, so that `%hi(x) << 16`
+/// plus the sign-extended `%lo(x)` is always `x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MpRelocation {
+    Hi,
+    Lo,
+}
+
+impl MpRelocation {
+    /// The 16 bits of `value` that this relocation operator gives.
+    pub fn apply(&self, value: u32) -> u16 {
+        match self {
+            Self::Hi => (value.wrapping_add(0x8000) >> 16) as u16,
+            Self::Lo => value as u16,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -47,6 +74,8 @@ impl fmt::Display for MpNumber {
             Self::Float32(float) => write!(f, "{}", float),
             Self::Float64(float) => write!(f, "{}", float),
             Self::Char(char) => write!(f, "'{}'", escape_char(*char)),
+            Self::Relocation(relocation, value) => write!(f, "{}({})", relocation, value.0),
+            Self::Expression(value) => write!(f, "{}", value.0),
         }
     }
 }
@@ -63,6 +92,15 @@ impl fmt::Display for MpImmediate {
     }
 }
 
+impl fmt::Display for MpRelocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hi => write!(f, "%hi"),
+            Self::Lo => write!(f, "%lo"),
+        }
+    }
+}
+
 impl fmt::Display for MpImmediateBinaryOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -82,6 +120,29 @@ pub fn parse_number(i: Span<'_>) -> IResult<Span<'_>, MpNumber> {
     ))(i)
 }
 
+/// Parses `%hi(value)` or `%lo(value)`.
+pub fn parse_relocation(i: Span<'_>) -> IResult<Span<'_>, MpNumber> {
+    let (remaining_data, (relocation, _, _, value, _, _)) = tuple((
+        alt((
+            map(tag("%hi"), |_| MpRelocation::Hi),
+            map(tag("%lo"), |_| MpRelocation::Lo),
+        )),
+        char('('),
+        space0,
+        parse_constant_value,
+        space0,
+        char(')'),
+    ))(i)?;
+
+    Ok((remaining_data, MpNumber::Relocation(relocation, value)))
+}
+
+/// Parses a constant expression (which can refer to labels),
+/// for when it's more than [`parse_number`] can represent.
+pub fn parse_expression(i: Span<'_>) -> IResult<Span<'_>, MpNumber> {
+    map(parse_constant_value, MpNumber::Expression)(i)
+}
+
 pub fn parse_binary_op_immedaite(i: Span<'_>) -> IResult<Span<'_>, MpNumber> {
     let (remaining_data, (i1, _, op, _, i2)) = tuple((
         parse_immediate,
@@ -100,6 +161,8 @@ pub fn parse_binary_op_immedaite(i: Span<'_>) -> IResult<Span<'_>, MpNumber> {
 
 pub fn parse_immediate(i: Span<'_>) -> IResult<Span<'_>, MpImmediate> {
     alt((
+        // before the numbers, so that `1f` isn't read as `1`
+        map(parse_local_label_ref, MpImmediate::LabelReference),
         map(parse_i16, MpImmediate::I16),
         map(parse_u16, MpImmediate::U16),
         map(parse_i32, MpImmediate::I32),
@@ -271,6 +334,26 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn relocation() {
+        let (rest, number) = unspan(parse_relocation(span("%hi(arr + 8), $t0")).unwrap());
+
+        assert_eq!(rest, ", $t0");
+        assert_eq!(number.to_string(), "%hi(arr + 8)");
+        assert!(matches!(number, MpNumber::Relocation(MpRelocation::Hi, _)));
+
+        assert!(parse_relocation(span("%mid(arr)")).is_err());
+    }
+
+    #[test]
+    fn relocation_halves() {
+        // %hi rounds up when %lo will be sign-extended to a negative number
+        assert_eq!(MpRelocation::Hi.apply(0x1001_0004), 0x1001);
+        assert_eq!(MpRelocation::Lo.apply(0x1001_0004), 0x0004);
+        assert_eq!(MpRelocation::Hi.apply(0x1001_8004), 0x1002);
+        assert_eq!(MpRelocation::Lo.apply(0x1001_8004), 0x8004);
+    }
 }
 
 pub trait RadixNum<O> {
@@ -1,15 +1,29 @@
-use std::rc::Rc;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use crate::{
     attribute::{parse_inner_attribute, parse_outer_attribute, Attribute},
     constant::{parse_constant, MpConst},
+    diagnose::diagnose,
     directive::{parse_directive, MpDirective, MpDirectiveLoc},
+    include,
     instruction::{parse_instruction, MpInstruction},
-    label::{parse_label, MpLabel},
-    misc::{comment_multispace0, comment_multispace1, parse_result},
+    label::{parse_label, MpLabel, LOCAL_LABEL_SEPARATOR},
+    macros::{self, MacroDefinition, MpMacroExpansion},
+    misc::{comment_multispace0, comment_multispace1, parse_result, ErrorKind, SyntaxError},
+    preprocess::{
+        directive_args, leading_spaces, parse_eqv, parse_set, strip_comment, substitute_eqvs,
+        Conditionals, Set,
+    },
     ErrorLocation, Span,
 };
-use nom::{branch::alt, combinator::map, multi::many0, sequence::tuple, AsBytes, IResult};
+use nom::{
+    branch::alt, bytes::complete::is_not, combinator::map, multi::many0, sequence::tuple, AsBytes,
+    IResult,
+};
 use nom_locate::{position, LocatedSpan};
 use serde::{Deserialize, Serialize};
 
@@ -30,7 +44,8 @@ pub struct Position {
 #[derive(Debug, Clone, PartialEq)]
 pub struct MpProgram {
     pub(crate) items: Vec<MpAttributedItem>,
-    pub(crate) file_attributes: Vec<Attribute>,
+    pub(crate) file_attributes: Vec<(Option<Rc<str>>, Vec<Attribute>)>,
+    pub(crate) included_files: Vec<(Rc<str>, Rc<str>)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,6 +54,7 @@ pub struct MpAttributedItem {
     pub(crate) attributes: Vec<Attribute>,
     pub(crate) file_tag: Option<Rc<str>>,
     pub(crate) line_number: u32,
+    pub(crate) expansion: Option<Rc<MpMacroExpansion>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -47,8 +63,13 @@ pub enum MpItem {
     Directive(MpDirectiveLoc),
     Label(MpLabel),
     Constant(MpConst),
+    /// The rest of a line that couldn't be parsed,
+    /// which is skipped so that parsing can carry on from the next line.
+    Error(MpSyntaxErrorLoc),
 }
 
+pub type MpSyntaxErrorLoc = (SyntaxError, Position);
+
 impl<'tag, 'file> TaggedFile<'tag, 'file> {
     pub fn new(tag: Option<&'tag str>, file_contents: &'file str) -> Self {
         Self { tag, file_contents }
@@ -115,6 +136,7 @@ impl MpAttributedItem {
             attributes,
             file_tag,
             line_number,
+            expansion: None,
         }
     }
 
@@ -137,13 +159,19 @@ impl MpAttributedItem {
     pub fn line_number(&self) -> u32 {
         self.line_number
     }
+
+    /// The macro invocation this item was expanded from, if any.
+    pub fn expansion(&self) -> Option<Rc<MpMacroExpansion>> {
+        self.expansion.clone()
+    }
 }
 
 impl MpProgram {
     pub fn new(items: Vec<MpAttributedItem>, file_attributes: Vec<Attribute>) -> Self {
         Self {
             items,
-            file_attributes,
+            file_attributes: vec![(None, file_attributes)],
+            included_files: vec![],
         }
     }
 
@@ -155,6 +183,39 @@ impl MpProgram {
         &mut self.items
     }
 
+    /// The `#![...]` attributes at the top of each file, by file tag.
+    pub fn file_attributes(&self) -> &[(Option<Rc<str>>, Vec<Attribute>)] {
+        &self.file_attributes
+    }
+
+    /// The tag and contents of every file pulled in with `.include`.
+    pub fn included_files(&self) -> &[(Rc<str>, Rc<str>)] {
+        &self.included_files
+    }
+
+    /// Every line that couldn't be parsed, in the order they appear.
+    pub fn syntax_errors(&self) -> Vec<ErrorLocation> {
+        self.items
+            .iter()
+            .filter_map(|item| match &item.item {
+                MpItem::Error((error, pos)) => Some(ErrorLocation {
+                    file_name: item.file_tag(),
+                    line: pos.line(),
+                    col: pos.col() as usize,
+                    expansion: item.expansion(),
+                    kind: ErrorKind::Syntax(error.clone()),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Removes the lines that couldn't be parsed, so that the rest can still be compiled.
+    pub fn remove_syntax_errors(&mut self) {
+        self.items
+            .retain(|item| !matches!(item.item, MpItem::Error(_)));
+    }
+
     fn merge(&mut self, mut other: MpProgram) {
         if !self.items.is_empty() {
             self.items.push(MpAttributedItem {
@@ -162,10 +223,12 @@ impl MpProgram {
                 attributes: vec![],
                 file_tag: None,
                 line_number: 0,
+                expansion: None,
             });
         }
 
         self.items.append(&mut other.items);
+        self.file_attributes.append(&mut other.file_attributes);
     }
 }
 
@@ -191,32 +254,72 @@ pub fn parse_mips_item(i: Span<'_>) -> IResult<Span<'_>, (MpItem, Vec<Attribute>
     )(i)
 }
 
+/// Skips the rest of a line that couldn't be parsed, working out what was wrong with it.
+pub fn parse_syntax_error(i: Span<'_>) -> IResult<Span<'_>, (MpItem, Vec<Attribute>, u32)> {
+    let (start, _) = comment_multispace0(i)?;
+    let (remaining_data, _) = is_not("\n")(start)?;
+
+    let line = String::from_utf8_lossy(start.get_line_beginning());
+    let (col, error) = diagnose(&line, start.get_column() - 1);
+
+    let line_number = start.location_line();
+    let position = Position::new(
+        line_number,
+        line_number,
+        col as u32 + 1,
+        line.len() as u32 + 1,
+    );
+
+    Ok((
+        remaining_data,
+        (MpItem::Error((error, position)), vec![], line_number),
+    ))
+}
+
 pub fn parse_mips_bytes<'a>(
     file_name: Option<Rc<str>>,
 ) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, MpProgram> {
     move |i| {
+        let to_item = |(item, attrs, line)| {
+            Some(MpAttributedItem {
+                item,
+                attributes: attrs,
+                file_tag: file_name.clone(),
+                line_number: line,
+                expansion: None,
+            })
+        };
+
         let (remaining_input, (attrs, items)) = tuple((
             parse_outer_attributes,
             many0(alt((
-                map(parse_mips_item, |(item, attrs, line)| {
-                    Some(MpAttributedItem {
-                        item,
-                        attributes: attrs,
-                        file_tag: file_name.clone(),
-                        line_number: line,
-                    })
-                }),
+                map(parse_mips_item, to_item),
                 map(comment_multispace1, |_| None),
+                map(parse_syntax_error, to_item),
             ))),
         ))(i)?;
 
-        let items = items.into_iter().flatten().collect();
+        let mut items: Vec<MpAttributedItem> = items.into_iter().flatten().collect();
+
+        // whatever was parsed before an error on the same line is probably
+        // only part of what was meant, so only its labels are kept
+        let error_lines: HashSet<u32> = items
+            .iter()
+            .filter(|item| matches!(item.item, MpItem::Error(_)))
+            .map(|item| item.line_number)
+            .collect();
+
+        items.retain(|item| {
+            !error_lines.contains(&item.line_number)
+                || matches!(item.item, MpItem::Error(_) | MpItem::Label(_))
+        });
 
         Ok((
             remaining_input,
             MpProgram {
                 items,
-                file_attributes: attrs,
+                file_attributes: vec![(file_name.clone(), attrs)],
+                included_files: vec![],
             },
         ))
     }
@@ -238,46 +341,601 @@ pub fn parse_outer_attributes(i: Span<'_>) -> IResult<Span<'_>, Vec<Attribute>>
 pub fn parse_mips(
     files: Vec<TaggedFile<'_, '_>>,
     default_tab_size: u32,
+) -> Result<MpProgram, ErrorLocation> {
+    parse_mips_with_defines(files, default_tab_size, &[])
+}
+
+/// Like [`parse_mips`], but with constants defined from outside the program
+/// (i.e. `-D NAME=VALUE`), which `.if` and `.ifdef` can use to decide
+/// whether to `.include` a file, or define an `.eqv` or macro.
+pub fn parse_mips_with_defines(
+    files: Vec<TaggedFile<'_, '_>>,
+    default_tab_size: u32,
+    defines: &[(String, i64)],
 ) -> Result<MpProgram, ErrorLocation> {
     let mut program = MpProgram {
         items: vec![],
         file_attributes: vec![],
+        included_files: vec![],
+    };
+    let mut context = ParseContext {
+        expansion_count: 0,
+        including: vec![],
+        included_files: vec![],
+        eqvs: vec![],
+        defines: defines.to_vec(),
+        constants: HashMap::new(),
     };
 
     for file in files {
-        let file_name = file.tag;
-        let input = file.file_contents;
+        let file_name = file.tag.map(Rc::from);
+
+        if let Some(tag) = file.tag {
+            context.including.push(include::identity(Path::new(tag)));
+        }
 
-        let file_name = file_name.map(Rc::from);
+        let (result, _) = parse_file(
+            file.file_contents,
+            file_name,
+            default_tab_size,
+            &mut context,
+        )?;
 
-        let initial_file_string = crate::misc::tabs_to_spaces(input, default_tab_size);
-        let initial_span = Span::new(initial_file_string.as_bytes());
+        context.including.clear();
+        context.eqvs.clear();
+
+        program.merge(result);
+    }
 
-        let (_remaining_input, outer_attrs) = parse_outer_attributes(initial_span)
-            .expect("Initial outer attributes parser should never fail");
+    program.included_files = context.included_files;
 
-        let mut actual_tabsize = default_tab_size;
+    Ok(program)
+}
+
+struct ParseContext {
+    /// How many macro invocations have been expanded so far,
+    /// used to give each expansion's labels unique names.
+    expansion_count: usize,
+    /// The files currently being parsed, outermost first.
+    including: Vec<PathBuf>,
+    included_files: Vec<(Rc<str>, Rc<str>)>,
+    /// The `.eqv`s defined so far, which carry on into (and out of) included files.
+    eqvs: Vec<(String, String)>,
+    /// Constants defined with `-D`, which override any definition in the program.
+    defines: Vec<(String, i64)>,
+    /// The constants defined so far (outside of a macro), for working out conditions.
+    constants: HashMap<String, i64>,
+}
+
+impl ParseContext {
+    /// The value of a constant defined so far, for working out a condition.
+    fn constant(&self, name: &str) -> Option<i64> {
+        self.defines
+            .iter()
+            .find(|(define, _)| define == name)
+            .map(|&(_, value)| value)
+            .or_else(|| self.constants.get(name).copied())
+    }
+
+    /// Remembers the constant `line` defines, if it defines one.
+    fn define_constant(&mut self, line: &str) {
+        let code = strip_comment(line).trim();
+
+        let constant = match parse_constant(Span::new(code.as_bytes())) {
+            Ok((remaining, constant)) if remaining.fragment().is_empty() => constant,
+            _ => return,
+        };
+
+        // an invalid constant will be reported when it's compiled
+        if let Ok(value) = constant.value().eval(&|name| self.constant(name)) {
+            self.constants.insert(constant.label().to_string(), value);
+        }
+    }
+}
+
+/// Parses a single file, along with everything it includes.
+///
+/// Also returns the macros it (and everything it includes) defines,
+/// so that the file including it can use them.
+fn parse_file(
+    input: &str,
+    file_name: Option<Rc<str>>,
+    default_tab_size: u32,
+    context: &mut ParseContext,
+) -> Result<(MpProgram, Vec<MacroDefinition>), ErrorLocation> {
+    let initial_file_string = crate::misc::tabs_to_spaces(input, default_tab_size);
+    let initial_span = Span::new(initial_file_string.as_bytes());
+
+    let (_remaining_input, outer_attrs) = parse_outer_attributes(initial_span)
+        .expect("Initial outer attributes parser should never fail");
+
+    let actual_tabsize =
+        tab_size(&outer_attrs, default_tab_size).map_err(|attr| ErrorLocation {
+            file_name: file_name.clone(),
+            line: attr.line(),
+            col: attr.col() as usize,
+            expansion: None,
+            kind: ErrorKind::InvalidTabSize {
+                value: attr.value().map(str::to_string),
+            },
+        })?;
+
+    let file_string = crate::misc::tabs_to_spaces(input, actual_tabsize);
+
+    let mut source = vec![];
+    let mut definitions: Vec<MacroDefinition> = vec![];
+    let mut current_definition: Option<MacroDefinition> = None;
+    let mut included = vec![];
+    let mut included_attributes = vec![];
+    let mut conditionals = Conditionals::default();
+
+    for (index, line) in file_string.split('\n').enumerate() {
+        let line_number = index as u32 + 1;
+        let col = leading_spaces(line) + 1;
+        let code = strip_comment(line).trim();
+        let substituted = substitute_eqvs(line, &context.eqvs);
+
+        let error = |kind| ErrorLocation {
+            file_name: file_name.clone(),
+            line: line_number,
+            col,
+            expansion: None,
+            kind,
+        };
+        let syntax_error = |syntax| error(ErrorKind::Syntax(syntax));
+
+        // everything but ordinary lines is blanked out,
+        // so that the line numbers of everything else stay the same
+        source.push(String::new());
+
+        if let Some(definition) = current_definition.as_mut() {
+            if directive_args(code, ".end_macro") == Some("") {
+                definitions.extend(current_definition.take());
+            } else if directive_args(code, ".macro").is_some() {
+                // macros can't be defined inside other macros
+                return Err(syntax_error(SyntaxError::NestedMacro));
+            } else {
+                definition.push_line(substituted);
+            }
+        } else if let Some(included) = conditionals
+            .update(strip_comment(&substituted).trim(), &|name| {
+                context.constant(name)
+            })
+        {
+            if included {
+                *source.last_mut().unwrap() = substituted;
+            }
+        } else if !conditionals.active() {
+            // in a branch that isn't taken, so left blank
+        } else if let Some(header) = directive_args(code, ".macro") {
+            current_definition = Some(
+                MacroDefinition::new(header, line_number, file_name.clone())
+                    .ok_or_else(|| syntax_error(SyntaxError::InvalidMacroHeader))?,
+            );
+        } else if let Some(args) = directive_args(code, ".eqv") {
+            let (name, value) =
+                parse_eqv(args).ok_or_else(|| syntax_error(SyntaxError::InvalidEqv))?;
+            let value = substitute_eqvs(&value, &context.eqvs);
+
+            context.eqvs.retain(|(eqv, _)| *eqv != name);
+            context.eqvs.push((name, value));
+        } else if let Some(args) = directive_args(code, ".include") {
+            let path = include::include_path(args)
+                .ok_or_else(|| syntax_error(SyntaxError::InvalidInclude))?;
+            let path = include::resolve(file_name.as_deref(), path);
+            let tag: Rc<str> = Rc::from(path.to_string_lossy().as_ref());
+
+            let identity = include::identity(&path);
+            if context.including.contains(&identity) {
+                return Err(error(ErrorKind::IncludeCycle {
+                    path: tag.to_string(),
+                }));
+            }
 
-        for attr in outer_attrs {
-            // TODO(zkol): Not a fan of this random hardcoding here
-            if attr.key().to_ascii_lowercase() == "tabsize" {
-                // TODO(zkol): This error handling needs to get wrapped up
-                // with the rest somehow...
-                actual_tabsize = attr
-                    .value()
-                    .expect("Tabsize attribute requires a value")
-                    .parse()
-                    .expect("Tabsize attribute value should be numeric");
+            let contents = std::fs::read_to_string(&path).map_err(|err| {
+                error(ErrorKind::CannotInclude {
+                    path: tag.to_string(),
+                    os_error: err.to_string(),
+                })
+            })?;
+
+            context.including.push(identity);
+            let (program, included_definitions) =
+                parse_file(&contents, Some(tag.clone()), default_tab_size, context)?;
+            context.including.pop();
+
+            context.included_files.push((tag, Rc::from(contents)));
+
+            definitions.extend(included_definitions);
+            included.push((line_number, program.items));
+            included_attributes.extend(program.file_attributes);
+        } else if let Some(args) = directive_args(code, ".set") {
+            match parse_set(line, args) {
+                Some(Set::Constant(constant)) => {
+                    let constant = substitute_eqvs(&constant, &context.eqvs);
+
+                    context.define_constant(&constant);
+                    *source.last_mut().unwrap() = constant;
+                }
+                Some(Set::Option) => {}
+                None => return Err(syntax_error(SyntaxError::InvalidSet)),
             }
+        } else {
+            context.define_constant(&substituted);
+            *source.last_mut().unwrap() = substituted;
         }
+    }
 
-        let file_string = crate::misc::tabs_to_spaces(input, actual_tabsize);
-        let span = Span::new(file_string.as_bytes());
+    if let Some(unterminated) = current_definition {
+        return Err(ErrorLocation {
+            file_name,
+            line: unterminated.line(),
+            col: 1,
+            expansion: None,
+            kind: ErrorKind::Syntax(SyntaxError::UnterminatedMacro {
+                name: unterminated.name().to_string(),
+            }),
+        });
+    }
 
-        let result = parse_result(span, file_name.clone(), parse_mips_bytes(file_name))?;
+    let mut program = parse_expanded(
+        &source.join("\n"),
+        file_name,
+        &definitions,
+        None,
+        0,
+        &mut context.expansion_count,
+        included,
+    )?;
+    program.file_attributes.extend(included_attributes);
+
+    Ok((program, definitions))
+}
 
-        program.merge(result);
+/// The tab size a file asks for with `#![tabsize(n)]`, if it does.
+///
+/// Gives back the attribute if its value isn't a valid tab size.
+fn tab_size(outer_attrs: &[Attribute], default_tab_size: u32) -> Result<u32, &Attribute> {
+    let mut tab_size = default_tab_size;
+
+    for attr in outer_attrs {
+        if attr.key().eq_ignore_ascii_case("tabsize") {
+            tab_size = attr
+                .value()
+                .and_then(|value| value.trim().parse().ok())
+                .filter(|&size| size > 0)
+                .ok_or(attr)?;
+        }
+    }
+
+    Ok(tab_size)
+}
+
+/// The tab size used for a file's contents, which is the default
+/// unless the file overrides it with `#![tabsize(n)]`.
+pub fn file_tab_size(input: &str, default_tab_size: u32) -> u32 {
+    let file_string = crate::misc::tabs_to_spaces(input, default_tab_size);
+
+    parse_outer_attributes(Span::new(file_string.as_bytes()))
+        .ok()
+        .and_then(|(_, outer_attrs)| tab_size(&outer_attrs, default_tab_size).ok())
+        .unwrap_or(default_tab_size)
+}
+
+/// Parses some source, splicing in the items of any macros it invokes,
+/// and any files it included (which have already been parsed).
+fn parse_expanded(
+    source: &str,
+    file_name: Option<Rc<str>>,
+    definitions: &[MacroDefinition],
+    expansion: Option<Rc<MpMacroExpansion>>,
+    depth: usize,
+    expansion_count: &mut usize,
+    mut insertions: Vec<(u32, Vec<MpAttributedItem>)>,
+) -> Result<MpProgram, ErrorLocation> {
+    let (source, invocations) = macros::expand_invocations(
+        source,
+        file_name.clone(),
+        definitions,
+        expansion.clone(),
+        depth,
+        expansion_count,
+    )?;
+
+    let span = Span::new(source.as_bytes());
+
+    let mut program = parse_result(span, file_name.clone(), parse_mips_bytes(file_name.clone()))
+        .map_err(|err| ErrorLocation {
+            expansion: expansion.clone(),
+            ..err
+        })?;
+
+    for item in program.items.iter_mut() {
+        item.expansion = expansion.clone();
+    }
+
+    if expansion.is_none() {
+        check_label_names(&program.items, None, &[])?;
+    }
+
+    for invocation in invocations {
+        let line = invocation.expansion.line();
+
+        let expanded = parse_expanded(
+            &invocation.text,
+            invocation.file_tag,
+            definitions,
+            Some(invocation.expansion.clone()),
+            depth + 1,
+            expansion_count,
+            vec![],
+        )?;
+        check_label_names(
+            &expanded.items,
+            Some(&invocation.expansion),
+            &invocation.labels,
+        )?;
+
+        insertions.push((line, expanded.items));
+    }
+
+    insertions.sort_by_key(|&(line, _)| line);
+
+    // each insertion goes just after whatever came before it on its line
+    let mut index = 0;
+
+    for (line, items) in insertions {
+        while index < program.items.len() && program.items[index].line_number <= line {
+            index += 1;
+        }
+
+        let items_len = items.len();
+        program.items.splice(index..index, items);
+        index += items_len;
     }
 
     Ok(program)
 }
+
+/// Makes sure the only labels from `expansion` named like a macro's labels
+/// are the ones it `renamed`, so they can't clash with any the program defines.
+fn check_label_names(
+    items: &[MpAttributedItem],
+    expansion: Option<&Rc<MpMacroExpansion>>,
+    renamed: &[String],
+) -> Result<(), ErrorLocation> {
+    for item in items
+        .iter()
+        .filter(|item| item.expansion.as_ref() == expansion)
+    {
+        if let MpItem::Label(label) = &item.item {
+            let name = label.label();
+
+            if name.contains(LOCAL_LABEL_SEPARATOR) && !renamed.contains(&name) {
+                return Err(ErrorLocation {
+                    file_name: item.file_tag.clone(),
+                    line: item.line_number,
+                    col: label.col() as usize,
+                    expansion: item.expansion.clone(),
+                    kind: ErrorKind::Syntax(SyntaxError::ReservedLabel { label: name }),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_syntax_error_recovery() {
+        let program = parse_mips(
+            vec![TaggedFile::new(
+                None,
+                "main: lw $t0 4($sp)\n    li $v0, 1\n    add $t0, $t1,\n    syscall\n",
+            )],
+            8,
+        )
+        .unwrap();
+
+        let errors: Vec<_> = program
+            .syntax_errors()
+            .into_iter()
+            .map(|error| (error.line, error.col, error.kind))
+            .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    1,
+                    14,
+                    ErrorKind::Syntax(SyntaxError::MissingComma {
+                        found: "4($sp)".to_string()
+                    })
+                ),
+                (
+                    3,
+                    17,
+                    ErrorKind::Syntax(SyntaxError::MissingArgument {
+                        after: ",".to_string()
+                    })
+                ),
+            ]
+        );
+
+        // only the label is kept from a line with an error on it
+        let items: Vec<_> = program
+            .items()
+            .iter()
+            .map(|item| (item.line_number(), item.item()))
+            .filter(|(_, item)| !matches!(item, MpItem::Error(_)))
+            .map(|(line, item)| match item {
+                MpItem::Instruction(inst) => (line, inst.name().to_string()),
+                MpItem::Label(label) => (line, label.label()),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            items,
+            vec![
+                (1, "main".to_string()),
+                (2, "li".to_string()),
+                (4, "syscall".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditional_preprocessing() {
+        let source = "
+.ifdef DEBUG
+    .include \"debug.s\"
+    .eqv PRINT jal debug_print
+.else
+    .eqv PRINT nop
+    .macro log
+    nop
+    .end_macro
+.endif
+main:
+    PRINT
+";
+
+        let parse = |defines: &[(String, i64)]| {
+            parse_mips_with_defines(vec![TaggedFile::new(None, source)], 8, defines)
+        };
+
+        // the file that doesn't exist is never included,
+        // and `PRINT` is the `.eqv` from the branch that's taken
+        let program = parse(&[]).unwrap();
+        let insts: Vec<_> = program
+            .items()
+            .iter()
+            .filter_map(|item| match item.item() {
+                MpItem::Instruction(inst) => Some((item.line_number(), inst.name())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(insts, vec![(12, "nop")]);
+
+        // but it is with `-D DEBUG`
+        let error = parse(&[("DEBUG".to_string(), 1)]).unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(matches!(error.kind, ErrorKind::CannotInclude { .. }));
+    }
+
+    #[test]
+    fn test_conditions() {
+        let lines = |source: &str| {
+            parse_mips(vec![TaggedFile::new(None, source)], 8)
+                .unwrap()
+                .items()
+                .iter()
+                .filter(|item| !matches!(item.item(), MpItem::Directive(_)))
+                .map(|item| item.line_number())
+                .collect::<Vec<_>>()
+        };
+
+        // constants (including those from `.set`) can be used once they're defined,
+        // and whatever's in a branch that isn't taken is never defined
+        assert_eq!(
+            lines("X = 2\n.set Y, X * 2\n.if Y - 4\nZ = 1\n.endif\n.ifdef Z\nnop\n.else\nnop\n.endif\n"),
+            vec![1, 2, 9]
+        );
+
+        // nested conditionals are only taken if everything around them is
+        assert_eq!(
+            lines(".if 0\n.if 1\nnop\n.else\nnop\n.endif\n.else\nnop\n.endif\n"),
+            vec![8]
+        );
+
+        // and one that can't be worked out yet is left to the compiler
+        assert_eq!(
+            lines(".if LATER\nnop\n.else\nnop\n.endif\nLATER = 1\n"),
+            vec![2, 4, 6]
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let program = parse_mips(
+            vec![TaggedFile::new(
+                None,
+                "    .set noreorder\n    .set SIZE, 4 # bytes\n",
+            )],
+            8,
+        )
+        .unwrap();
+
+        // the same as `SIZE = 4`, in the same place
+        match program.items()[0].item() {
+            MpItem::Constant(constant) => {
+                assert_eq!(constant.label(), "SIZE");
+                assert_eq!((constant.line(), constant.col()), (2, 10));
+            }
+            item => panic!("expected a constant, found {:?}", item),
+        }
+        assert_eq!(program.items().len(), 1);
+
+        for source in [".set", ".set SIZE 4", ".set 4, SIZE", ".set SIZE,"] {
+            assert_eq!(
+                parse_mips(vec![TaggedFile::new(None, source)], 8)
+                    .unwrap_err()
+                    .kind,
+                ErrorKind::Syntax(SyntaxError::InvalidSet),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_macro_labels() {
+        let source = "
+    .macro wait
+loop: bnez $t0, loop
+    .end_macro
+main:
+    wait
+loop: wait
+";
+        let program = parse_mips(vec![TaggedFile::new(None, source)], 8).unwrap();
+
+        // each expansion's `loop` is renamed, so none of them clash
+        let labels: Vec<_> = program
+            .items()
+            .iter()
+            .filter_map(|item| match item.item() {
+                MpItem::Label(label) => Some(label.label()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels, ["main", "loop::M0", "loop", "loop::M1"]);
+
+        // and the program can't use those names itself,
+        // whether directly or by passing one to a macro
+        let label_error = |source: &str| {
+            let error = parse_mips(vec![TaggedFile::new(None, source)], 8).unwrap_err();
+            (error.line, error.col, error.kind)
+        };
+        let reserved = |label: &str| {
+            ErrorKind::Syntax(SyntaxError::ReservedLabel {
+                label: label.to_string(),
+            })
+        };
+
+        assert_eq!(
+            label_error("main:\n  loop::M0: nop\n"),
+            (2, 3, reserved("loop::M0"))
+        );
+        assert_eq!(
+            label_error(".macro def(%name)\n%name: nop\n.end_macro\ndef(loop::M0)\n"),
+            (2, 1, reserved("loop::M0"))
+        );
+    }
+}
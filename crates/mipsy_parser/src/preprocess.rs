@@ -0,0 +1,341 @@
+//! Line-based processing done before parsing:
+//! `.eqv` substitution, `.set`, working out which branch of each `.if` is taken,
+//! and the helpers shared with macros and `.include`.
+
+use crate::{constant::parse_constant_value, Span};
+
+pub(crate) const IDENT_FIRST_CHAR: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_.";
+const IDENT_CONTD_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_0123456789.";
+
+/// Parses the arguments of `.eqv NAME token-sequence`.
+pub(crate) fn parse_eqv(args: &str) -> Option<(String, String)> {
+    let name_len = args.find(char::is_whitespace)?;
+    let (name, value) = args.split_at(name_len);
+
+    if !name.starts_with(|c| IDENT_FIRST_CHAR.contains(c)) || !name.chars().all(is_ident_contd) {
+        return None;
+    }
+
+    Some((name.to_string(), value.trim().to_string()))
+}
+
+/// What a `.set` line does.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Set {
+    /// `.set NAME, value`, which is the same as `NAME = value`,
+    /// given as the line rewritten to that, in the same columns.
+    Constant(String),
+    /// `.set option` (e.g. `.set noreorder`), which SPIM ignores, and so does mipsy.
+    Option,
+}
+
+/// Parses a `.set` line, where `args` is everything after the `.set`.
+pub(crate) fn parse_set(line: &str, args: &str) -> Option<Set> {
+    let is_ident = |name: &str| {
+        name.starts_with(|c| IDENT_FIRST_CHAR.contains(c)) && name.chars().all(is_ident_contd)
+    };
+
+    match args.split_once(',') {
+        Some((name, value)) if is_ident(name.trim()) && !value.trim().is_empty() => {
+            // `.set` becomes blank, and the comma becomes the `=`
+            let indent = leading_spaces(line);
+            let rest = line[indent + ".set".len()..].replacen(',', "=", 1);
+
+            Some(Set::Constant(format!(
+                "{}{}{}",
+                &line[..indent],
+                " ".repeat(".set".len()),
+                rest
+            )))
+        }
+        None if is_ident(args) => Some(Set::Option),
+        _ => None,
+    }
+}
+
+/// An `.if` or `.ifdef` in the file being preprocessed.
+struct Conditional {
+    /// Whether the condition holds, if it could be worked out.
+    taken: Option<bool>,
+    /// Whether everything around the `.if` is included.
+    enclosing_active: bool,
+    seen_else: bool,
+}
+
+impl Conditional {
+    fn active(&self) -> bool {
+        match self.taken {
+            // the `.else` is taken if the `.if` isn't
+            Some(taken) => self.enclosing_active && taken != self.seen_else,
+            None => self.enclosing_active,
+        }
+    }
+}
+
+/// # Which branch of each `.if` and `.ifdef` a file is in, as it's preprocessed.
+///
+/// Conditions are worked out as they're reached, so that an `.include`, `.eqv`
+/// or macro in a branch that isn't taken never happens at all.
+///
+/// The conditional directives themselves are left in for the compiler, which
+/// works them out again (along with any in a macro) and reports any that don't match up.
+/// A condition that can't be worked out yet, such as one using a constant that a
+/// macro defines, is left entirely to the compiler, so both of its branches are kept.
+#[derive(Default)]
+pub(crate) struct Conditionals {
+    conditionals: Vec<Conditional>,
+}
+
+impl Conditionals {
+    /// Whether lines in the current branch are included.
+    pub(crate) fn active(&self) -> bool {
+        self.conditionals.last().is_none_or(Conditional::active)
+    }
+
+    /// If `code` is a conditional directive, moves into (or out of) its branch,
+    /// giving back whether the directive itself is included.
+    ///
+    /// `lookup` gives the value of a constant defined so far.
+    pub(crate) fn update(
+        &mut self,
+        code: &str,
+        lookup: &impl Fn(&str) -> Option<i64>,
+    ) -> Option<bool> {
+        let active = self.active();
+
+        let taken = if let Some(name) = directive_args(code, ".ifdef") {
+            Some(lookup(name).is_some())
+        } else if let Some(condition) = directive_args(code, ".if") {
+            match parse_constant_value(Span::new(condition.as_bytes())) {
+                Ok((remaining, value)) if remaining.fragment().is_empty() => {
+                    value.eval(lookup).ok().map(|value| value != 0)
+                }
+                _ => None,
+            }
+        } else if directive_args(code, ".else") == Some("") {
+            return Some(match self.conditionals.last_mut() {
+                Some(conditional) if !conditional.seen_else => {
+                    conditional.seen_else = true;
+                    conditional.enclosing_active
+                }
+                _ => active,
+            });
+        } else if directive_args(code, ".endif") == Some("") {
+            return Some(
+                self.conditionals
+                    .pop()
+                    .map_or(active, |conditional| conditional.enclosing_active),
+            );
+        } else {
+            return None;
+        };
+
+        self.conditionals.push(Conditional {
+            taken,
+            enclosing_active: active,
+            seen_else: false,
+        });
+
+        Some(active)
+    }
+}
+
+/// Replaces every use of an `.eqv` name in a line with its token sequence.
+///
+/// `.ifdef NAME` is left alone (as that's asking about the name itself),
+/// unless `NAME` is one of the `.eqv`s, in which case it's always true.
+pub(crate) fn substitute_eqvs(line: &str, eqvs: &[(String, String)]) -> String {
+    if eqvs.is_empty() {
+        return line.to_string();
+    }
+
+    let code = strip_comment(line).trim();
+
+    if let Some(name) = directive_args(code, ".ifdef") {
+        if eqvs.iter().any(|(eqv, _)| eqv == name) {
+            let indent = leading_spaces(line);
+            return format!("{}.if 1{}", &line[..indent], &line[indent + code.len()..]);
+        }
+
+        return line.to_string();
+    }
+
+    rewrite_idents(line, |ident| {
+        eqvs.iter()
+            .rev()
+            .find(|(eqv, _)| eqv == ident)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+/// Calls `replace` for each identifier in a line (outside of strings and comments),
+/// replacing it if `replace` returns something.
+///
+/// Identifiers following a `%` (macro parameters) are given with the `%`,
+/// and register names (following a `$`) are never replaced.
+pub(crate) fn rewrite_idents(
+    line: &str,
+    mut replace: impl FnMut(&str) -> Option<String>,
+) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let char = chars[i];
+
+        let end = match char {
+            // attributes look like comments, but aren't
+            '#' if !chars[i..].starts_with(&['#', '['])
+                && !chars[i..].starts_with(&['#', '!', '[']) =>
+            {
+                chars.len()
+            }
+            '"' | '\'' => literal_end(&chars, i),
+            '$' | '%' if chars.get(i + 1).is_some_and(|&c| is_ident_contd(c)) => {
+                ident_end(&chars, i + 1)
+            }
+            _ if char.is_ascii_digit() => ident_end(&chars, i),
+            _ if IDENT_FIRST_CHAR.contains(char) => ident_end(&chars, i),
+            _ => i + 1,
+        };
+
+        let token: String = chars[i..end].iter().collect();
+
+        let is_ident = char == '%' || IDENT_FIRST_CHAR.contains(char);
+        match is_ident.then(|| replace(&token)).flatten() {
+            Some(replacement) => text.push_str(&replacement),
+            None => text.push_str(&token),
+        }
+
+        i = end;
+    }
+
+    text
+}
+
+/// If `line` is the directive `name`, returns everything after it.
+pub(crate) fn directive_args<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(name)?;
+
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// If `code` starts with a label, returns the index just past its colon.
+pub(crate) fn label_end(code: &str) -> Option<usize> {
+    let first = code.chars().next()?;
+    if !IDENT_FIRST_CHAR.contains(first) {
+        return None;
+    }
+
+    let ident_len = code
+        .find(|c: char| !is_ident_contd(c))
+        .unwrap_or(code.len());
+    let after = &code[ident_len..];
+    let colon = after.len() - after.trim_start_matches(' ').len();
+
+    if after[colon..].starts_with(':') {
+        Some(ident_len + colon + 1)
+    } else {
+        None
+    }
+}
+
+/// The index just past the end of the string or character literal starting at `start`.
+pub(crate) fn literal_end(chars: &[char], start: usize) -> usize {
+    let quote = chars[start];
+    let mut i = start + 1;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+
+    chars.len()
+}
+
+fn ident_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|&c| !is_ident_contd(c))
+        .map_or(chars.len(), |len| start + len)
+}
+
+pub(crate) fn is_ident_contd(char: char) -> bool {
+    IDENT_CONTD_CHARS.contains(char)
+}
+
+/// Removes a trailing `# comment` from a line,
+/// without being fooled by `#` inside a string.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut quote = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (index, char) = chars[i];
+
+        match (quote, char) {
+            (Some(_), '\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(char),
+            // attributes look like comments, but aren't
+            (None, '#')
+                if !line[index..].starts_with("#[") && !line[index..].starts_with("#![") =>
+            {
+                return &line[..index];
+            }
+            _ => {}
+        }
+
+        i += 1;
+    }
+
+    line
+}
+
+pub(crate) fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_eqv() {
+        let eqvs = vec![
+            parse_eqv("PRINT_INT 1").unwrap(),
+            parse_eqv("ARRAY_END ($t0)").unwrap(),
+        ];
+
+        assert_eq!(eqvs[1], ("ARRAY_END".to_string(), "($t0)".to_string()));
+        assert_eq!(parse_eqv("PRINT_INT"), None);
+
+        assert_eq!(
+            substitute_eqvs("    li $v0, PRINT_INT # PRINT_INT", &eqvs),
+            "    li $v0, 1 # PRINT_INT"
+        );
+        assert_eq!(
+            substitute_eqvs("    la $a0, PRINT_INT_msg", &eqvs),
+            "    la $a0, PRINT_INT_msg"
+        );
+        assert_eq!(
+            substitute_eqvs("    lw $t1, ARRAY_END", &eqvs),
+            "    lw $t1, ($t0)"
+        );
+        assert_eq!(
+            substitute_eqvs("    .asciiz \"PRINT_INT\"", &eqvs),
+            "    .asciiz \"PRINT_INT\""
+        );
+        assert_eq!(substitute_eqvs(".ifdef PRINT_INT", &eqvs), ".if 1");
+        assert_eq!(substitute_eqvs(".ifdef DEBUG", &eqvs), ".ifdef DEBUG");
+    }
+}
@@ -2,7 +2,10 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use crate::{
-    number::{parse_immediate, MpImmediate},
+    constant::MpConstValueLoc,
+    number::{
+        parse_expression, parse_immediate, parse_relocation, MpImmediate, MpNumber, MpRelocation,
+    },
     Span,
 };
 use nom::{
@@ -23,6 +26,9 @@ pub enum MpRegister {
         MpImmediate,
         MpRegisterIdentifier,
     ),
+    /// An offset given by a constant expression (`array + 4 * N($t0)`),
+    /// optionally inside a relocation operator (`%lo(array)($t0)`).
+    ExpressionOffset(Option<MpRelocation>, MpConstValueLoc, MpRegisterIdentifier),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -43,6 +49,7 @@ impl MpRegister {
             Self::Normal(ident) => ident,
             Self::Offset(_, ident) => ident,
             Self::BinaryOpOffset(_, _, _, ident) => ident,
+            Self::ExpressionOffset(_, _, ident) => ident,
         }
     }
 }
@@ -53,6 +60,10 @@ impl fmt::Display for MpRegister {
             Self::Normal(id) => write!(f, "${}", id),
             Self::Offset(imm, id) => write!(f, "{}(${})", imm, id),
             Self::BinaryOpOffset(i1, op, i2, id) => write!(f, "{} {} {}(${})", i1, op, i2, id),
+            Self::ExpressionOffset(Some(relocation), value, id) => {
+                write!(f, "{}({})(${})", relocation, value.0, id)
+            }
+            Self::ExpressionOffset(None, value, id) => write!(f, "{}(${})", value.0, id),
         }
     }
 }
@@ -80,6 +91,7 @@ pub fn parse_register(i: Span<'_>) -> IResult<Span<'_>, MpRegister> {
         parse_normal_register,
         parse_offset_register,
         parse_offset_binary_op_register,
+        parse_offset_expression_register,
     ))(i)
 }
 
@@ -142,3 +154,55 @@ pub fn parse_offset_binary_op_register(i: Span<'_>) -> IResult<Span<'_>, MpRegis
         MpRegister::BinaryOpOffset(i1, op, i2, reg.get_identifier().clone()),
     ))
 }
+
+pub fn parse_offset_expression_register(i: Span<'_>) -> IResult<Span<'_>, MpRegister> {
+    let (remaining_data, (num, _, _, _, reg, ..)) = tuple((
+        alt((parse_relocation, parse_expression)),
+        space0,
+        char('('),
+        space0,
+        parse_normal_register,
+        space0,
+        char(')'),
+    ))(i)?;
+
+    Ok((
+        remaining_data,
+        match num {
+            MpNumber::Relocation(relocation, value) => {
+                MpRegister::ExpressionOffset(Some(relocation), value, reg.get_identifier().clone())
+            }
+            MpNumber::Expression(value) => {
+                MpRegister::ExpressionOffset(None, value, reg.get_identifier().clone())
+            }
+            _ => unreachable!(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::misc::{span, unspan};
+
+    #[test]
+    fn expression_offset() {
+        let (rest, register) = unspan(parse_register(span("arr + 4 * N($t1)")).unwrap());
+
+        assert_eq!(rest, "");
+        assert_eq!(register.to_string(), "arr + 4 * N($t1)");
+        assert!(matches!(register, MpRegister::ExpressionOffset(None, ..)));
+
+        let (_, register) = unspan(parse_register(span("%lo(arr)($at)")).unwrap());
+
+        assert!(matches!(
+            register,
+            MpRegister::ExpressionOffset(Some(MpRelocation::Lo), ..)
+        ));
+
+        // simpler offsets keep their existing forms
+        let (_, register) = unspan(parse_register(span("arr+8($t1)")).unwrap());
+
+        assert!(matches!(register, MpRegister::BinaryOpOffset(..)));
+    }
+}
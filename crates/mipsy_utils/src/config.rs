@@ -16,6 +16,58 @@ const CONFIG_NAME: &str = "config.yaml";
 pub struct MipsyConfig {
     pub tab_size: u32,
     pub spim: bool,
+    #[serde(default)]
+    pub bounds_check: bool,
+    #[serde(default = "default_memory_layout")]
+    pub memory_layout: String,
+    #[serde(default)]
+    pub stack_size: Option<u32>,
+    #[serde(default)]
+    pub format: FormatConfig,
+    /// When disassembling, show the native instructions that each
+    /// pseudo-instruction expands into, rather than folding them back together.
+    #[serde(default)]
+    pub native_instructions: bool,
+}
+
+/// # How `mipsy fmt` lays out a program.
+///
+/// This lives under `format:` in the mipsy config file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatConfig {
+    /// How registers are written, e.g. `$t0` or `$8`.
+    pub registers: RegisterStyle,
+    /// Indent with tabs rather than `tab_size` spaces.
+    pub hard_tabs: bool,
+    /// How many columns the name of an instruction or directive
+    /// takes up before its operands.
+    pub mnemonic_width: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterStyle {
+    /// `$t0`, `$sp`
+    Names,
+    /// `$8`, `$29`
+    Numbers,
+    /// However they were written.
+    Keep,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            registers: RegisterStyle::Names,
+            hard_tabs: false,
+            mnemonic_width: 8,
+        }
+    }
+}
+
+fn default_memory_layout() -> String {
+    String::from("spim")
 }
 
 /// # Errors arising from reading the mipsy configuration.
@@ -61,6 +113,11 @@ impl Default for MipsyConfig {
         Self {
             tab_size: 8,
             spim: false,
+            bounds_check: false,
+            memory_layout: default_memory_layout(),
+            stack_size: None,
+            format: FormatConfig::default(),
+            native_instructions: false,
         }
     }
 }
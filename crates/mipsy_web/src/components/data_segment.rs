@@ -1,6 +1,6 @@
-use crate::state::state::RunningState;
-use mipsy_lib::runtime::PAGE_SIZE;
-use mipsy_lib::util::{get_segment, Segment};
+use crate::state::state::{MipsState, RunningState};
+use mipsy_lib::runtime::{HeapAllocation, PAGE_SIZE};
+use mipsy_lib::util::Segment;
 use mipsy_lib::Register;
 use mipsy_lib::Safe;
 use yew::prelude::*;
@@ -53,14 +53,23 @@ pub fn data_segment(props: &DataSegmentProps) -> Html {
 
     pages.sort_by_key(|(key, _)| *key);
 
+    let layout = props
+        .state
+        .mips_state
+        .binary
+        .as_ref()
+        .map(|binary| binary.layout)
+        .unwrap_or_default();
+
     let mut curr_segment = Segment::None;
 
     html! {
         <div id="output" style="min-width: 650px;margin-top: 10px;">
             <div style="width: 100%;">
+                { render_heap(&props.state.mips_state, &registers) }
                 {
                     for pages.into_iter().map(|(page_addr, page_contents)| {
-                        let segment = get_segment(page_addr);
+                        let segment = layout.segment_of(page_addr);
 
                         if !should_display_segment(segment) {
                             return html! {};
@@ -95,6 +104,102 @@ pub fn data_segment(props: &DataSegmentProps) -> Html {
     }
 }
 
+fn render_heap(mips_state: &MipsState, registers: &[Safe<i32>]) -> Html {
+    let allocations = mips_state
+        .heap_allocations
+        .iter()
+        .filter(|allocation| allocation.size_in_use() > 0)
+        .collect::<Vec<_>>();
+
+    if allocations.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        <>
+            <h4>{"Heap allocations"}</h4>
+            <table style="width: 100%; font-size: 11.5px; font-family: monospace; text-align: left; margin-bottom: 10px;">
+                <thead>
+                    <tr>
+                        <th>{"Address"}</th>
+                        <th>{"Size"}</th>
+                        <th>{"Allocated on"}</th>
+                        <th>{"Initialised"}</th>
+                        <th>{"Pointed to by"}</th>
+                    </tr>
+                </thead>
+                <tbody>
+                {
+                    for allocations.into_iter().map(|allocation| {
+                        let site = mips_state
+                            .binary
+                            .as_ref()
+                            .and_then(|binary| binary.line_numbers.get(&allocation.site))
+                            .map(|(_, line)| format!("line {}", line))
+                            .unwrap_or_else(|| format!("0x{:08x}", allocation.site));
+
+                        let initialised = (0..allocation.size_in_use())
+                            .filter(|offset| {
+                                let addr = allocation.addr + offset;
+                                let page_addr = addr - addr % PAGE_SIZE as u32;
+
+                                mips_state
+                                    .memory
+                                    .get(&page_addr)
+                                    .map(|page| page[(addr - page_addr) as usize].as_option().is_some())
+                                    .unwrap_or(false)
+                            })
+                            .count();
+
+                        let initialised_style = if initialised == allocation.size_in_use() as usize {
+                            ""
+                        } else {
+                            "color: red;"
+                        };
+
+                        html! {
+                            <tr>
+                                <td>{ format!("0x{:08x}", allocation.addr) }</td>
+                                <td>{ format!("{} bytes", allocation.size_in_use()) }</td>
+                                <td>{ site }</td>
+                                <td style={initialised_style}>
+                                    { format!("{}/{} bytes", initialised, allocation.size_in_use()) }
+                                </td>
+                                <td>{ pointers_into(allocation, registers) }</td>
+                            </tr>
+                        }
+                    })
+                }
+                </tbody>
+            </table>
+        </>
+    }
+}
+
+fn pointers_into(allocation: &HeapAllocation, registers: &[Safe<i32>]) -> String {
+    registers
+        .iter()
+        .enumerate()
+        .filter_map(|(reg, value)| {
+            let value = value.as_option()?;
+
+            if !allocation.contains(*value as u32) {
+                return None;
+            }
+
+            let name = format!("${}", Register::from_u32(reg as u32).ok()?.to_lower_str());
+            let offset = *value as u32 - allocation.addr;
+
+            Some(if offset == 0 {
+                name
+            } else {
+                format!("{} (+{})", name, offset)
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 fn render_segment_header(segment: Segment) -> Html {
     html! {
         <h4>
@@ -172,6 +277,17 @@ fn render_page(
 
                         title.push_str(&format!("0x{:08X}", full_page_addr));
 
+                        if let Some(allocation) = props.state.mips_state.heap_allocations
+                            .iter()
+                            .find(|allocation| allocation.contains(full_page_addr as u32))
+                        {
+                            title.push_str(&format!(
+                                "\nheap allocation at 0x{:08X} (+{})",
+                                allocation.addr,
+                                full_page_addr as u32 - allocation.addr,
+                            ));
+                        }
+
                         if full_page_addr == registers[SP].into_option().unwrap_or(0) as usize &&
                             full_page_addr == registers[FP].into_option().unwrap_or(0) as usize {
                                 style.push_str(&format!("border: 2px solid {};", SP_FP_COLOR));
@@ -1,7 +1,12 @@
 use crate::{
-    state::state::{ErrorType::RuntimeError, State},
+    state::{
+        config::MipsyWebConfig,
+        state::{ErrorType::RuntimeError, State},
+    },
+    utils::DecompiledText,
     worker::{Worker, WorkerRequest},
 };
+use bounce::use_atom;
 use derivative::Derivative;
 use yew::{classes, function_component, html, Callback, Properties, UseStateHandle};
 use yew_agent::UseBridgeHandle;
@@ -10,7 +15,7 @@ use yew_agent::UseBridgeHandle;
 #[derivative(PartialEq)]
 pub struct DecompiledProps {
     pub current_instr: Option<u32>,
-    pub decompiled: String,
+    pub decompiled: DecompiledText,
     pub state: UseStateHandle<State>,
 
     #[derivative(PartialEq = "ignore")]
@@ -19,8 +24,12 @@ pub struct DecompiledProps {
 
 #[function_component(DecompiledCode)]
 pub fn render_decompiled(props: &DecompiledProps) -> Html {
+    let config = use_atom::<MipsyWebConfig>();
     let runtime_instr = props.current_instr.unwrap_or(0);
-    let decompiled = &props.decompiled;
+    let decompiled = match config.mipsy_config.native_instructions {
+        true => &props.decompiled.native,
+        false => &props.decompiled.folded,
+    };
     html! {
             <pre class="text-xs">
             <table>
@@ -7,6 +7,7 @@ use crate::state::config::{
 };
 use bounce::use_atom;
 use gloo_utils::format::JsValueSerdeExt;
+use mipsy_utils::MipsyConfig;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsValue;
 use web_sys::{HtmlInputElement, HtmlSelectElement};
@@ -436,6 +437,27 @@ pub fn render_modal(props: &ModalProps) -> Html {
                             }
                         />
 
+                        // === Show Native Instructions ===
+                        <Heading
+                            title="Show Native Instructions"
+                            subtitle="Decompile pseudo-instructions into the instructions they expand into"
+                        />
+                        <ToggleSwitch
+                            checked= {config.mipsy_config.native_instructions}
+                            onclick={
+                                let config = config.clone();
+                                Callback::from(move |_| {
+                                    config.set(MipsyWebConfig {
+                                        mipsy_config: MipsyConfig {
+                                            native_instructions: !config.mipsy_config.native_instructions,
+                                            ..config.mipsy_config.clone()
+                                        },
+                                        ..(*config).clone()
+                                    });
+                                })
+                            }
+                        />
+
                         // === Analytics ===
                         // disable analytics info until implemented
                         if false {
@@ -27,6 +27,8 @@ extern "C" {
     pub fn highlight_section(startLineNumber: u32, startColumn: u32, endColumn: u32);
     pub fn remove_highlight();
 
+    pub fn set_editor_markers(markers: JsValue);
+
     pub fn set_model_change_listener(callback: &Closure<dyn Fn()>);
 
     pub fn get_localstorage(key: &str) -> Option<String>;
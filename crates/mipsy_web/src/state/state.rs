@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use crate::pages::main::app::ReadSyscalls;
-use mipsy_lib::{Binary, MipsyError, Runtime, Safe};
+use crate::utils::DecompiledText;
+use mipsy_lib::{runtime::HeapAllocation, Binary, MipsyError, Runtime, Safe};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq)]
@@ -28,6 +29,7 @@ pub struct MipsState {
     // cannot be a big array due to serde not using const-generics yet
     // https://github.com/serde-rs/serde/issues/631
     pub memory: HashMap<u32, Vec<Safe<u8> /*; PAGE_SIZE] */>>,
+    pub heap_allocations: Vec<HeapAllocation>,
     pub is_stepping: bool,
     pub binary: Option<Binary>,
     /// used to tell us if we have already exited from a breakpoint
@@ -64,7 +66,9 @@ impl MipsState {
             .pages()
             .iter()
             .map(|(key, val)| (*key, val.iter().copied().collect()))
-            .collect()
+            .collect();
+
+        self.heap_allocations = runtime.timeline().state().heap_allocations().to_vec();
     }
 }
 
@@ -77,7 +81,7 @@ pub enum State {
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct RunningState {
-    pub decompiled: String,
+    pub decompiled: DecompiledText,
     pub mips_state: MipsState,
     pub should_kill: bool,
     pub input_needed: Option<ReadSyscalls>,
@@ -99,5 +103,5 @@ pub struct ErrorState {
 pub struct RuntimeErrorState {
     pub error: MipsyError,
     pub mips_state: MipsState,
-    pub decompiled: String,
+    pub decompiled: DecompiledText,
 }
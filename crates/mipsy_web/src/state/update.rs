@@ -16,11 +16,13 @@ use crate::{
 };
 use bounce::prelude::UseAtomHandle;
 use gloo_console::log;
+use gloo_utils::format::JsValueSerdeExt;
 use log::{error, info};
-use mipsy_lib::Safe;
+use mipsy_lib::{CompilerWarning, Safe};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::{cell::RefCell, rc::Rc};
-use wasm_bindgen::UnwrapThrowExt;
+use wasm_bindgen::{JsValue, UnwrapThrowExt};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yew_agent::UseBridgeHandle;
@@ -40,6 +42,7 @@ pub fn handle_response_from_worker(
     match response {
         WorkerResponse::DecompiledCode(response_struct) => {
             log!("recieved decompiled code from worker");
+            show_warning_markers(&response_struct.binary.warnings);
             state.set(State::Compiled(RunningState {
                 decompiled: response_struct.decompiled,
                 mips_state: MipsState {
@@ -50,6 +53,7 @@ pub fn handle_response_from_worker(
                     current_instr: None,
                     mipsy_stdout: Vec::new(),
                     memory: HashMap::new(),
+                    heap_allocations: Vec::new(),
                     is_stepping: true,
                     binary: Some(response_struct.binary),
                     breakpoint_switch: false,
@@ -98,6 +102,7 @@ pub fn handle_response_from_worker(
         WorkerResponse::WorkerError(response_struct) => {
             log!("recieved compiler error from worker");
             log!("{}", &response_struct.message);
+            show_warning_markers(&[]);
             let state_struct = ErrorType::CompilerOrParserError(ErrorState {
                 error: response_struct.error,
                 mipsy_stdout: vec![response_struct.message],
@@ -180,7 +185,7 @@ pub fn handle_response_from_worker(
                 state.set(State::Error(ErrorType::RuntimeError(RuntimeErrorState {
                     mips_state,
                     error,
-                    decompiled: decompiled.to_string(),
+                    decompiled: decompiled.clone(),
                 })));
             }
         }
@@ -219,6 +224,43 @@ pub fn handle_response_from_worker(
     };
 }
 
+/// Marks each warning from the last compile in the editor,
+/// replacing any markers from before.
+fn show_warning_markers(warnings: &[CompilerWarning]) {
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct EditorMarker {
+        start_line_number: u32,
+        start_column: u32,
+        end_line_number: u32,
+        end_column: u32,
+        message: String,
+    }
+
+    let markers = warnings
+        .iter()
+        .map(|warning| {
+            let message = std::iter::once(warning.warning().message())
+                .chain(warning.warning().tips())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            EditorMarker {
+                start_line_number: warning.line(),
+                start_column: warning.col(),
+                end_line_number: warning.line(),
+                end_column: warning.col_end(),
+                message: String::from_utf8_lossy(
+                    &strip_ansi_escapes::strip(message.trim_end()).unwrap_or_default(),
+                )
+                .to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    crate::set_editor_markers(JsValue::from_serde(&markers).unwrap());
+}
+
 pub fn submit_input(
     worker: &UseBridgeHandle<Worker>,
     input_ref: &UseStateHandle<NodeRef>,
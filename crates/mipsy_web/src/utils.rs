@@ -1,4 +1,8 @@
-use mipsy_lib::{decompile::decompile_into_parts, Binary, CompilerError, InstSet};
+use mipsy_lib::{
+    decompile::{decompile_into_parts, fold_pseudo_instructions},
+    Binary, CompilerError, InstSet,
+};
+use serde::{Deserialize, Serialize};
 
 pub fn generate_highlighted_line(file: String, err: &CompilerError) -> String {
     let line = &file
@@ -47,11 +51,36 @@ pub fn generate_highlighted_line(file: String, err: &CompilerError) -> String {
     )
 }
 
-pub fn decompile(program: &Binary, iset: &InstSet, file: Option<String>) -> String {
+/// A decompiled program, both with its pseudo-instructions folded back
+/// together and as native instructions, so the view can switch between them.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DecompiledText {
+    pub folded: String,
+    pub native: String,
+}
+
+pub fn decompile(program: &Binary, iset: &InstSet, file: Option<String>) -> DecompiledText {
+    DecompiledText {
+        folded: decompile_text(program, iset, file.clone(), true),
+        native: decompile_text(program, iset, file, false),
+    }
+}
+
+fn decompile_text(program: &Binary, iset: &InstSet, file: Option<String>, fold: bool) -> String {
     let mut text = String::new();
     let unknown_instruction = String::from("# Unknown instruction");
 
-    let decompiled = decompile_into_parts(program, iset);
+    let mut decompiled = decompile_into_parts(program, iset);
+
+    if fold {
+        fold_pseudo_instructions(
+            program,
+            iset,
+            decompiled
+                .values_mut()
+                .filter_map(|parts| parts.as_mut().ok()),
+        );
+    }
 
     let mut keys: Vec<u32> = decompiled.keys().copied().collect();
     keys.sort_unstable();
@@ -83,11 +112,17 @@ pub fn decompile(program: &Binary, iset: &InstSet, file: Option<String>) -> Stri
             text.push_str(&format!("{}: \n", label));
         }
 
+        // a folded instruction is shown as part of the pseudo-instruction before it
+        let name = match parts.folded {
+            true => "",
+            false => parts.inst_name.as_ref().unwrap_or(&unknown_instruction),
+        };
+
         let decompiled_part = &format!(
             "0x{:08x} [0x{:08x}]    {:6} {}",
             addr,
             parts.opcode,
-            parts.inst_name.as_ref().unwrap_or(&unknown_instruction),
+            name,
             parts.arguments.join(", ")
         );
 
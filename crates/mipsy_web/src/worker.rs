@@ -1,5 +1,8 @@
 use crate::state::config::MipsyWebConfig;
-use crate::{state::state::MipsState, utils::decompile, utils::generate_highlighted_line};
+use crate::{
+    state::state::MipsState,
+    utils::{decompile, generate_highlighted_line, DecompiledText},
+};
 use log::{error, info};
 use mipsy_lib::compile::breakpoints::{
     get_affected_registers, Breakpoint, TargetAction, Watchpoint, WatchpointTarget,
@@ -95,7 +98,7 @@ pub enum WorkerRequest {
 
 #[derive(Serialize, Deserialize)]
 pub struct DecompiledResponse {
-    pub decompiled: String,
+    pub decompiled: DecompiledText,
     pub file: Option<String>,
     pub binary: Binary,
 }
@@ -195,7 +198,10 @@ impl Agent for Worker {
                             file: Some(file.clone()),
                             binary: binary.to_owned(),
                         });
-                        let runtime = mipsy_lib::runtime(&binary, &[]);
+                        let mut runtime = mipsy_lib::runtime(&binary, &[]);
+                        if config.bounds_check {
+                            runtime.enable_bounds_check(&binary);
+                        }
                         self.binary = Some(binary);
                         self.runtime = Some(RuntimeState::Running(runtime));
                         self.file = Some(file);
@@ -208,13 +214,21 @@ impl Agent for Worker {
                         let error_msg = match error {
                             MipsyError::Compiler(ref compiler_err) => {
                                 format!(
-                                    "{}\n{}\n{}",
+                                    "{}\nerror[{}]: {}\n{}",
                                     generate_highlighted_line(file.clone(), compiler_err),
+                                    compiler_err.error().code(),
                                     compiler_err.error().message(),
                                     compiler_err.error().tips().join("\n")
                                 )
                             }
-                            MipsyError::Parser(_) => String::from("failed to parse"),
+                            MipsyError::Parser(ref parser_err) => {
+                                format!(
+                                    "error[{}]: {}\n{}",
+                                    parser_err.error().code(),
+                                    parser_err.error().message(),
+                                    parser_err.error().tips().join("\n")
+                                )
+                            }
                             MipsyError::Runtime(_) => {
                                 unreachable!(
                                     "runtime error should not be possible at compile time"
@@ -833,7 +847,8 @@ impl Agent for Worker {
                                             .as_ref()
                                             .expect("binary should exist if runtime error");
                                         let message = format!(
-                                            "error: {}\ntips: {}\n",
+                                            "error[{}]: {}\ntips: {}\n",
+                                            runtime_error.error().code(),
                                             runtime_error.error().message(
                                                 ErrorContext::Binary,
                                                 &source_code[..],
@@ -0,0 +1,8 @@
+#![deny(unused)]
+
+main:
+    li   $v0, 0
+    jr   $ra
+
+helper:
+    jr   $ra
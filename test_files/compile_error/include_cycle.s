@@ -0,0 +1,7 @@
+# a file can't include itself
+
+.include "include_cycle.s"
+
+main:
+    li   $v0, 0
+    jr   $ra
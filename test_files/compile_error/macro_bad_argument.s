@@ -0,0 +1,15 @@
+# print_int is given an immediate where it needs a register
+
+.macro print_int(%reg)
+    move $a0, %reg
+    li   $v0, 1
+    syscall
+.end_macro
+
+main:
+    li   $t0, 42
+    print_int($t0)
+    print_int(42)
+
+    li   $v0, 0
+    jr   $ra
@@ -0,0 +1,11 @@
+# the offset is missing a comma before it
+
+main:
+    addi $sp, $sp, -4
+    sw   $ra, 0($sp)
+
+    lw   $ra 0($sp)
+    addi $sp, $sp, 4
+
+    li   $v0, 0
+    jr   $ra
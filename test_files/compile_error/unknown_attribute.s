@@ -0,0 +1,4 @@
+#[inline]
+main:
+    li   $v0, 0
+    jr   $ra
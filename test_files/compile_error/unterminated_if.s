@@ -0,0 +1,4 @@
+main:
+.if 1
+    li   $v0, 0
+    jr   $ra
@@ -0,0 +1,15 @@
+main:
+	la	$t0, arr
+	li	$t1, 0
+loop:
+	bgt	$t1, 10, end	# should be bge
+	sw	$t1, ($t0)
+	addi	$t0, $t0, 4
+	addi	$t1, $t1, 1
+	j	loop
+end:
+	jr	$ra
+
+	.data
+arr:	.word 0:10
+count:	.word 0
@@ -0,0 +1,12 @@
+main:
+	addi	$sp, $sp, -8
+	lw	$t0, 4($sp)	# this stack slot was never written
+	sw	$t0, 0($sp)
+	lw	$t1, 0($sp)
+
+	move	$a0, $t1
+	li	$v0, 1
+	syscall
+
+	addi	$sp, $sp, 8
+	jr	$ra
@@ -0,0 +1,29 @@
+# print a different answer depending on what's defined,
+# e.g. `mipsy conditional.s` vs `mipsy -D SOLUTION conditional.s`
+
+.eqv PRINT_INT 1
+.eqv PRINT_CHAR 11
+
+VERBOSE = 0
+
+main:
+.ifdef SOLUTION
+    li   $a0, 42
+.else
+    li   $a0, 0          # TODO: work out the answer
+.endif
+    li   $v0, PRINT_INT
+    syscall
+
+.if VERBOSE
+    li   $a0, '!'
+    li   $v0, PRINT_CHAR
+    syscall
+.endif
+
+    li   $a0, '\n'
+    li   $v0, PRINT_CHAR
+    syscall
+
+    li   $v0, 0
+    jr   $ra
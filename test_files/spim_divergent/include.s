@@ -0,0 +1,26 @@
+# uses the helpers from include/io_helpers.s,
+# without it having to be passed to mipsy as well
+
+.include "include/io_helpers.s"
+
+.data
+greeting:
+    .asciiz "the answer is "
+
+.text
+main:
+    addi $sp, $sp, -4
+    sw   $ra, 0($sp)
+
+    la   $a0, greeting
+    jal  print_string
+
+    li   $t0, 42
+    print_int($t0)
+    print_char('\n')
+
+    lw   $ra, 0($sp)
+    addi $sp, $sp, 4
+
+    li   $v0, 0
+    jr   $ra
@@ -0,0 +1,20 @@
+# helpers for printing, shared between labs
+
+.macro print_int(%reg)
+    move $a0, %reg
+    li   $v0, 1
+    syscall
+.end_macro
+
+.macro print_char(%c)
+    li   $a0, %c
+    li   $v0, 11
+    syscall
+.end_macro
+
+.text
+# print_string(char *s)
+print_string:
+    li   $v0, 4
+    syscall
+    jr   $ra
@@ -0,0 +1,29 @@
+# numeric local labels (`1:`, referred to as `1f` and `1b`),
+# and `.L` labels, which are only visible inside the function they're in
+
+main:
+	li	$t0, 0
+1:
+	bge	$t0, 3, 1f
+	move	$a0, $t0
+	li	$v0, 1
+	syscall
+	addi	$t0, $t0, 1
+	b	1b
+1:
+	jal	count_down
+
+	li	$v0, 10
+	syscall
+
+count_down:
+	li	$t0, 2
+.Lloop:
+	blt	$t0, 0, .Lend
+	move	$a0, $t0
+	li	$v0, 1
+	syscall
+	addi	$t0, $t0, -1
+	b	.Lloop
+.Lend:
+	jr	$ra
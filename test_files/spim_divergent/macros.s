@@ -0,0 +1,39 @@
+# print the numbers 1 to 3 (three times), using macros
+
+.macro print_int(%reg)
+    move $a0, %reg
+    li   $v0, 1
+    syscall
+.end_macro
+
+.macro print_char %c
+    li   $a0, %c
+    li   $v0, 11
+    syscall
+.end_macro
+
+# each expansion gets its own copy of the loop label
+.macro count_to(%n)
+    li   $t0, 1
+loop:
+    bgt  $t0, %n, done
+    print_int($t0)
+    print_char ' '
+    addi $t0, $t0, 1
+    b    loop
+done:
+    print_char '\n'
+.end_macro
+
+main:
+    count_to(3)
+    li   $s0, 1
+
+    # the labels in the macro don't end `.Lagain`'s scope
+.Lagain:
+    count_to(3)
+    addi $s0, $s0, -1
+    bgez $s0, .Lagain
+
+    li   $v0, 0
+    jr   $ra
@@ -0,0 +1,40 @@
+# `%hi`/`%lo` relocation operators, and labels plus constant expressions:
+# the pieces that `la` and `lw label($reg)` are made from
+
+N = 3
+
+main:
+	lui	$t0, %hi(numbers)		# la	$t0, numbers
+	addiu	$t0, $t0, %lo(numbers)
+	lw	$a0, 8($t0)
+	li	$v0, 1
+	syscall
+
+	li	$a0, '\n'
+	li	$v0, 11
+	syscall
+
+	lui	$t1, %hi(numbers + 4)		# lw	$a0, numbers + 4
+	lw	$a0, %lo(numbers + 4)($t1)
+	li	$v0, 1
+	syscall
+
+	li	$a0, '\n'
+	li	$v0, 11
+	syscall
+
+	li	$t2, 4
+	lw	$a0, numbers + 4 * N($t2)	# numbers[N + 1]
+	li	$v0, 1
+	syscall
+
+	li	$a0, '\n'
+	li	$v0, 11
+	syscall
+
+	li	$v0, 0
+	jr	$ra
+
+	.data
+numbers:
+	.word	10, 20, 30, 40, 50